@@ -0,0 +1,82 @@
+//! benchmarks for the per-line helpers, for cleaning a large file end to end and for
+//! the compiled patterns against cleaning a small file. run with `cargo bench`.
+
+use std::{fs, path::PathBuf};
+
+use cleaner_lib::{clean_file, compile_rules, n_chars_last_field, n_data_fields, CleanOptions};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use yaml_rust::YamlLoader;
+
+const N_LINES: usize = 100_000;
+const LINE: &str = "12:00:00.00\t1013.25\t21.5\t45.2\t0.001\t1\t0\t512";
+
+fn field_counting(c: &mut Criterion) {
+    c.bench_function("n_data_fields", |b| {
+        b.iter(|| n_data_fields(black_box(LINE), "\t"))
+    });
+    c.bench_function("n_chars_last_field", |b| {
+        b.iter(|| n_chars_last_field(black_box(LINE), "\t"))
+    });
+}
+
+/// the file has a cut off last line, so every iteration checks, fixes and rewrites it.
+fn generated_file() -> String {
+    let mut s = String::from("time\tp\tT\tRH\tq\tv1\tv2\tn\n");
+    for _ in 0..N_LINES {
+        s.push_str(LINE);
+        s.push('\n');
+    }
+    s.push_str("12:00:00.00\t1013");
+    s
+}
+
+fn clean_large_file(c: &mut Criterion) {
+    let cfg = &YamlLoader::load_from_str(include_str!("../resources/cfg/v25_data_cfg.yml"))
+        .expect("sample config is valid")[0];
+    let dir = std::env::temp_dir().join(format!("v25_bench_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path: PathBuf = dir.join("bench.DAT");
+    let text = generated_file();
+    let opts = CleanOptions::default();
+
+    c.bench_function("clean_file 100k lines", |b| {
+        b.iter_batched(
+            || fs::write(&path, &text).unwrap(),
+            |_| clean_file(&path, cfg, &opts).unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+    fs::remove_dir_all(&dir).ok();
+}
+
+/// the patterns are compiled once before the run, so cleaning a small file with a
+/// pattern must cost far less than compiling it.
+fn clean_small_file(c: &mut Criterion) {
+    let cfg = &YamlLoader::load_from_str(
+        "DAT:\n  min_n_lines: 2\n  skip_leading_garbage:\n    pattern: \"^(BOOT|INIT|FW v\\\\d+)\"\n",
+    )
+    .expect("bench config is valid")[0];
+    c.bench_function("compile_rules", |b| {
+        b.iter(|| compile_rules(black_box(cfg)).unwrap())
+    });
+
+    let dir = std::env::temp_dir().join(format!("v25_bench_small_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path: PathBuf = dir.join("bench.DAT");
+    let text = format!("BOOT\nINIT\ntime\tp\n{LINE}\n");
+    let opts = CleanOptions {
+        compiled_rules: compile_rules(cfg).unwrap(),
+        ..Default::default()
+    };
+    c.bench_function("clean_file small with leading garbage", |b| {
+        b.iter_batched(
+            || fs::write(&path, &text).unwrap(),
+            |_| clean_file(&path, cfg, &opts).unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+    fs::remove_dir_all(&dir).ok();
+}
+
+criterion_group!(benches, field_counting, clean_large_file, clean_small_file);
+criterion_main!(benches);
@@ -0,0 +1,52 @@
+//! embeds the git commit the cleaner is built from, see cleaner_lib::build_info. Built
+//! from a source tarball without git, the commit is left empty.
+
+use std::{env, fs, path::Path, process::Command};
+
+/// git runs git with args in the package directory and returns its trimmed output, or
+/// None if git is missing or fails, e.g. outside of a repository.
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(env::var("CARGO_MANIFEST_DIR").ok()?)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn main() {
+    let commit = git(&["rev-parse", "HEAD"]).unwrap_or_default();
+    let describe = git(&["describe", "--always", "--dirty", "--tags"]).unwrap_or_default();
+    println!("cargo:rustc-env=V25_GIT_COMMIT={commit}");
+    println!("cargo:rustc-env=V25_GIT_DESCRIBE={describe}");
+    println!(
+        "cargo:rustc-env=V25_TARGET={}",
+        env::var("TARGET").unwrap_or_default()
+    );
+
+    // run again on a new commit or a changed working tree
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    let Some(git_dir) = git(&["rev-parse", "--git-dir"]) else {
+        return;
+    };
+    let git_dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap_or_default()).join(git_dir);
+    for name in ["HEAD", "index", "packed-refs"] {
+        if git_dir.join(name).exists() {
+            println!("cargo:rerun-if-changed={}", git_dir.join(name).display());
+        }
+    }
+    // HEAD names the branch, a commit moves the branch
+    if let Some(branch) = fs::read_to_string(git_dir.join("HEAD"))
+        .ok()
+        .and_then(|head| head.strip_prefix("ref: ").map(|r| r.trim().to_string()))
+    {
+        if git_dir.join(&branch).exists() {
+            println!("cargo:rerun-if-changed={}", git_dir.join(branch).display());
+        }
+    }
+}
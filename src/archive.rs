@@ -0,0 +1,287 @@
+//! archive cleans the files inside zip archives, e.g. campaigns archived as one zip per
+//! day. Each member is checked like a file in a directory and the archive is replaced
+//! by one that holds only the surviving, cleaned members.
+
+use std::{io, path::Path};
+
+use yaml_rust::Yaml;
+
+use crate::{CleanOptions, FileOutcome};
+
+/// is_zip tells if path is a zip archive by its extension.
+pub fn is_zip(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+}
+
+/// Member is what happens to a member of the archive.
+#[cfg(feature = "zip")]
+enum Member {
+    /// copy the member as it is, compressed data included
+    Keep,
+    /// write the member with new content, keeping its compression method and mtime.
+    /// files split off the member are added after it with the same options.
+    Replace(Vec<u8>, Vec<(String, Vec<u8>)>),
+    /// leave the member out
+    Drop,
+}
+
+/// WorkDir is a temporary directory that is removed with its content when dropped.
+#[cfg(feature = "zip")]
+struct WorkDir(std::path::PathBuf);
+
+#[cfg(feature = "zip")]
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// clean_zip cleans the members of the zip archive at path and returns their outcomes,
+/// with paths like `day.zip/file.DAT`. Members are extracted one at a time to a
+/// temporary directory and checked there by clean_file. Directories and members in
+/// nested directories are kept as they are, like subdirectories of a cleaned directory.
+/// if a member was modified or deleted, the archive is replaced atomically.
+#[cfg(feature = "zip")]
+pub fn clean_zip(path: &Path, cfg: &Yaml, opts: &CleanOptions) -> io::Result<Vec<FileOutcome>> {
+    use std::{
+        fs,
+        io::{Read, Write},
+    };
+
+    use crate::{clean_file, tmpfile, Outcome, FLAG_EXTENSION};
+
+    // the members are checked in a temporary directory, their hashes could not be
+    // compared later, see spotcheck; the archive is backed up as a whole
+    let backup = opts.backup;
+    let opts = &CleanOptions {
+        hash_unchanged: false,
+        backup: None,
+        ..opts.clone()
+    };
+
+    let mut archive = zip::ZipArchive::new(io::BufReader::new(fs::File::open(path)?))
+        .map_err(io::Error::other)?;
+    let name = path.file_name().unwrap_or_default();
+    let work = WorkDir(tmpfile::temp_path(&std::env::temp_dir().join(name)));
+    fs::create_dir(&work.0)?;
+
+    let mut outcomes = Vec::new();
+    let mut plan = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let mut member = archive.by_index(i).map_err(io::Error::other)?;
+        let member_name = member.name().to_string();
+        if member.is_dir() || member_name.contains('/') || member.enclosed_name().is_none() {
+            plan.push(Member::Keep);
+            continue;
+        }
+        let display_path = path.join(&member_name);
+        let mut bytes = Vec::new();
+        if let Err(err) = member.read_to_end(&mut bytes) {
+            // e.g. a compression method this build can't read, keep the member
+            outcomes.push(FileOutcome::from_error(&display_path, &err));
+            plan.push(Member::Keep);
+            continue;
+        }
+        let member_path = work.0.join(&member_name);
+        let mut file = fs::File::create(&member_path)?;
+        file.write_all(&bytes)?;
+        // checks may compare the content with the modification time
+        if let Some(mtime) = member.last_modified().and_then(system_time) {
+            file.set_modified(mtime)?;
+        }
+        drop(file);
+
+        let mut outcome = clean_file(&member_path, cfg, opts)
+            .unwrap_or_else(|err| FileOutcome::from_error(&member_path, &err));
+        plan.push(match outcome.outcome {
+            Outcome::Modified => {
+                // e.g. acquisition blocks split off an OSC file
+                let mut added = Vec::new();
+                for entry in fs::read_dir(&work.0)?.filter_map(|r| r.ok()) {
+                    let added_path = entry.path();
+                    let added_name = entry.file_name().to_string_lossy().to_string();
+                    let is_flag = added_path
+                        .extension()
+                        .is_some_and(|ext| ext == FLAG_EXTENSION);
+                    if added_path != member_path && !is_flag {
+                        added.push((added_name, fs::read(&added_path)?));
+                    }
+                }
+                added.sort();
+                Member::Replace(fs::read(&member_path)?, added)
+            }
+            Outcome::Deleted(_) => Member::Drop,
+            _ => Member::Keep,
+        });
+        // the work directory only ever holds the files of one member
+        for entry in fs::read_dir(&work.0)?.filter_map(|r| r.ok()) {
+            let _ = fs::remove_file(entry.path());
+        }
+        outcome.path = display_path;
+        outcomes.push(outcome);
+    }
+    if plan.iter().all(|m| matches!(m, Member::Keep)) {
+        return Ok(outcomes);
+    }
+
+    let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+    for (i, member) in plan.into_iter().enumerate() {
+        let file = archive.by_index_raw(i).map_err(io::Error::other)?;
+        match member {
+            Member::Keep => writer.raw_copy_file(file).map_err(io::Error::other)?,
+            Member::Replace(bytes, added) => {
+                let options = file.options();
+                let member_name = file.name().to_string();
+                drop(file);
+                writer
+                    .start_file(member_name, options)
+                    .map_err(io::Error::other)?;
+                writer.write_all(&bytes)?;
+                for (added_name, bytes) in added {
+                    writer
+                        .start_file(added_name, options)
+                        .map_err(io::Error::other)?;
+                    writer.write_all(&bytes)?;
+                }
+            }
+            Member::Drop => (),
+        }
+    }
+    let bytes = writer.finish().map_err(io::Error::other)?.into_inner();
+    if let Some(backup) = backup {
+        let kept = backup.keep(path)?;
+        for outcome in outcomes.iter_mut() {
+            if matches!(outcome.outcome, Outcome::Modified | Outcome::Deleted(_)) {
+                outcome.backup = Some(kept.clone());
+            }
+        }
+    }
+    tmpfile::write_atomic(path, |file| file.write_all(&bytes))?;
+    Ok(outcomes)
+}
+
+/// clean_zip without the zip feature fails, the archive is left untouched.
+#[cfg(not(feature = "zip"))]
+pub fn clean_zip(path: &Path, _cfg: &Yaml, _opts: &CleanOptions) -> io::Result<Vec<FileOutcome>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("cannot clean {path:?}, built without the zip feature"),
+    ))
+}
+
+/// system_time converts the local time of a zip member to a system time.
+#[cfg(feature = "zip")]
+fn system_time(dt: zip::DateTime) -> Option<std::time::SystemTime> {
+    use chrono::{Local, NaiveDateTime, TimeZone};
+
+    let naive = NaiveDateTime::try_from(dt).ok()?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(std::time::SystemTime::from)
+}
+
+#[cfg(all(test, feature = "zip"))]
+mod tests {
+    use std::{
+        fs,
+        io::{Read, Write},
+    };
+
+    use yaml_rust::YamlLoader;
+
+    use super::*;
+    use crate::{testutil::FIXTURE_DIR, Outcome};
+
+    const CLEAN: &str = "time\tp\tT\n1\t2\t3\n4\t5\t6\n";
+
+    /// zip_of writes an archive at path with the members, a name ending in / is a
+    /// directory.
+    fn zip_of(path: &Path, members: &[(&str, &str)]) {
+        let mut writer = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, content) in members {
+            if name.ends_with('/') {
+                writer.add_directory(*name, options).unwrap();
+            } else {
+                writer.start_file(*name, options).unwrap();
+                writer.write_all(content.as_bytes()).unwrap();
+            }
+        }
+        writer.finish().unwrap();
+    }
+
+    /// entries returns the names and contents of the members of the archive at path.
+    fn entries(path: &Path) -> Vec<(String, String)> {
+        let mut archive = zip::ZipArchive::new(fs::File::open(path).unwrap()).unwrap();
+        (0..archive.len())
+            .map(|i| {
+                let mut member = archive.by_index(i).unwrap();
+                let mut content = String::new();
+                member.read_to_string(&mut content).unwrap();
+                (member.name().to_string(), content)
+            })
+            .collect()
+    }
+
+    fn cfg() -> Yaml {
+        let text = fs::read_to_string(Path::new(FIXTURE_DIR).join("cfg.yml")).unwrap();
+        YamlLoader::load_from_str(&text).unwrap().remove(0)
+    }
+
+    #[test]
+    fn archive_keeps_only_the_cleaned_members() {
+        let dir = std::env::temp_dir().join(format!("v25_archive_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("day.zip");
+        let trailing = format!("{CLEAN}\n\n");
+        zip_of(
+            &path,
+            &[
+                ("dat_clean.DAT", CLEAN),
+                ("dat_trailing_empty_lines.DAT", &trailing),
+                ("dat_too_few_lines.DAT", "time\tp\tT\n"),
+                ("raw/", ""),
+                ("raw/dat_too_few_lines.DAT", "time\tp\tT\n"),
+            ],
+        );
+
+        let outcomes = clean_zip(&path, &cfg(), &CleanOptions::default()).unwrap();
+        let outcomes: Vec<(&Path, &Outcome)> = outcomes
+            .iter()
+            .map(|o| (o.path.strip_prefix(&dir).unwrap(), &o.outcome))
+            .collect();
+        assert!(matches!(
+            outcomes[..],
+            [
+                (_, Outcome::Unchanged),
+                (_, Outcome::Modified),
+                (_, Outcome::Deleted(_))
+            ]
+        ));
+        assert_eq!(
+            outcomes[1].0,
+            Path::new("day.zip/dat_trailing_empty_lines.DAT")
+        );
+        // members in directories are kept as they are, like subdirectories
+        assert_eq!(
+            entries(&path),
+            [
+                ("dat_clean.DAT", CLEAN),
+                ("dat_trailing_empty_lines.DAT", CLEAN),
+                ("raw/", ""),
+                ("raw/dat_too_few_lines.DAT", "time\tp\tT\n"),
+            ]
+            .map(|(name, content)| (name.to_string(), content.to_string()))
+        );
+
+        // a clean archive is not written again
+        let before = fs::read(&path).unwrap();
+        let outcomes = clean_zip(&path, &cfg(), &CleanOptions::default()).unwrap();
+        assert!(outcomes.iter().all(|o| o.outcome == Outcome::Unchanged));
+        assert_eq!(fs::read(&path).unwrap(), before);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,134 @@
+//! audit records who ran the cleaner where, for archives cleaned by several people: the
+//! user, the host and the working directory of the run go into the log, the report and
+//! the done marker. What can't be found out is left out, not written empty.
+
+use std::{env, fmt, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::report::lossy_opt_path;
+
+/// AUDIT_ENV names the environment variable that turns on --audit, e.g. for a shared
+/// archive that should always be audited. Any value other than empty or "0" does.
+pub const AUDIT_ENV: &str = "V25_AUDIT";
+
+/// Audit is the user, host and working directory of a run, as far as they are known.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Audit {
+    /// the name of the user the process runs as
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "lossy_opt_path"
+    )]
+    pub working_directory: Option<PathBuf>,
+}
+
+impl Audit {
+    /// current returns the user, host and working directory of this process.
+    pub fn current() -> Self {
+        Audit {
+            user: user_name().filter(|name| !name.is_empty()),
+            host: host_name().filter(|name| !name.is_empty()),
+            working_directory: env::current_dir().ok(),
+        }
+    }
+
+    /// render_line renders the audit as one line, e.g. `user anna, host station-pc,
+    /// working directory "/data"`.
+    pub fn render_line(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(user) = &self.user {
+            parts.push(format!("user {user}"));
+        }
+        if let Some(host) = &self.host {
+            parts.push(format!("host {host}"));
+        }
+        if let Some(dir) = &self.working_directory {
+            parts.push(format!("working directory {dir:?}"));
+        }
+        if parts.is_empty() {
+            return "user, host and working directory unknown".to_string();
+        }
+        parts.join(", ")
+    }
+}
+
+impl fmt::Display for Audit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(user) = &self.user {
+            writeln!(f, "user: {user}")?;
+        }
+        if let Some(host) = &self.host {
+            writeln!(f, "host: {host}")?;
+        }
+        if let Some(dir) = &self.working_directory {
+            writeln!(f, "working directory: {}", dir.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// enabled_by_env returns true if AUDIT_ENV is set to turn on --audit.
+pub fn enabled_by_env() -> bool {
+    env::var_os(AUDIT_ENV).is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+/// user_name returns the name of the effective user from the user database, else from
+/// the environment.
+#[cfg(unix)]
+fn user_name() -> Option<String> {
+    // SAFETY: passwd is a plain C struct, all zeroes is a valid value
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut result = std::ptr::null_mut();
+    // SAFETY: pwd and buf outlive the call and buf.len() is the size of buf; pw_name
+    // points into buf and is only read if an entry was found
+    let name = unsafe {
+        let rc = libc::getpwuid_r(
+            libc::geteuid(),
+            &mut pwd,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        );
+        (rc == 0 && !result.is_null() && !pwd.pw_name.is_null()).then(|| {
+            std::ffi::CStr::from_ptr(pwd.pw_name)
+                .to_string_lossy()
+                .into_owned()
+        })
+    };
+    name.or_else(|| env::var("USER").ok())
+}
+
+/// user_name returns the name of the user from the environment.
+#[cfg(not(unix))]
+fn user_name() -> Option<String> {
+    env::var("USERNAME").or_else(|_| env::var("USER")).ok()
+}
+
+/// host_name returns the name of the host.
+#[cfg(unix)]
+fn host_name() -> Option<String> {
+    let mut buf = [0u8; 256];
+    // SAFETY: buf.len() is the size of buf, the name is cut at the first NUL or at the
+    // end of buf if it was truncated
+    let rc = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if rc != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+    Some(String::from_utf8_lossy(&buf[..len]).into_owned())
+}
+
+/// host_name returns the name of the host from the environment.
+#[cfg(not(unix))]
+fn host_name() -> Option<String> {
+    env::var("COMPUTERNAME")
+        .or_else(|_| env::var("HOSTNAME"))
+        .ok()
+}
@@ -0,0 +1,61 @@
+//! backup keeps a copy of each file before a run rewrites, renames or deletes it, with
+//! --backup. Rewrites are atomic, but a bug in a check or a bad cfg file can still
+//! remove data that was good. The copies go to a BACKUP_DIR next to the file, with the
+//! name and modification time of the file, so restoring one is a plain move back.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::tmpfile;
+
+/// BACKUP_DIR is the directory next to a file that its backup goes to. It is never
+/// cleaned, nor counted as a subdirectory that was not processed.
+pub const BACKUP_DIR: &str = ".v25_backup";
+
+/// Backup makes the copies of --backup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Backup {
+    /// replace the backup of an earlier run; otherwise a file whose backup exists is
+    /// left as it is, see keep
+    pub overwrite: bool,
+}
+
+impl Backup {
+    /// keep copies the file at path to backup_path and returns where it went. if a backup
+    /// of the same name exists and overwrite is not set, it fails with AlreadyExists and
+    /// the file must not be changed.
+    pub fn keep(&self, path: &Path) -> io::Result<PathBuf> {
+        let target = backup_path(path);
+        if !self.overwrite && target.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("backup {target:?} exists from an earlier run, pass --force to replace it"),
+            ));
+        }
+        let modified = fs::metadata(path)?.modified()?;
+        fs::create_dir_all(target.parent().unwrap_or(Path::new(".")))?;
+        tmpfile::write_atomic(&target, |file| {
+            io::copy(&mut fs::File::open(path)?, file).map(|_| ())
+        })?;
+        fs::File::options()
+            .write(true)
+            .open(&target)?
+            .set_modified(modified)?;
+        Ok(target)
+    }
+}
+
+/// backup_path returns where the backup of the file at path goes: BACKUP_DIR in its
+/// directory, with its name.
+pub fn backup_path(path: &Path) -> PathBuf {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    dir.join(BACKUP_DIR)
+        .join(path.file_name().unwrap_or_default())
+}
+
+/// is_backup_dir tells if dir is a BACKUP_DIR.
+pub fn is_backup_dir(dir: &Path) -> bool {
+    dir.file_name().is_some_and(|name| name == BACKUP_DIR)
+}
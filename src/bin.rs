@@ -1,250 +1,2387 @@
-use std::{fs, io, path::PathBuf, time::Instant};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    process::{self, ExitCode},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use clap::Parser;
-use lazy_static::lazy_static;
-use regex::Regex;
+use chrono::{NaiveDate, NaiveTime};
+use clap::{
+    parser::ValueSource, ArgAction, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand,
+};
+use yaml_rust::{Yaml, YamlEmitter};
 
 use cleaner_lib::{
-    get_cfg_path, lines_from_file, lines_to_file, load_yml, n_chars_last_field, n_data_fields,
-    write_osc,
+    archive::{clean_zip, is_zip},
+    audit::{self, Audit},
+    backup::{Backup, BACKUP_DIR},
+    budget::{default_budget, MemoryBudget},
+    build_info::BuildInfo,
+    cert::Certificate,
+    check_formats, check_line_counts, check_osc_prefix, check_readonly,
+    classify::{classify_file, ExtensionStatus},
+    clean_file, collect_ext_map, compile_rules,
+    completeness::{missing_files, name_formats},
+    config,
+    delete::{delete_files, quarantine_files, DeleteStrategy, DEFAULT_WORKERS},
+    deprecated_settings,
+    diff::render_diff,
+    dryrun::DryRun,
+    flag_path, get_cfg_path,
+    hook::{self, Hook, DELETE_PLACEHOLDERS, FINISH_PLACEHOLDERS},
+    instrument::check_majority,
+    load_reference_headers,
+    logfile::{parse_size, RotatingLog},
+    manifest::{file_extension, rules_fingerprint, Entry, Manifest},
+    marker::{self, DONE_MARKER, PARTIAL_MARKER},
+    parse_duration, parse_ext_map,
+    preflight::{check_writable, free_space, FreeSpaceThreshold},
+    preset::{layer, Preset, Source},
+    privilege::{self, ALLOW_ELEVATED_ENV},
+    profile::{record, Profile},
+    repair::{repair_osc, OscState, RepairAction},
+    report::{
+        coverage, outcome_label, render_dry_run, render_dry_run_counts, render_finding,
+        render_foreign_instruments, render_grouped, render_health, render_mapped, render_metrics,
+        render_missing_files, render_osc_prefix, render_overlaps, render_size_outliers,
+        render_slowest, render_unknown_warning, render_would_differ, size_outliers, write_health,
+        write_metrics, Grouped, Limit, MessageCap, Quarantine, Report, ReportWriter, Sample, Tally,
+        DEFAULT_OUTLIER_FRACTION, DEFAULT_OUTLIER_MIN_FILES, GROUP_CAP, HEALTH_FILE,
+    },
+    sample::{parse_fraction, sample_size, select},
+    selftest::{self, Verdict},
+    spotcheck,
+    station::StationPattern,
+    tmpfile::{cleanup_registered, sweep_stale},
+    walk::{self, walk},
+    CheckId, CleanOptions, CompiledRules, FileOutcome, Outcome, RemovedCaps, SkipReason,
 };
 
 /// A tool to clean up V25 log files.
 /// Removes empty files, trailing newlines, incomplete last lines etc.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    /// directory to clean
-    #[arg(short, long)]
-    dirname: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// directory to clean, or a single file or zip archive
+    #[arg(short, long, required_unless_present_any = ["print_config", "build_info"])]
+    dirname: Option<PathBuf>,
+
+    /// defaults for common uses, below the options given on the command line: paranoid
+    /// is a dry run with --backup and a --quarantine next to the directory, and with
+    /// --no-dry-run flags the files the checks would delete instead of deleting them;
+    /// fast cleans and removes them with one thread per core and prints only the summary
+    #[arg(long)]
+    preset: Option<Preset>,
+
+    /// print the effective options of --preset and where their values come from, and
+    /// the cfg file the rules of each file type come from, see `include` in the cfg
+    /// file; then exit
+    #[arg(long, default_value_t = false)]
+    print_config: bool,
+
+    /// print the version, git commit, target and cargo features of this build, then
+    /// exit; reports and the done marker hold the same information
+    #[arg(long, default_value_t = false)]
+    build_info: bool,
 
     /// check files regardless if cleaned before
     #[arg(short, long, default_value_t = false)]
     force: bool,
 
+    /// print what cleaning would do to each file without changing any, e.g. "would
+    /// delete"; the files are cleaned as temporary copies, and neither the markers nor
+    /// the manifest are written
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+
+    /// clean for real with --preset paranoid, which is a dry run otherwise
+    #[arg(long, default_value_t = false, conflicts_with = "dry_run")]
+    no_dry_run: bool,
+
+    /// with --force, skip files that did not change since their last clean according to
+    /// the manifest, and record the cleaned files in it
+    #[arg(long, default_value_t = false, requires = "force")]
+    trust_manifest: bool,
+
+    /// with --trust-manifest, also compare the content hash of files, not only their
+    /// size and modification time
+    #[arg(long, default_value_t = false, requires = "trust_manifest")]
+    manifest_hash: bool,
+
+    /// do not check that the directory is writable before cleaning
+    #[arg(long, default_value_t = false)]
+    no_preflight: bool,
+
+    /// clean as root or as an elevated Windows administrator, which is refused otherwise;
+    /// setting the environment variable V25_ALLOW_ELEVATED=1 does the same, e.g. in
+    /// containers that always run as root. files the checks would delete are flagged
+    /// instead, see --really-delete
+    #[arg(long, default_value_t = false)]
+    allow_elevated: bool,
+
+    /// delete files as the checks say also when running elevated or with --preset
+    /// paranoid, instead of flagging them
+    #[arg(long, default_value_t = false)]
+    really_delete: bool,
+
+    /// record the user, host and working directory of the run in the log, the report and
+    /// the done marker; setting the environment variable V25_AUDIT=1 does the same
+    #[arg(long, default_value_t = false)]
+    audit: bool,
+
+    /// write a certificate of the cleaned directory (file hashes and config fingerprint)
+    #[arg(long, default_value_t = false)]
+    certify: bool,
+
+    /// check files flagged by a previous run again
+    #[arg(long, default_value_t = false)]
+    recheck_flagged: bool,
+
+    /// remove temporary files left behind by crashed runs that are older than this many
+    /// seconds before cleaning
+    #[arg(long)]
+    sweep_temp_older_than: Option<u64>,
+
+    /// memory in MB that loaded files may use together; default: half of the available memory
+    #[arg(long)]
+    memory_budget: Option<u64>,
+
     /// verbose print output
     #[arg(long, default_value_t = false)]
     verbose: bool,
+
+    /// print messages per file while cleaning instead of a summary grouped by action
+    #[arg(long, default_value_t = false)]
+    no_group: bool,
+
+    /// print at most this many verbose messages per check and sum up the others at the
+    /// end of the run; default: all
+    #[arg(long)]
+    max_messages_per_check: Option<usize>,
+
+    /// list the N files (default 10) the run spent the most time on, e.g. to find a
+    /// failing disk; the report has the duration of every file
+    #[arg(long, num_args = 0..=1, default_missing_value = "10")]
+    timings: Option<usize>,
+
+    /// print a unified diff of each modified file
+    #[arg(long, default_value_t = false)]
+    diff: bool,
+
+    /// maximum number of diff lines per file; longer diffs show their start and end
+    #[arg(long, default_value_t = 40, requires = "diff")]
+    diff_max_lines: usize,
+
+    /// only run these checks (comma-separated check ids, see `checks list`)
+    #[arg(long, value_delimiter = ',', conflicts_with = "skip_checks")]
+    only_checks: Option<Vec<CheckId>>,
+
+    /// do not run these checks (comma-separated check ids, see `checks list`)
+    #[arg(long, value_delimiter = ',')]
+    skip_checks: Vec<CheckId>,
+
+    /// maximum number of removed lines recorded per file
+    #[arg(long, default_value_t = RemovedCaps::default().max_lines)]
+    max_removed_lines: usize,
+
+    /// maximum number of bytes of removed lines recorded per file
+    #[arg(long, default_value_t = RemovedCaps::default().max_bytes)]
+    max_removed_bytes: usize,
+
+    /// also write the removed lines to the log file
+    #[arg(long, default_value_t = false)]
+    log_removed_lines: bool,
+
+    /// write a report of the run to this file, as JSON
+    #[arg(long)]
+    report_json: Option<PathBuf>,
+
+    /// write a report of the run to this file, as YAML
+    #[arg(long)]
+    report_yaml: Option<PathBuf>,
+
+    /// write the files of --report-json while they are cleaned, in that order, instead of
+    /// keeping all of them for a report sorted by path; for directories too large to hold
+    /// them. Only the files the run may still change after cleaning are kept: the deleted
+    /// ones and those whose instrument ID is checked against the majority. The options
+    /// that look at all files at the end can't be used with it
+    #[arg(
+        long,
+        default_value_t = false,
+        requires = "report_json",
+        conflicts_with_all = [
+            "report_yaml", "coverage_report", "detect_overlaps", "size_outliers", "health",
+            "spot_check", "timings", "also_evaluate"
+        ]
+    )]
+    report_unordered: bool,
+
+    /// only list the line numbers of removed lines in the report, not their content
+    #[arg(long, default_value_t = false)]
+    no_content_in_report: bool,
+
+    /// add the time covered by the files to the report, per extension; needs
+    /// `timestamp_format` (and optionally `timestamp_column`) in the cfg file
+    #[arg(long, default_value_t = false)]
+    coverage_report: bool,
+
+    /// report gaps between files longer than this, in seconds
+    #[arg(long, default_value_t = 60, requires = "coverage_report")]
+    coverage_max_gap: i64,
+
+    /// warn about files of the same extension covering the same time; needs
+    /// `timestamp_format` in the cfg file like --coverage-report
+    #[arg(long, default_value_t = false)]
+    detect_overlaps: bool,
+
+    /// ignore overlaps up to this many seconds
+    #[arg(long, default_value_t = 0, requires = "detect_overlaps")]
+    overlap_tolerance: i64,
+
+    /// exit with an error code if overlapping files were found
+    #[arg(long, default_value_t = false, requires = "detect_overlaps")]
+    strict_overlaps: bool,
+
+    /// warn about files much smaller than the median size of their extension, e.g. an
+    /// aborted acquisition that passes the checks; never changes a file
+    #[arg(long, default_value_t = false)]
+    size_outliers: bool,
+
+    /// files smaller than this fraction of the median size are outliers
+    #[arg(long, default_value_t = DEFAULT_OUTLIER_FRACTION, value_parser = parse_fraction, requires = "size_outliers")]
+    size_outlier_fraction: f64,
+
+    /// only look for outliers among at least this many kept files of an extension
+    #[arg(long, default_value_t = DEFAULT_OUTLIER_MIN_FILES, requires = "size_outliers")]
+    size_outlier_min_files: usize,
+
+    /// warn about hourly files missing between the first and the last file of the
+    /// directory, per extension with `hourly_file_name` in the cfg file; never changes a
+    /// file
+    #[arg(long, default_value_t = false)]
+    missing_files: bool,
+
+    /// exit with an error code if files are missing
+    #[arg(long, default_value_t = false, requires = "missing_files")]
+    strict_missing: bool,
+
+    /// print one line per extension at the end, e.g. files, truncated tails, deleted
+    /// files and the time covered, and write it to STATION_HEALTH.txt in the directory;
+    /// the time covered needs `timestamp_format` in the cfg file like --coverage-report
+    #[arg(long, default_value_t = false)]
+    health: bool,
+
+    /// list unchanged files in the report; the summary always counts them
+    #[arg(long, default_value_t = true, action = ArgAction::Set)]
+    report_include_unchanged: bool,
+
+    /// warn if more than this fraction of files has an extension not defined in the cfg file
+    #[arg(long, default_value_t = 0.1)]
+    unknown_warn_fraction: f64,
+
+    /// exit with an error code if the unknown extension warning is triggered
+    #[arg(long, default_value_t = false)]
+    strict_unknown: bool,
+
+    /// write Prometheus metrics of the run to this file (node_exporter textfile collector)
+    #[arg(long)]
+    metrics: Option<PathBuf>,
+
+    /// label the metrics with the cleaned directory
+    #[arg(long, default_value_t = false, requires = "metrics")]
+    metrics_label_directory: bool,
+
+    /// warn at the end of the run if the file system of the directory has less free
+    /// space than this, e.g. 5G or 10%; the report and the metrics always have it
+    #[arg(long)]
+    warn_free_space: Option<FreeSpaceThreshold>,
+
+    /// prefix OSC files larger than this size, e.g. 64M, line by line instead of loading
+    /// them; 0 streams all OSC files that need no other fix
+    #[arg(long, value_parser = parse_size)]
+    stream_osc_above: Option<u64>,
+
+    /// what to do if the directory has subdirectories that are not cleaned: notice
+    /// lists them, error also exits without cleaning anything
+    #[arg(long, default_value = "notice")]
+    warn_subdirs: SubdirsAction,
+
+    /// also clean the subdirectories of the directory, e.g. one per day; each gets its
+    /// own markers and manifest, as if it was cleaned on its own, and is skipped once
+    /// its done marker is there. symlinked directories are not followed
+    #[arg(long, default_value_t = false)]
+    recursive: bool,
+
+    /// with --recursive, how many levels of subdirectories to clean; 1 is only the
+    /// subdirectories of the directory. unlimited if not given
+    #[arg(long, requires = "recursive")]
+    max_depth: Option<usize>,
+
+    /// also clean the members of zip archives in the directory (needs the zip feature)
+    #[arg(long, default_value_t = false)]
+    recurse_archives: bool,
+
+    /// clean files with the left extension using the rules of the right one, e.g.
+    /// TXT=DAT; can be given several times
+    #[arg(long, value_parser = parse_ext_map)]
+    ext_map: Vec<(String, String)>,
+
+    /// report files failing the header checks of their extension whose column header
+    /// matches the reference header of another extension
+    #[arg(long, default_value_t = false)]
+    sniff_type: bool,
+
+    /// rename files detected by --sniff-type to the detected extension instead of
+    /// deleting them, and clean them with its rules
+    #[arg(long, default_value_t = false, requires = "sniff_type")]
+    fix_misnamed: bool,
+
+    /// the instrument ID files should have, instead of the expected of instrument_id in
+    /// the cfg file; files with another ID are handled by on_instrument_id_mismatch
+    #[arg(long)]
+    expect_id: Option<String>,
+
+    /// how to remove the files the checks delete, once all files were checked: direct
+    /// removes one after the other, staged renames them into a staging directory that is
+    /// removed at once, parallel removes them with --delete-workers threads
+    #[arg(long, default_value = "direct")]
+    delete_strategy: DeleteStrategy,
+
+    /// number of threads of --delete-strategy=parallel
+    #[arg(long, default_value_t = DEFAULT_WORKERS)]
+    delete_workers: usize,
+
+    /// move the files the checks delete into this directory instead of removing them,
+    /// e.g. to restore them after a bad cfg file, and the files flagged as written by
+    /// another instrument, with their flag; it is created if missing and a name that is
+    /// taken there gets a number. --delete-strategy does not apply then
+    #[arg(long)]
+    quarantine: Option<PathBuf>,
+
+    /// copy each file to .v25_backup/ in its directory before it is rewritten, renamed or
+    /// deleted; a file whose backup exists from an earlier run is left as it is, unless
+    /// --force is given too
+    #[arg(long, default_value_t = false)]
+    backup: bool,
+
+    /// run this command for each removed file, e.g. to send a notification; {path},
+    /// {name}, {check} and {reason} are replaced by the file and why it was deleted.
+    /// it runs without a shell unless --hook-shell, a failure is logged and the run goes on
+    #[arg(long)]
+    on_delete_cmd: Option<String>,
+
+    /// run this command at the end of the run; {directory}, {count_scanned},
+    /// {count_modified}, {count_deleted}, {count_flagged} and {count_errors} are replaced
+    /// by the summary of the run
+    #[arg(long)]
+    on_finish_cmd: Option<String>,
+
+    /// run --on-delete-cmd and --on-finish-cmd with sh -c, with the replaced values quoted
+    /// for it; Unix only
+    #[arg(long, default_value_t = false)]
+    hook_shell: bool,
+
+    /// kill a hook command that runs longer than this many seconds
+    #[arg(long, default_value_t = hook::DEFAULT_TIMEOUT_S, value_parser = clap::value_parser!(u64).range(1..))]
+    hook_timeout: u64,
+
+    /// also evaluate each file under this cfg file, e.g. with stricter settings, and
+    /// report the files whose outcome would differ; files are only changed as the main
+    /// cfg file says
+    #[arg(long)]
+    also_evaluate: Option<PathBuf>,
+
+    /// clean only this fraction of the files, e.g. 0.01, chosen at random, and
+    /// extrapolate the summary to all files; the sampled files are cleaned for real
+    #[arg(long, value_parser = parse_fraction, conflicts_with = "sample_n")]
+    sample: Option<f64>,
+
+    /// clean only this many files, chosen at random, see --sample
+    #[arg(long)]
+    sample_n: Option<usize>,
+
+    /// seed of the random choice of --sample or --sample-n, to repeat a sample;
+    /// default: a new seed for every run
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// after the run, hash this many of the files left unchanged again, chosen at random
+    /// with --seed, and compare them with their hashes from when they were checked; a
+    /// file that differs is a critical error. the files are only hashed with this option
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    spot_check: Option<u64>,
+
+    /// clean only the files of this station, see station_pattern in the cfg file;
+    /// unassigned selects the files the pattern does not match
+    #[arg(long)]
+    station: Option<String>,
+
+    /// stop after looking at this many files, in name order, e.g. to try a new cfg file;
+    /// files skipped by the manifest do not count. the markers are not written
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    limit: Option<u64>,
+
+    /// stop starting new files once the run took this long, e.g. 9m or 1h30m; the file
+    /// being cleaned is finished and the next run cleans the rest. at least one file is
+    /// cleaned per run
+    #[arg(long, value_parser = parse_duration)]
+    max_runtime: Option<Duration>,
+
+    /// number of files cleaned at the same time, by as many threads; the number of cores
+    /// if not given, 1 cleans one file after the other. the output is in name order
+    /// either way
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    jobs: Option<u64>,
+
+    /// also write all output to this log file
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+
+    /// rotate the log file once it exceeds this size, e.g. 512K or 10M
+    #[arg(long, default_value = "10M", value_parser = parse_size)]
+    log_rotate_size: u64,
+
+    /// number of rotated log files to keep
+    #[arg(long, default_value_t = 5)]
+    log_rotate_keep: usize,
 }
 
-const CLEANUP_DONE: &str = "V25Logs_cleaned.done";
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// inspect the checks and transforms applied to files
+    Checks {
+        #[command(subcommand)]
+        action: ChecksCommand,
+    },
+    /// verify that a directory did not change since it was certified with --certify
+    VerifyCert {
+        /// certified directory
+        #[arg(short, long)]
+        dirname: PathBuf,
+    },
+    /// classify the OSC files of a directory by how far older versions prefixed them,
+    /// prefix the data lines that a half processed file lacks
+    RepairOsc {
+        /// directory with the OSC files
+        #[arg(short, long)]
+        dirname: PathBuf,
+        /// date (YYYY-MM-DD) from which on the cleaner kept the last data line of OSC
+        /// files; processed files last modified before are flagged
+        #[arg(long)]
+        fixed_since: Option<NaiveDate>,
+        /// repair files with the left extension as OSC files, e.g. OSX=OSC; can be
+        /// given several times
+        #[arg(long, value_parser = parse_ext_map)]
+        ext_map: Vec<(String, String)>,
+    },
+    /// list the files of a report written with --report-json or --report-yaml whose
+    /// number of data lines after cleaning is off the expected one; exits with 1 if any
+    Report {
+        /// the report, read as YAML if it ends in .yaml or .yml
+        path: PathBuf,
+        /// number of data lines each file should have, e.g. 3600 for hourly files of
+        /// a logger that writes every second
+        #[arg(long)]
+        expect_lines: usize,
+        /// number of lines a file may be off the expected number
+        #[arg(long, default_value_t = 0)]
+        tolerance: usize,
+    },
+    /// check the binary and the cfg file on generated files in a temporary directory: a
+    /// valid, a truncated, an empty file and others per file type; prints PASS, FAIL or
+    /// SKIP per scenario and exits with 1 if any failed
+    SelfTest {
+        /// keep the directory with the cleaned files instead of removing it
+        #[arg(long, default_value_t = false)]
+        keep_dir: bool,
+    },
+}
 
-fn main() -> io::Result<()> {
-    let now = Instant::now();
+#[derive(Subcommand, Debug)]
+enum ChecksCommand {
+    /// print all check identifiers with a description
+    List,
+    /// print the rules that apply to a file and the checks that would fire, without
+    /// changing the file
+    File {
+        /// file to classify
+        path: PathBuf,
+    },
+}
 
-    // get command line args
-    let args = Args::parse();
+/// SubdirsAction is what to do about subdirectories of the cleaned directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SubdirsAction {
+    /// list them after cleaning
+    Notice,
+    /// list them and exit without cleaning
+    Error,
+}
 
-    // cfg file path must be ./cfg/v25_data_cfg.yml, rel. to directory of executable
-    let cfg_path = get_cfg_path()?;
-    let cfg = &load_yml(&cfg_path)[0];
+impl FromStr for SubdirsAction {
+    type Err = String;
 
-    // make sure that all commands such as ../ are resolved:
-    let basepath = fs::canonicalize(args.dirname.clone())?;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "notice" => Ok(SubdirsAction::Notice),
+            "error" => Ok(SubdirsAction::Error),
+            _ => Err(format!(
+                "invalid action '{s}', expected one of notice, error"
+            )),
+        }
+    }
+}
 
-    println!("cleaning files in {:?}", basepath);
+/// exit code if --strict-unknown is set and too many files had unknown extensions
+const EXIT_UNKNOWN_EXTENSIONS: u8 = 3;
+/// exit codes if a single file was cleaned; unchanged is success, errors exit with 1
+const EXIT_FILE_MODIFIED: u8 = 4;
+const EXIT_FILE_DELETED: u8 = 5;
+const EXIT_FILE_SKIPPED: u8 = 6;
+const EXIT_FILE_FLAGGED: u8 = 7;
+/// exit code if --strict-overlaps is set and files cover the same time
+const EXIT_TIME_OVERLAPS: u8 = 8;
+/// exit code if --warn-subdirs=error is set and the directory has subdirectories
+const EXIT_SUBDIRECTORIES: u8 = 9;
+/// exit code if the tool runs elevated without --allow-elevated
+const EXIT_ELEVATED: u8 = 10;
+/// exit code if --max-runtime stopped the run; the next run cleans the rest
+const EXIT_TIMED_OUT: u8 = 11;
+/// exit code if --spot-check found an unchanged file that differs from what was checked
+const EXIT_SPOT_CHECK: u8 = 12;
+/// exit code if --strict-missing is set and hourly files are missing
+const EXIT_MISSING_FILES: u8 = 13;
+/// exit code if the run was interrupted with Ctrl-C, as a shell reports SIGINT
+const EXIT_INTERRUPTED: u8 = 130;
 
-    let cleaned_identifier = [args.dirname, CLEANUP_DONE.to_string()]
-        .iter()
-        .collect::<PathBuf>();
+/// file_exit_code is the exit code of cleaning or classifying a single file.
+fn file_exit_code(outcome: Outcome) -> ExitCode {
+    match outcome {
+        Outcome::Unchanged | Outcome::Protected => ExitCode::SUCCESS,
+        Outcome::Modified => ExitCode::from(EXIT_FILE_MODIFIED),
+        Outcome::Deleted(_) => ExitCode::from(EXIT_FILE_DELETED),
+        Outcome::Flagged(_) => ExitCode::from(EXIT_FILE_FLAGGED),
+        Outcome::Skipped(_) => ExitCode::from(EXIT_FILE_SKIPPED),
+        Outcome::Error => ExitCode::FAILURE,
+    }
+}
+
+/// INTERRUPTED is set by the Ctrl-C handler and checked by the workers between files.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
 
-    // if cleaning is not forced, check if the directory was cleaned before
-    if !args.force {
-        if cleaned_identifier.is_file() {
-            println!("cleanup was already done, found file '{CLEANUP_DONE}' :)");
-            return Ok(());
+/// Log prints messages to stdout and, if configured, appends them to the log file.
+struct Log {
+    file: Option<RotatingLog>,
+}
+
+impl Log {
+    fn line(&mut self, msg: &str) {
+        println!("{msg}");
+        if let Some(file) = &mut self.file {
+            let stamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+            let result = msg
+                .lines()
+                .try_for_each(|line| file.write_line(&format!("{stamp} {line}")));
+            if let Err(err) = result {
+                // don't stop cleaning because of the log file, just stop logging to it
+                println!("! could not write to log file, disabling it: {err}");
+                self.file = None;
+            }
         }
     }
+}
+
+/// Cleaned is what a worker did to one file of the run, see --jobs.
+enum Cleaned {
+    /// the outcomes of the members of an archive, see clean_zip
+    Archive(Vec<FileOutcome>),
+    /// the outcome of a file, with its content before and after cleaning for --diff;
+    /// after is only known for a dry run, a real one left it in the file
+    File {
+        outcome: Box<FileOutcome>,
+        before: Option<Vec<u8>>,
+        after: Option<Vec<u8>>,
+    },
+}
 
-    // collect all files in specified directory
-    let entries: Vec<PathBuf> = fs::read_dir(basepath)?
-        .into_iter()
-        .filter(|r| r.is_ok()) // Get rid of Err variants for Result<DirEntry>
-        .map(|r| r.unwrap().path()) // This is safe, since we only have the Ok variants
-        .filter(|r| r.is_file()) // Filter out directories
+/// Streamed is the report of --report-unordered while the files are cleaned: each file
+/// is written to it as it is recorded, and only added up for the log and the fields of
+/// the report that follow the files.
+struct Streamed {
+    writer: ReportWriter<io::BufWriter<fs::File>>,
+    tally: Tally,
+    grouped: Grouped,
+    /// the lines of the OSC prefixes listed below the groups with --verbose, if kept
+    osc_prefixes: Option<Vec<String>>,
+}
+
+impl Streamed {
+    /// push writes file to the report and adds it up.
+    fn push(&mut self, file: &FileOutcome) -> io::Result<()> {
+        self.writer.push(file)?;
+        self.tally.add(file);
+        self.grouped.add(file);
+        if let (Some(lines), Some(prefix)) = (&mut self.osc_prefixes, &file.osc_prefix) {
+            lines.push(render_osc_prefix(&file.path, prefix));
+        }
+        Ok(())
+    }
+}
+
+/// parent_dir returns the directory of the file at path, which holds its markers.
+fn parent_dir(path: &Path) -> PathBuf {
+    path.parent().unwrap_or(Path::new("")).to_path_buf()
+}
+
+/// render_subdirs lists the subdirectories of base that were not cleaned, at most cap
+/// of them, or returns None if there are none. recursive tells if --recursive was set.
+fn render_subdirs(base: &Path, subdirs: &[PathBuf], recursive: bool, cap: usize) -> Option<String> {
+    if subdirs.is_empty() {
+        return None;
+    }
+    let mut names: Vec<String> = subdirs
+        .iter()
+        .map(|p| {
+            p.strip_prefix(base)
+                .unwrap_or(p)
+                .to_string_lossy()
+                .to_string()
+        })
         .collect();
+    names.sort();
+    let mut msg = format!(
+        "{} subdirector{} not processed, {}:",
+        subdirs.len(),
+        if subdirs.len() == 1 {
+            "y was"
+        } else {
+            "ies were"
+        },
+        if recursive {
+            "symlinked, unreadable or deeper than --max-depth"
+        } else {
+            "only files directly in the directory are cleaned"
+        }
+    );
+    for name in names.iter().take(cap) {
+        msg.push_str(&format!("\n  {name}"));
+    }
+    if names.len() > cap {
+        msg.push_str(&format!("\n  ... and {} more", names.len() - cap));
+    }
+    Some(msg)
+}
 
-    for file_path in entries.iter() {
-        // >>> check #1
-        // make sure the file has an extension and it is defined in config file
-        let mut file_ext = String::new();
-        match file_path.extension() {
-            None => {
-                if args.verbose {
-                    println!("nok: {:?}\n  has no extension -> delete file", file_path)
-                };
-                fs::remove_file(file_path)?;
-                continue;
+/// apply_preset sets the options of args that the preset sets and that were not given
+/// on the command line. returns whether to flag deletes and the effective value of
+/// each option a preset can set, with its source.
+fn apply_preset(
+    args: &mut Args,
+    matches: &ArgMatches,
+) -> (bool, Vec<(&'static str, String, Source)>) {
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    let preset = args.preset;
+
+    let (flag_deletes, flag_source) = layer(
+        given("really_delete").then_some(false),
+        preset.and_then(|preset| preset.value(|options| options.flag_deletes)),
+        false,
+    );
+    let strategy_source;
+    (args.delete_strategy, strategy_source) = layer(
+        given("delete_strategy").then_some(args.delete_strategy),
+        preset.and_then(|preset| preset.value(|options| options.delete_strategy)),
+        args.delete_strategy,
+    );
+    let workers_source;
+    (args.delete_workers, workers_source) = layer(
+        given("delete_workers").then_some(args.delete_workers),
+        preset.and_then(|preset| preset.value(|options| options.delete_workers)),
+        args.delete_workers,
+    );
+    let verbose_source;
+    (args.verbose, verbose_source) = layer(
+        given("verbose").then_some(args.verbose),
+        preset.and_then(|preset| preset.value(|options| options.verbose)),
+        args.verbose,
+    );
+    let dry_run_source;
+    (args.dry_run, dry_run_source) = layer(
+        (given("dry_run") || given("no_dry_run")).then_some(args.dry_run),
+        preset.and_then(|preset| preset.value(|options| options.dry_run)),
+        args.dry_run,
+    );
+    // the quarantine of a preset is next to the directory to clean
+    let beside = |name: PathBuf| {
+        let dir = args.dirname.as_deref()?;
+        let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        Some(dir.parent()?.join(name))
+    };
+    let quarantine_source;
+    (args.quarantine, quarantine_source) = layer(
+        given("quarantine").then(|| args.quarantine.clone()),
+        preset
+            .and_then(|preset| preset.value(|options| options.quarantine))
+            .map(|(preset, name)| (preset, beside(name))),
+        args.quarantine.clone(),
+    );
+    let backup_source;
+    (args.backup, backup_source) = layer(
+        given("backup").then_some(args.backup),
+        preset.and_then(|preset| preset.value(|options| options.backup)),
+        args.backup,
+    );
+    let jobs_source;
+    (args.jobs, jobs_source) = layer(
+        given("jobs").then_some(args.jobs),
+        preset.and_then(|preset| preset.value(|options| options.jobs.map(Some))),
+        args.jobs,
+    );
+    let sources = vec![
+        ("flag_deletes", flag_deletes.to_string(), flag_source),
+        (
+            "delete_strategy",
+            args.delete_strategy.to_string(),
+            strategy_source,
+        ),
+        (
+            "delete_workers",
+            args.delete_workers.to_string(),
+            workers_source,
+        ),
+        ("verbose", args.verbose.to_string(), verbose_source),
+        ("dry_run", args.dry_run.to_string(), dry_run_source),
+        (
+            "quarantine",
+            args.quarantine
+                .as_ref()
+                .map_or("none".to_string(), |dir| format!("{dir:?}")),
+            quarantine_source,
+        ),
+        ("backup", args.backup.to_string(), backup_source),
+        ("jobs", n_jobs(args.jobs).to_string(), jobs_source),
+    ];
+    (flag_deletes, sources)
+}
+
+/// n_jobs returns the number of files cleaned at the same time for --jobs, the number of
+/// cores if not given.
+fn n_jobs(jobs: Option<u64>) -> usize {
+    jobs.map_or_else(
+        || thread::available_parallelism().map_or(1, |n| n.get()),
+        |n| n as usize,
+    )
+}
+
+/// print_outcome prints what happened to a file. warnings are always printed,
+/// findings and skips only in verbose mode, up to the cap per check.
+fn print_outcome(log: &mut Log, file: &FileOutcome, verbose: bool, cap: &mut MessageCap) {
+    if let Some(err) = &file.error {
+        log.line(&format!("! error processing {:?}:\n  {err}", file.path));
+    }
+    for warning in file.warnings.iter() {
+        log.line(&format!("nok: {:?}:\n  {warning}", file.path));
+    }
+    if !verbose {
+        return;
+    }
+    for finding in file.findings.iter().filter(|f| cap.admit(f.check)) {
+        log.line(&render_finding(&file.path, finding));
+    }
+    if let Some(prefix) = &file.osc_prefix {
+        log.line(&render_osc_prefix(&file.path, prefix));
+    }
+    match file.outcome {
+        Outcome::Skipped(SkipReason::UnknownExtension) => log.line(&format!(
+            "unknown file extension '{}', skipping",
+            file.display_extension().unwrap_or_default()
+        )),
+        Outcome::Skipped(SkipReason::InvalidExtension) => log.line(&format!(
+            "! unexpected fail during file extension analysis, skipping {:?}",
+            file.path
+        )),
+        Outcome::Skipped(SkipReason::VanishedDuringRun) => log.line(&format!(
+            "{:?} vanished before it was processed, skipping",
+            file.path
+        )),
+        _ => (),
+    }
+}
+
+/// log_removed_lines writes the recorded removed lines of a file to the log file only,
+/// they would clutter the terminal.
+fn log_removed_lines(log: &mut Log, file: &FileOutcome) {
+    let Some(log_file) = &mut log.file else {
+        return;
+    };
+    let stamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+    let mut lines: Vec<String> = file
+        .removed_lines
+        .iter()
+        .map(|l| {
+            let content = l.content.as_deref().unwrap_or_default();
+            format!(
+                "{stamp} removed {:?} line {}: {content:?}",
+                file.path, l.line
+            )
+        })
+        .collect();
+    if file.removed_lines_truncated {
+        lines.push(format!(
+            "{stamp} removed {:?}: more lines not recorded",
+            file.path
+        ));
+    }
+    if let Err(err) = lines.iter().try_for_each(|line| log_file.write_line(line)) {
+        println!("! could not write to log file, disabling it: {err}");
+        log.file = None;
+    }
+}
+
+/// ValidCfg is what validate_cfg compiled and loaded from a cfg file.
+struct ValidCfg {
+    compiled_rules: HashMap<String, CompiledRules>,
+    reference_headers: HashMap<String, String>,
+    ext_map: HashMap<String, String>,
+}
+
+/// validate_cfg checks the cfg file at cfg_path and the extension mapping, which must
+/// refer to it, and compiles its patterns, so a bad one stops any command before a file
+/// is touched. the error is the line to print.
+fn validate_cfg(
+    cfg: &Yaml,
+    cfg_path: &Path,
+    mappings: &[(String, String)],
+) -> Result<ValidCfg, String> {
+    let cfg_dir = cfg_path.parent().unwrap_or(Path::new("."));
+    let (compiled_rules, reference_headers) = check_readonly(cfg)
+        .and_then(|_| check_line_counts(cfg))
+        .and_then(|_| check_formats(cfg))
+        .and_then(|_| check_osc_prefix(cfg))
+        .and_then(|_| compile_rules(cfg))
+        .and_then(|compiled| Ok((compiled, load_reference_headers(cfg, cfg_dir)?)))
+        .map_err(|err| format!("invalid cfg file {cfg_path:?}: {err}"))?;
+    let ext_map = collect_ext_map(mappings).map_err(|err| format!("invalid --ext-map: {err}"))?;
+    if let Some((from, to)) = mappings
+        .iter()
+        .find(|(_, to)| cfg[to.as_str()].is_badvalue())
+    {
+        return Err(format!(
+            "invalid --ext-map {from}={to}: {to} is not defined in the cfg file {cfg_path:?}"
+        ));
+    }
+    Ok(ValidCfg {
+        compiled_rules,
+        reference_headers,
+        ext_map,
+    })
+}
+
+/// check_file prints the rules that apply to the file at path and the checks that would
+/// fire, for the `checks file` command; see classify_file. the exit code is the one a
+/// cleaning run of the file would have.
+fn check_file(path: &Path) -> io::Result<ExitCode> {
+    let cfg_path = get_cfg_path()?;
+    let cfg = &match config::load(&cfg_path) {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            println!("! invalid cfg file {cfg_path:?}: {err}");
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    let opts = match validate_cfg(cfg, &cfg_path, &[]) {
+        Ok(valid) => CleanOptions {
+            reference_headers: valid.reference_headers,
+            compiled_rules: valid.compiled_rules,
+            ..Default::default()
+        },
+        Err(err) => {
+            println!("! {err}");
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    let class = classify_file(path, cfg, &opts)?;
+    match class.status {
+        ExtensionStatus::Known => {
+            println!("{path:?}: rules of {}", class.effective_extension);
+            let mut rules = String::new();
+            let _ = YamlEmitter::new(&mut rules).dump(&class.rules);
+            for line in rules.lines().skip(1) {
+                println!("  {line}");
             }
-            Some(ext) => match ext.to_ascii_uppercase().to_str() {
-                Some("") => {
-                    if args.verbose {
-                        println!("nok: {:?}\n  has no extension -> delete file", file_path)
-                    };
-                    fs::remove_file(file_path)?;
-                    continue;
+        }
+        ExtensionStatus::Unknown => println!(
+            "{path:?}: extension {} is not defined in the cfg file {cfg_path:?}",
+            class.extension
+        ),
+        ExtensionStatus::None => println!("{path:?}: has no extension"),
+    }
+    for finding in &class.findings {
+        println!("{}", render_finding(path, finding));
+    }
+    if let Some(err) = &class.error {
+        println!("! {path:?}: {err}");
+    }
+    println!("{path:?}: would be {}", outcome_label(class.outcome));
+    Ok(file_exit_code(class.outcome))
+}
+
+fn main() -> io::Result<ExitCode> {
+    let now = Instant::now();
+
+    // get command line args, the preset fills in those not given
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    let (preset_flags_deletes, option_sources) = apply_preset(&mut args, &matches);
+
+    match &args.command {
+        Some(Command::Checks {
+            action: ChecksCommand::List,
+        }) => {
+            for id in CheckId::ALL {
+                println!("{:<32}{}", id, id.description());
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::Checks {
+            action: ChecksCommand::File { path },
+        }) => {
+            return check_file(path);
+        }
+        Some(Command::VerifyCert { dirname }) => {
+            let cert = Certificate::read(dirname)?;
+            let problems = cert.verify(dirname)?;
+            if problems.is_empty() {
+                println!(
+                    "{:?} unchanged since {} ({} files)",
+                    dirname,
+                    cert.run_timestamp,
+                    cert.files.len()
+                );
+                return Ok(ExitCode::SUCCESS);
+            }
+            println!("{:?} changed since {}:", dirname, cert.run_timestamp);
+            for problem in problems {
+                println!("  {problem}");
+            }
+            return Ok(ExitCode::FAILURE);
+        }
+        Some(Command::Report {
+            path,
+            expect_lines,
+            tolerance,
+        }) => {
+            let report = Report::read(path)?;
+            let deviations = report.line_deviations(*expect_lines, *tolerance);
+            println!(
+                "{} of {} counted file(s) deviate from {expect_lines} ± {tolerance} data lines",
+                deviations.len(),
+                report.n_counted()
+            );
+            for deviation in &deviations {
+                println!("  {}", deviation.render());
+            }
+            if report.summary.unchanged > 0
+                && !report.files.iter().any(|f| f.outcome == Outcome::Unchanged)
+            {
+                println!("the report lists no unchanged files, see --report-include-unchanged");
+            }
+            return Ok(if deviations.is_empty() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            });
+        }
+        Some(Command::RepairOsc {
+            dirname,
+            fixed_since,
+            ext_map,
+        }) => {
+            let cfg_path = get_cfg_path()?;
+            let cfg = &match config::load(&cfg_path) {
+                Ok(cfg) => cfg,
+                Err(err) => {
+                    println!("! invalid cfg file {cfg_path:?}: {err}");
+                    return Ok(ExitCode::FAILURE);
                 }
-                Some(other_str) => {
-                    if cfg[other_str].is_badvalue() {
-                        if args.verbose {
-                            println!("unknown file extension '{other_str}', skipping");
-                            continue;
-                        }
-                    } else {
-                        // file extension was found in config, so set file_ext
-                        file_ext = other_str.to_owned();
+            };
+            let ext_map = match validate_cfg(cfg, &cfg_path, ext_map) {
+                Ok(valid) => valid.ext_map,
+                Err(err) => {
+                    println!("! {err}");
+                    return Ok(ExitCode::FAILURE);
+                }
+            };
+            let cfg_osc = &cfg["OSC"];
+            if cfg_osc.is_badvalue() {
+                println!("! OSC is not defined in the cfg file {cfg_path:?}");
+                return Ok(ExitCode::FAILURE);
+            }
+            // files of other extensions are OSC files under --ext-map, too
+            let opts = CleanOptions {
+                ext_map,
+                ..Default::default()
+            };
+            let mut paths: Vec<PathBuf> = fs::read_dir(dirname)?
+                .filter_map(|r| r.ok())
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.is_file() && opts.effective_extension(&file_extension(path)) == "OSC"
+                })
+                .collect();
+            paths.sort();
+            let fixed_since = fixed_since.map(|date| date.and_time(NaiveTime::MIN));
+            let mut counts: BTreeMap<OscState, usize> = BTreeMap::new();
+            let mut n_errors = 0;
+            for path in paths {
+                let repair = match repair_osc(&path, cfg_osc, fixed_since) {
+                    Ok(repair) => repair,
+                    Err(err) => {
+                        println!("! {path:?}: {err}");
+                        n_errors += 1;
+                        continue;
+                    }
+                };
+                *counts.entry(repair.state).or_default() += 1;
+                match repair.action {
+                    RepairAction::None => (),
+                    RepairAction::Reprefixed(n) => {
+                        println!("{path:?}: {} -> prefixed {n} data lines", repair.state)
+                    }
+                    RepairAction::Flagged => println!(
+                        "{path:?}: {} before {} -> flagged, may have lost its last data line",
+                        repair.state,
+                        fixed_since.unwrap_or_default().date()
+                    ),
+                    RepairAction::Skipped(reason) => {
+                        println!("{path:?}: {} -> skipped, {reason}", repair.state)
                     }
                 }
-                None => {
-                    if args.verbose {
-                        println!(
-                            "! unexpected fail during file extension analysis, skipping {:?}",
-                            file_path
-                        );
-                    };
-                    continue;
+            }
+            let counts: Vec<String> = counts
+                .iter()
+                .map(|(state, n)| format!("{n} {state}"))
+                .collect();
+            println!("OSC files: {}, {n_errors} errors", counts.join(", "));
+            if fixed_since.is_none() {
+                println!(
+                    "lost last data lines can't be told from the content, \
+                     use --fixed-since to flag files processed before the fix"
+                );
+            }
+            return Ok(if n_errors > 0 {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            });
+        }
+        Some(Command::SelfTest { keep_dir }) => {
+            let cfg_path = get_cfg_path()?;
+            let cfg = &match config::load(&cfg_path) {
+                Ok(cfg) => cfg,
+                Err(err) => {
+                    println!("! invalid cfg file {cfg_path:?}: {err}");
+                    return Ok(ExitCode::FAILURE);
                 }
-            },
+            };
+            let opts = match validate_cfg(cfg, &cfg_path, &args.ext_map) {
+                Ok(valid) => CleanOptions {
+                    reference_headers: valid.reference_headers,
+                    compiled_rules: valid.compiled_rules,
+                    ..Default::default()
+                },
+                Err(err) => {
+                    println!("! {err}");
+                    return Ok(ExitCode::FAILURE);
+                }
+            };
+            let dir = std::env::temp_dir().join(format!("v25_selftest_{}", process::id()));
+            let results = selftest::run(cfg, &opts, &dir);
+            if !*keep_dir {
+                let _ = fs::remove_dir_all(&dir);
+            }
+            let results = results?;
+            for result in &results {
+                println!("{result}");
+            }
+            let count = |f: fn(&Verdict) -> bool| results.iter().filter(|r| f(&r.verdict)).count();
+            let n_failed = count(|v| matches!(v, Verdict::Fail(_)));
+            println!(
+                "self-test of {cfg_path:?}: {} passed, {n_failed} failed, {} skipped",
+                count(|v| matches!(v, Verdict::Pass(_))),
+                count(|v| matches!(v, Verdict::Skip(_))),
+            );
+            if *keep_dir {
+                println!("the cleaned files are kept in {dir:?}");
+            }
+            return Ok(if n_failed > 0 {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            });
         }
-        file_ext = file_ext.to_ascii_uppercase();
-        // <<< check 1 done.
+        None => (),
+    }
+    if args.build_info {
+        print!("{}", BuildInfo::current());
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.print_config {
+        for (name, value, source) in option_sources {
+            println!("{name}: {value} ({source})");
+        }
+        let cfg_path = get_cfg_path()?;
+        match config::load_with_sources(&cfg_path) {
+            Ok((_, sources)) => {
+                for (file_type, source) in sources {
+                    println!("cfg {file_type}: {source:?}");
+                }
+            }
+            Err(err) => {
+                println!("! invalid cfg file {cfg_path:?}: {err}");
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    let dirname = args
+        .dirname
+        .clone()
+        .expect("dirname is required without subcommand");
 
-        // load file content to a vector of strings
-        let mut content = lines_from_file(file_path)?;
+    // a wrong directory argument as root can remove files no user could
+    let elevated = privilege::is_elevated();
+    if elevated && !args.allow_elevated && !privilege::bypassed() {
+        println!(
+            "! running as root or elevated administrator, nothing was cleaned; pass \
+             --allow-elevated or set {ALLOW_ELEVATED_ENV}=1 to clean anyway"
+        );
+        return Ok(ExitCode::from(EXIT_ELEVATED));
+    }
 
-        let mut write: bool = false;
+    // cfg file path must be ./cfg/v25_data_cfg.yml, rel. to directory of executable
+    let cfg_path = get_cfg_path()?;
+    let cfg = &match config::load(&cfg_path) {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            println!("! invalid cfg file {cfg_path:?}: {err}");
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    let ValidCfg {
+        compiled_rules,
+        reference_headers,
+        ext_map,
+    } = match validate_cfg(cfg, &cfg_path, &args.ext_map) {
+        Ok(valid) => valid,
+        Err(err) => {
+            println!("! {err}");
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    let station_pattern = match StationPattern::from_cfg(cfg) {
+        Ok(pattern) => pattern,
+        Err(err) => {
+            println!("! invalid cfg file {cfg_path:?}: {err}");
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    let name_formats = match name_formats(cfg) {
+        Ok(formats) => formats,
+        Err(err) => {
+            println!("! invalid cfg file {cfg_path:?}: {err}");
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    if args.missing_files && name_formats.is_empty() {
+        println!("! --missing-files needs hourly_file_name in the cfg file {cfg_path:?}");
+        return Ok(ExitCode::FAILURE);
+    }
+    if args.station.is_some() && station_pattern.is_none() {
+        println!("! --station needs a station_pattern in the cfg file {cfg_path:?}");
+        return Ok(ExitCode::FAILURE);
+    }
+    for setting in deprecated_settings(cfg) {
+        println!("! cfg file {cfg_path:?}: {setting}");
+    }
+    // hooks only run if given, a bad command stops the run before any file is touched
+    let hook = |flag: &str, template: &Option<String>, placeholders: &[&str]| {
+        let timeout = Duration::from_secs(args.hook_timeout);
+        template
+            .as_deref()
+            .map(|template| {
+                Hook::new(template, placeholders, args.hook_shell, timeout)
+                    .map_err(|err| format!("invalid {flag} '{template}': {err}"))
+            })
+            .transpose()
+    };
+    let (on_delete, on_finish) =
+        match hook("--on-delete-cmd", &args.on_delete_cmd, DELETE_PLACEHOLDERS).and_then(
+            |on_delete| {
+                Ok((
+                    on_delete,
+                    hook("--on-finish-cmd", &args.on_finish_cmd, FINISH_PLACEHOLDERS)?,
+                ))
+            },
+        ) {
+            Ok(hooks) => hooks,
+            Err(err) => {
+                println!("! {err}");
+                return Ok(ExitCode::FAILURE);
+            }
+        };
 
-        // check #2
-        // remove all empty strings at the end of content (trailing newlines)
-        while content.last() == Some(&"".to_owned()) {
-            if args.verbose {
-                println!("nok: {:?}\n  last line is empty -> remove line", file_path)
+    let opts = CleanOptions {
+        only_checks: args.only_checks.clone(),
+        skip_checks: args.skip_checks.clone(),
+        removed_caps: RemovedCaps {
+            max_lines: args.max_removed_lines,
+            max_bytes: args.max_removed_bytes,
+        },
+        recheck_flagged: args.recheck_flagged,
+        coverage: args.coverage_report || args.detect_overlaps || args.health,
+        reference_headers,
+        compiled_rules,
+        stream_osc_above: args.stream_osc_above,
+        ext_map,
+        sniff_type: args.sniff_type,
+        fix_misnamed: args.fix_misnamed,
+        defer_delete: true,
+        flag_deletes: (elevated || preset_flags_deletes) && !args.really_delete,
+        hash_unchanged: args.spot_check.is_some(),
+        backup: args.backup.then_some(Backup {
+            overwrite: args.force,
+        }),
+        expect_id: args.expect_id.clone(),
+    };
+    if opts.expect_id.is_some()
+        && opts
+            .compiled_rules
+            .values()
+            .all(|rules| rules.instrument_id.is_none())
+    {
+        println!("! --expect-id has nothing to compare with, no instrument_id in the cfg file");
+    }
+    if opts.sniff_type && opts.reference_headers.is_empty() {
+        println!(
+            "! --sniff-type has nothing to compare with, no reference_header_file in the cfg file"
+        );
+    }
+    // files are evaluated under the alternate cfg before they are cleaned
+    let profile = match &args.also_evaluate {
+        Some(path) => {
+            let alternate_cfg = match config::load(path) {
+                Ok(cfg) => cfg,
+                Err(err) => {
+                    println!("! invalid cfg file {path:?} for --also-evaluate: {err}");
+                    return Ok(ExitCode::FAILURE);
+                }
             };
-            content.pop();
-            write = true;
+            let dir = path.parent().unwrap_or(Path::new("."));
+            match Profile::new(alternate_cfg, dir, &opts) {
+                Ok(profile) => Some(profile),
+                Err(err) => {
+                    println!("! invalid cfg file {path:?} for --also-evaluate: {err}");
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+        }
+        None => None,
+    };
+    let dry_run = if args.dry_run {
+        match DryRun::new(&opts) {
+            Ok(dry_run) => Some(dry_run),
+            Err(err) => {
+                println!("! --dry-run: {err}");
+                return Ok(ExitCode::FAILURE);
+            }
         }
+    } else {
+        None
+    };
 
-        // depending on the file extension, determine minimum number of lines.
-        // the default is 2:
-        let mut min_len = 2;
-        // file_ext will only be set if it is defined in cfg yml.
-        match cfg[file_ext.as_str()]["min_n_lines"].as_i64() {
-            Some(n) => min_len = n as usize,
-            None => {
-                println!(
-                "nok: {:?}:\n  failed to obtain minimum number of lines from cfg file; defaulting to {min_len}", file_path
-            )
+    // make sure that all commands such as ../ are resolved:
+    let basepath = fs::canonicalize(&dirname)?;
+
+    let mut log = Log {
+        file: match &args.log_file {
+            Some(path) => Some(RotatingLog::open(
+                path,
+                args.log_rotate_size,
+                args.log_rotate_keep,
+            )?),
+            None => None,
+        },
+    };
+
+    log.line(&format!("cleaning files in {:?}", basepath));
+    if dry_run.is_some() {
+        log.line("dry run, the files are cleaned as temporary copies and left as they are");
+    }
+    let audit = (args.audit || audit::enabled_by_env()).then(Audit::current);
+    if let Some(audit) = &audit {
+        log.line(&format!("audit: {}", audit.render_line()));
+    }
+    if opts.flag_deletes {
+        log.line("! running elevated, files the checks delete are flagged instead; pass --really-delete to delete them");
+    }
+
+    // a single file is cleaned without the marker logic
+    let single_file = basepath.is_file();
+
+    // the quarantine is created up front, a run should not fail after all files were
+    // checked; it must not hold the files it receives
+    let quarantine = match (&args.quarantine, &dry_run) {
+        (Some(dir), None) => match fs::create_dir_all(dir).and_then(|_| fs::canonicalize(dir)) {
+            Ok(dir) if basepath.starts_with(&dir) => {
+                log.line(&format!(
+                    "! --quarantine: {dir:?} holds the files to clean, pick another directory"
+                ));
+                return Ok(ExitCode::FAILURE);
+            }
+            Ok(dir) => {
+                log.line(&format!(
+                    "deleted files are moved to the quarantine {dir:?}"
+                ));
+                Some(dir)
+            }
+            Err(err) => {
+                log.line(&format!("! --quarantine: cannot create {dir:?}: {err}"));
+                return Ok(ExitCode::FAILURE);
+            }
+        },
+        _ => None,
+    };
+
+    // the directories to clean, only the given one unless --recursive is set; a single
+    // file has none
+    let walked = if single_file {
+        walk::Walk::default()
+    } else {
+        let max_depth = match args.max_depth {
+            _ if !args.recursive => 0,
+            Some(depth) => depth,
+            None => usize::MAX,
+        };
+        let mut walked = walk(&basepath, max_depth)?;
+        // the quarantine and the backups are not cleaned, nor listed as directories that
+        // were not
+        let ours = |dir: &PathBuf| {
+            quarantine.as_ref().is_some_and(|q| dir.starts_with(q))
+                || dir
+                    .strip_prefix(&basepath)
+                    .is_ok_and(|rel| rel.iter().any(|name| name == BACKUP_DIR))
+        };
+        walked.dirs.retain(|dir| !ours(dir));
+        walked.skipped.retain(|dir| !ours(dir));
+        walked
+    };
+
+    // if cleaning is not forced, check which directories were cleaned before, completely
+    // or in part; the files a partial run left to clean are kept per directory
+    let mut dirs: BTreeMap<PathBuf, Option<BTreeSet<PathBuf>>> = BTreeMap::new();
+    let mut n_done = 0;
+    for dir in walked.dirs {
+        let state = if args.force {
+            marker::State::Fresh
+        } else {
+            marker::read(&dir)?
+        };
+        match state {
+            marker::State::Done => n_done += 1,
+            marker::State::Partial(names) => {
+                dirs.insert(dir, Some(names.into_iter().collect()));
+            }
+            marker::State::Fresh => {
+                dirs.insert(dir, None);
             }
         }
+    }
+    if !single_file && dirs.is_empty() {
+        log.line(&format!(
+            "cleanup was already done, found file '{DONE_MARKER}' :)"
+        ));
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.recursive {
+        log.line(&format!(
+            "cleaning {} director(ies), {n_done} skipped as already done",
+            dirs.len()
+        ));
+    }
+    // the directories files are written to, the one of a single file otherwise
+    let write_dirs: Vec<&Path> = match basepath.parent() {
+        Some(parent) if single_file => vec![parent],
+        _ => dirs.keys().map(PathBuf::as_path).collect(),
+    };
 
-        if content.len() < min_len {
-            if args.verbose {
-                println!(
-                    "nok: {:?}\n  has less than the minimum {min_len} lines -> delete file",
-                    file_path
-                )
-            };
-            fs::remove_file(file_path)?;
-            continue; // these files should be deleted, so we can skip further tests
-        }
-        // <<< check 2 done.
-
-        // >>> check #3
-        // determine number of columns based on the first line (column header),
-        // and the first line of data. Those must be equal.
-        let n_col_header = n_data_fields(&content[min_len - 2], "\t");
-        let n_col_data = n_data_fields(&content[min_len - 1], "\t");
-        if n_col_data != n_col_header {
-            if args.verbose {
-                println!(
-                    "nok: {:?}\n  has invalid number of fields in first line of data -> delete file",
-                    file_path
-                )
-            };
-            fs::remove_file(file_path)?;
-            continue;
+    // fail early instead of halfway through rewriting a file
+    if !args.no_preflight && dry_run.is_none() {
+        if let Err(err) = write_dirs
+            .iter()
+            .copied()
+            .chain(quarantine.as_deref())
+            .try_for_each(check_writable)
+        {
+            log.line(&format!(
+                "! preflight check failed, nothing was cleaned: {err}"
+            ));
+            return Ok(ExitCode::FAILURE);
         }
-        // <<< check 3 done.
+    }
 
-        // >>> check #4.1
-        // check number of fields in last line, must be the same as column header
-        let n_col_data = n_data_fields(&content[content.len() - 1], "\t");
-        if n_col_data != n_col_header {
-            if args.verbose {
-                println!(
-                    "nok: {:?}\n  {n_col_data} field(s) in last line of data but header has {n_col_header} -> remove line",
-                    file_path
-                )
+    if let (Some(age), None) = (args.sweep_temp_older_than, &dry_run) {
+        let mut n = 0;
+        for dir in write_dirs.iter() {
+            n += sweep_stale(dir, Duration::from_secs(age))?;
+        }
+        log.line(&format!("removed {n} stale temporary file(s)"));
+    }
+
+    // collect all files in the directories to clean
+    let mut entries = if single_file {
+        vec![basepath.clone()]
+    } else {
+        let mut entries = Vec::new();
+        for dir in dirs.keys() {
+            entries.extend(walk::files(dir)?);
+        }
+        entries
+    };
+    let subdirs = walked.skipped;
+    // in name order, so --limit takes the same files every run
+    entries.sort();
+    // before the files are picked below, the whole directory counts
+    let missing = if args.missing_files && !single_file {
+        missing_files(&entries, &name_formats)
+    } else {
+        Vec::new()
+    };
+    // a partial run before left these files to clean, the others are done
+    if dirs.values().any(Option::is_some) {
+        entries.retain(|path| {
+            let resume = path
+                .parent()
+                .and_then(|dir| dirs.get(dir))
+                .and_then(Option::as_ref);
+            resume.is_none_or(|names| {
+                path.file_name()
+                    .is_some_and(|name| names.contains(Path::new(name)))
+            })
+        });
+        let resumed = entries
+            .iter()
+            .filter(|path| {
+                path.parent()
+                    .is_some_and(|dir| dirs.get(dir).is_some_and(Option::is_some))
+            })
+            .count();
+        log.line(&format!(
+            "resuming, cleaning the {resumed} file(s) left in '{PARTIAL_MARKER}'"
+        ));
+    }
+    // the files of one station, the others are left as they are
+    if let (Some(station), Some(pattern)) = (&args.station, &station_pattern) {
+        entries.retain(|path| pattern.station(path) == *station);
+        log.line(&format!(
+            "cleaning the {} file(s) of station {station}",
+            entries.len()
+        ));
+    }
+    // a random sample of the files, to estimate what cleaning all of them would do
+    let n_total = entries.len();
+    let n_sample = match (args.sample, args.sample_n) {
+        _ if single_file => None,
+        (Some(fraction), _) => Some(sample_size(n_total, fraction)),
+        (None, n) => n,
+    };
+    let seed = args.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64)
+    });
+    if let Some(n) = n_sample {
+        entries = select(entries, n, seed);
+        log.line(&format!(
+            "sampled {} of {n_total} files, repeat with --seed {seed}",
+            entries.len()
+        ));
+        if dry_run.is_none() {
+            log.line(
+                "! the sampled files are cleaned, not only checked; sample a copy to keep them",
+            );
+        }
+    }
+    let subdirs_notice = render_subdirs(&basepath, &subdirs, args.recursive, GROUP_CAP);
+    if let (Some(notice), SubdirsAction::Error) = (&subdirs_notice, args.warn_subdirs) {
+        log.line(&format!("! {notice}"));
+        log.line("! --warn-subdirs=error is set, nothing was cleaned");
+        return Ok(ExitCode::from(EXIT_SUBDIRECTORIES));
+    }
+
+    // on Ctrl-C, finish the files being cleaned and stop; a second Ctrl-C exits immediately
+    if let Err(err) = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            cleanup_registered();
+            process::exit(EXIT_INTERRUPTED.into());
+        }
+    }) {
+        log.line(&format!("! could not install Ctrl-C handler: {err}"));
+    }
+
+    let budget = MemoryBudget::new(
+        args.memory_budget
+            .map_or_else(default_budget, |mb| mb * 1024 * 1024),
+    );
+
+    // a manifest lives next to each done marker, or next to a single file
+    let mut manifests: BTreeMap<PathBuf, Manifest> = BTreeMap::new();
+    for dir in write_dirs.iter() {
+        let manifest = if args.trust_manifest {
+            Manifest::read(dir).unwrap_or_else(|err| {
+                log.line(&format!("! invalid manifest, checking all files: {err}"));
+                Manifest::default()
+            })
+        } else {
+            Manifest::default()
+        };
+        manifests.insert(dir.to_path_buf(), manifest);
+    }
+
+    // the sizes before cleaning, a runt is compared with what its siblings were
+    let sizes: HashMap<PathBuf, u64> = if args.size_outliers {
+        entries
+            .iter()
+            .filter_map(|path| Some((path.clone(), fs::metadata(path).ok()?.len())))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+    // verbose lists all files of a group, otherwise the list is capped
+    let group_cap = (!args.verbose).then_some(GROUP_CAP);
+    // a report of a single file is as small as it gets
+    let mut streamed = match (&args.report_json, args.report_unordered && !single_file) {
+        (Some(path), true) => Some(Streamed {
+            writer: ReportWriter::new(
+                io::BufWriter::new(fs::File::create(path)?),
+                args.report_include_unchanged,
+                args.no_content_in_report,
+            )?,
+            tally: Tally::default(),
+            grouped: Grouped::new(group_cap),
+            osc_prefixes: (!args.no_group && args.verbose).then(Vec::new),
+        }),
+        _ => None,
+    };
+    // the files the run may still change after the loop, they are streamed after that
+    let held = |outcome: &FileOutcome| {
+        matches!(outcome.outcome, Outcome::Deleted(_))
+            || (quarantine.is_some() && outcome.outcome == Outcome::Flagged(CheckId::InstrumentId))
+            // see check_majority
+            || (outcome.instrument_id.is_some() && outcome.expected_instrument_id.is_none())
+    };
+    // the first error writing the streamed report, it stops the workers
+    let mut report_error: Option<io::Error> = None;
+    let stop = AtomicBool::new(false);
+    let mut outcomes: Vec<FileOutcome> = match streamed {
+        Some(_) => Vec::new(),
+        None => Vec::with_capacity(entries.len()),
+    };
+    // files that failed, they are cleaned again by the next run
+    let mut unfinished: Vec<PathBuf> = Vec::new();
+    // files the checks deleted, with the index of their outcome; removed after the loop
+    let mut to_delete: Vec<(usize, PathBuf)> = Vec::new();
+    let mut message_cap = MessageCap::new(args.max_messages_per_check);
+    // archives give one outcome per member, so count the entries separately
+    let mut n_processed = 0;
+    // files looked at, for --limit; the index of the next job
+    let mut n_evaluated = 0;
+    let mut limited = false;
+
+    let rules_of = |file_path: &Path| {
+        if args.trust_manifest {
+            let ext = file_extension(file_path);
+            rules_fingerprint(cfg, opts.effective_extension(&ext), &opts)
+        } else {
+            String::new()
+        }
+    };
+    // the files the manifest vouches for are skipped, the others are the jobs of the
+    // workers, up to --limit of them; the entries after that are not looked at
+    let mut current: HashSet<&PathBuf> = HashSet::new();
+    let mut jobs: Vec<&PathBuf> = Vec::new();
+    let mut n_listed = entries.len();
+    for (i, file_path) in entries.iter().enumerate() {
+        if args.trust_manifest
+            && manifests
+                .get(&parent_dir(file_path))
+                .is_some_and(|manifest| {
+                    manifest.is_current(file_path, &rules_of(file_path), args.manifest_hash)
+                })
+        {
+            current.insert(file_path);
+        } else if args.limit.is_some_and(|limit| jobs.len() as u64 == limit) {
+            limited = true;
+            n_listed = i;
+            break;
+        } else {
+            jobs.push(file_path);
+        }
+    }
+
+    // clean_one is the work on one file, done by a worker
+    let clean_one = |file_path: &Path| -> Cleaned {
+        // archives are cleaned member by member, see clean_zip
+        if is_zip(file_path) && (single_file || args.recurse_archives) {
+            // the cleaned archive is built in memory
+            let size = fs::metadata(file_path).map_or(0, |m| m.len());
+            let permit = budget.acquire(size);
+            let members = match &dry_run {
+                Some(dry_run) => dry_run.evaluate_zip(file_path, cfg),
+                None => clean_zip(file_path, cfg, &opts),
+            }
+            .unwrap_or_else(|err| vec![FileOutcome::from_error(file_path, &err)]);
+            drop(permit);
+            return Cleaned::Archive(members);
+        }
+        // keep the original content to show what was changed
+        let before = if args.diff {
+            fs::read(file_path).ok()
+        } else {
+            None
+        };
+        // hold the size of the file from the budget while it is loaded and rewritten
+        let size = fs::metadata(file_path).map_or(0, |m| m.len());
+        let permit = budget.acquire(size);
+        let started = Instant::now();
+        let alternate = profile.as_ref().map(|profile| {
+            profile
+                .evaluate(file_path)
+                .unwrap_or_else(|err| FileOutcome::from_error(file_path, &err))
+        });
+        // an I/O error on one file should not stop the cleaning of the others
+        let (mut outcome, after) = match &dry_run {
+            Some(dry_run) => dry_run.evaluate(file_path, cfg, args.diff),
+            None => clean_file(file_path, cfg, &opts).map(|outcome| (outcome, None)),
+        }
+        .unwrap_or_else(|err| (FileOutcome::from_error(file_path, &err), None));
+        // failed reads and the alternate cfg take their time too
+        outcome.duration_us = Some(started.elapsed().as_micros() as u64);
+        drop(permit);
+        if let Some(alternate) = &alternate {
+            record(&mut outcome, alternate);
+        }
+        Cleaned::File {
+            outcome: Box::new(outcome),
+            before,
+            after,
+        }
+    };
+
+    // the workers take the jobs in order, each cleaning one file at a time; the results
+    // are logged and recorded here in the same order, so the lines of one file are never
+    // torn apart by another's, and the jobs done are always the first ones
+    let n_workers = n_jobs(args.jobs).clamp(1, jobs.len().max(1));
+    // the tests interrupt a run after some files, see testutil::INTERRUPT_AFTER
+    #[cfg(feature = "test-util")]
+    let interrupt_after = cleaner_lib::testutil::interrupt_after();
+    #[cfg(not(feature = "test-util"))]
+    let interrupt_after: Option<usize> = None;
+    let next_job = AtomicUsize::new(0);
+    let runtime_exceeded = AtomicBool::new(false);
+    let (sender, results) = mpsc::channel::<(usize, Cleaned)>();
+    thread::scope(|scope| {
+        for _ in 0..n_workers {
+            let sender = sender.clone();
+            let (clean_one, jobs, next_job) = (&clean_one, &jobs, &next_job);
+            let (runtime_exceeded, stop) = (&runtime_exceeded, &stop);
+            scope.spawn(move || loop {
+                if INTERRUPTED.load(Ordering::SeqCst) || stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                // at least one file is cleaned per run
+                if next_job.load(Ordering::SeqCst) > 0
+                    && args.max_runtime.is_some_and(|max| now.elapsed() >= max)
+                {
+                    runtime_exceeded.store(true, Ordering::SeqCst);
+                    break;
+                }
+                let job = next_job.fetch_add(1, Ordering::SeqCst);
+                if interrupt_after.is_some_and(|n| job >= n) {
+                    INTERRUPTED.store(true, Ordering::SeqCst);
+                    break;
+                }
+                let Some(file_path) = jobs.get(job) else {
+                    break;
+                };
+                if sender.send((job, clean_one(file_path))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        // record keeps the outcome of a file, or writes it to the streamed report
+        let mut record = |mut outcome: FileOutcome, outcomes: &mut Vec<FileOutcome>| {
+            let streamed = streamed
+                .as_mut()
+                .filter(|_| report_error.is_none() && !held(&outcome));
+            let Some(streamed) = streamed else {
+                outcomes.push(outcome);
+                return;
             };
-            content.pop(); // coming from #3, if we pop one line, we still have at least one line of data
-            write = true;
-        }
-        // <<< check 4.1 done.
-
-        // >>> check #4.2
-        // check the last field of the last line. assume that the line is
-        // corrupted if that field has less characters than the last field
-        // of the preceeding line.
-        // this can only be done if there are at least two lines of data.
-        if content.len() > min_len {
-            let have = n_chars_last_field(&content[content.len() - 1], "\t").unwrap();
-            let want = n_chars_last_field(&content[content.len() - 2], "\t").unwrap();
-            if have < want {
-                if args.verbose {
-                    println!(
-                        "nok: {:?}\n  last field of last line has {have} character(s), but want {want} -> remove line",
-                        file_path
-                    )
+            if let Some(pattern) = &station_pattern {
+                outcome.station = Some(pattern.station(&outcome.path));
+            }
+            if let Err(err) = streamed.push(&outcome) {
+                report_error = Some(err);
+                stop.store(true, Ordering::SeqCst);
+            }
+        };
+        // results that arrived before those of earlier jobs
+        let mut waiting: BTreeMap<usize, Cleaned> = BTreeMap::new();
+        for file_path in entries[..n_listed].iter() {
+            let manifest = manifests.entry(parent_dir(file_path)).or_default();
+            if current.contains(file_path) {
+                n_processed += 1;
+                // the file is as its last clean left it
+                let lines = manifest
+                    .entry(file_path)
+                    .and_then(|entry| entry.lines_after);
+                let outcome = FileOutcome {
+                    lines_before: lines,
+                    lines_after: lines,
+                    ..FileOutcome::skipped(file_path, SkipReason::Manifest)
                 };
-                content.pop();
-                write = true;
+                record(outcome, &mut outcomes);
+                continue;
+            }
+            // the workers stopped before this job if all of them are gone without it
+            let cleaned = loop {
+                if let Some(cleaned) = waiting.remove(&n_evaluated) {
+                    break Some(cleaned);
+                }
+                match results.recv() {
+                    Ok((done, cleaned)) => {
+                        waiting.insert(done, cleaned);
+                    }
+                    Err(_) => break None,
+                }
+            };
+            let Some(cleaned) = cleaned else {
+                break;
+            };
+            n_evaluated += 1;
+            n_processed += 1;
+            let (outcome, before, after) = match cleaned {
+                Cleaned::Archive(members) => {
+                    for outcome in members.iter() {
+                        if args.no_group {
+                            print_outcome(&mut log, outcome, args.verbose, &mut message_cap);
+                        }
+                        if args.log_removed_lines {
+                            log_removed_lines(&mut log, outcome);
+                        }
+                        if let Some(line) = dry_run.as_ref().and_then(|_| render_dry_run(outcome)) {
+                            log.line(&line);
+                        }
+                    }
+                    manifest.forget(file_path);
+                    if members.iter().any(|m| m.outcome == Outcome::Error) {
+                        unfinished.push(file_path.clone());
+                    }
+                    for member in members {
+                        record(member, &mut outcomes);
+                    }
+                    continue;
+                }
+                Cleaned::File {
+                    outcome,
+                    before,
+                    after,
+                } => (*outcome, before, after),
+            };
+            if let (Some(before), Outcome::Modified) = (&before, &outcome.outcome) {
+                // a dry run left the file as it was, the copy had the content
+                let after = after
+                    .or_else(|| fs::read(outcome.renamed_to.as_deref().unwrap_or(file_path)).ok());
+                if let Some(after) = after {
+                    let diff = render_diff(
+                        file_path,
+                        &String::from_utf8_lossy(before),
+                        &String::from_utf8_lossy(&after),
+                        args.diff_max_lines,
+                    );
+                    log.line(diff.trim_end());
+                }
+            }
+            if args.no_group {
+                print_outcome(&mut log, &outcome, args.verbose, &mut message_cap);
+            }
+            if args.log_removed_lines {
+                log_removed_lines(&mut log, &outcome);
+            }
+            if let Some(line) = dry_run.as_ref().and_then(|_| render_dry_run(&outcome)) {
+                log.line(&line);
+            }
+            // only files that passed all checks, possibly after fixing them, are trusted;
+            // warnings would be hidden if the file was skipped next time
+            let passed = outcome.warnings.is_empty() && !outcome.needs_attention;
+            match outcome.outcome {
+                Outcome::Unchanged | Outcome::Modified if args.trust_manifest && passed => {
+                    let rules = rules_of(file_path);
+                    match Entry::of(file_path, &rules, args.manifest_hash) {
+                        Ok(entry) => manifest.record(
+                            file_path,
+                            Entry {
+                                lines_before: outcome.lines_before,
+                                lines_after: outcome.lines_after,
+                                ..entry
+                            },
+                        ),
+                        Err(_) => manifest.forget(file_path),
+                    }
+                }
+                _ => manifest.forget(file_path),
+            }
+            if let Outcome::Error | Outcome::Skipped(SkipReason::ChangedDuringRun) = outcome.outcome
+            {
+                unfinished.push(file_path.clone());
+            }
+            if let (Outcome::Deleted(_), None) = (outcome.outcome, &dry_run) {
+                let path = outcome.renamed_to.clone();
+                to_delete.push((outcomes.len(), path.unwrap_or_else(|| file_path.clone())));
+            }
+            record(outcome, &mut outcomes);
+        }
+    });
+    if let Some(err) = &report_error {
+        log.line(&format!("! could not write the report, stopped: {err}"));
+    }
+    let timed_out = runtime_exceeded.load(Ordering::SeqCst) && n_processed < n_listed;
+    // the ID most files of a directory have is only known now
+    for majority in check_majority(&mut outcomes, &opts, dry_run.is_none()) {
+        if let Some(notice) = majority.render_tie() {
+            log.line(&notice);
+        }
+    }
+    for outcome in outcomes.iter() {
+        if outcome.outcome == Outcome::Flagged(CheckId::InstrumentId) {
+            if let Some(manifest) = manifests.get_mut(&parent_dir(&outcome.path)) {
+                manifest.forget(&outcome.path);
+            }
+        }
+    }
+    for line in message_cap.render_suppressed() {
+        log.line(&line);
+    }
+    // files may have been replaced since they were checked, e.g. rotated by the
+    // acquisition software; those are left alone
+    to_delete.retain(|(i, path)| {
+        let still = outcomes[*i].still_as_read(path);
+        if !still {
+            if args.no_group {
+                print_outcome(&mut log, &outcomes[*i], args.verbose, &mut message_cap);
+            }
+            if let Outcome::Error | Outcome::Skipped(SkipReason::ChangedDuringRun) =
+                outcomes[*i].outcome
+            {
+                unfinished.push(path.clone());
+            }
+        }
+        still
+    });
+    let mut n_quarantined = 0;
+    if !to_delete.is_empty() {
+        let start = Instant::now();
+        let paths: Vec<PathBuf> = to_delete.iter().map(|(_, path)| path.clone()).collect();
+        let mut failed = Vec::new();
+        if let Some(quarantine) = &quarantine {
+            for (path, moved) in quarantine_files(quarantine, &paths) {
+                if let Err(err) = moved {
+                    failed.push((path, err));
+                }
+            }
+        } else {
+            // a staged delete moves the files within their directory
+            let mut by_dir: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+            for path in paths.iter() {
+                by_dir
+                    .entry(parent_dir(path))
+                    .or_default()
+                    .push(path.clone());
+            }
+            for (dir, paths) in by_dir.iter() {
+                failed.extend(delete_files(
+                    dir,
+                    paths,
+                    args.delete_strategy,
+                    args.delete_workers,
+                ));
+            }
+        }
+        n_quarantined = quarantine
+            .as_ref()
+            .map_or(0, |_| paths.len() - failed.len());
+        for (path, err) in failed.iter() {
+            if let Some((i, _)) = to_delete.iter().find(|(_, p)| p == path) {
+                outcomes[*i] = FileOutcome {
+                    findings: std::mem::take(&mut outcomes[*i].findings),
+                    ..FileOutcome::from_error(&outcomes[*i].path, err)
+                };
+                unfinished.push(path.clone());
+            }
+        }
+        log.line(&match &quarantine {
+            Some(quarantine) => format!(
+                "moved {} of {} deleted files to the quarantine {quarantine:?} in {:.2?}",
+                paths.len() - failed.len(),
+                paths.len(),
+                start.elapsed(),
+            ),
+            None => format!(
+                "removed {} of {} deleted files in {:.2?} ({} strategy)",
+                paths.len() - failed.len(),
+                paths.len(),
+                start.elapsed(),
+                args.delete_strategy
+            ),
+        });
+        if let Some(hook) = &on_delete {
+            for (i, path) in to_delete.iter() {
+                let Outcome::Deleted(check) = outcomes[*i].outcome else {
+                    continue; // could not be removed, see above
+                };
+                let reason = outcomes[*i]
+                    .findings
+                    .iter()
+                    .find(|finding| finding.check == check)
+                    .map_or_else(|| check.description().to_string(), |f| f.message.clone());
+                let values = [
+                    ("path", path.to_string_lossy().into_owned()),
+                    (
+                        "name",
+                        path.file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .into_owned(),
+                    ),
+                    ("check", check.to_string()),
+                    ("reason", reason),
+                ];
+                if let Err(err) = hook.run(&values) {
+                    log.line(&format!("! --on-delete-cmd for {path:?}: {err}"));
+                }
             }
         }
-        // <<< check 4.2 done.
+    }
+    // the files of another instrument are flagged, and with a quarantine moved there
+    // with their flag, they are not data of this directory
+    if let Some(quarantine) = &quarantine {
+        let foreign: Vec<(usize, PathBuf)> = outcomes
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, outcome)| outcome.outcome == Outcome::Flagged(CheckId::InstrumentId))
+            .filter_map(|(i, outcome)| {
+                let path = outcome.renamed_to.clone();
+                let path = path.unwrap_or_else(|| outcome.path.clone());
+                if outcome.still_as_read(&path) {
+                    return Some((i, path));
+                }
+                if let Outcome::Error | Outcome::Skipped(SkipReason::ChangedDuringRun) =
+                    outcome.outcome
+                {
+                    unfinished.push(path);
+                }
+                None
+            })
+            .collect();
+        let paths: Vec<PathBuf> = foreign.iter().map(|(_, path)| path.clone()).collect();
+        let mut n_moved = 0;
+        for ((i, _), (path, moved)) in foreign.iter().zip(quarantine_files(quarantine, &paths)) {
+            match moved {
+                Ok(target) => {
+                    n_moved += 1;
+                    let _ = fs::rename(flag_path(&path), flag_path(&target));
+                }
+                Err(err) => outcomes[*i]
+                    .warnings
+                    .push(format!("could not move it to the quarantine: {err}")),
+            }
+        }
+        if !paths.is_empty() {
+            log.line(&format!(
+                "moved {n_moved} of {} files of another instrument to the quarantine {quarantine:?}",
+                paths.len()
+            ));
+        }
+        n_quarantined += n_moved;
+    }
+    // the unchanged files must still be what was checked, after the deletes and hooks
+    let spot_check = args
+        .spot_check
+        .map(|n| spotcheck::spot_check(&outcomes, n as usize, seed));
 
-        // >>> check #5
-        // after removing the last line again in #4.2, content could be too short...
-        if content.len() < min_len {
-            if args.verbose {
-                println!(
-                    "nok: {:?}\n  has less than the minimum {min_len} lines -> delete file",
-                    file_path
-                )
+    // stations only group what is reported, the files were cleaned alike
+    if let Some(pattern) = &station_pattern {
+        for outcome in outcomes.iter_mut() {
+            outcome.station = Some(pattern.station(&outcome.path));
+        }
+    }
+    if args.trust_manifest && dry_run.is_none() {
+        for (dir, manifest) in manifests.iter_mut() {
+            manifest.files.retain(|name, _| dir.join(name).is_file());
+            if let Err(err) = manifest.write(dir) {
+                log.line(&format!("! could not write manifest: {err}"));
+            }
+        }
+    }
+
+    // the held files are streamed last, the report has all files now
+    let mut report_writer = None;
+    let (tally, grouped, osc_prefixes) = match streamed {
+        Some(mut streamed) => {
+            for outcome in outcomes.drain(..) {
+                if let Err(err) = streamed.push(&outcome) {
+                    report_error.get_or_insert(err);
+                }
+            }
+            report_writer = Some(streamed.writer);
+            let grouped = if args.no_group {
+                String::new()
+            } else {
+                streamed.grouped.render()
             };
-            fs::remove_file(file_path)?;
-            continue;
+            let osc_prefixes = streamed.osc_prefixes.unwrap_or_default();
+            (streamed.tally, grouped, osc_prefixes)
+        }
+        None if args.no_group => (Tally::of(&outcomes), String::new(), Vec::new()),
+        None => {
+            let grouped = render_grouped(&outcomes, group_cap);
+            // without grouping, print_outcome prints these with the findings
+            let osc_prefixes = outcomes
+                .iter()
+                .filter(|_| args.verbose)
+                .filter_map(|outcome| {
+                    Some(render_osc_prefix(
+                        &outcome.path,
+                        outcome.osc_prefix.as_ref()?,
+                    ))
+                })
+                .collect();
+            (Tally::of(&outcomes), grouped, osc_prefixes)
+        }
+    };
+    if !grouped.is_empty() {
+        log.line(grouped.trim_end());
+    }
+    for line in osc_prefixes.iter() {
+        log.line(line);
+    }
+
+    let unknown_warning = render_unknown_warning(&tally, args.unknown_warn_fraction);
+    if let Some(warning) = &unknown_warning {
+        log.line(warning);
+    }
+
+    let time_coverage = if opts.coverage {
+        let tolerance = args.detect_overlaps.then_some(args.overlap_tolerance);
+        coverage(&outcomes, args.coverage_max_gap, tolerance)
+    } else {
+        Default::default()
+    };
+    let overlap_warning = render_overlaps(&time_coverage);
+    if let Some(warning) = &overlap_warning {
+        log.line(warning);
+    }
+    let outliers = if args.size_outliers {
+        size_outliers(
+            &outcomes,
+            &sizes,
+            args.size_outlier_min_files,
+            args.size_outlier_fraction,
+        )
+    } else {
+        Vec::new()
+    };
+    if let Some(warning) = render_size_outliers(&outliers) {
+        log.line(&warning);
+    }
+    if let Some(warning) = render_foreign_instruments(&tally) {
+        log.line(&warning);
+    }
+    let cap = if args.verbose { usize::MAX } else { GROUP_CAP };
+    let missing_warning = render_missing_files(&missing, cap);
+    if let Some(warning) = &missing_warning {
+        log.line(warning);
+    }
+    if let Some(spot_check) = &spot_check {
+        log.line(&spot_check.render());
+    }
+    if let Some(notice) = &subdirs_notice {
+        log.line(notice);
+    }
+    if let Some(notice) = render_mapped(&tally) {
+        log.line(&notice);
+    }
+    if let Some(path) = &args.also_evaluate {
+        let cap = if args.verbose { usize::MAX } else { GROUP_CAP };
+        if let Some(notice) = render_would_differ(&outcomes, path, cap) {
+            log.line(&notice);
         }
-        // <<< check 5 done.
+    }
+
+    if let Some(n) = args.timings {
+        if let Some(slowest) = render_slowest(&outcomes, n) {
+            log.line(&slowest);
+        }
+    }
+
+    let interrupted =
+        n_processed < entries.len() && !limited && !timed_out && report_error.is_none();
+    if limited {
+        log.line(&format!(
+            "! limited by --limit to {} files, {} of {} files were not looked at; the markers \
+             were not written",
+            n_evaluated,
+            entries.len() - n_processed,
+            entries.len()
+        ));
+    }
+    if timed_out {
+        log.line(&format!(
+            "! stopped by --max-runtime after {:.2?}, cleaned {} of {} files; the next run \
+             cleans the rest",
+            now.elapsed(),
+            n_processed,
+            entries.len()
+        ));
+    }
+    if interrupted {
+        log.line(&format!(
+            "! interrupted, cleaned {} of {} files; run again to clean the rest",
+            n_processed,
+            entries.len()
+        ));
+    }
 
-        // all checked, write updated data back to file
-        if file_ext.to_ascii_uppercase() == "OSC" {
-            // special case: oscar / chemiluminescence detector files.
-            lazy_static! { // use lazy_static to avoid regex compilation in each loop iteration
-                static ref RE_DT: Regex =
-                    Regex::new(r"\d{2}\.\d{2}\.\d{2} \d{2}:\d{2}:\d{2}\.\d{2}").unwrap();
+    // dump an empty file after all files were cleaned, else list the files left
+    // a sample, a station or a limit leaves the markers as they are, the other files
+    // were not looked at
+    let whole_directory = n_sample.is_none() && args.station.is_none() && !limited;
+    // like the markers, only a complete run of a directory is recorded, before the
+    // certificate so that it is written by the same run
+    let health = args
+        .health
+        .then(|| render_health(&outcomes, &time_coverage));
+    if let Some(health) = &health {
+        if !single_file && whole_directory && !interrupted && !timed_out && dry_run.is_none() {
+            if let Err(err) = write_health(&basepath, health, SystemTime::now()) {
+                log.line(&format!("! could not write '{HEALTH_FILE}': {err}"));
             }
-            // check datetime format in first line of file,
-            // also make sure the file has not been updated before
-            let datetime = content[0].clone();
-            if RE_DT.is_match(datetime.as_str()) && !content[4].contains("DateTime") {
-                // update header line and write to file
-                content[4] = "\tDateTime".to_string() + content[4].clone().as_str();
-                write_osc(file_path, content, 5, &datetime)?;
+        }
+    }
+    if !single_file && whole_directory && dry_run.is_none() {
+        unfinished.extend(entries[n_processed..].iter().cloned());
+        // each directory has its own markers, with --recursive too
+        for dir in dirs.keys() {
+            let left: Vec<PathBuf> = unfinished
+                .iter()
+                .filter(|path| path.parent() == Some(dir.as_path()))
+                .cloned()
+                .collect();
+            if let Err(err) = marker::write(dir, &left, &BuildInfo::current(), audit.as_ref()) {
+                log.line(&format!("! could not write the marker file: {err}"));
+            }
+            if left.is_empty() && args.certify {
+                Certificate::new(dir, &cfg_path)?.write(dir)?;
             }
-        } else if write {
-            lines_to_file(file_path, content)?;
         }
-
-        // // write false and not an oscar file:
-        // if args.verbose {
-        //     println!("ok:  {:?}", file_path)
-        // }
+        if !unfinished.is_empty() {
+            log.line(&format!(
+                "! {} file(s) not cleaned, listed in '{PARTIAL_MARKER}'; the next run cleans only these",
+                unfinished.len()
+            ));
+        }
     }
 
-    // dump an empty file after all files were cleaned
-    let _ = fs::File::create(cleaned_identifier);
+    // a full disk truncates the files written next, which is what the checks find
+    let space_dir = match basepath.parent() {
+        Some(parent) if single_file => parent,
+        _ => basepath.as_path(),
+    };
+    let space = match free_space(space_dir) {
+        Ok(space) => Some(space),
+        Err(err) => {
+            if args.warn_free_space.is_some() {
+                log.line(&format!(
+                    "! could not get the free space of {space_dir:?}: {err}"
+                ));
+            }
+            None
+        }
+    };
+    let free_space_warning = args
+        .warn_free_space
+        .zip(space)
+        .and_then(|(threshold, space)| threshold.warning(&space, space_dir));
+
+    if let Some(err) = report_error {
+        return Err(err);
+    }
+    if args.report_json.is_some() || args.report_yaml.is_some() {
+        let mut report = if report_writer.is_some() {
+            Report::head(&basepath, &tally)
+        } else {
+            Report::new(&basepath, &outcomes, args.report_include_unchanged)
+        };
+        if args.no_content_in_report {
+            report = report.without_content();
+        }
+        report.subdirectories = subdirs.len();
+        report.audit = audit.clone();
+        report.free_space_bytes = space.map(|space| space.free);
+        report.timed_out = timed_out;
+        report.dry_run = dry_run.is_some();
+        report.size_outliers = outliers;
+        report.missing_files = missing;
+        report.spot_check = spot_check.clone();
+        report.quarantine = quarantine.as_ref().map(|directory| Quarantine {
+            directory: directory.clone(),
+            files: n_quarantined,
+        });
+        if n_sample.is_some() && !entries.is_empty() {
+            report.sample = Some(Sample {
+                seed,
+                sampled: entries.len(),
+                total: n_total,
+                extrapolated: report
+                    .summary
+                    .extrapolated(n_total as f64 / entries.len() as f64),
+            });
+        }
+        if let (true, Some(limit)) = (limited, args.limit) {
+            report.limit = Some(Limit {
+                limit,
+                total: entries.len(),
+                not_processed: entries.len() - n_processed,
+            });
+        }
+        if args.coverage_report || args.detect_overlaps {
+            report.coverage = time_coverage.clone();
+        }
+        if let Some(writer) = report_writer {
+            writer.finish(&report)?;
+        } else if let Some(path) = &args.report_json {
+            report.write_json(path)?;
+        }
+        if let Some(path) = &args.report_yaml {
+            report.write_yaml(path)?;
+        }
+    }
 
     let elapsed = now.elapsed();
-    println!("updated {} files in {:.2?}", entries.len(), elapsed);
-    Ok(())
+    let summary = &tally.summary;
+    if let Some(path) = &args.metrics {
+        let directory = args.metrics_label_directory.then_some(basepath.as_path());
+        let metrics = render_metrics(
+            summary,
+            directory,
+            elapsed,
+            SystemTime::now(),
+            space.map(|space| space.free),
+        );
+        write_metrics(path, &metrics)?;
+    }
+
+    log.line(&summary.render_line(elapsed));
+    for (station, summary) in tally.stations.iter() {
+        log.line(&format!("  station {station}: {}", summary.render_counts()));
+    }
+    if let Some(io) = summary.render_io() {
+        log.line(&io);
+    }
+    if let Some(quarantine) = &quarantine {
+        log.line(&format!(
+            "quarantined {n_quarantined} file(s) in {quarantine:?}"
+        ));
+    }
+    if opts.backup.is_some() {
+        log.line(&format!(
+            "backed up {} file(s) before changing them, to {BACKUP_DIR}/ in their directories",
+            tally.backups
+        ));
+    }
+    if dry_run.is_some() {
+        log.line(&render_dry_run_counts(summary));
+    }
+    if n_sample.is_some() && !entries.is_empty() {
+        let factor = n_total as f64 / entries.len() as f64;
+        let extrapolated = summary.extrapolated(factor);
+        log.line(&format!("extrapolated to all {n_total} files:"));
+        log.line(&format!(
+            "  {}",
+            extrapolated.render_line(elapsed.mul_f64(factor))
+        ));
+        if let Some(io) = extrapolated.render_io() {
+            log.line(&format!("  {io}"));
+        }
+    }
+    if let Some(health) = &health {
+        log.line("station health:");
+        for line in health.lines() {
+            log.line(&format!("  {line}"));
+        }
+    }
+    // last, so it is not missed below the summary
+    if let Some(warning) = &free_space_warning {
+        log.line(warning);
+    }
+    if let (Some(hook), None) = (&on_finish, &dry_run) {
+        let values = [
+            ("directory", basepath.to_string_lossy().into_owned()),
+            ("count_scanned", summary.scanned.to_string()),
+            ("count_modified", summary.modified.to_string()),
+            ("count_deleted", summary.deleted.to_string()),
+            ("count_flagged", summary.flagged.to_string()),
+            ("count_errors", summary.errors.to_string()),
+        ];
+        if let Err(err) = hook.run(&values) {
+            log.line(&format!("! --on-finish-cmd: {err}"));
+        }
+    }
+    if spot_check.is_some_and(|spot_check| !spot_check.mismatches.is_empty()) {
+        return Ok(ExitCode::from(EXIT_SPOT_CHECK));
+    }
+    if interrupted {
+        return Ok(ExitCode::from(EXIT_INTERRUPTED));
+    }
+    if timed_out {
+        return Ok(ExitCode::from(EXIT_TIMED_OUT));
+    }
+    // an archive has the exit code of a directory
+    if single_file && !is_zip(&basepath) {
+        return Ok(file_exit_code(outcomes[0].outcome));
+    }
+    if args.strict_unknown && unknown_warning.is_some() {
+        return Ok(ExitCode::from(EXIT_UNKNOWN_EXTENSIONS));
+    }
+    if args.strict_overlaps && overlap_warning.is_some() {
+        return Ok(ExitCode::from(EXIT_TIME_OVERLAPS));
+    }
+    if args.strict_missing && missing_warning.is_some() {
+        return Ok(ExitCode::from(EXIT_MISSING_FILES));
+    }
+    Ok(ExitCode::SUCCESS)
 }
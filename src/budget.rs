@@ -0,0 +1,141 @@
+//! budget limits the memory held by files that are loaded for rewriting, so parallel
+//! workers cannot together exceed the available memory.
+
+use std::{
+    fs,
+    sync::{Condvar, Mutex},
+};
+
+/// MemoryBudget hands out permits for a number of bytes and blocks while the budget is
+/// used up. A request larger than the whole budget waits until nothing else is held,
+/// i.e. such a file is processed alone.
+#[derive(Debug)]
+pub struct MemoryBudget {
+    total: u64,
+    used: Mutex<u64>,
+    released: Condvar,
+}
+
+/// Permit holds bytes of a MemoryBudget until it is dropped.
+#[derive(Debug)]
+pub struct Permit<'a> {
+    budget: &'a MemoryBudget,
+    bytes: u64,
+}
+
+impl MemoryBudget {
+    /// new creates a budget of total bytes.
+    pub fn new(total: u64) -> Self {
+        MemoryBudget {
+            total,
+            used: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// acquire blocks until bytes are available and returns a permit holding them.
+    pub fn acquire(&self, bytes: u64) -> Permit<'_> {
+        // more than the budget can never be available, take all of it instead
+        let bytes = bytes.min(self.total);
+        let mut used = self.used.lock().unwrap();
+        while *used + bytes > self.total {
+            used = self.released.wait(used).unwrap();
+        }
+        *used += bytes;
+        Permit {
+            budget: self,
+            bytes,
+        }
+    }
+
+    /// available returns the number of bytes not held by a permit.
+    pub fn available(&self) -> u64 {
+        self.total - *self.used.lock().unwrap()
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut used = self.budget.used.lock().unwrap();
+        *used -= self.bytes;
+        self.budget.released.notify_all();
+    }
+}
+
+/// default_budget returns half of the available system memory, or 1 GiB if that cannot
+/// be determined (only Linux is supported).
+pub fn default_budget() -> u64 {
+    available_memory().map_or(1 << 30, |bytes| bytes / 2)
+}
+
+/// available_memory reads MemAvailable from /proc/meminfo, in bytes.
+fn available_memory() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|l| l.starts_with("MemAvailable:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, thread, time::Duration};
+
+    use super::*;
+
+    /// acquired_before_release acquires bytes on another thread while held is held, and
+    /// tells if that succeeded before held was dropped; the budget is then all available
+    /// again.
+    fn acquired_before_release(budget: &MemoryBudget, held: Permit, bytes: u64) -> bool {
+        let (sender, acquired) = mpsc::channel();
+        thread::scope(|scope| {
+            scope.spawn(move || {
+                let _permit = budget.acquire(bytes);
+                sender.send(()).unwrap();
+            });
+            let early = acquired.recv_timeout(Duration::from_millis(200)).is_ok();
+            drop(held);
+            early
+        })
+    }
+
+    #[test]
+    fn permits_hold_bytes_until_dropped() {
+        let budget = MemoryBudget::new(100);
+        let a = budget.acquire(30);
+        let b = budget.acquire(70);
+        assert_eq!(budget.available(), 0);
+        drop(a);
+        assert_eq!(budget.available(), 30);
+        drop(b);
+        assert_eq!(budget.available(), 100);
+    }
+
+    #[test]
+    fn exceeded_budget_waits_for_a_release() {
+        let budget = MemoryBudget::new(100);
+        let held = budget.acquire(60);
+        assert!(!acquired_before_release(&budget, held, 60));
+        assert_eq!(budget.available(), 100);
+
+        // what still fits does not wait
+        let held = budget.acquire(60);
+        assert!(acquired_before_release(&budget, held, 40));
+    }
+
+    #[test]
+    fn request_over_the_budget_waits_until_nothing_is_held() {
+        let budget = MemoryBudget::new(100);
+        let held = budget.acquire(1);
+        assert!(!acquired_before_release(&budget, held, 500));
+        assert_eq!(budget.available(), 100);
+
+        let permit = budget.acquire(500);
+        assert_eq!(budget.available(), 0, "takes the whole budget");
+        drop(permit);
+        assert_eq!(budget.available(), 100);
+    }
+}
@@ -0,0 +1,71 @@
+//! build_info tells which build of the cleaner produced a report or a done marker, for
+//! debugging station issues remotely. The git commit is embedded by build.rs.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// BuildInfo identifies a build of the cleaner.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildInfo {
+    pub version: String,
+    /// the full hash of the commit, "unknown" if built without git
+    pub git_commit: String,
+    /// `git describe --always --dirty --tags`, "unknown" if built without git
+    pub git_describe: String,
+    /// the working tree had uncommitted changes
+    pub git_dirty: bool,
+    /// the target triple, e.g. x86_64-unknown-linux-gnu
+    pub target: String,
+    /// the cargo features the library was built with
+    pub features: Vec<String>,
+}
+
+impl BuildInfo {
+    /// current returns the information of this build.
+    pub fn current() -> Self {
+        let or_unknown = |s: &str| {
+            if s.is_empty() {
+                "unknown".to_string()
+            } else {
+                s.to_string()
+            }
+        };
+        let describe = env!("V25_GIT_DESCRIBE");
+        let features = [
+            ("cli", cfg!(feature = "cli")),
+            ("yaml-config", cfg!(feature = "yaml-config")),
+            ("regex-checks", cfg!(feature = "regex-checks")),
+            ("mmap", cfg!(feature = "mmap")),
+            ("zip", cfg!(feature = "zip")),
+            ("test-util", cfg!(feature = "test-util")),
+        ];
+        BuildInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_commit: or_unknown(env!("V25_GIT_COMMIT")),
+            git_describe: or_unknown(describe),
+            git_dirty: describe.ends_with("-dirty"),
+            target: or_unknown(env!("V25_TARGET")),
+            features: features
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(name, _)| name.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "version: {}", self.version)?;
+        writeln!(
+            f,
+            "git commit: {}{}",
+            self.git_commit,
+            if self.git_dirty { " (dirty)" } else { "" }
+        )?;
+        writeln!(f, "git describe: {}", self.git_describe)?;
+        writeln!(f, "target: {}", self.target)?;
+        writeln!(f, "features: {}", self.features.join(", "))
+    }
+}
@@ -0,0 +1,232 @@
+//! cert writes and verifies a certificate that a directory was cleaned: which files
+//! survived cleaning, with which config, so a later change of the data can be detected.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::is_tool_artifact;
+
+/// CERT_FILE is the name of the certificate, next to the done marker.
+pub const CERT_FILE: &str = "V25Logs_cleaned.cert.json";
+
+/// Certificate proves that a directory was cleaned with a certain config.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Certificate {
+    pub tool_version: String,
+    /// sha256 of the cfg file
+    pub config_sha256: String,
+    /// local time the certificate was written
+    pub run_timestamp: String,
+    /// number of files per upper case extension, "" for files without extension
+    pub counts: BTreeMap<String, usize>,
+    /// sha256 over the sorted (name, size, sha256) of all files
+    pub digest: String,
+    pub files: Vec<FileDigest>,
+}
+
+/// FileDigest identifies the content of a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileDigest {
+    pub name: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl Certificate {
+    /// new certifies the current content of dir, without the files of the tool.
+    pub fn new(dir: &Path, cfg_path: &Path) -> io::Result<Self> {
+        let files = file_digests(dir)?;
+        let mut counts = BTreeMap::new();
+        for file in files.iter() {
+            let ext = Path::new(&file.name)
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_uppercase())
+                .unwrap_or_default();
+            *counts.entry(ext).or_insert(0) += 1;
+        }
+        Ok(Certificate {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            config_sha256: sha256_file(cfg_path)?,
+            run_timestamp: chrono::Local::now().to_rfc3339(),
+            counts,
+            digest: digest(&files),
+            files,
+        })
+    }
+
+    /// write writes the certificate to CERT_FILE in dir.
+    pub fn write(&self, dir: &Path) -> io::Result<()> {
+        let file = fs::File::create(dir.join(CERT_FILE))?;
+        serde_json::to_writer_pretty(io::BufWriter::new(file), self).map_err(io::Error::other)
+    }
+
+    /// read reads the certificate from CERT_FILE in dir.
+    pub fn read(dir: &Path) -> io::Result<Self> {
+        let file = fs::File::open(dir.join(CERT_FILE))?;
+        serde_json::from_reader(io::BufReader::new(file)).map_err(io::Error::other)
+    }
+
+    /// verify compares the certificate with the current content of dir and returns the
+    /// differences, an empty list if the directory is unchanged.
+    pub fn verify(&self, dir: &Path) -> io::Result<Vec<String>> {
+        let current = file_digests(dir)?;
+        let mut problems = Vec::new();
+        if digest(&self.files) != self.digest {
+            problems.push("the file list of the certificate does not match its digest".to_string());
+        }
+        let certified: BTreeMap<&str, &FileDigest> =
+            self.files.iter().map(|f| (f.name.as_str(), f)).collect();
+        let found: BTreeMap<&str, &FileDigest> =
+            current.iter().map(|f| (f.name.as_str(), f)).collect();
+        for (name, file) in certified.iter() {
+            match found.get(name) {
+                None => problems.push(format!("removed: {name}")),
+                Some(now) if now != file => problems.push(format!("changed: {name}")),
+                _ => (),
+            }
+        }
+        for name in found.keys().filter(|name| !certified.contains_key(*name)) {
+            problems.push(format!("added: {name}"));
+        }
+        Ok(problems)
+    }
+}
+
+/// file_digests hashes all files in dir except those of the tool, see is_tool_artifact,
+/// sorted by name. They are written after the certificate, or by a later run, and are
+/// not data.
+fn file_digests(dir: &Path) -> io::Result<Vec<FileDigest>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|r| r.ok())
+        .map(|r| r.path())
+        .filter(|p| p.is_file())
+        .collect();
+    paths.sort();
+    let mut files = Vec::with_capacity(paths.len());
+    for path in paths {
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        if is_tool_artifact(&path) {
+            continue;
+        }
+        files.push(FileDigest {
+            size: fs::metadata(&path)?.len(),
+            sha256: sha256_file(&path)?,
+            name,
+        });
+    }
+    Ok(files)
+}
+
+/// digest hashes the list of files, one "name\tsize\tsha256" line per file.
+fn digest(files: &[FileDigest]) -> String {
+    let mut hasher = Sha256::new();
+    for file in files {
+        hasher.update(format!("{}\t{}\t{}\n", file.name, file.size, file.sha256));
+    }
+    hex(&hasher.finalize())
+}
+
+/// sha256_file returns the hex encoded sha256 of the content of a file.
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut io::BufReader::new(fs::File::open(path)?), &mut hasher)?;
+    Ok(hex(&hasher.finalize()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        let _ = write!(out, "{b:02x}");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// certified returns a directory with two data files certified with the cfg file
+    /// next to it, and the certificate.
+    fn certified(name: &str) -> (PathBuf, Certificate) {
+        let root = std::env::temp_dir().join(format!("v25_cert_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let dir = root.join("data");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(root.join("cfg.yml"), "DAT:\n  min_n_lines: 2\n").unwrap();
+        fs::write(dir.join("03120000.DAT"), "time\tp\n1\t2\n").unwrap();
+        fs::write(dir.join("03120100.DAT"), "time\tp\n3\t4\n").unwrap();
+        let cert = Certificate::new(&dir, &root.join("cfg.yml")).unwrap();
+        cert.write(&dir).unwrap();
+        (dir, cert)
+    }
+
+    fn verify(dir: &Path) -> Vec<String> {
+        let problems = Certificate::read(dir).unwrap().verify(dir).unwrap();
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+        problems
+    }
+
+    #[test]
+    fn unchanged_directory_verifies() {
+        let (dir, cert) = certified("unchanged");
+        assert_eq!(cert.counts, BTreeMap::from([("DAT".to_string(), 2)]));
+        assert_eq!(cert.files.len(), 2);
+        assert_eq!(verify(&dir), Vec::<String>::new());
+    }
+
+    #[test]
+    fn changed_byte_is_found() {
+        let (dir, _) = certified("changed");
+        fs::write(dir.join("03120000.DAT"), "time\tp\n1\t3\n").unwrap();
+        assert_eq!(verify(&dir), ["changed: 03120000.DAT"]);
+    }
+
+    #[test]
+    fn added_file_is_found() {
+        let (dir, _) = certified("added");
+        fs::write(dir.join("03120200.DAT"), "time\tp\n5\t6\n").unwrap();
+        assert_eq!(verify(&dir), ["added: 03120200.DAT"]);
+    }
+
+    #[test]
+    fn removed_file_is_found() {
+        let (dir, _) = certified("removed");
+        fs::remove_file(dir.join("03120100.DAT")).unwrap();
+        assert_eq!(verify(&dir), ["removed: 03120100.DAT"]);
+    }
+
+    #[test]
+    fn broken_digest_is_found() {
+        let (dir, mut cert) = certified("digest");
+        // the list is edited to match the tampered file, the digest is not
+        fs::write(dir.join("03120000.DAT"), "time\tp\n1\t3\n").unwrap();
+        cert.files[0].sha256 = sha256_file(&dir.join("03120000.DAT")).unwrap();
+        cert.write(&dir).unwrap();
+        assert_eq!(
+            verify(&dir),
+            ["the file list of the certificate does not match its digest"]
+        );
+    }
+
+    #[test]
+    fn files_of_a_later_run_are_left_out() {
+        let (dir, _) = certified("later_run");
+        for name in crate::TOOL_FILES.iter().filter(|name| **name != CERT_FILE) {
+            fs::write(dir.join(name), "written by a later run\n").unwrap();
+        }
+        fs::write(dir.join("03120000.DAT.flagged"), "findings: []\n").unwrap();
+        fs::write(crate::tmpfile::temp_path(&dir.join("03120000.DAT")), "").unwrap();
+        assert_eq!(verify(&dir), Vec::<String>::new());
+    }
+}
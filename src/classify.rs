@@ -0,0 +1,85 @@
+//! classify tells what the cleaner would say about a file without acting on it, e.g. for
+//! an inventory of the files of a station. The file is checked on a copy in a temporary
+//! directory, so its own directory need not be writable.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use yaml_rust::Yaml;
+
+use crate::{
+    check_and_fix, clean_copy, manifest::file_extension, tmpfile, CleanOptions, Finding, Outcome,
+};
+
+/// ExtensionStatus tells whether the cfg file has rules for the extension of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtensionStatus {
+    /// the extension, after CleanOptions::ext_map, is defined in the cfg file
+    Known,
+    /// the extension is not defined in the cfg file, such files are skipped
+    Unknown,
+    /// the file has no extension, such files are deleted
+    None,
+}
+
+/// Classification is what the cleaner would say about a file.
+#[derive(Debug, Clone)]
+pub struct Classification {
+    pub path: PathBuf,
+    /// the upper-case extension of the file, empty without one
+    pub extension: String,
+    /// the extension whose rules apply, differs from extension under ext_map
+    pub effective_extension: String,
+    pub status: ExtensionStatus,
+    /// the cfg section of the effective extension, BadValue unless the status is Known
+    pub rules: Yaml,
+    /// the checks that would fire, with their measured values
+    pub findings: Vec<Finding>,
+    /// the outcome cleaning the file would have; Protected for readonly types, whose
+    /// findings are listed nevertheless
+    pub outcome: Outcome,
+    /// the I/O error that stopped the checks, with Outcome::Error
+    pub error: Option<String>,
+}
+
+/// classify_file checks the file at path like clean_file, on a copy, and returns what
+/// cleaning it would do. the file is never written, nor is its directory. a file
+/// flagged by a previous run is classified by its content, the run would skip it.
+pub fn classify_file(path: &Path, cfg: &Yaml, opts: &CleanOptions) -> io::Result<Classification> {
+    let extension = file_extension(path);
+    let effective_extension = opts.effective_extension(&extension).to_string();
+    let rules = cfg[effective_extension.as_str()].clone();
+    let status = match (extension.is_empty(), rules.is_badvalue()) {
+        (true, _) => ExtensionStatus::None,
+        (false, true) => ExtensionStatus::Unknown,
+        (false, false) => ExtensionStatus::Known,
+    };
+
+    let work = tmpfile::temp_path(&std::env::temp_dir().join("v25_classify"));
+    fs::create_dir(&work)?;
+    let opts = CleanOptions {
+        defer_delete: false,
+        ..opts.clone()
+    };
+    let result = clean_copy(path, &work, |copy| check_and_fix(copy, cfg, &opts));
+    let _ = fs::remove_dir_all(&work);
+    let outcome = result?;
+
+    let readonly = rules["readonly"].as_bool() == Some(true);
+    Ok(Classification {
+        path: path.to_path_buf(),
+        extension,
+        effective_extension,
+        status,
+        rules,
+        findings: outcome.findings,
+        error: outcome.error,
+        outcome: match outcome.outcome {
+            Outcome::Error | Outcome::Skipped(_) => outcome.outcome,
+            _ if readonly => Outcome::Protected,
+            other => other,
+        },
+    })
+}
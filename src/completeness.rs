@@ -0,0 +1,196 @@
+//! completeness lists the files that should be in a directory but are not. A V25 writes
+//! one file per type and hour, named after the hour it starts, e.g. `03121400.DAT`, so
+//! a missing hour is a stream that dropped. The `hourly_file_name` of a file type in
+//! the cfg file gives the name without the extension, with `%Y`, `%y`, `%m`, `%d` and
+//! `%H` for the parts of the hour, e.g. `'%m%d%H00'`. The files that are expected are
+//! the hours between the first and the last file of all such types; only the report
+//! says so, no file is touched.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, TimeDelta, Timelike};
+use yaml_rust::Yaml;
+
+use crate::{manifest::file_extension, report::MissingFiles};
+
+/// HOURLY_FILE_NAME_KEY is the setting of a file type with the pattern of its names.
+pub const HOURLY_FILE_NAME_KEY: &str = "hourly_file_name";
+
+/// Part is a piece of an hourly file name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Part {
+    Literal(char),
+    Year,
+    ShortYear,
+    Month,
+    Day,
+    Hour,
+}
+
+/// NameFormat is the hourly_file_name of a file type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameFormat(Vec<Part>);
+
+impl NameFormat {
+    /// parse parses format, which needs at least `%m`, `%d` and `%H`; `%%` is a `%`.
+    pub fn parse(format: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                parts.push(Part::Literal(c));
+                continue;
+            }
+            parts.push(match chars.next() {
+                Some('Y') => Part::Year,
+                Some('y') => Part::ShortYear,
+                Some('m') => Part::Month,
+                Some('d') => Part::Day,
+                Some('H') => Part::Hour,
+                Some('%') => Part::Literal('%'),
+                other => {
+                    let spec = other.map_or_else(String::new, String::from);
+                    return Err(format!(
+                        "unsupported '%{spec}' in '{format}', expected %Y, %y, %m, %d, %H or %%"
+                    ));
+                }
+            });
+        }
+        if [Part::Month, Part::Day, Part::Hour]
+            .iter()
+            .any(|part| !parts.contains(part))
+        {
+            return Err(format!("'{format}' needs %m, %d and %H"));
+        }
+        Ok(NameFormat(parts))
+    }
+
+    /// hour_of returns the hour a file stem stands for. names without a year take the
+    /// year that puts the hour closest to modified, the time the file was last written;
+    /// without a year and modified, or if stem does not match, it returns None.
+    pub fn hour_of(&self, stem: &str, modified: Option<SystemTime>) -> Option<NaiveDateTime> {
+        let mut rest = stem;
+        let (mut year, mut month, mut day, mut hour) = (None, 0, 0, 0);
+        for part in &self.0 {
+            if let Part::Literal(c) = part {
+                rest = rest.strip_prefix(*c)?;
+                continue;
+            }
+            let width = if *part == Part::Year { 4 } else { 2 };
+            let digits = rest
+                .get(..width)
+                .filter(|s| s.bytes().all(|b| b.is_ascii_digit()))?;
+            let value: u32 = digits.parse().ok()?;
+            rest = &rest[width..];
+            match part {
+                Part::Year => year = Some(value as i32),
+                Part::ShortYear => year = Some(2000 + value as i32),
+                Part::Month => month = value,
+                Part::Day => day = value,
+                _ => hour = value,
+            }
+        }
+        if !rest.is_empty() {
+            return None;
+        }
+        let at = |year: i32| NaiveDate::from_ymd_opt(year, month, day)?.and_hms_opt(hour, 0, 0);
+        if let Some(year) = year {
+            return at(year);
+        }
+        let modified = DateTime::<Local>::from(modified?).naive_local();
+        (modified.year() - 1..=modified.year() + 1)
+            .filter_map(at)
+            .min_by_key(|hour| (*hour - modified).abs())
+    }
+
+    /// render returns the file stem for hour.
+    pub fn render(&self, hour: NaiveDateTime) -> String {
+        let mut out = String::new();
+        for part in &self.0 {
+            let _ = match part {
+                Part::Literal(c) => write!(out, "{c}"),
+                Part::Year => write!(out, "{:04}", hour.year()),
+                Part::ShortYear => write!(out, "{:02}", hour.year() % 100),
+                Part::Month => write!(out, "{:02}", hour.month()),
+                Part::Day => write!(out, "{:02}", hour.day()),
+                Part::Hour => write!(out, "{:02}", hour.hour()),
+            };
+        }
+        out
+    }
+}
+
+/// name_formats returns the hourly_file_name of each file type that has one. one that
+/// is not a string or can't be parsed is an error.
+pub fn name_formats(cfg: &Yaml) -> Result<BTreeMap<String, NameFormat>, String> {
+    let mut formats = BTreeMap::new();
+    let Some(types) = cfg.as_hash() else {
+        return Ok(formats);
+    };
+    for (ext, cfg_ext) in types.iter() {
+        let ext = ext.as_str().unwrap_or_default();
+        let format = match &cfg_ext[HOURLY_FILE_NAME_KEY] {
+            Yaml::BadValue => continue,
+            Yaml::String(format) => NameFormat::parse(format)
+                .map_err(|err| format!("{ext}: {HOURLY_FILE_NAME_KEY} {err}"))?,
+            _ => return Err(format!("{ext}: {HOURLY_FILE_NAME_KEY} must be a string")),
+        };
+        formats.insert(ext.to_string(), format);
+    }
+    Ok(formats)
+}
+
+/// missing_files returns the files missing among paths, per extension with a format.
+/// the hours between the first and the last file that matches any of the formats are
+/// expected of every extension; files that don't match are ignored.
+pub fn missing_files(
+    paths: &[PathBuf],
+    formats: &BTreeMap<String, NameFormat>,
+) -> Vec<MissingFiles> {
+    let mut present: BTreeMap<&str, BTreeSet<NaiveDateTime>> = BTreeMap::new();
+    for path in paths {
+        let ext = file_extension(path);
+        let Some((ext, format)) = formats.get_key_value(&ext) else {
+            continue;
+        };
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        if let Some(hour) = format.hour_of(&stem, modified(path)) {
+            present.entry(ext).or_default().insert(hour);
+        }
+    }
+    let hours = present.values().flatten();
+    let (Some(first), Some(last)) = (hours.clone().min(), hours.max()) else {
+        return Vec::new();
+    };
+    let stamp = |hour: &NaiveDateTime| hour.format("%Y-%m-%d %H:%M").to_string();
+    let mut missing = Vec::new();
+    for (ext, format) in formats {
+        let present = present.get(ext.as_str());
+        let names: Vec<String> = std::iter::successors(Some(*first), |hour| {
+            Some(*hour + TimeDelta::hours(1)).filter(|next| next <= last)
+        })
+        .filter(|hour| !present.is_some_and(|present| present.contains(hour)))
+        .map(|hour| format!("{}.{ext}", format.render(hour)))
+        .collect();
+        if !names.is_empty() {
+            missing.push(MissingFiles {
+                extension: ext.clone(),
+                from: stamp(first),
+                to: stamp(last),
+                names,
+            });
+        }
+    }
+    missing
+}
+
+/// modified returns when the file at path was last written, if known.
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
@@ -0,0 +1,238 @@
+//! config loads the cfg file and makes sure it has the shape the cleaner expects: a
+//! mapping of file types, each a mapping of its settings. Anything else, e.g. a stray
+//! `-` that turns the file into a sequence, would make every file type unknown and
+//! skip every file without a hint why, so it is an error that names the line.
+//!
+//! A cfg file can take the rules of some file types from other files, e.g. those an
+//! instrument group maintains, with `include: [group_a.yml, group_b.yml]`. The paths are
+//! relative to the including file, which may include further files in turn. A file
+//! type takes its whole block from the last file that defines it: a later include
+//! overrides an earlier one, the including file overrides them all.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use yaml_rust::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::{Marker, TScalarStyle},
+    yaml::Hash,
+    Yaml, YamlLoader,
+};
+
+use crate::station::STATION_PATTERN_KEY;
+
+/// EXAMPLE shows the expected shape in the errors.
+const EXAMPLE: &str = "e.g. `DAT: {min_n_lines: 2}`";
+
+/// INCLUDE_KEY is the top-level key of the cfg file that lists the files to include.
+pub const INCLUDE_KEY: &str = "include";
+
+/// load reads the cfg file at path with the files it includes, see parse and
+/// load_with_sources.
+pub fn load(path: &Path) -> Result<Yaml, String> {
+    load_with_sources(path).map(|(cfg, _)| cfg)
+}
+
+/// load_with_sources is load that also returns the file each top-level key of the cfg
+/// was taken from. a file that includes itself, directly or not, is an error.
+pub fn load_with_sources(path: &Path) -> Result<(Yaml, BTreeMap<String, PathBuf>), String> {
+    let mut merged = Hash::new();
+    let mut sources = BTreeMap::new();
+    merge(path, &mut Vec::new(), &mut merged, &mut sources)?;
+    Ok((Yaml::Hash(merged), sources))
+}
+
+/// merge adds the top-level keys of the cfg file at path to merged, after those of the
+/// files it includes. including is the chain of files that led to path, to detect a cycle.
+fn merge(
+    path: &Path,
+    including: &mut Vec<PathBuf>,
+    merged: &mut Hash,
+    sources: &mut BTreeMap<String, PathBuf>,
+) -> Result<(), String> {
+    // an error in an included file names it, one in the cfg file itself is known
+    let included = !including.is_empty();
+    let name = |err: String| match included {
+        true => format!("included {path:?}: {err}"),
+        false => err,
+    };
+    let canonical = fs::canonicalize(path).map_err(|err| name(format!("could not read: {err}")))?;
+    if including.contains(&canonical) {
+        let chain: Vec<String> = including
+            .iter()
+            .chain([&canonical])
+            .map(|path| format!("{path:?}"))
+            .collect();
+        return Err(format!("include cycle: {}", chain.join(" -> ")));
+    }
+    let content = fs::read_to_string(path).map_err(|err| name(format!("could not read: {err}")))?;
+    let Yaml::Hash(cfg) = parse(&content).map_err(name)? else {
+        unreachable!("parse returns a mapping");
+    };
+    // relative to the file itself, not to the working directory
+    let dir = canonical.parent().unwrap_or(Path::new(".")).to_path_buf();
+    including.push(canonical);
+    for include in includes(&cfg).map_err(name)? {
+        merge(&dir.join(include), including, merged, sources)?;
+    }
+    let canonical = including.pop().unwrap_or_default();
+    for (key, mut value) in cfg {
+        let key_str = key
+            .as_str()
+            .map_or_else(|| format!("{key:?}"), str::to_owned);
+        if key_str == INCLUDE_KEY {
+            continue;
+        }
+        // a reference header goes with the rules, relative to the file that has them
+        if let Yaml::Hash(settings) = &mut value {
+            let header_file = Yaml::String("reference_header_file".into());
+            if let (true, Some(Yaml::String(file))) = (included, settings.get_mut(&header_file)) {
+                *file = dir.join(&*file).to_string_lossy().into_owned();
+            }
+        }
+        merged.remove(&key);
+        merged.insert(key, value);
+        sources.insert(key_str, canonical.clone());
+    }
+    Ok(())
+}
+
+/// includes returns the paths listed under INCLUDE_KEY of cfg, none if it has no such key.
+fn includes(cfg: &Hash) -> Result<Vec<&str>, String> {
+    match cfg.get(&Yaml::String(INCLUDE_KEY.into())) {
+        None | Some(Yaml::Null) => Ok(Vec::new()),
+        Some(Yaml::Array(paths)) => paths
+            .iter()
+            .map(|path| {
+                path.as_str().ok_or_else(|| {
+                    format!("{INCLUDE_KEY} lists {path:?}, expected the path of a cfg file")
+                })
+            })
+            .collect(),
+        Some(other) => Err(format!(
+            "{INCLUDE_KEY} is {other:?}, expected a list of cfg files, e.g. `{INCLUDE_KEY}: [group_a.yml]`"
+        )),
+    }
+}
+
+/// parse parses the content of a cfg file and returns its first document. it is an
+/// error if that is not a non-empty mapping, or if a file type in it is not a mapping;
+/// a file type without settings is fine.
+pub fn parse(content: &str) -> Result<Yaml, String> {
+    let mut docs = YamlLoader::load_from_str(content).map_err(|err| err.to_string())?;
+    let mut events = Events(Vec::new());
+    Parser::new(content.chars())
+        .load(&mut events, false)
+        .map_err(|err| err.to_string())?;
+    let mut nodes = events.0.iter().skip_while(|(event, _)| !is_node(event));
+    let Some((root, mark)) = nodes.next().filter(|(event, _)| !is_null(event)) else {
+        return Err(format!(
+            "is empty, expected a mapping of file types, {EXAMPLE}"
+        ));
+    };
+    if !matches!(root, Event::MappingStart(_)) {
+        return Err(format!(
+            "{}: the top level is {}, expected a mapping of file types, {EXAMPLE}",
+            position(mark),
+            describe(root)
+        ));
+    }
+    let mut depth = 0;
+    let mut key: Option<(&Event, &Marker)> = None;
+    let mut n_types = 0;
+    for (event, mark) in nodes {
+        if depth == 0 {
+            if matches!(event, Event::MappingEnd) {
+                break;
+            }
+            match key.take() {
+                None => key = Some((event, mark)),
+                Some((name, name_mark)) => {
+                    check_file_type(name, name_mark, event)?;
+                    n_types += 1;
+                }
+            }
+        }
+        match event {
+            Event::MappingStart(_) | Event::SequenceStart(_) => depth += 1,
+            Event::MappingEnd | Event::SequenceEnd => depth -= 1,
+            _ => (),
+        }
+    }
+    if n_types == 0 {
+        return Err(format!(
+            "{}: the top level is an empty mapping, expected file types, {EXAMPLE}",
+            position(mark)
+        ));
+    }
+    Ok(docs.swap_remove(0))
+}
+
+/// check_file_type returns an error if the value of the top-level key name is not a
+/// mapping of settings, except for the keys that are not file types.
+fn check_file_type(name: &Event, mark: &Marker, value: &Event) -> Result<(), String> {
+    let Event::Scalar(name, ..) = name else {
+        return Err(format!(
+            "{}: a key of the top level is {}, expected the extension of a file type",
+            position(mark),
+            describe(name)
+        ));
+    };
+    if name == STATION_PATTERN_KEY
+        || name == INCLUDE_KEY
+        || is_null(value)
+        || matches!(value, Event::MappingStart(_) | Event::Alias(_))
+    {
+        return Ok(());
+    }
+    Err(format!(
+        "{}: {name} is {}, expected a mapping of its settings, e.g. `{name}: {{min_n_lines: 2}}`",
+        position(mark),
+        describe(value)
+    ))
+}
+
+/// Events collects the events of the parser with their positions.
+struct Events(Vec<(Event, Marker)>);
+
+impl MarkedEventReceiver for Events {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        self.0.push((event, mark));
+    }
+}
+
+/// is_node tells if event starts a node of the document.
+fn is_node(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Scalar(..) | Event::SequenceStart(_) | Event::MappingStart(_) | Event::Alias(_)
+    )
+}
+
+/// is_null tells if event is a null scalar, e.g. `DAT:` without settings.
+fn is_null(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Scalar(value, TScalarStyle::Plain, ..)
+            if matches!(value.as_str(), "" | "~" | "null" | "Null" | "NULL")
+    )
+}
+
+/// describe names the type of the node event starts, with the value of a scalar.
+fn describe(event: &Event) -> String {
+    match event {
+        Event::Scalar(value, ..) => format!("the scalar '{value}'"),
+        Event::SequenceStart(_) => "a sequence".to_string(),
+        Event::MappingStart(_) => "a mapping".to_string(),
+        Event::Alias(_) => "an alias".to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+/// position renders mark as line and column, both counted from 1.
+fn position(mark: &Marker) -> String {
+    format!("line {}, column {}", mark.line(), mark.col() + 1)
+}
@@ -0,0 +1,233 @@
+//! delete removes the files a run decided to delete in one batch after all files were
+//! checked. Removing tens of thousands of files one by one is slow on network shares;
+//! renaming them into one staging directory or removing them in parallel is faster.
+//! With --quarantine they are moved to a directory instead, where they can be checked
+//! and restored if a bad cfg file deleted good data.
+
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use crate::tmpfile;
+
+/// DEFAULT_WORKERS is the number of threads that delete files in parallel.
+pub const DEFAULT_WORKERS: usize = 8;
+
+/// DeleteStrategy is how the files to delete are removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeleteStrategy {
+    /// remove one file after the other
+    #[default]
+    Direct,
+    /// rename the files into a staging directory next to them and remove that
+    Staged,
+    /// remove the files with a number of threads
+    Parallel,
+}
+
+impl FromStr for DeleteStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "direct" => Ok(DeleteStrategy::Direct),
+            "staged" => Ok(DeleteStrategy::Staged),
+            "parallel" => Ok(DeleteStrategy::Parallel),
+            _ => Err(format!(
+                "invalid strategy '{s}', expected one of direct, staged, parallel"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DeleteStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            DeleteStrategy::Direct => "direct",
+            DeleteStrategy::Staged => "staged",
+            DeleteStrategy::Parallel => "parallel",
+        })
+    }
+}
+
+/// delete_files removes paths, all in dir, with strategy and returns the files that
+/// could not be removed with the error. workers is only used by the parallel strategy.
+/// the staging directory is a temporary name in dir; files that can't be renamed into
+/// it are removed directly.
+pub fn delete_files(
+    dir: &Path,
+    paths: &[PathBuf],
+    strategy: DeleteStrategy,
+    workers: usize,
+) -> Vec<(PathBuf, io::Error)> {
+    if paths.is_empty() {
+        return Vec::new();
+    }
+    match strategy {
+        DeleteStrategy::Direct => remove_each(paths),
+        DeleteStrategy::Staged => {
+            let staging = tmpfile::temp_path(&dir.join("to_delete"));
+            if fs::create_dir(&staging).is_err() {
+                return remove_each(paths);
+            }
+            let mut failed = Vec::new();
+            for path in paths {
+                let target = staging.join(path.file_name().unwrap_or_default());
+                if fs::rename(path, target).is_err() {
+                    if let Err(err) = fs::remove_file(path) {
+                        failed.push((path.clone(), err));
+                    }
+                }
+            }
+            // the files are gone from dir already, a leftover staging directory is
+            // reported as a subdirectory by the next run
+            let _ = fs::remove_dir_all(&staging);
+            failed
+        }
+        DeleteStrategy::Parallel => {
+            let next = AtomicUsize::new(0);
+            let failed = Mutex::new(Vec::new());
+            thread::scope(|scope| {
+                for _ in 0..workers.clamp(1, paths.len()) {
+                    scope.spawn(|| {
+                        while let Some(path) = paths.get(next.fetch_add(1, Ordering::Relaxed)) {
+                            if let Err(err) = fs::remove_file(path) {
+                                failed.lock().unwrap().push((path.clone(), err));
+                            }
+                        }
+                    });
+                }
+            });
+            let mut failed = failed.into_inner().unwrap();
+            failed.sort_by(|a, b| a.0.cmp(&b.0));
+            failed
+        }
+    }
+}
+
+/// quarantine_files moves paths into the directory quarantine, keeping their names; a
+/// name that is taken there gets a number, e.g. `03120000_1.DAT`. it returns where each
+/// file was moved, or the error if it could not be. a file on another file system is
+/// copied with its modification time and then removed.
+pub fn quarantine_files(
+    quarantine: &Path,
+    paths: &[PathBuf],
+) -> Vec<(PathBuf, io::Result<PathBuf>)> {
+    paths
+        .iter()
+        .map(|path| {
+            let target = free_name(quarantine, path);
+            let moved = fs::rename(path, &target)
+                .or_else(|_| copy_and_remove(path, &target))
+                .map(|_| target);
+            (path.clone(), moved)
+        })
+        .collect()
+}
+
+/// free_name returns the path in dir for the file at path that is not taken yet.
+fn free_name(dir: &Path, path: &Path) -> PathBuf {
+    let target = dir.join(path.file_name().unwrap_or_default());
+    if !target.exists() {
+        return target;
+    }
+    (1..)
+        .map(|n| {
+            let mut name = path.file_stem().unwrap_or_default().to_owned();
+            name.push(format!("_{n}"));
+            if let Some(ext) = path.extension() {
+                name.push(".");
+                name.push(ext);
+            }
+            dir.join(name)
+        })
+        .find(|candidate| !candidate.exists())
+        .unwrap_or(target)
+}
+
+/// copy_and_remove moves the file at path to target by copying it, for a target on
+/// another file system. the copy gets the modification time of the file.
+fn copy_and_remove(path: &Path, target: &Path) -> io::Result<()> {
+    let modified = fs::metadata(path)?.modified()?;
+    fs::copy(path, target)?;
+    let copied = fs::File::options()
+        .write(true)
+        .open(target)
+        .and_then(|file| file.set_modified(modified));
+    if let Err(err) = copied.and_then(|_| fs::remove_file(path)) {
+        let _ = fs::remove_file(target);
+        return Err(err);
+    }
+    Ok(())
+}
+
+fn remove_each(paths: &[PathBuf]) -> Vec<(PathBuf, io::Error)> {
+    paths
+        .iter()
+        .filter_map(|path| fs::remove_file(path).err().map(|err| (path.clone(), err)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// names returns the names of the entries of dir, sorted.
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn each_strategy_removes_the_targeted_files_only() {
+        for strategy in [
+            DeleteStrategy::Direct,
+            DeleteStrategy::Staged,
+            DeleteStrategy::Parallel,
+        ] {
+            let dir =
+                std::env::temp_dir().join(format!("v25_delete_{strategy}_{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(dir.join("sub")).unwrap();
+            fs::write(dir.join("sub").join("03120001.DAT"), "time\tp\n").unwrap();
+            let mut targets = Vec::new();
+            let mut kept = vec!["sub".to_string()];
+            for i in 0..40 {
+                let name = format!("031200{i:02}.DAT");
+                fs::write(dir.join(&name), "time\tp\n").unwrap();
+                if i % 2 == 1 {
+                    targets.push(dir.join(name));
+                } else {
+                    kept.push(name);
+                }
+            }
+            kept.sort();
+
+            let failed = delete_files(&dir, &targets, strategy, 4);
+            assert!(failed.is_empty(), "{strategy}: {failed:?}");
+            // no staging directory is left behind, the subdirectory is untouched
+            assert_eq!(names(&dir), kept, "{strategy}");
+            assert_eq!(names(&dir.join("sub")), ["03120001.DAT"], "{strategy}");
+
+            // files that are gone already are reported, the others are still removed
+            let again = [targets[0].clone(), dir.join(&kept[0])];
+            let failed = delete_files(&dir, &again, strategy, 4);
+            let failed: Vec<&PathBuf> = failed.iter().map(|(path, _)| path).collect();
+            assert_eq!(failed, [&targets[0]], "{strategy}");
+            assert!(!dir.join(&kept[0]).exists(), "{strategy}");
+            assert_eq!(names(&dir).len(), kept.len() - 1, "{strategy}");
+            let _ = fs::remove_dir_all(&dir);
+        }
+    }
+}
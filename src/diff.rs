@@ -0,0 +1,78 @@
+//! diff renders what cleaning changed in a file as a unified diff.
+
+use std::{path::Path, time::Duration};
+
+use similar::TextDiff;
+
+/// DIFF_TIMEOUT limits the time spent on finding the smallest diff of a file; after
+/// that, a larger but still correct diff is returned.
+const DIFF_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// RUN_KEEP is the number of lines kept at the start and the end of a long run of
+/// removed or added lines.
+const RUN_KEEP: usize = 3;
+
+/// render_diff returns the unified diff between the content of path before and after
+/// cleaning, with one line of context. Long runs of removed or added lines, e.g. from
+/// the OSC transform of a huge file, are shortened to their first and last lines. If
+/// the diff is still longer than max_lines, only its first and last lines are kept.
+pub fn render_diff(path: &Path, before: &str, after: &str, max_lines: usize) -> String {
+    let diff = TextDiff::configure()
+        .timeout(DIFF_TIMEOUT)
+        .diff_lines(before, after);
+    let name = path.to_string_lossy();
+    let text = diff
+        .unified_diff()
+        .context_radius(1)
+        .header(&name, &name)
+        .to_string();
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut run: Vec<&str> = Vec::new();
+    for line in text.lines() {
+        let kind = change_kind(line);
+        if kind.is_none() || run.first().map(|l| change_kind(l)) != Some(kind) {
+            elide(&mut lines, &run, RUN_KEEP);
+            run.clear();
+        }
+        match kind {
+            Some(_) => run.push(line),
+            None => lines.push(line.to_string()),
+        }
+    }
+    elide(&mut lines, &run, RUN_KEEP);
+
+    let mut out = String::new();
+    if lines.len() > max_lines {
+        let tail = max_lines / 2;
+        let head = lines.len() - tail;
+        let mut capped = lines[..max_lines - tail].to_vec();
+        capped.push(format!("… {} diff lines elided …", head - capped.len()));
+        capped.extend_from_slice(&lines[head..]);
+        lines = capped;
+    }
+    for line in lines.iter() {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+/// change_kind returns '-' or '+' for a removed or added line of a unified diff.
+fn change_kind(line: &str) -> Option<char> {
+    if line.starts_with("---") || line.starts_with("+++") {
+        return None;
+    }
+    line.chars().next().filter(|c| *c == '-' || *c == '+')
+}
+
+/// elide appends run to lines, keeping only the first and last keep lines of a long run.
+fn elide(lines: &mut Vec<String>, run: &[&str], keep: usize) {
+    if run.len() <= 2 * keep + 1 {
+        lines.extend(run.iter().map(|l| l.to_string()));
+        return;
+    }
+    lines.extend(run[..keep].iter().map(|l| l.to_string()));
+    lines.push(format!("  … {} lines …", run.len() - 2 * keep));
+    lines.extend(run[run.len() - keep..].iter().map(|l| l.to_string()));
+}
@@ -0,0 +1,105 @@
+//! dryrun tells what a run would do to the files of a directory without changing them.
+//! Each file is copied to a temporary directory and cleaned there with the options of
+//! the run, so the decisions are the ones a real run makes, down to the fast paths.
+//! Only checks that look past the file itself can differ, e.g. whether the name a
+//! misnamed file is renamed to is taken, since the copy is alone.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf, MAIN_SEPARATOR},
+};
+
+use yaml_rust::Yaml;
+
+use crate::{
+    archive::clean_zip, clean_copy, clean_file, flag_path, tmpfile, CleanOptions, FileOutcome,
+    Outcome,
+};
+
+/// DryRun cleans copies of files instead of the files.
+pub struct DryRun {
+    opts: CleanOptions,
+    /// the directory files are copied to, removed on drop
+    work: PathBuf,
+}
+
+impl DryRun {
+    /// new prepares a dry run with opts, the options of the run.
+    pub fn new(opts: &CleanOptions) -> Result<Self, String> {
+        let opts = CleanOptions {
+            // the copy is removed right away, there is nothing to batch
+            defer_delete: false,
+            // nor anything to keep
+            backup: None,
+            ..opts.clone()
+        };
+        let work = tmpfile::temp_path(&std::env::temp_dir().join("v25_dry_run"));
+        fs::create_dir(&work).map_err(|err| format!("cannot create {work:?}: {err}"))?;
+        Ok(DryRun { opts, work })
+    }
+
+    /// evaluate returns the outcome the file at path would have, and with content what
+    /// cleaning would leave in it if it would be modified.
+    pub fn evaluate(
+        &self,
+        path: &Path,
+        cfg: &Yaml,
+        content: bool,
+    ) -> io::Result<(FileOutcome, Option<Vec<u8>>)> {
+        let mut after = None;
+        let mut copy_dir = PathBuf::new();
+        let mut outcome = clean_copy(path, &self.work, |copy| {
+            copy_dir = copy.parent().unwrap_or(&self.work).to_path_buf();
+            // a flagged file is skipped like in a real run
+            if flag_path(path).is_file() {
+                fs::copy(flag_path(path), flag_path(copy))?;
+            }
+            let outcome = clean_file(copy, cfg, &self.opts)?;
+            if content && outcome.outcome == Outcome::Modified {
+                after = fs::read(outcome.renamed_to.as_deref().unwrap_or(copy)).ok();
+            }
+            Ok(outcome)
+        })?;
+        outcome.renamed_to = outcome
+            .renamed_to
+            .as_deref()
+            .and_then(Path::file_name)
+            .map(|name| path.with_file_name(name));
+        // e.g. the name a misnamed file is renamed to
+        let work = format!("{}{MAIN_SEPARATOR}", copy_dir.display());
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => {
+                format!("{}{MAIN_SEPARATOR}", dir.display())
+            }
+            _ => String::new(),
+        };
+        for warning in outcome.warnings.iter_mut() {
+            *warning = warning.replace(&work, &dir);
+        }
+        Ok((outcome, after))
+    }
+
+    /// evaluate_zip returns the outcomes the members of the zip archive at path would
+    /// have, see clean_zip.
+    pub fn evaluate_zip(&self, path: &Path, cfg: &Yaml) -> io::Result<Vec<FileOutcome>> {
+        // a directory of its own, other threads may evaluate an archive of the same name
+        let dir = tmpfile::temp_path(&self.work.join("zip"));
+        fs::create_dir(&dir)?;
+        let copy = dir.join(path.file_name().unwrap_or_default());
+        let result = fs::copy(path, &copy).and_then(|_| clean_zip(&copy, cfg, &self.opts));
+        let _ = fs::remove_dir_all(&dir);
+        let mut members = result?;
+        for member in members.iter_mut() {
+            if let Ok(name) = member.path.strip_prefix(&copy) {
+                member.path = path.join(name);
+            }
+        }
+        Ok(members)
+    }
+}
+
+impl Drop for DryRun {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.work);
+    }
+}
@@ -0,0 +1,227 @@
+//! fuzz holds what the fuzz targets in fuzz/ run on the bytes they are given, so that
+//! the inputs they found can be replayed by the tests. A failing CF card leaves files
+//! of random bytes, and no such file may panic the cleaner; each function here panics
+//! only if the code under test does. The cfg of the fixture corpus decides the rules,
+//! see testutil::FIXTURE_DIR.
+
+use std::{
+    cell::RefCell,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use yaml_rust::Yaml;
+
+use crate::{
+    check_formats, check_line_counts, check_osc_prefix, classify::classify_file, clean_file,
+    clean_lines, compile_rules, load_reference_headers, load_yml, looks_like_data,
+    looks_like_header, n_chars_last_field, n_data_fields, n_quoted_fields, testutil::FIXTURE_DIR,
+    tmpfile, trim_fields, CleanOptions, ColumnFormat, FileOutcome, FileTypeRules, PrefixLayout,
+    PrefixPosition, RemovedCaps,
+};
+
+/// DELIMITERS are the delimiters the field helpers are tried with, picked by the first
+/// byte of the input; multi-byte ones catch slicing inside a character. The cfg file
+/// can't set an empty one, but the helpers are public.
+const DELIMITERS: [&str; 7] = ["\t", ",", ";", " ", "||", "\u{b0}", ""];
+
+/// QUOTES are the quotes the field helpers are tried with, see DELIMITERS.
+const QUOTES: [Option<char>; 3] = [None, Some('"'), Some('\u{ab}')];
+
+/// BLOCK_ACTIONS and PREFIX_POSITIONS are the settings of OSC files fuzz_osc tries,
+/// picked by the first byte of the input; the corpus has neither.
+const BLOCK_ACTIONS: [&str; 3] = ["warn", "split", "per_block"];
+const PREFIX_POSITIONS: [&str; 2] = ["prepend", "append"];
+
+/// Fixture is the cfg of the corpus with the options to clean with, loaded once per
+/// thread, and a directory for the files of fuzz_file.
+struct Fixture {
+    cfg: Yaml,
+    /// cfg with each combination of BLOCK_ACTIONS and PREFIX_POSITIONS for OSC files
+    osc_cfgs: Vec<Yaml>,
+    opts: CleanOptions,
+    work: PathBuf,
+}
+
+impl Fixture {
+    fn load() -> Self {
+        let fixtures = Path::new(FIXTURE_DIR);
+        let cfg = load_yml(&fixtures.join("cfg.yml")).swap_remove(0);
+        let opts = CleanOptions {
+            reference_headers: check_line_counts(&cfg)
+                .and_then(|_| check_formats(&cfg))
+                .and_then(|_| check_osc_prefix(&cfg))
+                .and_then(|_| load_reference_headers(&cfg, fixtures))
+                .expect("the cfg of the fixtures is valid"),
+            compiled_rules: compile_rules(&cfg).expect("the patterns of the fixtures compile"),
+            coverage: true,
+            ..Default::default()
+        };
+        let osc_cfgs = PREFIX_POSITIONS
+            .iter()
+            .flat_map(|position| BLOCK_ACTIONS.map(|action| (action, position)))
+            .map(|(action, position)| {
+                let mut cfg = cfg.clone();
+                if let Yaml::Hash(types) = &mut cfg {
+                    if let Some(Yaml::Hash(osc)) = types.get_mut(&Yaml::String("OSC".into())) {
+                        for (key, value) in [
+                            ("on_multiple_blocks", action),
+                            ("prefix_position", position),
+                        ] {
+                            osc.insert(Yaml::String(key.into()), Yaml::String(value.to_string()));
+                        }
+                    }
+                }
+                cfg
+            })
+            .collect();
+        let work = tmpfile::temp_path(&std::env::temp_dir().join("v25_fuzz"));
+        fs::create_dir(&work).expect("the temporary directory is writable");
+        Fixture {
+            cfg,
+            osc_cfgs,
+            opts,
+            work,
+        }
+    }
+
+    /// extension returns the file type of the cfg that byte picks.
+    fn extension(&self, byte: u8) -> String {
+        let types: Vec<&str> = self
+            .cfg
+            .as_hash()
+            .into_iter()
+            .flat_map(|types| types.keys())
+            .filter_map(Yaml::as_str)
+            .filter(|key| self.cfg[*key].as_hash().is_some())
+            .collect();
+        types[byte as usize % types.len()].to_string()
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.work);
+    }
+}
+
+thread_local! {
+    static FIXTURE: RefCell<Option<Fixture>> = const { RefCell::new(None) };
+}
+
+/// with_fixture runs f with the fixture of the thread.
+fn with_fixture<T>(f: impl FnOnce(&Fixture) -> T) -> T {
+    FIXTURE.with(|fixture| f(fixture.borrow_mut().get_or_insert_with(Fixture::load)))
+}
+
+/// lines splits data into lines like content_from_file, with invalid UTF-8 replaced,
+/// and tells if the last line was terminated.
+fn lines(data: &[u8]) -> (Vec<String>, bool) {
+    let text = String::from_utf8_lossy(data);
+    let final_newline = text.is_empty() || text.ends_with('\n');
+    let lines = text
+        .split_terminator('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line).to_string())
+        .collect();
+    (lines, final_newline)
+}
+
+/// fuzz_clean_lines runs checks 2 to 5 on data, with the rules of the file type its
+/// first byte picks; the rest is the content of the file.
+pub fn fuzz_clean_lines(data: &[u8]) {
+    let Some((&pick, data)) = data.split_first() else {
+        return;
+    };
+    with_fixture(|fixture| {
+        let ext = fixture.extension(pick);
+        let mut warnings = Vec::new();
+        let rules = FileTypeRules::from_cfg(
+            &fixture.cfg[ext.as_str()],
+            fixture.opts.reference_headers.get(&ext),
+            &mut warnings,
+        );
+        let (mut content, final_newline) = lines(data);
+        let mut outcome = FileOutcome::new(Path::new("fuzz"));
+        clean_lines(
+            &mut content,
+            final_newline,
+            &rules,
+            &|_| true,
+            &RemovedCaps::default(),
+            &mut outcome,
+        );
+    });
+}
+
+/// fuzz_fields counts and trims the fields of each line of data and prefixes it, with
+/// the delimiter and quote its first byte picks.
+pub fn fuzz_fields(data: &[u8]) {
+    let Some((&pick, data)) = data.split_first() else {
+        return;
+    };
+    let delimiter = DELIMITERS[pick as usize % DELIMITERS.len()];
+    let quote = QUOTES[pick as usize / DELIMITERS.len() % QUOTES.len()];
+    let columns = ColumnFormat {
+        delimiter: delimiter.to_string(),
+        header_delimiter: delimiter.to_string(),
+        quote,
+        ..Default::default()
+    };
+    let (lines, _) = lines(data);
+    for line in lines.iter() {
+        n_data_fields(line, delimiter);
+        n_quoted_fields(line, delimiter, quote);
+        columns.n_fields(line);
+        columns.n_header_fields(line);
+        trim_fields(line, delimiter);
+        n_chars_last_field(line, delimiter);
+        looks_like_header(line, delimiter, columns.min_header_tokens);
+        looks_like_data(line, delimiter);
+        for position in [PrefixPosition::Prepend, PrefixPosition::Append] {
+            let layout = PrefixLayout {
+                separator: delimiter.to_string(),
+                header_separator: delimiter.to_string(),
+                position,
+                quote,
+                ..Default::default()
+            };
+            // the line itself is the value, it has every odd character the data has
+            let prefixed = layout.apply(line, line);
+            layout.column_value(&prefixed);
+            layout.column_value(line);
+        }
+    }
+}
+
+/// fuzz_osc cleans data as an OSC file, which runs the checks and the OSC transform,
+/// with the on_multiple_blocks and prefix_position its first byte picks; the rest is
+/// the content of the file.
+pub fn fuzz_osc(data: &[u8]) {
+    let Some((&pick, data)) = data.split_first() else {
+        return;
+    };
+    with_fixture(|fixture| {
+        let cfg = &fixture.osc_cfgs[pick as usize % fixture.osc_cfgs.len()];
+        clean_in(fixture, cfg, data, "OSC");
+    });
+}
+
+/// fuzz_file classifies and cleans data as a file of type ext with classify_file and
+/// clean_file, the whole pipeline of a file: reading, the checks, the transforms and
+/// writing it back.
+pub fn fuzz_file(data: &[u8], ext: &str) {
+    with_fixture(|fixture| clean_in(fixture, &fixture.cfg, data, ext));
+}
+
+/// clean_in classifies and cleans data as a file of type ext in the directory of fixture, with cfg.
+fn clean_in(fixture: &Fixture, cfg: &Yaml, data: &[u8], ext: &str) {
+    let path = fixture.work.join(format!("fuzz.{ext}"));
+    fs::write(&path, data).expect("the temporary directory is writable");
+    // an I/O error is an outcome like any other, only a panic is not
+    let _ = classify_file(&path, cfg, &fixture.opts);
+    let _ = clean_file(&path, cfg, &fixture.opts);
+    // a split or a rename leaves more files, the next input starts afresh
+    for entry in fs::read_dir(&fixture.work).into_iter().flatten().flatten() {
+        let _ = fs::remove_file(entry.path());
+    }
+}
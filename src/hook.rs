@@ -0,0 +1,199 @@
+//! hook runs a command given on the command line for each deleted file and at the end
+//! of a run, e.g. to let station software send a notification. Hooks are off unless a
+//! command is given. Placeholders like `{path}` are replaced by their values after the
+//! command was split into words, and the command runs without a shell, so a value is
+//! always one argument whatever it holds. With a shell, on Unix only, values are quoted
+//! for it; cmd on Windows has no quoting that keeps `"` and `%VAR%` in a value as they
+//! are.
+
+use std::{
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// DELETE_PLACEHOLDERS are the placeholders of the command run for each deleted file:
+/// its path and name, the check that deleted it and the message of that check.
+pub const DELETE_PLACEHOLDERS: &[&str] = &["path", "name", "check", "reason"];
+
+/// FINISH_PLACEHOLDERS are the placeholders of the command run at the end of a run.
+pub const FINISH_PLACEHOLDERS: &[&str] = &[
+    "directory",
+    "count_scanned",
+    "count_modified",
+    "count_deleted",
+    "count_flagged",
+    "count_errors",
+];
+
+/// DEFAULT_TIMEOUT_S is how many seconds a hook command may run before it is killed.
+pub const DEFAULT_TIMEOUT_S: u64 = 10;
+
+/// Hook is a command with placeholders, checked once before a run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hook {
+    template: String,
+    words: Vec<String>,
+    shell: bool,
+    timeout: Duration,
+}
+
+impl Hook {
+    /// new checks template, which may only use the allowed placeholders. with shell, it
+    /// is run by `sh -c`, which is refused on Windows, else split into words like a
+    /// shell would: whitespace separates words, quotes keep them together.
+    pub fn new(
+        template: &str,
+        allowed: &[&str],
+        shell: bool,
+        timeout: Duration,
+    ) -> Result<Self, String> {
+        if shell && cfg!(windows) {
+            return Err(
+                "--hook-shell is only supported on Unix, cmd can't pass every value as it \
+                 is; run the command without it"
+                    .to_string(),
+            );
+        }
+        let words = split_words(template)?;
+        if words.is_empty() {
+            return Err("command is empty".to_string());
+        }
+        for (_, name) in placeholders(template) {
+            if !allowed.contains(&name) {
+                let expected: Vec<String> =
+                    allowed.iter().map(|name| format!("{{{name}}}")).collect();
+                return Err(format!(
+                    "unknown placeholder '{{{name}}}', expected one of {}",
+                    expected.join(", ")
+                ));
+            }
+        }
+        Ok(Hook {
+            template: template.to_string(),
+            words,
+            shell,
+            timeout,
+        })
+    }
+
+    /// command returns the command with the placeholders replaced by values.
+    pub fn command(&self, values: &[(&str, String)]) -> Command {
+        if self.shell {
+            let line = substitute(&self.template, values, shell_quote);
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(line);
+            return command;
+        }
+        let mut words = self
+            .words
+            .iter()
+            .map(|word| substitute(word, values, |value| value.to_string()));
+        let mut command = Command::new(words.next().unwrap_or_default());
+        command.args(words);
+        command
+    }
+
+    /// run runs the command with values and waits for it, at most the timeout of the
+    /// hook. returns an error if it could not be started, failed or was killed.
+    pub fn run(&self, values: &[(&str, String)]) -> Result<(), String> {
+        let mut child = self
+            .command(values)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|err| format!("could not start '{}': {err}", self.words[0]))?;
+        let start = Instant::now();
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) if status.success() => return Ok(()),
+                Ok(Some(status)) => return Err(format!("failed, {status}")),
+                Ok(None) if start.elapsed() >= self.timeout => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!("killed after {}s", self.timeout.as_secs()));
+                }
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                Err(err) => return Err(err.to_string()),
+            }
+        }
+    }
+}
+
+/// split_words splits s into words at whitespace. single quotes keep everything until
+/// the next one, double quotes too except for `\"` and `\\`; backslashes outside of
+/// quotes are kept, e.g. in Windows paths.
+fn split_words(s: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unclosed single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unclosed double quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unclosed double quote".to_string()),
+                    }
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// placeholders returns the positions and names of the placeholders in s: a name of
+/// lower-case letters and underscores in braces. other braces are kept as they are.
+fn placeholders(s: &str) -> impl Iterator<Item = (usize, &str)> {
+    s.match_indices('{').filter_map(move |(start, _)| {
+        let rest = &s[start + 1..];
+        let end = rest.find('}')?;
+        let name = &rest[..end];
+        (!name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c == '_'))
+            .then_some((start, name))
+    })
+}
+
+/// substitute replaces the placeholders in s by their values, passed through quote.
+fn substitute(s: &str, values: &[(&str, String)], quote: impl Fn(&str) -> String) -> String {
+    let mut out = String::new();
+    let mut pos = 0;
+    for (start, name) in placeholders(s) {
+        let Some((_, value)) = values.iter().find(|(key, _)| *key == name) else {
+            continue;
+        };
+        out.push_str(&s[pos..start]);
+        out.push_str(&quote(value));
+        pos = start + name.len() + 2;
+    }
+    out.push_str(&s[pos..]);
+    out
+}
+
+/// shell_quote quotes value as a single word for sh.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
@@ -0,0 +1,261 @@
+//! inspect decides whether a file is already clean by looking only at its first and last
+//! lines, without loading it line by line. It only ever answers "clean" when the full
+//! checks would not change the file; anything unusual is left to them.
+
+use std::{
+    collections::VecDeque,
+    fs,
+    io::{self, BufRead},
+    path::Path,
+};
+
+use crate::{
+    n_chars_last_field, normalize_header, ColumnFormat, DatetimeMatcher, FileContent, LineEnding,
+    LineEndingPolicy,
+};
+
+/// Expect is what a clean file of a type looks like.
+#[derive(Debug, Clone, Copy)]
+pub struct Expect<'a> {
+    /// minimum number of lines of the file type, header and data
+    pub min_len: usize,
+    /// index of the column header line
+    pub header_idx: usize,
+    /// normalized reference header, if configured
+    pub reference: Option<&'a str>,
+    /// delimiters of the header and data lines
+    pub columns: &'a ColumnFormat,
+}
+
+/// clean_lines returns the number of lines of text if it certainly passes checks 2 to
+/// 4 unchanged: it ends with a newline, does not mix line endings, has no trailing
+/// empty line, at least min_len lines and a data line, and its first and last data
+/// lines have as many fields as the header.
+pub fn clean_lines(text: &str, expect: &Expect) -> Option<usize> {
+    // missing final newline
+    let body = text.strip_suffix('\n')?;
+    let n_lf = text.matches('\n').count();
+    let n_crlf = text.matches("\r\n").count();
+    if n_crlf != 0 && n_crlf != n_lf {
+        return None; // mixed line endings
+    }
+    let first: Vec<&str> = body
+        .split('\n')
+        .take(expect.min_len + 1)
+        .map(strip_cr)
+        .collect();
+    let last: Vec<&str> = body.rsplit('\n').take(2).map(strip_cr).collect();
+    lines_are_clean(&first, &last, expect).then_some(n_lf)
+}
+
+/// lines_are_clean runs the checks of clean_lines that look at lines: first holds up to
+/// min_len + 1 lines from the start of the file, last its last two lines, last first.
+fn lines_are_clean(first: &[&str], last: &[&str], expect: &Expect) -> bool {
+    // a file without data lines is left to the full checks
+    if expect.min_len < expect.header_idx + 2 {
+        return false;
+    }
+    if first.len() < expect.min_len {
+        return false; // too few lines
+    }
+    if last[0].is_empty() {
+        return false; // trailing empty line
+    }
+    let delimiter = expect.columns.delimiter.as_str();
+    let n_col_header = expect.columns.n_header_fields(first[expect.header_idx]);
+    if expect.columns.n_fields(first[expect.header_idx + 1]) != n_col_header
+        || expect.columns.n_fields(last[0]) != n_col_header
+    {
+        return false;
+    }
+    if let Some(reference) = expect.reference {
+        if normalize_header(first[expect.header_idx]) != reference {
+            return false;
+        }
+    }
+    // the last field of the last line is only compared with at least two lines of data
+    if first.len() > expect.header_idx + 2
+        && n_chars_last_field(last[0], delimiter) < n_chars_last_field(last[1], delimiter)
+    {
+        return false;
+    }
+    true
+}
+
+/// Scan is what one pass over a file tells without keeping it in memory.
+#[derive(Debug, Clone, Default)]
+pub struct Scan {
+    /// the first lines of the file, without terminators
+    pub head: Vec<String>,
+    /// the last two lines of the file, last first
+    pub tail: Vec<String>,
+    pub n_lines: usize,
+    /// number of lines terminated by "\n" and "\r\n"
+    pub n_lf: usize,
+    pub n_crlf: usize,
+    pub final_newline: bool,
+    /// a line after the first one holds only a datetime, i.e. may start another
+    /// acquisition block of an OSC file
+    pub later_datetime_line: bool,
+}
+
+impl Scan {
+    /// read reads the file at path once, keeping only its first n_head and last two lines.
+    /// like content_from_file, it fails if the file is not valid UTF-8. datetime lines
+    /// are recognized by matcher.
+    pub fn read(path: &Path, n_head: usize, matcher: &dyn DatetimeMatcher) -> io::Result<Self> {
+        let mut reader = io::BufReader::new(fs::File::open(path)?);
+        let mut scan = Scan {
+            final_newline: true,
+            ..Default::default()
+        };
+        let mut tail = VecDeque::with_capacity(2);
+        let mut raw = Vec::new();
+        loop {
+            raw.clear();
+            if reader.read_until(b'\n', &mut raw)? == 0 {
+                break;
+            }
+            scan.final_newline = raw.last() == Some(&b'\n');
+            if scan.final_newline {
+                raw.pop();
+                if raw.last() == Some(&b'\r') {
+                    raw.pop();
+                    scan.n_crlf += 1;
+                } else {
+                    scan.n_lf += 1;
+                }
+            }
+            let line = std::str::from_utf8(&raw).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stream did not contain valid UTF-8",
+                )
+            })?;
+            // a datetime line is 20 characters, only those are worth the matcher
+            if scan.n_lines > 0 && line.trim().len() == 20 {
+                scan.later_datetime_line |= matcher.is_datetime_line(line);
+            }
+            if scan.n_lines < n_head {
+                scan.head.push(line.to_string());
+            }
+            if tail.len() == 2 {
+                tail.pop_back();
+            }
+            tail.push_front(line.to_string());
+            scan.n_lines += 1;
+        }
+        scan.tail = tail.into();
+        Ok(scan)
+    }
+
+    /// is_clean tells if the scanned file certainly passes checks 2 to 4 unchanged,
+    /// see clean_lines. head must hold at least min_len + 1 lines, if the
+    /// file has that many.
+    pub fn is_clean(&self, expect: &Expect) -> bool {
+        if !self.final_newline || (self.n_lf > 0 && self.n_crlf > 0) {
+            return false;
+        }
+        let n = self.head.len().min(expect.min_len + 1);
+        let first: Vec<&str> = self.head[..n].iter().map(String::as_str).collect();
+        let last: Vec<&str> = self.tail.iter().map(String::as_str).collect();
+        !last.is_empty() && lines_are_clean(&first, &last, expect)
+    }
+
+    /// line_ending resolves the line ending policy for the scanned file.
+    pub fn line_ending(&self, policy: LineEndingPolicy) -> LineEnding {
+        FileContent {
+            lines: Vec::new(),
+            n_lf: self.n_lf,
+            n_crlf: self.n_crlf,
+            final_newline: self.final_newline,
+        }
+        .line_ending(policy)
+    }
+}
+
+fn strip_cr(line: &str) -> &str {
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// mmap_clean_lines maps the file into memory and runs clean_lines on it. returns None
+/// if the file is not clean, cannot be mapped or is not valid UTF-8; the caller then
+/// reads it normally.
+#[cfg(feature = "mmap")]
+pub fn mmap_clean_lines(path: &Path, expect: &Expect) -> Option<usize> {
+    let file = std::fs::File::open(path).ok()?;
+    // SAFETY: the map is only read while the file is inspected. if another process
+    // truncates the file meanwhile, reading may fault; the cleaner must not run on files
+    // that are still being written anyway.
+    let map = unsafe { memmap2::Mmap::map(&file) }.ok()?;
+    let text = std::str::from_utf8(&map).ok()?;
+    clean_lines(text, expect)
+}
+
+/// mmap_clean_lines without the mmap feature never decides, files are always read
+/// normally.
+#[cfg(not(feature = "mmap"))]
+pub fn mmap_clean_lines(_path: &Path, _expect: &Expect) -> Option<usize> {
+    None
+}
+
+#[cfg(all(test, feature = "mmap"))]
+mod tests {
+    use super::*;
+
+    /// buffered_clean_lines is mmap_clean_lines reading the file into memory instead.
+    fn buffered_clean_lines(path: &Path, expect: &Expect) -> Option<usize> {
+        let text = String::from_utf8(fs::read(path).ok()?).ok()?;
+        clean_lines(&text, expect)
+    }
+
+    #[test]
+    fn mmap_and_buffered_reads_agree() {
+        let dir = std::env::temp_dir().join(format!("v25_inspect_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let odd: [(&str, &[u8]); 8] = [
+            ("clean", b"time\tp\n1\t2\n3\t4\n"),
+            ("crlf", b"time\tp\r\n1\t2\r\n3\t4\r\n"),
+            ("mixed", b"time\tp\r\n1\t2\n3\t4\n"),
+            ("no_final_newline", b"time\tp\n1\t2\n3\t4"),
+            ("trailing_empty", b"time\tp\n1\t2\n\n"),
+            ("truncated", b"time\tp\n1\t22\n3\t4\n"),
+            ("not_utf8", b"time\tp\n1\t\xff\n3\t4\n"),
+            ("empty", b""),
+        ];
+        let mut paths = Vec::new();
+        for (name, content) in odd {
+            let path = dir.join(format!("{name}.DAT"));
+            fs::write(&path, content).unwrap();
+            paths.push(path);
+        }
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/input");
+        for entry in fs::read_dir(corpus).unwrap() {
+            paths.push(entry.unwrap().path());
+        }
+
+        let columns = ColumnFormat::default();
+        let mut n_clean = 0;
+        for path in paths.iter().filter(|path| path.is_file()) {
+            for (min_len, header_idx) in [(2, 0), (3, 0), (6, 4)] {
+                let expect = Expect {
+                    min_len,
+                    header_idx,
+                    reference: None,
+                    columns: &columns,
+                };
+                let mapped = mmap_clean_lines(path, &expect);
+                assert_eq!(
+                    mapped,
+                    buffered_clean_lines(path, &expect),
+                    "{path:?} with {min_len} lines"
+                );
+                n_clean += usize::from(mapped.is_some());
+            }
+        }
+        // both must find clean files, not only agree on none
+        assert!(n_clean > 10, "{n_clean}");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,272 @@
+//! instrument tells the files of one instrument from those of another in the same
+//! directory. A V25 writes its ID to the preamble of each file, e.g. `ID=NOX-01`; after
+//! a card swap or a copy into the wrong directory, the files of another instrument pass
+//! every other check. The `instrument_id` of a file type in the cfg file says where the
+//! ID is and which one to expect, e.g. `instrument_id: {line: 0, pattern: 'ID=(\w+)',
+//! expected: NOX-01}`, and `on_instrument_id_mismatch` what to do with a file whose ID
+//! differs. `expected: majority` expects the ID most files of the type in a directory
+//! have, which is only known once all of them were read, see check_majority.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use regex::Regex;
+#[cfg(feature = "yaml-config")]
+use yaml_rust::Yaml;
+
+use crate::{
+    write_flag, CheckId, CleanOptions, FileOutcome, Finding, MissingHeaderAction, Outcome,
+    MAX_LINE_COUNT,
+};
+
+/// INSTRUMENT_ID_KEY is the setting of a file type with the line and pattern of the ID.
+pub const INSTRUMENT_ID_KEY: &str = "instrument_id";
+
+/// ON_MISMATCH_KEY is the setting of a file type with the action for a foreign file.
+pub const ON_MISMATCH_KEY: &str = "on_instrument_id_mismatch";
+
+/// MAJORITY is the value of `expected` that expects the ID most files have.
+pub const MAJORITY: &str = "majority";
+
+/// ExpectedId is the ID the files of a type should have.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpectedId {
+    Id(String),
+    /// the ID more than half of the files of the type in a directory have
+    Majority,
+}
+
+/// InstrumentId reads the ID of the instrument that wrote a file from a line of its
+/// preamble. line counts from 0, like header_line, from the first line after any
+/// leading garbage.
+#[derive(Debug, Clone)]
+pub struct InstrumentId {
+    pub line: usize,
+    /// the first group captures the ID
+    pub pattern: Regex,
+    pub expected: ExpectedId,
+    pub on_mismatch: MissingHeaderAction,
+}
+
+impl InstrumentId {
+    /// from_cfg reads instrument_id and on_instrument_id_mismatch from the cfg of a file
+    /// type, None if instrument_id is not set. A majority is only known after the run,
+    /// when the files of the other checks were already deleted, so it can't delete.
+    #[cfg(feature = "yaml-config")]
+    pub fn from_cfg(cfg_ext: &Yaml) -> Result<Option<Self>, String> {
+        let key = INSTRUMENT_ID_KEY;
+        let cfg_id = &cfg_ext[key];
+        if cfg_id.is_badvalue() {
+            return Ok(None);
+        }
+        if cfg_id.as_hash().is_none() {
+            return Err(format!(
+                "{key} must be a map, e.g. {{line: 0, pattern: 'ID=(\\w+)', expected: {MAJORITY}}}"
+            ));
+        }
+        let line = match cfg_id["line"].as_i64() {
+            Some(n) if (0..MAX_LINE_COUNT).contains(&n) => n as usize,
+            _ => {
+                return Err(format!(
+                    "invalid {key} line, want 0 to {}",
+                    MAX_LINE_COUNT - 1
+                ))
+            }
+        };
+        let Some(pattern) = cfg_id["pattern"].as_str() else {
+            return Err(format!("{key} needs a pattern"));
+        };
+        let pattern = crate::compile_pattern(pattern)
+            .map_err(|err| format!("invalid {key} pattern '{pattern}', {err}"))?;
+        if pattern.captures_len() < 2 {
+            return Err(format!(
+                "{key} pattern '{pattern}' has no group to capture the ID"
+            ));
+        }
+        let expected = match &cfg_id["expected"] {
+            Yaml::String(s) if s == MAJORITY => ExpectedId::Majority,
+            Yaml::String(s) if !s.trim().is_empty() => ExpectedId::Id(s.trim().to_string()),
+            Yaml::Integer(n) => ExpectedId::Id(n.to_string()),
+            _ => {
+                return Err(format!(
+                    "{key} needs the expected ID, or expected: {MAJORITY}"
+                ))
+            }
+        };
+        let on_mismatch = match cfg_ext[ON_MISMATCH_KEY].as_str() {
+            Some(s) => s.parse()?,
+            None => MissingHeaderAction::default(),
+        };
+        if expected == ExpectedId::Majority && on_mismatch == MissingHeaderAction::Delete {
+            return Err(format!(
+                "{ON_MISMATCH_KEY}: delete needs a fixed expected ID, with expected: \
+                 {MAJORITY} use warn or quarantine"
+            ));
+        }
+        Ok(Some(InstrumentId {
+            line,
+            pattern,
+            expected,
+            on_mismatch,
+        }))
+    }
+
+    /// read returns the ID in content, or why it could not be read.
+    pub fn read(&self, content: &[String]) -> Result<String, String> {
+        let Some(line) = content.get(self.line) else {
+            return Err(format!("has no line {}", self.line));
+        };
+        self.pattern
+            .captures(line)
+            .and_then(|captures| captures.get(1))
+            .map(|id| id.as_str().trim().to_string())
+            .filter(|id| !id.is_empty())
+            .ok_or_else(|| {
+                format!(
+                    "line {} '{line}' does not match '{}'",
+                    self.line, self.pattern
+                )
+            })
+    }
+
+    /// expected_id returns the ID a file should have: the one of --expect-id, else the
+    /// one of the cfg file; None for a majority, see check_majority.
+    pub fn expected_id<'a>(&'a self, opts: &'a CleanOptions) -> Option<&'a str> {
+        match (&opts.expect_id, &self.expected) {
+            (Some(id), _) => Some(id),
+            (None, ExpectedId::Id(id)) => Some(id),
+            (None, ExpectedId::Majority) => None,
+        }
+    }
+}
+
+/// mismatch returns the finding for a file with the ID id if that is not expected.
+pub fn mismatch(id: &str, expected: &str) -> Option<Finding> {
+    (id != expected).then(|| {
+        Finding::new(
+            CheckId::InstrumentId,
+            format!("instrument ID is '{id}', expected '{expected}'"),
+        )
+    })
+}
+
+/// Majority is the outcome of check_majority for the files of one type in a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Majority {
+    pub directory: PathBuf,
+    pub extension: String,
+    /// the ID more than half of the files have, None if no ID does
+    pub id: Option<String>,
+    /// number of files with each ID
+    pub counts: BTreeMap<String, usize>,
+}
+
+impl Majority {
+    /// render_tie renders the notice for a group without a majority, None if it has one.
+    pub fn render_tie(&self) -> Option<String> {
+        if self.id.is_some() {
+            return None;
+        }
+        let counts: Vec<String> = self
+            .counts
+            .iter()
+            .map(|(id, n)| format!("{n} {id}"))
+            .collect();
+        Some(format!(
+            "! no instrument ID is held by most {} files in {:?} ({}), not checking them",
+            self.extension,
+            self.directory,
+            counts.join(", ")
+        ))
+    }
+}
+
+/// check_majority compares the ID of each file of the types with `expected: majority`
+/// with the ID more than half of the files of its type in its directory have, unless
+/// --expect-id gave one, and applies on_instrument_id_mismatch to the others: warn adds
+/// a warning, quarantine flags the file, writing the flag if write_flags is set; the
+/// caller moves it to --quarantine. Only files that were kept count and are changed. It
+/// returns the groups it looked at.
+pub fn check_majority(
+    outcomes: &mut [FileOutcome],
+    opts: &CleanOptions,
+    write_flags: bool,
+) -> Vec<Majority> {
+    if opts.expect_id.is_some() {
+        return Vec::new();
+    }
+    let rules = |file: &FileOutcome| {
+        let ext = file
+            .effective_extension
+            .as_ref()
+            .or(file.extension.as_ref())?;
+        let rules = opts.compiled_rules.get(ext)?.instrument_id.as_ref()?;
+        (rules.expected == ExpectedId::Majority).then_some((ext.clone(), rules))
+    };
+    let mut groups: BTreeMap<(PathBuf, String), Vec<usize>> = BTreeMap::new();
+    for (i, file) in outcomes.iter().enumerate() {
+        let kept = matches!(file.outcome, Outcome::Unchanged | Outcome::Modified);
+        if let (true, Some(_), Some((ext, _))) = (kept, &file.instrument_id, rules(file)) {
+            let dir = file.path.parent().unwrap_or(Path::new("")).to_path_buf();
+            groups.entry((dir, ext)).or_default().push(i);
+        }
+    }
+    let mut majorities = Vec::new();
+    for ((directory, extension), files) in groups.into_iter() {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for &i in files.iter() {
+            if let Some(id) = &outcomes[i].instrument_id {
+                *counts.entry(id.clone()).or_default() += 1;
+            }
+        }
+        let id = counts
+            .iter()
+            .find(|(_, &n)| n * 2 > files.len())
+            .map(|(id, _)| id.clone());
+        if let Some(expected) = &id {
+            for &i in files.iter() {
+                let file = &mut outcomes[i];
+                let Some(on_mismatch) = rules(file).map(|(_, rules)| rules.on_mismatch) else {
+                    continue;
+                };
+                file.expected_instrument_id = Some(expected.clone());
+                let Some(mut finding) = file
+                    .instrument_id
+                    .as_deref()
+                    .and_then(|id| mismatch(id, expected))
+                else {
+                    continue;
+                };
+                finding.message.push_str(" by most files in the directory");
+                match on_mismatch {
+                    MissingHeaderAction::Quarantine => {
+                        finding.message.push_str(" -> flag file");
+                        file.findings.push(finding);
+                        let path = file.renamed_to.clone().unwrap_or_else(|| file.path.clone());
+                        if write_flags {
+                            if let Err(err) = write_flag(&path, &file.findings) {
+                                file.warnings
+                                    .push(format!("could not write the flag file: {err}"));
+                            }
+                        }
+                        file.outcome = Outcome::Flagged(CheckId::InstrumentId);
+                        file.lines_after = None;
+                    }
+                    // from_cfg refuses delete with a majority
+                    MissingHeaderAction::Warn | MissingHeaderAction::Delete => {
+                        file.warnings.push(finding.message)
+                    }
+                }
+            }
+        }
+        majorities.push(Majority {
+            directory,
+            extension,
+            id,
+            counts,
+        });
+    }
+    majorities
+}
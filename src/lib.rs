@@ -1,12 +1,629 @@
 use std::{
-    fs,
-    io::{self, prelude::*, BufRead, Write},
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    io::{self, BufRead, Write},
+    ops::Range,
     path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, SystemTime},
 };
 
-use yaml_rust::YamlLoader;
+#[cfg(feature = "yaml-config")]
+use std::{io::Read, time::Instant};
+
+#[cfg(feature = "yaml-config")]
+use chrono::{DateTime, Local, NaiveDate};
+use chrono::{Datelike, NaiveDateTime, TimeDelta};
+#[cfg(feature = "regex-checks")]
+use lazy_static::lazy_static;
+#[cfg(feature = "regex-checks")]
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "yaml-config")]
+use yaml_rust::{Yaml, YamlLoader};
+
+#[cfg(feature = "yaml-config")]
+pub mod archive;
+pub mod audit;
+pub mod backup;
+pub mod budget;
+pub mod build_info;
+pub mod cert;
+#[cfg(feature = "yaml-config")]
+pub mod classify;
+#[cfg(feature = "yaml-config")]
+pub mod completeness;
+#[cfg(feature = "yaml-config")]
+pub mod config;
+pub mod delete;
+pub mod diff;
+#[cfg(feature = "yaml-config")]
+pub mod dryrun;
+#[cfg(feature = "test-util")]
+pub mod fuzz;
+pub mod hook;
+pub mod inspect;
+#[cfg(feature = "regex-checks")]
+pub mod instrument;
+pub mod logfile;
+pub mod manifest;
+pub mod marker;
+pub mod observer;
+pub mod preflight;
+pub mod preset;
+pub mod privilege;
+#[cfg(feature = "yaml-config")]
+pub mod profile;
+#[cfg(feature = "yaml-config")]
+pub mod repair;
+pub mod report;
+pub mod sample;
+#[cfg(feature = "yaml-config")]
+pub mod selftest;
+pub mod spotcheck;
+#[cfg(feature = "yaml-config")]
+pub mod station;
+#[cfg(feature = "test-util")]
+pub mod testutil;
+pub mod tmpfile;
+pub mod walk;
+
+/// CheckId identifies a check or transform applied to a file. The names of as_str are
+/// part of the report format and the config / command line interface, so they must
+/// never change; add new variants instead. They are serialized as those names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CheckId {
+    NoExtension,
+    EmptyFile,
+    MixedLineEndings,
+    LeadingGarbage,
+    DuplicatePreamble,
+    TrailingEmptyLine,
+    TooFewLines,
+    LinePatternMismatch,
+    FirstDataLineFieldMismatch,
+    HeaderMismatch,
+    MissingFinalNewline,
+    LastLineFieldMismatch,
+    LastFieldTruncated,
+    DuplicateTimestamp,
+    TimeReset,
+    RemovedFraction,
+    TrimFields,
+    OscDatetimePrefix,
+    DuplicateHeaderField,
+    MissingHeader,
+    ExpectedCount,
+    OutputDelimiter,
+    InstrumentId,
+}
+
+impl CheckId {
+    /// all checks and transforms, in the order they are applied to a file.
+    pub const ALL: [CheckId; 23] = [
+        CheckId::NoExtension,
+        CheckId::EmptyFile,
+        CheckId::MixedLineEndings,
+        CheckId::LeadingGarbage,
+        CheckId::MissingHeader,
+        CheckId::DuplicatePreamble,
+        CheckId::TrailingEmptyLine,
+        CheckId::TooFewLines,
+        CheckId::LinePatternMismatch,
+        CheckId::FirstDataLineFieldMismatch,
+        CheckId::HeaderMismatch,
+        CheckId::DuplicateHeaderField,
+        CheckId::MissingFinalNewline,
+        CheckId::LastLineFieldMismatch,
+        CheckId::LastFieldTruncated,
+        CheckId::InstrumentId,
+        CheckId::ExpectedCount,
+        CheckId::DuplicateTimestamp,
+        CheckId::TimeReset,
+        CheckId::RemovedFraction,
+        CheckId::TrimFields,
+        CheckId::OutputDelimiter,
+        CheckId::OscDatetimePrefix,
+    ];
+
+    /// as_str returns the stable string identifier of the check.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheckId::NoExtension => "no_extension",
+            CheckId::EmptyFile => "empty_file",
+            CheckId::TrailingEmptyLine => "trailing_empty_line",
+            CheckId::TooFewLines => "too_few_lines",
+            CheckId::LinePatternMismatch => "line_pattern_mismatch",
+            CheckId::FirstDataLineFieldMismatch => "first_data_line_field_mismatch",
+            CheckId::HeaderMismatch => "header_mismatch",
+            CheckId::MissingFinalNewline => "missing_final_newline",
+            CheckId::LastLineFieldMismatch => "last_line_field_mismatch",
+            CheckId::LastFieldTruncated => "last_field_truncated",
+            CheckId::OscDatetimePrefix => "osc_datetime_prefix",
+            CheckId::MixedLineEndings => "mixed_line_endings",
+            CheckId::LeadingGarbage => "leading_garbage",
+            CheckId::DuplicatePreamble => "duplicate_preamble",
+            CheckId::DuplicateTimestamp => "duplicate_timestamp",
+            CheckId::TimeReset => "time_reset",
+            CheckId::RemovedFraction => "removed_fraction",
+            CheckId::TrimFields => "trim_fields",
+            CheckId::DuplicateHeaderField => "duplicate_header_field",
+            CheckId::MissingHeader => "missing_header",
+            CheckId::ExpectedCount => "expected_count",
+            CheckId::InstrumentId => "instrument_id",
+            CheckId::OutputDelimiter => "output_delimiter",
+        }
+    }
+
+    /// description returns a short human-readable explanation of the check.
+    pub fn description(&self) -> &'static str {
+        match self {
+            CheckId::NoExtension => "file has no extension -> delete file",
+            CheckId::EmptyFile => "file has a size of zero bytes -> delete file",
+            CheckId::TrailingEmptyLine => "empty lines at the end of the file -> remove lines",
+            CheckId::TooFewLines => {
+                "file has less than min_data_lines data lines below the header -> delete file"
+            }
+            CheckId::LinePatternMismatch => {
+                "freeform files: lines not matching line_pattern -> warn or remove lines"
+            }
+            CheckId::FirstDataLineFieldMismatch => {
+                "first line of data has a different number of fields than the header -> delete file"
+            }
+            CheckId::HeaderMismatch => {
+                "column header differs from reference_header_file -> warn or delete file"
+            }
+            CheckId::MissingFinalNewline => {
+                "last line is not terminated by a newline -> remove line, add newline or keep it"
+            }
+            CheckId::LastLineFieldMismatch => {
+                "last line has a different number of fields than the header -> remove line"
+            }
+            CheckId::LastFieldTruncated => {
+                "last field of last line is shorter than in the preceding line -> remove line"
+            }
+            CheckId::OscDatetimePrefix => {
+                "OSC files: prefix data lines with the datetime from the first line"
+            }
+            CheckId::MixedLineEndings => {
+                "file mixes LF and CRLF line endings -> rewrite if normalize_line_endings is set"
+            }
+            CheckId::LeadingGarbage => {
+                "lines at the start matching skip_leading_garbage -> remove lines"
+            }
+            CheckId::DuplicatePreamble => {
+                "the header lines are repeated right below them -> warn or remove the copies"
+            }
+            CheckId::DuplicateTimestamp => {
+                "consecutive data lines with the same timestamp -> warn or collapse lines"
+            }
+            CheckId::TrimFields => {
+                "spaces around fields -> trim them if trim_fields is set and the file is rewritten"
+            }
+            CheckId::TimeReset => {
+                "timestamps jump back by more than time_reset_threshold_s -> warn, split or drop lines"
+            }
+            CheckId::RemovedFraction => {
+                "more than max_removed_fraction of the data lines would be removed -> flag or delete file"
+            }
+            CheckId::DuplicateHeaderField => {
+                "column header names a field more than once, if unique_header_fields is set -> warn or delete file"
+            }
+            CheckId::MissingHeader => {
+                "no line in the first max_preamble_lines matches header_match -> delete, quarantine or warn"
+            }
+            CheckId::ExpectedCount => {
+                "fewer data lines than the preamble announces, see expected_count_from_header -> warn, delete or quarantine"
+            }
+            CheckId::OutputDelimiter => {
+                "fields not delimited by output_delimiter -> convert them if the file is rewritten"
+            }
+            CheckId::InstrumentId => {
+                "instrument ID in the preamble is not the expected one, see instrument_id -> warn, delete or quarantine"
+            }
+        }
+    }
+}
+
+impl fmt::Display for CheckId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl Serialize for CheckId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for CheckId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = Cow::<str>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl FromStr for CheckId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CheckId::ALL
+            .into_iter()
+            .find(|id| id.as_str() == s)
+            .ok_or_else(|| {
+                let valid: Vec<&str> = CheckId::ALL.iter().map(|id| id.as_str()).collect();
+                format!("unknown check '{s}', valid checks: {}", valid.join(", "))
+            })
+    }
+}
+
+/// check_enabled tells if a check is enabled for the given file extension in the cfg.
+/// checks can be disabled per extension by setting `checks: {<check id>: false}`;
+/// anything else (including a missing key) leaves the check enabled.
+#[cfg(feature = "yaml-config")]
+pub fn check_enabled(cfg: &Yaml, file_ext: &str, id: CheckId) -> bool {
+    cfg[file_ext]["checks"][id.as_str()]
+        .as_bool()
+        .unwrap_or(true)
+}
+
+/// normalize_header splits a column header on tabs and spaces and joins the non-empty
+/// fields with a tab, so headers differing only in whitespace compare equal.
+pub fn normalize_header(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<&str>>().join("\t")
+}
+
+/// check_readonly returns an error if a file type is `readonly: true` and also
+/// configures an action that would change its files, e.g. `on_header_mismatch: delete`.
+#[cfg(feature = "yaml-config")]
+pub fn check_readonly(cfg: &Yaml) -> Result<(), String> {
+    let Some(types) = cfg.as_hash() else {
+        return Ok(());
+    };
+    for (ext, cfg_ext) in types.iter() {
+        if cfg_ext["readonly"].as_bool() != Some(true) {
+            continue;
+        }
+        let ext = ext.as_str().unwrap_or_default();
+        let destructive = [
+            ("on_delete", !cfg_ext["on_delete"].is_badvalue()),
+            (
+                "on_header_mismatch",
+                cfg_ext["on_header_mismatch"].as_str() == Some("delete"),
+            ),
+            (
+                "on_duplicate_header_field",
+                cfg_ext["on_duplicate_header_field"].as_str() == Some("delete"),
+            ),
+            (
+                "on_missing_header",
+                matches!(
+                    cfg_ext["on_missing_header"].as_str(),
+                    Some("delete" | "quarantine")
+                ),
+            ),
+            (
+                "on_short_count",
+                matches!(
+                    cfg_ext["on_short_count"].as_str(),
+                    Some("delete" | "quarantine")
+                ),
+            ),
+            (
+                "on_instrument_id_mismatch",
+                matches!(
+                    cfg_ext["on_instrument_id_mismatch"].as_str(),
+                    Some("delete" | "quarantine")
+                ),
+            ),
+            (
+                "on_missing_final_newline",
+                matches!(
+                    cfg_ext["on_missing_final_newline"].as_str(),
+                    Some("drop" | "pad")
+                ),
+            ),
+            (
+                "normalize_line_endings",
+                cfg_ext["normalize_line_endings"].as_bool() == Some(true),
+            ),
+            (
+                "on_multiple_blocks",
+                matches!(
+                    cfg_ext["on_multiple_blocks"].as_str(),
+                    Some("per_block" | "split")
+                ),
+            ),
+            (
+                "on_duplicate_timestamp",
+                matches!(
+                    cfg_ext["on_duplicate_timestamp"].as_str(),
+                    Some("keep_first" | "keep_last" | "drop_both")
+                ),
+            ),
+            (
+                "on_time_reset",
+                matches!(
+                    cfg_ext["on_time_reset"].as_str(),
+                    Some("split" | "drop_before")
+                ),
+            ),
+            (
+                "trim_fields",
+                cfg_ext["trim_fields"].as_bool() == Some(true),
+            ),
+            (
+                "output_delimiter",
+                !cfg_ext["output_delimiter"].is_badvalue(),
+            ),
+            (
+                "skip_leading_garbage",
+                !cfg_ext["skip_leading_garbage"].is_badvalue(),
+            ),
+            (
+                "on_duplicate_preamble",
+                cfg_ext["on_duplicate_preamble"].as_str() == Some("strip"),
+            ),
+            (
+                "on_too_many_removed",
+                cfg_ext["on_too_many_removed"].as_str() == Some("delete"),
+            ),
+        ];
+        if let Some((key, _)) = destructive.iter().find(|(_, set)| *set) {
+            return Err(format!(
+                "{ext} is readonly, but {key} configures an action that changes its files"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// MAX_LINE_COUNT bounds the line counts of a file type, larger values are typos.
+pub const MAX_LINE_COUNT: i64 = 10_000;
+
+/// check_line_counts returns an error if a file type sets min_n_lines, n_header_lines,
+/// min_data_lines or header_line to anything but an integer in a sane range. a typo
+/// like `min_n_lines: -7` would otherwise make every file of the type too short.
+/// freeform types may set min_n_lines to 1, columnar ones need a header and a data
+/// line.
+#[cfg(feature = "yaml-config")]
+pub fn check_line_counts(cfg: &Yaml) -> Result<(), String> {
+    let Some(types) = cfg.as_hash() else {
+        return Ok(());
+    };
+    for (ext, cfg_ext) in types.iter() {
+        let ext = ext.as_str().unwrap_or_default();
+        for (key, min) in [
+            ("min_n_lines", if is_freeform(cfg_ext) { 1 } else { 2 }),
+            ("n_header_lines", 1),
+            ("min_data_lines", 0),
+            ("header_line", 0),
+        ] {
+            let value = match &cfg_ext[key] {
+                Yaml::BadValue => continue,
+                Yaml::Integer(n) if (min..=MAX_LINE_COUNT).contains(n) => continue,
+                Yaml::Integer(n) => n.to_string(),
+                Yaml::Real(s) | Yaml::String(s) => format!("'{s}'"),
+                other => format!("{other:?}"),
+            };
+            return Err(format!(
+                "{ext}: {key} must be an integer from {min} to {MAX_LINE_COUNT}, not {value}"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// COLUMNAR_ONLY are the settings of the cfg file that need a column header or fields,
+/// which freeform types lack.
+#[cfg(feature = "yaml-config")]
+const COLUMNAR_ONLY: [&str; 27] = [
+    "n_header_lines",
+    "header_line",
+    "header_match",
+    "max_preamble_lines",
+    "on_missing_header",
+    "expected_count_from_header",
+    "on_short_count",
+    "delimiter",
+    "header_delimiter",
+    "min_header_tokens",
+    "reference_header_file",
+    "on_header_mismatch",
+    "unique_header_fields",
+    "header_fields_ignore_case",
+    "on_duplicate_header_field",
+    "on_missing_final_newline",
+    "on_duplicate_preamble",
+    "timestamp_format",
+    "timestamp_column",
+    "on_duplicate_timestamp",
+    "on_time_reset",
+    "time_reset_threshold_s",
+    "trim_fields",
+    "trim_fields_force_rewrite",
+    "output_delimiter",
+    "output_delimiter_force",
+    "quote_char",
+];
+
+/// check_formats returns an error if a file type has an invalid format, or settings that
+/// do not apply to its format: the COLUMNAR_ONLY ones on freeform types, line_pattern
+/// and on_line_pattern_mismatch on columnar ones.
+#[cfg(feature = "yaml-config")]
+pub fn check_formats(cfg: &Yaml) -> Result<(), String> {
+    let Some(types) = cfg.as_hash() else {
+        return Ok(());
+    };
+    for (ext, cfg_ext) in types.iter() {
+        let ext = ext.as_str().unwrap_or_default();
+        let format = match cfg_ext["format"].as_str() {
+            Some(s) => s.parse().map_err(|err| format!("{ext}: {err}"))?,
+            None if cfg_ext["format"].is_badvalue() => FileFormat::Columnar,
+            None => return Err(format!("{ext}: format must be columnar or freeform")),
+        };
+        let (invalid, needs): (&[&str], &str) = match format {
+            FileFormat::Columnar => (&["line_pattern", "on_line_pattern_mismatch"], "freeform"),
+            FileFormat::Freeform => (&COLUMNAR_ONLY, "columnar"),
+        };
+        if let Some(key) = invalid.iter().find(|key| !cfg_ext[**key].is_badvalue()) {
+            return Err(format!("{ext}: {key} only applies to {needs} types"));
+        }
+    }
+    Ok(())
+}
+
+/// check_osc_prefix returns an error if the datetime prefix of OSC files would contain
+/// the delimiter of their data, e.g. a space in `prefix_output_format` of a type
+/// delimited by spaces: every prefixed line would get more fields than its header, and
+/// the next run would find the files broken. with `quote_char`, a datetime containing
+/// the separator of the prefix is quoted instead. the format is tried on two datetimes,
+/// padding like `%e` differs between them.
+#[cfg(feature = "yaml-config")]
+pub fn check_osc_prefix(cfg: &Yaml) -> Result<(), String> {
+    let cfg_ext = &cfg["OSC"];
+    if cfg_ext.is_badvalue() {
+        return Ok(());
+    }
+    // invalid settings are warned about for each file, see osc_rules
+    let (Ok(layout), Ok(columns), Ok(output), Ok(rules)) = (
+        PrefixLayout::from_cfg(cfg_ext),
+        ColumnFormat::from_cfg(cfg_ext),
+        OutputDelimiter::from_cfg(cfg_ext),
+        DatetimeRules::from_cfg(cfg_ext),
+    ) else {
+        return Ok(());
+    };
+    let (layout, columns) = match &output {
+        Some(output) => (output.layout(layout), output.columns(&columns)),
+        None => (layout, columns),
+    };
+    let format = match (&rules.output_format, &rules.sample_interval) {
+        (Some(format), _) => format.clone(),
+        (None, Some(_)) => rules.format.replace("%.f", "%.2f"),
+        // written as found, which is what the datetime format parses
+        (None, None) => rules.format.clone(),
+    };
+    let samples =
+        [(2000, 1, 1, 0, 0, 0), (2023, 12, 31, 23, 59, 59)].map(|(y, m, d, h, min, s)| {
+            NaiveDate::from_ymd_opt(y, m, d)
+                .and_then(|date| date.and_hms_opt(h, min, s))
+                .unwrap_or_default()
+        });
+    for dt in samples {
+        let prefix = format_datetime(&dt, &format)?;
+        for delimiter in [&layout.separator, &columns.delimiter] {
+            let quoted = layout.quote.is_some() && *delimiter == layout.separator;
+            if prefix.contains(delimiter.as_str()) && !quoted {
+                return Err(format!(
+                    "OSC: the datetime prefix '{prefix}' of format '{format}' contains the \
+                     delimiter '{}' of the data lines; set quote_char to quote it, or set \
+                     prefix_output_format to a format without it",
+                    delimiter.escape_default()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// is_freeform tells if the cfg of a file type sets `format: freeform`.
+#[cfg(feature = "yaml-config")]
+fn is_freeform(cfg_ext: &Yaml) -> bool {
+    cfg_ext["format"].as_str() == Some("freeform")
+}
+
+/// load_reference_headers loads the reference header of each file type that has a
+/// `reference_header_file`, relative to cfg_dir. The header is the first non-empty line
+/// of the file, normalized. A missing or empty reference file is an error.
+#[cfg(feature = "yaml-config")]
+pub fn load_reference_headers(
+    cfg: &Yaml,
+    cfg_dir: &Path,
+) -> Result<HashMap<String, String>, String> {
+    let mut headers = HashMap::new();
+    let Some(types) = cfg.as_hash() else {
+        return Ok(headers);
+    };
+    for (ext, cfg_ext) in types.iter() {
+        let (Some(ext), Some(file)) = (ext.as_str(), cfg_ext["reference_header_file"].as_str())
+        else {
+            continue;
+        };
+        let path = cfg_dir.join(file);
+        let content = fs::read_to_string(&path)
+            .map_err(|err| format!("{ext}: reference header file {path:?}: {err}"))?;
+        let header = content
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .ok_or_else(|| format!("{ext}: reference header file {path:?} is empty"))?;
+        headers.insert(ext.to_uppercase(), normalize_header(header));
+    }
+    Ok(headers)
+}
+
+/// header_difference describes how header differs from reference, None if they are
+/// equal after normalization.
+pub fn header_difference(header: &str, reference: &str) -> Option<String> {
+    let header = normalize_header(header);
+    if header == reference {
+        return None;
+    }
+    let have: Vec<&str> = header.split('\t').collect();
+    let want: Vec<&str> = reference.split('\t').collect();
+    match have.iter().zip(want.iter()).position(|(h, w)| h != w) {
+        Some(i) => Some(format!(
+            "column {} is '{}', want '{}'",
+            i + 1,
+            have[i],
+            want[i]
+        )),
+        None => Some(format!("{} columns, want {}", have.len(), want.len())),
+    }
+}
+
+/// header_resembles tells if header is the reference header, or nearly: after
+/// normalization, it has as many columns and at most one column name differs. that
+/// tolerance needs at least three columns.
+pub fn header_resembles(header: &str, reference: &str) -> bool {
+    let header = normalize_header(header);
+    if header == reference {
+        return true;
+    }
+    let have: Vec<&str> = header.split('\t').collect();
+    let want: Vec<&str> = reference.split('\t').collect();
+    have.len() == want.len()
+        && have.len() >= 3
+        && have.iter().zip(want.iter()).filter(|(h, w)| h != w).count() <= 1
+}
+
+/// sniff_type returns the extension whose reference header the content of a file of
+/// extension file_ext resembles, if exactly one other extension matches. the header is
+/// looked for at the header line of each candidate extension.
+#[cfg(feature = "yaml-config")]
+pub fn sniff_type(
+    content: &[String],
+    file_ext: &str,
+    cfg: &Yaml,
+    reference_headers: &HashMap<String, String>,
+) -> Option<String> {
+    let mut matches = reference_headers.iter().filter(|(ext, reference)| {
+        let header_idx = line_counts(&cfg[ext.as_str()]).0.unwrap_or(1).max(1) - 1;
+        ext.as_str() != file_ext
+            && content
+                .get(header_idx)
+                .is_some_and(|header| header_resembles(header, reference))
+    });
+    match (matches.next(), matches.next()) {
+        (Some((ext, _)), None) => Some(ext.clone()),
+        _ => None,
+    }
+}
 
 /// load_yml loads a yaml file, used here to specifiy minimum number of lines per file type.
+#[cfg(feature = "yaml-config")]
 pub fn load_yml(filename: &PathBuf) -> Vec<yaml_rust::Yaml> {
     let mut file =
         fs::File::open(filename).unwrap_or_else(|_| panic!("could not open: {:?}", filename));
@@ -27,62 +644,4444 @@ pub fn lines_from_file(filename: impl AsRef<Path>) -> Result<Vec<String>, io::Er
     buf.lines().collect::<Result<Vec<String>, io::Error>>()
 }
 
-/// lines_to_file writes a vector of strings to a textfile. trims lines before write.
-pub fn lines_to_file(filename: impl AsRef<Path>, content: Vec<String>) -> io::Result<()> {
+/// LineEnding is the terminator written after each line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
+/// LineEndingPolicy selects the terminator used when a file is rewritten, configured
+/// per file type as `line_ending: lf|crlf|majority`. majority uses the terminator that
+/// occurs most often in the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEndingPolicy {
+    Lf,
+    Crlf,
+    Majority,
+}
+
+impl FromStr for LineEndingPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lf" => Ok(LineEndingPolicy::Lf),
+            "crlf" => Ok(LineEndingPolicy::Crlf),
+            "majority" => Ok(LineEndingPolicy::Majority),
+            _ => Err(format!(
+                "invalid line_ending '{s}', expected one of lf, crlf, majority"
+            )),
+        }
+    }
+}
+
+/// LastLineAction is what to do with a last line that looks corrupted, configured per
+/// file type, e.g. `on_missing_final_newline: drop|pad|keep`. pad keeps the line and
+/// completes it, i.e. adds the missing newline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LastLineAction {
+    #[default]
+    Drop,
+    Pad,
+    Keep,
+}
+
+impl FromStr for LastLineAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop" => Ok(LastLineAction::Drop),
+            "pad" => Ok(LastLineAction::Pad),
+            "keep" => Ok(LastLineAction::Keep),
+            _ => Err(format!(
+                "invalid action '{s}', expected one of drop, pad, keep"
+            )),
+        }
+    }
+}
+
+/// BlocksAction is what to do with an OSC file that holds more than one acquisition
+/// block, i.e. the software was restarted and appended a new preamble with a new start
+/// datetime, configured as `on_multiple_blocks: split|per_block|warn`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlocksAction {
+    /// write each block to its own file, `<name>_2.OSC` etc. for the blocks after the first
+    Split,
+    /// keep the blocks in one file, each prefixed with its own datetime
+    PerBlock,
+    /// only warn, the file is not prefixed
+    Warn,
+}
+
+impl FromStr for BlocksAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "split" => Ok(BlocksAction::Split),
+            "per_block" => Ok(BlocksAction::PerBlock),
+            "warn" => Ok(BlocksAction::Warn),
+            _ => Err(format!(
+                "invalid action '{s}', expected one of split, per_block, warn"
+            )),
+        }
+    }
+}
+
+/// TimeResetAction is what to do with a file whose timestamps jump back, e.g. when the
+/// clock was re-synced by GPS, configured as `on_time_reset: warn|split|drop_before`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeResetAction {
+    /// only warn, the file is not changed
+    Warn,
+    /// write the data after each jump to its own file, `<name>_2.DAT` etc., each with
+    /// a copy of the header
+    Split,
+    /// remove the data before the last jump, the data after it has the corrected time
+    DropBefore,
+}
+
+impl FromStr for TimeResetAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warn" => Ok(TimeResetAction::Warn),
+            "split" => Ok(TimeResetAction::Split),
+            "drop_before" => Ok(TimeResetAction::DropBefore),
+            _ => Err(format!(
+                "invalid action '{s}', expected one of warn, split, drop_before"
+            )),
+        }
+    }
+}
+
+/// DuplicateAction is what to do with consecutive data lines that have the same
+/// timestamp, e.g. a buffer flushed twice, configured as
+/// `on_duplicate_timestamp: keep_first|keep_last|drop_both|warn`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateAction {
+    /// keep the first line of each run with the same timestamp
+    KeepFirst,
+    /// keep the last line of each run with the same timestamp
+    KeepLast,
+    /// remove all lines of the run, none of them can be trusted
+    DropBoth,
+    /// only warn, the file is not changed
+    Warn,
+}
+
+impl FromStr for DuplicateAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep_first" => Ok(DuplicateAction::KeepFirst),
+            "keep_last" => Ok(DuplicateAction::KeepLast),
+            "drop_both" => Ok(DuplicateAction::DropBoth),
+            "warn" => Ok(DuplicateAction::Warn),
+            _ => Err(format!(
+                "invalid action '{s}', expected one of keep_first, keep_last, drop_both, warn"
+            )),
+        }
+    }
+}
+
+/// FileFormat is how the lines of the files of a type are structured, configured as
+/// `format: columnar|freeform`. freeform lines, e.g. of an event log, have neither a
+/// column header nor fields: only trailing empty lines and the number of lines are
+/// checked, and line_pattern if it is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileFormat {
+    #[default]
+    Columnar,
+    Freeform,
+}
+
+impl FromStr for FileFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "columnar" => Ok(FileFormat::Columnar),
+            "freeform" => Ok(FileFormat::Freeform),
+            _ => Err(format!(
+                "invalid format '{s}', expected one of columnar, freeform"
+            )),
+        }
+    }
+}
+
+/// LinePatternAction is what to do with the lines of a freeform file that do not match
+/// its line_pattern, configured as `on_line_pattern_mismatch: warn|remove`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LinePatternAction {
+    /// only warn, the file is not changed
+    #[default]
+    Warn,
+    /// remove the lines
+    Remove,
+}
+
+impl FromStr for LinePatternAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warn" => Ok(LinePatternAction::Warn),
+            "remove" => Ok(LinePatternAction::Remove),
+            _ => Err(format!(
+                "invalid action '{s}', expected one of warn, remove"
+            )),
+        }
+    }
+}
+
+/// PreambleAction is what to do with copies of the header lines right below them, e.g.
+/// written by a logger that appended its preamble twice, configured as
+/// `on_duplicate_preamble: strip|warn`. without the setting, no file is checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreambleAction {
+    /// remove the copies before the other checks run
+    Strip,
+    /// only warn, the file is not changed
+    Warn,
+}
+
+impl FromStr for PreambleAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strip" => Ok(PreambleAction::Strip),
+            "warn" => Ok(PreambleAction::Warn),
+            _ => Err(format!("invalid action '{s}', expected one of strip, warn")),
+        }
+    }
+}
+
+/// preamble_copies returns how many times the first header_len lines of content are
+/// repeated right below them. lines must be equal to count, a single differing
+/// character means no copy.
+pub fn preamble_copies(content: &[String], header_len: usize) -> usize {
+    if header_len == 0 {
+        return 0;
+    }
+    let preamble = &content[..header_len.min(content.len())];
+    content[preamble.len()..]
+        .chunks(header_len)
+        .take_while(|chunk| *chunk == preamble)
+        .count()
+}
+
+/// DeleteAction is what to do with a file that a check would delete, configured per
+/// file type, e.g. `on_delete: delete|flag`. flag keeps the file untouched and writes
+/// the reasons to a sidecar file, see flag_path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteAction {
+    Delete,
+    Flag,
+}
+
+impl FromStr for DeleteAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "delete" => Ok(DeleteAction::Delete),
+            "flag" => Ok(DeleteAction::Flag),
+            _ => Err(format!(
+                "invalid action '{s}', expected one of delete, flag"
+            )),
+        }
+    }
+}
+
+/// TOOL_FILES are the files the tool writes to a cleaned directory. They are not data:
+/// they are not cleaned, and a certificate does not cover them.
+pub const TOOL_FILES: [&str; 5] = [
+    marker::DONE_MARKER,
+    marker::PARTIAL_MARKER,
+    manifest::MANIFEST_FILE,
+    report::HEALTH_FILE,
+    cert::CERT_FILE,
+];
+
+/// FLAG_EXTENSION is appended to the name of a flagged file to get its sidecar file.
+pub const FLAG_EXTENSION: &str = "flagged";
+
+/// is_tool_artifact tells if the file at path was written by the tool: one of the
+/// TOOL_FILES, a flag file or a temporary file.
+pub fn is_tool_artifact(path: &Path) -> bool {
+    tmpfile::is_temp_file(path)
+        || path
+            .file_name()
+            .is_some_and(|name| TOOL_FILES.iter().any(|tool_file| name == *tool_file))
+        || path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case(FLAG_EXTENSION))
+}
+
+/// flag_path returns the sidecar file of a flagged file, e.g. `03140000.DAT.flagged`.
+pub fn flag_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(FLAG_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// Flag is the content of a sidecar file, written as YAML.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Flag {
+    /// local time the file was flagged
+    pub flagged: String,
+    pub reasons: Vec<Finding>,
+}
+
+/// write_flag writes the sidecar file of path with the given reasons.
+#[cfg(feature = "yaml-config")]
+pub fn write_flag(path: &Path, reasons: &[Finding]) -> io::Result<()> {
+    let flag = Flag {
+        flagged: Local::now().format("%Y-%m-%dT%H:%M:%S").to_string(),
+        reasons: reasons.to_vec(),
+    };
+    let file = fs::File::create(flag_path(path))?;
+    serde_yaml::to_writer(io::BufWriter::new(file), &flag).map_err(io::Error::other)
+}
+
+/// HeaderMismatchAction is what to do with a file whose column header differs from the
+/// reference header, configured per file type, e.g. `on_header_mismatch: warn|delete`,
+/// or names a field twice, `on_duplicate_header_field`. delete honours `on_delete`,
+/// i.e. can flag the file instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HeaderMismatchAction {
+    #[default]
+    Warn,
+    Delete,
+}
+
+impl FromStr for HeaderMismatchAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warn" => Ok(HeaderMismatchAction::Warn),
+            "delete" => Ok(HeaderMismatchAction::Delete),
+            _ => Err(format!(
+                "invalid action '{s}', expected one of warn, delete"
+            )),
+        }
+    }
+}
+
+/// FileContent holds the lines of a text file (without terminators) and counts the
+/// line terminators found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileContent {
+    pub lines: Vec<String>,
+    /// number of lines terminated by "\n"
+    pub n_lf: usize,
+    /// number of lines terminated by "\r\n"
+    pub n_crlf: usize,
+    /// the last line is terminated (also true for empty files)
+    pub final_newline: bool,
+}
+
+impl Default for FileContent {
+    fn default() -> Self {
+        FileContent {
+            lines: Vec::new(),
+            n_lf: 0,
+            n_crlf: 0,
+            final_newline: true,
+        }
+    }
+}
+
+impl FileContent {
+    /// mixed_line_endings tells if both LF and CRLF terminators occur.
+    pub fn mixed_line_endings(&self) -> bool {
+        self.n_lf > 0 && self.n_crlf > 0
+    }
+
+    /// line_ending resolves the line ending policy for this file.
+    pub fn line_ending(&self, policy: LineEndingPolicy) -> LineEnding {
+        match policy {
+            LineEndingPolicy::Lf => LineEnding::Lf,
+            LineEndingPolicy::Crlf => LineEnding::Crlf,
+            LineEndingPolicy::Majority if self.n_crlf > self.n_lf => LineEnding::Crlf,
+            LineEndingPolicy::Majority => LineEnding::Lf,
+        }
+    }
+}
+
+/// content_from_file reads all lines from a text file like lines_from_file, but also
+/// keeps track of the line terminators.
+pub fn content_from_file(filename: impl AsRef<Path>) -> io::Result<FileContent> {
+    let file = fs::File::open(filename)?;
+    let mut buf = io::BufReader::new(file);
+    let mut content = FileContent::default();
+    let mut raw = Vec::new();
+    loop {
+        raw.clear();
+        if buf.read_until(b'\n', &mut raw)? == 0 {
+            break;
+        }
+        content.final_newline = raw.last() == Some(&b'\n');
+        if content.final_newline {
+            raw.pop();
+            if raw.last() == Some(&b'\r') {
+                raw.pop();
+                content.n_crlf += 1;
+            } else {
+                content.n_lf += 1;
+            }
+        }
+        let line = String::from_utf8(raw.clone()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "stream did not contain valid UTF-8",
+            )
+        })?;
+        content.lines.push(line);
+    }
+    Ok(content)
+}
+
+/// lines_to_file writes a vector of strings to a textfile, terminating each line with line_ending.
+/// the file is replaced atomically, see tmpfile::write_atomic.
+pub fn lines_to_file(
+    filename: impl AsRef<Path>,
+    content: Vec<String>,
+    line_ending: LineEnding,
+) -> io::Result<()> {
+    tmpfile::write_atomic(filename.as_ref(), |file| {
+        for line in content.iter() {
+            write!(file, "{}{}", line, line_ending.as_str())?;
+        }
+        Ok(())
+    })
+}
+
+/// lines_to_new_file writes a vector of strings to a new textfile like lines_to_file,
+/// but fails if the file already exists.
+pub fn lines_to_new_file(
+    filename: impl AsRef<Path>,
+    content: Vec<String>,
+    line_ending: LineEnding,
+) -> io::Result<()> {
     let mut file = fs::OpenOptions::new()
         .write(true)
-        .truncate(true) // fully truncate existing content
+        .create_new(true)
         .open(filename)?;
     for line in content.iter() {
-        writeln!(file, "{}", line)?;
+        write!(file, "{}{}", line, line_ending.as_str())?;
     }
     Ok(())
 }
 
+/// Stamp is the size and modification time of a file when it was read. A file that no
+/// longer matches its stamp before it is rewritten or deleted was replaced meanwhile,
+/// e.g. by the acquisition software rotating its files, and must be left alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stamp {
+    size: u64,
+    mtime: Option<SystemTime>,
+}
+
+impl Stamp {
+    /// of returns the stamp of the file at path.
+    pub fn of(path: &Path) -> io::Result<Self> {
+        let meta = fs::metadata(path)?;
+        Ok(Stamp {
+            size: meta.len(),
+            mtime: meta.modified().ok(),
+        })
+    }
+
+    /// verify returns an error if the file at path no longer matches the stamp: NotFound
+    /// if it is gone, a ChangedDuringRun if it was replaced.
+    pub fn verify(&self, path: &Path) -> io::Result<()> {
+        let now = Stamp::of(path)?;
+        if now == *self {
+            return Ok(());
+        }
+        let change = if now.size != self.size {
+            format!("size {} -> {} bytes", self.size, now.size)
+        } else {
+            "modification time".to_string()
+        };
+        Err(io::Error::other(ChangedDuringRun(change)))
+    }
+}
+
+/// ChangedDuringRun is the error of Stamp::verify for a file that was replaced after it
+/// was read, with what changed. clean_file turns it into SkipReason::ChangedDuringRun.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedDuringRun(pub String);
+
+impl fmt::Display for ChangedDuringRun {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "changed on disk after it was checked ({}) -> skip, it is checked again next run",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ChangedDuringRun {}
+
+/// PrefixPosition tells if the OSC datetime column is put before or after the other columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefixPosition {
+    Prepend,
+    Append,
+}
+
+/// PrefixLayout describes how the OSC transform adds the datetime column to a line,
+/// configured per file type with `prefix_separator` (default tab), `prefix_position`
+/// (prepend or append, default prepend), `leading_delimiter` (default true) and
+/// `join_delimiter` (default false). The default layout puts a separator in front of
+/// the datetime, which gives an empty first column, and the line right after it, as the
+/// transform always did: V25 lines start with a tab, other lines are fused to the
+/// datetime unless join_delimiter is set. The DateTime column is added to the column
+/// header with `header_delimiter` if that is configured, see ColumnFormat, and with
+/// `output_delimiter` to both if that is, see OutputDelimiter. A datetime that contains
+/// the separator is put in `quote_char`, if that is configured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrefixLayout {
+    pub separator: String,
+    pub header_separator: String,
+    pub position: PrefixPosition,
+    pub leading_delimiter: bool,
+    pub join_delimiter: bool,
+    pub quote: Option<char>,
+}
+
+impl Default for PrefixLayout {
+    fn default() -> Self {
+        PrefixLayout {
+            separator: "\t".to_string(),
+            header_separator: "\t".to_string(),
+            position: PrefixPosition::Prepend,
+            leading_delimiter: true,
+            join_delimiter: false,
+            quote: None,
+        }
+    }
+}
+
+impl PrefixLayout {
+    /// from_cfg reads the layout from the cfg of a file type.
+    #[cfg(feature = "yaml-config")]
+    pub fn from_cfg(cfg_ext: &Yaml) -> Result<Self, String> {
+        let mut layout = PrefixLayout::default();
+        if let Some(sep) = cfg_ext["prefix_separator"].as_str() {
+            if sep.is_empty() {
+                return Err("prefix_separator must not be empty".to_string());
+            }
+            layout.separator = sep.to_string();
+        }
+        layout.header_separator = match cfg_ext["header_delimiter"].as_str() {
+            Some("") => return Err("header_delimiter must not be empty".to_string()),
+            Some(sep) => sep.to_string(),
+            None => layout.separator.clone(),
+        };
+        layout.position = match cfg_ext["prefix_position"].as_str() {
+            None | Some("prepend") => PrefixPosition::Prepend,
+            Some("append") => PrefixPosition::Append,
+            Some(other) => {
+                return Err(format!(
+                    "invalid prefix_position '{other}', expected prepend or append"
+                ))
+            }
+        };
+        if let Some(leading) = cfg_ext["leading_delimiter"].as_bool() {
+            layout.leading_delimiter = leading;
+        }
+        if let Some(join) = cfg_ext["join_delimiter"].as_bool() {
+            layout.join_delimiter = join;
+        }
+        layout.quote = quote_char(cfg_ext)?;
+        Ok(layout)
+    }
+
+    /// apply adds value as a new column to line, in quotes if it contains the separator.
+    pub fn apply(&self, line: &str, value: &str) -> String {
+        let sep = self.separator.as_str();
+        let value = match self.quote {
+            Some(quote) if value.contains(sep) || value.contains(quote) => Cow::Owned(format!(
+                "{quote}{}{quote}",
+                value.replace(quote, &format!("{quote}{quote}"))
+            )),
+            _ => Cow::Borrowed(value),
+        };
+        match self.position {
+            PrefixPosition::Prepend => {
+                let lead = if self.leading_delimiter { sep } else { "" };
+                // V25 lines start with the separator already
+                let join = if self.join_delimiter && !line.starts_with(sep) {
+                    sep
+                } else {
+                    ""
+                };
+                format!("{lead}{value}{join}{line}")
+            }
+            PrefixPosition::Append => format!("{line}{sep}{value}"),
+        }
+    }
+
+    /// column_value returns the value of line at the position where apply puts the new
+    /// column, ignoring empty fields, without the quotes apply put around it.
+    pub fn column_value<'a>(&self, line: &'a str) -> Option<&'a str> {
+        let sep = self.separator.as_str();
+        if let Some(quote) = self.quote {
+            let quoted = match self.position {
+                PrefixPosition::Prepend => {
+                    let mut rest = line.trim_start();
+                    while let Some(after) = rest.strip_prefix(sep).filter(|_| !sep.is_empty()) {
+                        rest = after.trim_start();
+                    }
+                    rest.strip_prefix(quote)
+                        .and_then(|rest| rest.find(quote).map(|end| &rest[..end]))
+                }
+                PrefixPosition::Append => {
+                    let mut rest = line.trim_end();
+                    while let Some(before) = rest.strip_suffix(sep).filter(|_| !sep.is_empty()) {
+                        rest = before.trim_end();
+                    }
+                    rest.strip_suffix(quote).and_then(|rest| {
+                        rest.rfind(quote)
+                            .map(|start| &rest[start + quote.len_utf8()..])
+                    })
+                }
+            };
+            if quoted.is_some() {
+                return quoted;
+            }
+        }
+        let mut fields = line
+            .split(self.separator.as_str())
+            .map(str::trim)
+            .filter(|field| !field.is_empty());
+        match self.position {
+            PrefixPosition::Prepend => fields.next(),
+            PrefixPosition::Append => fields.last(),
+        }
+    }
+
+    /// has_column tells if line already has a column named name at the position where
+    /// apply would put it, i.e. if the transform was applied before. without
+    /// join_delimiter, the name may be fused to the first field of the line.
+    pub fn has_column(&self, line: &str, name: &str) -> bool {
+        match self.column_value(line) {
+            Some(value) if value == name => true,
+            Some(value) => {
+                self.position == PrefixPosition::Prepend
+                    && !self.join_delimiter
+                    && value.starts_with(name)
+            }
+            None => false,
+        }
+    }
+
+    /// header returns the layout for the column header line, which uses the header
+    /// separator.
+    pub fn header(&self) -> PrefixLayout {
+        PrefixLayout {
+            separator: self.header_separator.clone(),
+            ..self.clone()
+        }
+    }
+}
+
+/// DEFAULT_DATETIME_FORMAT is the chrono format of the datetime in the first line of
+/// OSC files, e.g. `01.06.23 12:00:00.00`.
+pub const DEFAULT_DATETIME_FORMAT: &str = "%d.%m.%y %H:%M:%S%.f";
+
+/// DatetimeWindow restricts which datetimes are plausible, configured per file type with
+/// `datetime_min_year`, `datetime_max_year` and `datetime_max_days_from_mtime`.
+/// All limits are optional.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DatetimeWindow {
+    pub min_year: Option<i32>,
+    pub max_year: Option<i32>,
+    /// maximum distance to the modification time of the file, in days
+    pub max_days_from_mtime: Option<i64>,
+}
+
+impl DatetimeWindow {
+    /// from_cfg reads the window from the cfg of a file type.
+    #[cfg(feature = "yaml-config")]
+    pub fn from_cfg(cfg_ext: &Yaml) -> Self {
+        DatetimeWindow {
+            min_year: cfg_ext["datetime_min_year"].as_i64().map(|y| y as i32),
+            max_year: cfg_ext["datetime_max_year"].as_i64().map(|y| y as i32),
+            max_days_from_mtime: cfg_ext["datetime_max_days_from_mtime"].as_i64(),
+        }
+    }
+}
+
+/// DatetimeRules describe how the datetime in the first line of OSC files is parsed,
+/// validated and written as prefix, configured per file type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatetimeRules {
+    /// chrono format of the datetime, `datetime_format`
+    pub format: String,
+    /// two-digit years below the pivot are 20xx, the others 19xx, `two_digit_year_pivot`.
+    /// without pivot, all two-digit years are 20xx.
+    pub year_pivot: Option<i32>,
+    pub window: DatetimeWindow,
+    /// chrono format of the prefix, `prefix_output_format`. if not set, the datetime is
+    /// written as found in the file.
+    pub output_format: Option<String>,
+    /// time between two data lines. if not set, all data lines get the same datetime.
+    pub sample_interval: Option<SampleInterval>,
+}
+
+impl Default for DatetimeRules {
+    fn default() -> Self {
+        DatetimeRules {
+            format: DEFAULT_DATETIME_FORMAT.to_string(),
+            year_pivot: None,
+            window: DatetimeWindow::default(),
+            output_format: None,
+            sample_interval: None,
+        }
+    }
+}
+
+/// SampleInterval is the time between two data lines, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleInterval {
+    /// fixed interval, `sample_interval_s`
+    Fixed(f64),
+    /// interval given in a field of a preamble line, `sample_interval_line` and
+    /// `sample_interval_field` (tab-separated, default 0)
+    FromHeader { line: usize, field: usize },
+}
+
+impl SampleInterval {
+    /// from_cfg reads the sample interval from the cfg of a file type, if configured.
+    #[cfg(feature = "yaml-config")]
+    pub fn from_cfg(cfg_ext: &Yaml) -> Result<Option<Self>, String> {
+        if let Some(seconds) = yaml_f64(&cfg_ext["sample_interval_s"]) {
+            return check_interval(seconds).map(|s| Some(SampleInterval::Fixed(s)));
+        }
+        Ok(cfg_ext["sample_interval_line"]
+            .as_i64()
+            .map(|line| SampleInterval::FromHeader {
+                line: line as usize,
+                field: cfg_ext["sample_interval_field"].as_i64().unwrap_or(0) as usize,
+            }))
+    }
+
+    /// seconds returns the interval in seconds, reading it from content if needed.
+    pub fn seconds(&self, content: &[String]) -> Result<f64, String> {
+        match *self {
+            SampleInterval::Fixed(seconds) => Ok(seconds),
+            SampleInterval::FromHeader { line, field } => {
+                let value = content
+                    .get(line)
+                    .and_then(|l| l.split('\t').nth(field))
+                    .map(str::trim)
+                    .ok_or_else(|| format!("no sample interval in line {line}, field {field}"))?;
+                let seconds = value.parse::<f64>().map_err(|_| {
+                    format!("invalid sample interval '{value}' in line {line}, field {field}")
+                })?;
+                check_interval(seconds)
+            }
+        }
+    }
+}
+
+fn check_interval(seconds: f64) -> Result<f64, String> {
+    if seconds.is_finite() && seconds > 0.0 {
+        Ok(seconds)
+    } else {
+        Err(format!("invalid sample interval {seconds}, must be > 0 s"))
+    }
+}
+
+/// yaml_f64 reads a number from the cfg, which may be written with or without decimals.
+#[cfg(feature = "yaml-config")]
+fn yaml_f64(value: &Yaml) -> Option<f64> {
+    value.as_f64().or_else(|| value.as_i64().map(|n| n as f64))
+}
+
+impl DatetimeRules {
+    /// from_cfg reads the rules from the cfg of a file type.
+    #[cfg(feature = "yaml-config")]
+    pub fn from_cfg(cfg_ext: &Yaml) -> Result<Self, String> {
+        let rules = DatetimeRules {
+            format: cfg_ext["datetime_format"]
+                .as_str()
+                .unwrap_or(DEFAULT_DATETIME_FORMAT)
+                .to_string(),
+            year_pivot: cfg_ext["two_digit_year_pivot"].as_i64().map(|y| y as i32),
+            window: DatetimeWindow::from_cfg(cfg_ext),
+            output_format: cfg_ext["prefix_output_format"].as_str().map(String::from),
+            sample_interval: SampleInterval::from_cfg(cfg_ext)?,
+        };
+        if let Some(fmt) = &rules.output_format {
+            format_datetime(&NaiveDateTime::default(), fmt)?;
+        }
+        Ok(rules)
+    }
+
+    /// parse parses s with the configured format. two-digit years are resolved with the
+    /// year pivot instead of chrono's default (1969-2068).
+    pub fn parse(&self, s: &str) -> Result<NaiveDateTime, String> {
+        let dt = NaiveDateTime::parse_from_str(s.trim(), &self.format)
+            .map_err(|err| format!("'{s}' is not a valid datetime ({err})"))?;
+        if !self.format.contains("%y") {
+            return Ok(dt);
+        }
+        let yy = dt.year().rem_euclid(100);
+        let year = match self.year_pivot {
+            Some(pivot) if yy >= pivot => 1900 + yy,
+            _ => 2000 + yy,
+        };
+        dt.with_year(year)
+            .ok_or_else(|| format!("'{s}' is not a valid datetime in the year {year}"))
+    }
+
+    /// validate parses s and checks that the result lies within the window. mtime is
+    /// the modification time of the file the datetime was taken from.
+    pub fn validate(&self, s: &str, mtime: Option<NaiveDateTime>) -> Result<NaiveDateTime, String> {
+        let dt = self.parse(s)?;
+        let window = &self.window;
+        if let Some(min_year) = window.min_year {
+            if dt.year() < min_year {
+                return Err(format!("'{s}' is before the year {min_year}"));
+            }
+        }
+        if let Some(max_year) = window.max_year {
+            if dt.year() > max_year {
+                return Err(format!("'{s}' is after the year {max_year}"));
+            }
+        }
+        if let (Some(max_days), Some(mtime)) = (window.max_days_from_mtime, mtime) {
+            let days = (dt - mtime).num_days().abs();
+            if days > max_days {
+                return Err(format!(
+                    "'{s}' is {days} days away from the file modification time, allowed are {max_days}"
+                ));
+            }
+        }
+        Ok(dt)
+    }
+
+    /// prefix returns the string written in front of the data lines: raw as found in the
+    /// file, or dt formatted with the output format.
+    pub fn prefix(&self, raw: &str, dt: &NaiveDateTime) -> Result<String, String> {
+        match &self.output_format {
+            Some(fmt) => format_datetime(dt, fmt),
+            None => Ok(raw.to_string()),
+        }
+    }
+
+    /// prefixes returns the prefix of each of n data lines. with a sample interval, data
+    /// line i gets start + i * interval, formatted with the output format or, if not
+    /// set, the datetime format with two decimals of the second. the offset is computed
+    /// from the index for each line, so there is no accumulated rounding error.
+    pub fn prefixes(
+        &self,
+        raw: &str,
+        start: &NaiveDateTime,
+        content: &[String],
+        n: usize,
+    ) -> Result<Vec<String>, String> {
+        let prefixer = self.prefixer(raw, start, content)?;
+        (0..n).map(|i| prefixer.get(i)).collect()
+    }
+
+    /// prefixer is like prefixes, but computes the prefix of a data line on request, so
+    /// the prefixes of a large file are never held all at once. content only needs to
+    /// hold the preamble.
+    pub fn prefixer(
+        &self,
+        raw: &str,
+        start: &NaiveDateTime,
+        content: &[String],
+    ) -> Result<Prefixer, String> {
+        let Some(interval) = &self.sample_interval else {
+            return Ok(Prefixer::Fixed(self.prefix(raw, start)?));
+        };
+        let prefixer = Prefixer::Interval {
+            start: *start,
+            seconds: interval.seconds(content)?,
+            format: match &self.output_format {
+                Some(fmt) => fmt.clone(),
+                None => self.format.replace("%.f", "%.2f"),
+            },
+        };
+        // an invalid output format fails for every line, find out before writing
+        prefixer.get(0)?;
+        Ok(prefixer)
+    }
+
+    /// is_prefix tells if s is a datetime prefix, in the raw or the output format.
+    pub fn is_prefix(&self, s: &str) -> bool {
+        self.parse(s).is_ok()
+            || self.output_format.as_ref().is_some_and(|fmt| {
+                NaiveDateTime::parse_from_str(s.trim(), &fmt.replace("%.2f", "%.f")).is_ok()
+            })
+    }
+}
+
+/// Prefixer gives the datetime prefix of the i-th data line, see DatetimeRules::prefixer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Prefixer {
+    /// all lines get the same prefix
+    Fixed(String),
+    /// line i gets start + i * seconds
+    Interval {
+        start: NaiveDateTime,
+        seconds: f64,
+        format: String,
+    },
+}
+
+impl Prefixer {
+    /// get returns the prefix of data line i.
+    pub fn get(&self, i: usize) -> Result<String, String> {
+        match self {
+            Prefixer::Fixed(prefix) => Ok(prefix.clone()),
+            Prefixer::Interval {
+                start,
+                seconds,
+                format,
+            } => {
+                let offset = TimeDelta::nanoseconds((i as f64 * seconds * 1e9).round() as i64);
+                format_datetime(&(*start + offset), format)
+            }
+        }
+    }
+}
+
+/// format_datetime formats dt with a chrono format string. in addition to chrono's
+/// specifiers, `%.2f` gives the fraction of the second with two digits, as written by
+/// the V25.
+pub fn format_datetime(dt: &NaiveDateTime, fmt: &str) -> Result<String, String> {
+    let centis = format!(".{:02}", dt.and_utc().timestamp_subsec_millis() / 10);
+    let fmt = fmt.replace("%.2f", &centis);
+    let mut out = String::new();
+    use std::fmt::Write as _;
+    write!(out, "{}", dt.format(&fmt))
+        .map_err(|_| format!("invalid datetime output format '{fmt}'"))?;
+    Ok(out)
+}
+
 /// write_OSC is a special write function that updates OSC files by prefixing datetime to each line of data
 pub fn write_osc(
     filename: impl AsRef<Path>,
     content: Vec<String>,
     nl_head: usize,
-    data_prefix: &str,
+    data_prefixes: &[String],
+    layout: &PrefixLayout,
+    line_ending: LineEnding,
 ) -> io::Result<()> {
-    let mut file = fs::OpenOptions::new()
-        .write(true)
-        .truncate(true) // fully truncate existing content
-        .open(filename)?;
-    // write header
-    for line in content[0..nl_head].iter() {
-        writeln!(file, "{}", line)?;
-    }
-    // write data
-    for line in content[nl_head..content.len() - 1].iter() {
-        writeln!(file, "\t{}{}", data_prefix, line)?;
-    }
-    Ok(())
+    let eol = line_ending.as_str();
+    tmpfile::write_atomic(filename.as_ref(), |file| {
+        // write header
+        for line in content[0..nl_head].iter() {
+            write!(file, "{}{}", line, eol)?;
+        }
+        // write data
+        for (line, prefix) in content[nl_head..].iter().zip(data_prefixes) {
+            write!(file, "{}{}", layout.apply(line, prefix), eol)?;
+        }
+        Ok(())
+    })
 }
 
-/// n_data_fields takes a string, trims surrounding whitespaces and splits jit on delimiter.
-/// returns number of fields returned from split.
-pub fn n_data_fields(s: &String, delimiter: &str) -> usize {
-    s.trim().split(delimiter).collect::<Vec<&str>>().len()
+/// stream_osc is the streaming variant of write_osc: it reads the file line by line and
+/// writes the DateTime column header at header_idx and the prefixed data lines below
+/// it, never holding more than one line in memory.
+pub fn stream_osc(
+    filename: impl AsRef<Path>,
+    header_idx: usize,
+    prefixer: &Prefixer,
+    layout: &PrefixLayout,
+    line_ending: LineEnding,
+) -> io::Result<()> {
+    let filename = filename.as_ref();
+    let mut reader = io::BufReader::new(fs::File::open(filename)?);
+    let eol = line_ending.as_str();
+    let mut raw = Vec::new();
+    tmpfile::write_atomic(filename, |file| {
+        for i in 0.. {
+            raw.clear();
+            if reader.read_until(b'\n', &mut raw)? == 0 {
+                break;
+            }
+            if raw.last() == Some(&b'\n') {
+                raw.pop();
+                if raw.last() == Some(&b'\r') {
+                    raw.pop();
+                }
+            }
+            let line = std::str::from_utf8(&raw).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stream did not contain valid UTF-8",
+                )
+            })?;
+            match i.cmp(&header_idx) {
+                std::cmp::Ordering::Less => write!(file, "{}{}", line, eol)?,
+                std::cmp::Ordering::Equal => {
+                    write!(file, "{}{}", layout.header().apply(line, "DateTime"), eol)?
+                }
+                std::cmp::Ordering::Greater => {
+                    let prefix = prefixer.get(i - header_idx - 1).map_err(io::Error::other)?;
+                    write!(file, "{}{}", layout.apply(line, &prefix), eol)?
+                }
+            }
+        }
+        Ok(())
+    })
 }
 
-/// n_chars_last_field returns the number of characters found in the last field of a
-/// delimited string.
-pub fn n_chars_last_field(s: &String, delimiter: &str) -> Option<usize> {
-    match s.trim().split(delimiter).collect::<Vec<&str>>().last() {
-        Some(field) => Some(field.chars().count()),
-        None => None,
+/// ColumnFormat describes how the columns of a file type are delimited, configured per
+/// file type with `delimiter` (default tab) and `header_delimiter` (default: delimiter)
+/// for exporters that write the column header differently from the data lines.
+/// `min_header_tokens` (default 2) is the number of fields a column header must have
+/// that are not numbers. With `quote_char`, a delimiter inside quotes does not separate
+/// fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnFormat {
+    pub delimiter: String,
+    pub header_delimiter: String,
+    pub min_header_tokens: usize,
+    pub quote: Option<char>,
+}
+
+impl Default for ColumnFormat {
+    fn default() -> Self {
+        ColumnFormat {
+            delimiter: "\t".to_string(),
+            header_delimiter: "\t".to_string(),
+            min_header_tokens: 2,
+            quote: None,
+        }
     }
 }
 
-/// get_cfg_path returns the directory where the cfg file is expected
-pub fn get_cfg_path() -> io::Result<PathBuf> {
-    let exec_path = std::env::current_exe()?;
-    let exec_dir = exec_path
-        .parent()
+impl ColumnFormat {
+    /// from_cfg reads the column format from the cfg of a file type.
+    #[cfg(feature = "yaml-config")]
+    pub fn from_cfg(cfg_ext: &Yaml) -> Result<Self, String> {
+        let mut format = ColumnFormat::default();
+        for (key, value) in [
+            ("delimiter", &mut format.delimiter),
+            ("header_delimiter", &mut format.header_delimiter),
+        ] {
+            match cfg_ext[key].as_str() {
+                Some("") => return Err(format!("{key} must not be empty")),
+                Some(delimiter) => *value = delimiter.to_string(),
+                None => (),
+            }
+        }
+        if cfg_ext["header_delimiter"].is_badvalue() {
+            format.header_delimiter = format.delimiter.clone();
+        }
+        if let Some(n) = cfg_ext["min_header_tokens"].as_i64() {
+            format.min_header_tokens = n as usize;
+        }
+        format.quote = quote_char(cfg_ext)?;
+        Ok(format)
+    }
+
+    /// n_fields returns the number of fields of a data line, see n_quoted_fields.
+    pub fn n_fields(&self, line: &str) -> usize {
+        n_quoted_fields(line, &self.delimiter, self.quote)
+    }
+
+    /// n_header_fields returns the number of fields of the column header line.
+    pub fn n_header_fields(&self, line: &str) -> usize {
+        n_quoted_fields(line, &self.header_delimiter, self.quote)
+    }
+}
+
+/// quote_char reads the `quote_char` of a file type, a single character, or None if it
+/// is not set.
+#[cfg(feature = "yaml-config")]
+fn quote_char(cfg_ext: &Yaml) -> Result<Option<char>, String> {
+    match &cfg_ext["quote_char"] {
+        Yaml::BadValue => Ok(None),
+        Yaml::String(s) if s.chars().count() == 1 => Ok(s.chars().next()),
+        other => Err(format!(
+            "invalid quote_char {other:?}, expected a single character"
+        )),
+    }
+}
+
+/// OutputDelimiter is the delimiter the fields of a file type are written with when its
+/// files are rewritten, configured per file type with `output_delimiter`, e.g. "," for
+/// tools that want comma-delimited data. A field that contains it or `quote_char`
+/// (default `"`) is put in quote_char, with the quote_chars in it doubled, as in CSV.
+/// Files are only converted if they are rewritten anyway, unless `output_delimiter_force`
+/// is set. The OSC datetime column is added with it too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputDelimiter {
+    pub delimiter: String,
+    pub quote: char,
+    pub force: bool,
+}
+
+impl OutputDelimiter {
+    /// from_cfg reads the output delimiter from the cfg of a file type, None if it is
+    /// not set.
+    #[cfg(feature = "yaml-config")]
+    pub fn from_cfg(cfg_ext: &Yaml) -> Result<Option<Self>, String> {
+        let delimiter = match &cfg_ext["output_delimiter"] {
+            Yaml::BadValue => return Ok(None),
+            Yaml::String(delimiter) if !delimiter.is_empty() => delimiter.clone(),
+            _ => return Err("output_delimiter must be a non-empty string".to_string()),
+        };
+        let quote = quote_char(cfg_ext)?.unwrap_or('"');
+        if delimiter.contains(quote) {
+            return Err(format!(
+                "output_delimiter '{delimiter}' must not contain quote_char '{quote}'"
+            ));
+        }
+        Ok(Some(OutputDelimiter {
+            delimiter,
+            quote,
+            force: cfg_ext["output_delimiter_force"].as_bool() == Some(true),
+        }))
+    }
+
+    /// convert joins the fields of a line delimited by delimiter with the output
+    /// delimiter, quoting them as needed, so the line keeps its number of fields. a line
+    /// without delimiter is left as it is, it has a single field or was converted
+    /// before. returns None if nothing changed.
+    pub fn convert(&self, line: &str, delimiter: &str) -> Option<String> {
+        if !line.contains(delimiter) {
+            return None;
+        }
+        let converted = line
+            .split(delimiter)
+            .map(|field| self.quote_field(field))
+            .collect::<Vec<Cow<str>>>()
+            .join(self.delimiter.as_str());
+        (converted != line).then_some(converted)
+    }
+
+    /// quote_field puts field in quote_char if it contains the output delimiter or
+    /// quote_char.
+    fn quote_field<'a>(&self, field: &'a str) -> Cow<'a, str> {
+        if !field.contains(self.delimiter.as_str()) && !field.contains(self.quote) {
+            return Cow::Borrowed(field);
+        }
+        let quote = self.quote.to_string();
+        let doubled = format!("{quote}{quote}");
+        Cow::Owned(format!("{quote}{}{quote}", field.replace(&quote, &doubled)))
+    }
+
+    /// columns returns the column format of the converted lines.
+    pub fn columns(&self, columns: &ColumnFormat) -> ColumnFormat {
+        ColumnFormat {
+            delimiter: self.delimiter.clone(),
+            header_delimiter: self.delimiter.clone(),
+            quote: Some(self.quote),
+            ..columns.clone()
+        }
+    }
+
+    /// layout returns layout with the output delimiter as separator, for the datetime
+    /// column of converted OSC files. converted lines never start with the separator,
+    /// so the datetime is joined to them.
+    pub fn layout(&self, layout: PrefixLayout) -> PrefixLayout {
+        PrefixLayout {
+            separator: self.delimiter.clone(),
+            header_separator: self.delimiter.clone(),
+            join_delimiter: true,
+            quote: Some(self.quote),
+            ..layout
+        }
+    }
+}
+
+/// LeadingGarbage is junk before the first line of the files of a type, e.g. boot
+/// messages a logger firmware writes before the preamble. It is read from
+/// `skip_leading_garbage: {max_lines: 5, pattern: "^(BOOT|INIT)"}`: up to max_lines
+/// (default 5) leading lines that match pattern are dropped before the checks on lines.
+#[cfg(feature = "regex-checks")]
+#[derive(Debug, Clone)]
+pub struct LeadingGarbage {
+    pub max_lines: usize,
+    pub pattern: Regex,
+}
+
+#[cfg(feature = "regex-checks")]
+impl LeadingGarbage {
+    /// from_cfg reads skip_leading_garbage from the cfg of a file type, None if it is
+    /// not set.
+    #[cfg(feature = "yaml-config")]
+    pub fn from_cfg(cfg_ext: &Yaml) -> Result<Option<Self>, String> {
+        let cfg = &cfg_ext["skip_leading_garbage"];
+        if cfg.is_badvalue() {
+            return Ok(None);
+        }
+        let Some(pattern) = cfg["pattern"].as_str() else {
+            return Err("skip_leading_garbage has no pattern".to_string());
+        };
+        let max_lines = match cfg["max_lines"].as_i64() {
+            Some(n) if n >= 0 => n as usize,
+            None if cfg["max_lines"].is_badvalue() => 5,
+            _ => return Err("invalid skip_leading_garbage max_lines".to_string()),
+        };
+        let pattern = compile_pattern(pattern)
+            .map_err(|err| format!("invalid skip_leading_garbage pattern '{pattern}', {err}"))?;
+        Ok(Some(LeadingGarbage { max_lines, pattern }))
+    }
+
+    /// count returns the number of leading lines of content that are garbage.
+    pub fn count(&self, content: &[String]) -> usize {
+        content
+            .iter()
+            .take(self.max_lines)
+            .take_while(|line| self.pattern.is_match(line))
+            .count()
+    }
+}
+
+/// MissingHeaderAction is what to do with a file in which header_match finds no column
+/// header, configured per file type, e.g. `on_missing_header: delete|quarantine|warn`,
+/// or that has fewer data lines than its preamble announces, `on_short_count`, or the ID
+/// of another instrument, `on_instrument_id_mismatch`. delete
+/// honours `on_delete`; quarantine flags the file for review like `on_delete: flag`, a
+/// file of another instrument is also moved to --quarantine if given;
+/// warn keeps checking the file, with the header at n_header_lines if none was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingHeaderAction {
+    Delete,
+    Quarantine,
+    #[default]
+    Warn,
+}
+
+impl FromStr for MissingHeaderAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "delete" => Ok(MissingHeaderAction::Delete),
+            "quarantine" => Ok(MissingHeaderAction::Quarantine),
+            "warn" => Ok(MissingHeaderAction::Warn),
+            _ => Err(format!(
+                "invalid action '{s}', expected one of delete, quarantine, warn"
+            )),
+        }
+    }
+}
+
+/// DEFAULT_MAX_PREAMBLE_LINES is how many lines header_match looks at by default.
+pub const DEFAULT_MAX_PREAMBLE_LINES: usize = 50;
+
+/// HeaderMatch locates the column header of the files of a type by its content instead
+/// of n_header_lines, configured with `header_match`: the first of the first
+/// max_preamble_lines lines that matches pattern is the header, the lines above it are
+/// preamble. Firmware versions that write preambles of different lengths need it.
+#[cfg(feature = "regex-checks")]
+#[derive(Debug, Clone)]
+pub struct HeaderMatch {
+    pub pattern: Regex,
+    pub max_preamble_lines: usize,
+    pub on_missing: MissingHeaderAction,
+}
+
+#[cfg(feature = "regex-checks")]
+impl HeaderMatch {
+    /// from_cfg reads header_match, max_preamble_lines and on_missing_header from the
+    /// cfg of a file type, None if header_match is not set.
+    #[cfg(feature = "yaml-config")]
+    pub fn from_cfg(cfg_ext: &Yaml) -> Result<Option<Self>, String> {
+        let Some(pattern) = cfg_ext["header_match"].as_str() else {
+            if cfg_ext["header_match"].is_badvalue() {
+                return Ok(None);
+            }
+            return Err("header_match must be a string".to_string());
+        };
+        let max_preamble_lines = match cfg_ext["max_preamble_lines"].as_i64() {
+            Some(n) if n >= 1 => n as usize,
+            None if cfg_ext["max_preamble_lines"].is_badvalue() => DEFAULT_MAX_PREAMBLE_LINES,
+            _ => return Err("invalid max_preamble_lines, want at least 1".to_string()),
+        };
+        let on_missing = match cfg_ext["on_missing_header"].as_str() {
+            Some(s) => s.parse()?,
+            None => MissingHeaderAction::default(),
+        };
+        let pattern = compile_pattern(pattern)
+            .map_err(|err| format!("invalid header_match '{pattern}', {err}"))?;
+        Ok(Some(HeaderMatch {
+            pattern,
+            max_preamble_lines,
+            on_missing,
+        }))
+    }
+
+    /// find returns the index of the column header in content, None if none of the
+    /// first max_preamble_lines lines matches.
+    pub fn find(&self, content: &[String]) -> Option<usize> {
+        content
+            .iter()
+            .take(self.max_preamble_lines)
+            .position(|line| self.pattern.is_match(line))
+    }
+}
+
+/// DEFAULT_MIN_COUNT_FRACTION is the share of the announced count of data lines a file
+/// needs by default, see ExpectedCount.
+pub const DEFAULT_MIN_COUNT_FRACTION: f64 = 0.9;
+
+/// ExpectedCount reads the number of data lines a file should have from a line of its
+/// preamble, configured with `expected_count_from_header: {line: 2, pattern: 'Samples:
+/// (\d+)', min_fraction: 0.9}`. Files with fewer data lines than min_fraction of it are
+/// suspect even if they have min_data_lines. line counts from 1, from the first line
+/// after any leading garbage.
+#[cfg(feature = "regex-checks")]
+#[derive(Debug, Clone)]
+pub struct ExpectedCount {
+    pub line: usize,
+    /// the first group captures the count
+    pub pattern: Regex,
+    pub min_fraction: f64,
+    pub on_short: MissingHeaderAction,
+}
+
+#[cfg(feature = "regex-checks")]
+impl ExpectedCount {
+    /// from_cfg reads expected_count_from_header and on_short_count from the cfg of a
+    /// file type, None if expected_count_from_header is not set.
+    #[cfg(feature = "yaml-config")]
+    pub fn from_cfg(cfg_ext: &Yaml) -> Result<Option<Self>, String> {
+        let key = "expected_count_from_header";
+        let cfg_count = &cfg_ext[key];
+        if cfg_count.is_badvalue() {
+            return Ok(None);
+        }
+        if cfg_count.as_hash().is_none() {
+            return Err(format!(
+                "{key} must be a map, e.g. {{line: 2, pattern: 'Samples: (\\d+)'}}"
+            ));
+        }
+        let line = match cfg_count["line"].as_i64() {
+            Some(n) if (1..=MAX_LINE_COUNT).contains(&n) => n as usize,
+            _ => return Err(format!("invalid {key} line, want 1 to {MAX_LINE_COUNT}")),
+        };
+        let Some(pattern) = cfg_count["pattern"].as_str() else {
+            return Err(format!("{key} needs a pattern"));
+        };
+        let pattern = compile_pattern(pattern)
+            .map_err(|err| format!("invalid {key} pattern '{pattern}', {err}"))?;
+        if pattern.captures_len() < 2 {
+            return Err(format!(
+                "{key} pattern '{pattern}' has no group to capture the count"
+            ));
+        }
+        let value = &cfg_count["min_fraction"];
+        let min_fraction = match value.as_f64().or_else(|| value.as_i64().map(|n| n as f64)) {
+            Some(fraction) if fraction > 0.0 && fraction <= 1.0 => fraction,
+            None if value.is_badvalue() => DEFAULT_MIN_COUNT_FRACTION,
+            _ => {
+                return Err(format!(
+                    "invalid {key} min_fraction {value:?}, expected a number in (0, 1]"
+                ))
+            }
+        };
+        let on_short = match cfg_ext["on_short_count"].as_str() {
+            Some(s) => s.parse()?,
+            None => MissingHeaderAction::default(),
+        };
+        Ok(Some(ExpectedCount {
+            line,
+            pattern,
+            min_fraction,
+            on_short,
+        }))
+    }
+
+    /// count returns the count announced in content, or why it could not be read.
+    pub fn count(&self, content: &[String]) -> Result<usize, String> {
+        let Some(line) = content.get(self.line - 1) else {
+            return Err(format!("has no line {}", self.line));
+        };
+        let captured = self
+            .pattern
+            .captures(line)
+            .and_then(|captures| captures.get(1))
+            .ok_or_else(|| {
+                format!(
+                    "line {} '{line}' does not match '{}'",
+                    self.line, self.pattern
+                )
+            })?;
+        captured.as_str().trim().parse().map_err(|_| {
+            format!(
+                "line {} announces '{}', not a count",
+                self.line,
+                captured.as_str()
+            )
+        })
+    }
+
+    /// short returns the finding for a file with n_data data lines if that is less
+    /// than min_fraction of the expected count.
+    pub fn short(&self, n_data: usize, expected: usize) -> Option<Finding> {
+        ((n_data as f64) < expected as f64 * self.min_fraction).then(|| {
+            Finding::new(
+                CheckId::ExpectedCount,
+                format!(
+                    "has {n_data} data line(s), less than {:.0}% of the {expected} announced on line {}",
+                    self.min_fraction * 100.0,
+                    self.line
+                ),
+            )
+            .with("data_lines", n_data)
+            .with("expected", expected)
+        })
+    }
+}
+
+/// compile_pattern compiles a pattern of the cfg file. the error is only the reason,
+/// the parse error of regex shows the pattern over several lines.
+#[cfg(feature = "yaml-config")]
+fn compile_pattern(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|err| {
+        let err = err.to_string();
+        let reason = err.lines().last().unwrap_or_default();
+        reason.trim_start_matches("error: ").to_string()
+    })
+}
+
+/// CompiledRules holds the patterns of a file type from the cfg file, compiled once
+/// before a run by compile_rules instead of for each file.
+#[cfg(feature = "regex-checks")]
+#[derive(Debug, Clone, Default)]
+pub struct CompiledRules {
+    pub leading_garbage: Option<LeadingGarbage>,
+    /// the pattern every line of a freeform type must match, see FileFormat
+    pub line_pattern: Option<Regex>,
+    pub header_match: Option<HeaderMatch>,
+    /// the pattern of a time column OSC files already have, see osc_rules
+    pub existing_time_pattern: Option<Regex>,
+    pub expected_count: Option<ExpectedCount>,
+    pub instrument_id: Option<instrument::InstrumentId>,
+}
+
+/// compile_rules compiles the patterns of each file type of the cfg file. An invalid
+/// pattern is an error naming the type and the pattern, so a run can stop before it
+/// touches any file.
+#[cfg(feature = "yaml-config")]
+pub fn compile_rules(cfg: &Yaml) -> Result<HashMap<String, CompiledRules>, String> {
+    let mut compiled = HashMap::new();
+    let Some(types) = cfg.as_hash() else {
+        return Ok(compiled);
+    };
+    for (ext, cfg_ext) in types.iter() {
+        let Some(ext) = ext.as_str() else {
+            continue;
+        };
+        let rules = CompiledRules {
+            leading_garbage: LeadingGarbage::from_cfg(cfg_ext)
+                .map_err(|err| format!("{ext}: {err}"))?,
+            line_pattern: match cfg_ext["line_pattern"].as_str() {
+                Some(pattern) => Some(
+                    compile_pattern(pattern)
+                        .map_err(|err| format!("{ext}: invalid line_pattern '{pattern}', {err}"))?,
+                ),
+                None => None,
+            },
+            header_match: HeaderMatch::from_cfg(cfg_ext).map_err(|err| format!("{ext}: {err}"))?,
+            existing_time_pattern: match cfg_ext["existing_time_pattern"].as_str() {
+                Some(pattern) => Some(compile_pattern(pattern).map_err(|err| {
+                    format!("{ext}: invalid existing_time_pattern '{pattern}', {err}")
+                })?),
+                None => None,
+            },
+            expected_count: ExpectedCount::from_cfg(cfg_ext)
+                .map_err(|err| format!("{ext}: {err}"))?,
+            instrument_id: instrument::InstrumentId::from_cfg(cfg_ext)
+                .map_err(|err| format!("{ext}: {err}"))?,
+        };
+        compiled.insert(ext.to_uppercase(), rules);
+    }
+    Ok(compiled)
+}
+
+/// n_data_fields takes a string, trims surrounding whitespaces and splits jit on delimiter.
+/// returns number of fields returned from split.
+pub fn n_data_fields(s: &str, delimiter: &str) -> usize {
+    s.trim().split(delimiter).count()
+}
+
+/// n_quoted_fields is n_data_fields for lines whose fields may be put in quote, e.g.
+/// by OutputDelimiter: a delimiter between two quotes does not separate fields.
+pub fn n_quoted_fields(s: &str, delimiter: &str, quote: Option<char>) -> usize {
+    // an empty delimiter would match at every position and never advance
+    let Some(quote) = quote.filter(|quote| s.contains(*quote) && !delimiter.is_empty()) else {
+        return n_data_fields(s, delimiter);
+    };
+    let mut n = 1;
+    let mut quoted = false;
+    let mut rest = s.trim();
+    while let Some(c) = rest.chars().next() {
+        if !quoted && rest.starts_with(delimiter) {
+            n += 1;
+            rest = &rest[delimiter.len()..];
+            continue;
+        }
+        quoted ^= c == quote;
+        rest = &rest[c.len_utf8()..];
+    }
+    n
+}
+
+/// trim_fields removes the spaces around each field of a line delimited by delimiter,
+/// e.g. fixed width padding. the delimiters are kept, a field of only spaces becomes
+/// empty. returns None if there was nothing to trim.
+pub fn trim_fields(line: &str, delimiter: &str) -> Option<String> {
+    let trimmed = line
+        .split(delimiter)
+        .map(|field| field.trim_matches(' '))
+        .collect::<Vec<&str>>()
+        .join(delimiter);
+    (trimmed.len() != line.len()).then_some(trimmed)
+}
+
+/// n_chars_last_field returns the number of characters found in the last field of a
+/// delimited string.
+pub fn n_chars_last_field(s: &str, delimiter: &str) -> Option<usize> {
+    s.trim()
+        .rsplit(delimiter)
+        .next()
+        .map(|field| field.chars().count())
+}
+
+/// looks_like_header tells if a line could be a column header, i.e. if it has at least
+/// min_tokens non-empty fields that are not numbers.
+pub fn looks_like_header(s: &str, delimiter: &str, min_tokens: usize) -> bool {
+    s.split(delimiter)
+        .map(str::trim)
+        .filter(|field| !field.is_empty() && field.parse::<f64>().is_err())
+        .count()
+        >= min_tokens
+}
+
+/// looks_like_data tells if a line could be a line of data, i.e. if it has at least
+/// one numeric field.
+pub fn looks_like_data(s: &str, delimiter: &str) -> bool {
+    s.split(delimiter)
+        .any(|field| field.trim().parse::<f64>().is_ok())
+}
+
+/// get_cfg_path returns the directory where the cfg file is expected
+pub fn get_cfg_path() -> io::Result<PathBuf> {
+    let exec_path = std::env::current_exe()?;
+    let exec_dir = exec_path
+        .parent()
         .expect("executable must be in some directory");
     let mut cfg_dir = exec_dir.join("cfg");
     cfg_dir.push("v25_data_cfg.yml");
     Ok(cfg_dir)
 }
+
+/// CleanOptions holds the settings of a cleaning run that do not come from the cfg file.
+#[derive(Debug, Clone, Default)]
+pub struct CleanOptions {
+    /// if set, only these checks run
+    pub only_checks: Option<Vec<CheckId>>,
+    /// these checks never run
+    pub skip_checks: Vec<CheckId>,
+    /// limits for recording the content of removed lines
+    pub removed_caps: RemovedCaps,
+    /// check files flagged by a previous run again instead of skipping them
+    pub recheck_flagged: bool,
+    /// determine the time covered by each file, see time_span
+    pub coverage: bool,
+    /// reference column header per file type, see load_reference_headers
+    pub reference_headers: HashMap<String, String>,
+    /// patterns per file type, see compile_rules
+    #[cfg(feature = "regex-checks")]
+    pub compiled_rules: HashMap<String, CompiledRules>,
+    /// OSC files larger than this many bytes are prefixed line by line instead of being
+    /// loaded, if they pass the checks unchanged; see stream_osc
+    pub stream_osc_above: Option<u64>,
+    /// files with a key extension are cleaned with the rules of the value extension,
+    /// e.g. TXT -> DAT for a logger that was configured with the wrong extension
+    pub ext_map: HashMap<String, String>,
+    /// compare the column header of files failing the header checks of their extension
+    /// with the reference headers of the other extensions, see sniff_type
+    pub sniff_type: bool,
+    /// rename files whose content was detected as another type instead of deleting
+    /// them, and clean them with the rules of that type
+    pub fix_misnamed: bool,
+    /// do not remove files a check deletes, only report them as deleted; the caller
+    /// removes them in one batch, see delete::delete_files
+    pub defer_delete: bool,
+    /// flag files a check would delete instead, whatever on_delete says; set for runs
+    /// with elevated privileges, see privilege::is_elevated
+    pub flag_deletes: bool,
+    /// hash each file before it is checked and keep the hash if it is left unchanged,
+    /// see spotcheck
+    pub hash_unchanged: bool,
+    /// copy each file before it is rewritten, renamed or deleted, see backup
+    pub backup: Option<backup::Backup>,
+    /// the instrument ID files should have, instead of the expected of instrument_id in
+    /// the cfg file, see instrument
+    pub expect_id: Option<String>,
+}
+
+/// RemovedCaps limits how much of the removed content is recorded per file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RemovedCaps {
+    pub max_lines: usize,
+    pub max_bytes: usize,
+}
+
+impl Default for RemovedCaps {
+    fn default() -> Self {
+        RemovedCaps {
+            max_lines: 20,
+            max_bytes: 4096,
+        }
+    }
+}
+
+impl CleanOptions {
+    /// check_selected tells if a check was selected to run, regardless of the cfg file.
+    pub fn check_selected(&self, id: CheckId) -> bool {
+        match &self.only_checks {
+            Some(only) => only.contains(&id),
+            None => !self.skip_checks.contains(&id),
+        }
+    }
+
+    /// effective_extension returns the extension whose rules apply to files with the
+    /// upper case extension ext, see ext_map.
+    pub fn effective_extension<'a>(&'a self, ext: &'a str) -> &'a str {
+        self.ext_map.get(ext).map_or(ext, String::as_str)
+    }
+}
+
+/// parse_ext_map parses an extension mapping like `TXT=DAT` to upper case extensions.
+pub fn parse_ext_map(s: &str) -> Result<(String, String), String> {
+    let err = || format!("invalid extension mapping '{s}', expected e.g. TXT=DAT");
+    let (from, to) = s.split_once('=').ok_or_else(err)?;
+    let clean = |ext: &str| ext.trim().trim_start_matches('.').to_ascii_uppercase();
+    let (from, to) = (clean(from), clean(to));
+    if from.is_empty() || to.is_empty() {
+        return Err(err());
+    }
+    Ok((from, to))
+}
+
+/// collect_ext_map collects the mappings of parse_ext_map. an extension must be mapped
+/// once, and not to one that is mapped itself, as only one mapping is ever followed.
+pub fn collect_ext_map(mappings: &[(String, String)]) -> Result<HashMap<String, String>, String> {
+    let mut ext_map = HashMap::new();
+    for (from, to) in mappings {
+        if let Some(other) = ext_map.insert(from.clone(), to.clone()) {
+            return Err(format!("{from} is mapped to {other} and to {to}"));
+        }
+    }
+    if let Some((from, to)) = mappings.iter().find(|(_, to)| ext_map.contains_key(to)) {
+        return Err(format!(
+            "{from} is mapped to {to}, which is mapped to {}",
+            ext_map[to]
+        ));
+    }
+    Ok(ext_map)
+}
+
+/// parse_duration parses a duration like `90s`, `9m`, `1h30m` or `500ms`: numbers with a
+/// unit of ms, s, m, h or d, added up. whitespace between the parts is allowed.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let err = || format!("invalid duration '{s}', expected e.g. 90s, 9m or 1h30m");
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(err());
+    }
+    let mut total = Duration::ZERO;
+    while !rest.is_empty() {
+        let n_digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let n: u64 = rest[..n_digits].parse().map_err(|_| err())?;
+        rest = &rest[n_digits..];
+        let n_unit = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let part = match &rest[..n_unit] {
+            "ms" => Duration::from_millis(n),
+            "s" => Duration::from_secs(n),
+            "m" | "min" => Duration::from_secs(n.saturating_mul(60)),
+            "h" => Duration::from_secs(n.saturating_mul(3600)),
+            "d" => Duration::from_secs(n.saturating_mul(86400)),
+            _ => return Err(err()),
+        };
+        total = total.saturating_add(part);
+        rest = rest[n_unit..].trim_start();
+    }
+    Ok(total)
+}
+
+/// Outcome is what happened to a file. Deleted carries the check that caused the
+/// deletion, Skipped the reason why the file was not checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", content = "reason", rename_all = "snake_case")]
+pub enum Outcome {
+    Unchanged,
+    Modified,
+    Deleted(CheckId),
+    /// the file would have been deleted, but was flagged instead, see DeleteAction
+    Flagged(CheckId),
+    Skipped(SkipReason),
+    /// an I/O error occurred, see FileOutcome::error
+    Error,
+    /// the file type is readonly: the file was checked, but not changed; what would
+    /// have been done is in the warnings
+    Protected,
+}
+
+/// SkipReason tells why a file was skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// the file has no extension, but the no_extension check was not selected
+    NoExtension,
+    /// the file extension is not defined in the cfg file
+    UnknownExtension,
+    /// the file extension could not be analysed, e.g. because it is not valid UTF-8
+    InvalidExtension,
+    /// the file was flagged by a previous run
+    Flagged,
+    /// the file was written by this tool, e.g. a flag sidecar file
+    ToolArtifact,
+    /// the file did not change since its last clean, according to the manifest
+    Manifest,
+    /// the file was gone when it was processed, e.g. rotated away by the acquisition
+    /// software after the directory was listed
+    VanishedDuringRun,
+    /// the file was replaced after it was checked, so it was neither rewritten nor
+    /// deleted, see Stamp
+    ChangedDuringRun,
+}
+
+/// Finding is a single check or transform that fired on a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Finding {
+    pub check: CheckId,
+    pub message: String,
+    /// measured and expected counts, e.g. `lines: 3, min_n_lines: 7`. the message may
+    /// change wording between versions, the keys don't.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub values: BTreeMap<String, usize>,
+}
+
+impl Finding {
+    /// new returns a finding of check without values.
+    pub fn new(check: CheckId, message: String) -> Self {
+        Finding {
+            check,
+            message,
+            values: BTreeMap::new(),
+        }
+    }
+
+    /// value adds a measured or expected count to the finding.
+    pub fn value(&mut self, key: &str, value: usize) -> &mut Self {
+        self.values.insert(key.to_string(), value);
+        self
+    }
+
+    /// with is value for a finding that is not yet added to an outcome.
+    pub fn with(mut self, key: &str, value: usize) -> Self {
+        self.value(key, value);
+        self
+    }
+}
+
+/// TimeSpan is the time covered by the data of a file, from the timestamp of the
+/// first to that of the last line of data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeSpan {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+/// time_span returns the time covered by content. the timestamp is taken from a
+/// tab-separated column and parsed with rules; lines where it does not parse, e.g.
+/// the header, are ignored.
+pub fn time_span(content: &[String], column: usize, rules: &DatetimeRules) -> Option<TimeSpan> {
+    let timestamp = |line: &String| line_timestamp(line, column, rules);
+    let start = content.iter().find_map(timestamp)?;
+    let end = content.iter().rev().find_map(timestamp)?;
+    Some(TimeSpan { start, end })
+}
+
+/// line_timestamp parses the timestamp in a tab-separated column of line with rules.
+fn line_timestamp(line: &str, column: usize, rules: &DatetimeRules) -> Option<NaiveDateTime> {
+    line.split('\t')
+        .nth(column)
+        .and_then(|field| rules.parse(field).ok())
+}
+
+/// DEFAULT_TIME_RESET_THRESHOLD_S is the smallest backward jump of the timestamps that
+/// counts as a clock reset, if `time_reset_threshold_s` is not set.
+pub const DEFAULT_TIME_RESET_THRESHOLD_S: f64 = 60.0;
+
+/// TimeReset is a backward jump of the timestamps within a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeReset {
+    /// index of the first line after the jump
+    pub idx: usize,
+    pub before: NaiveDateTime,
+    pub after: NaiveDateTime,
+}
+
+/// time_resets returns the places where the timestamps of content jump back by more
+/// than threshold_s seconds. timestamps are read like in time_span; lines without one
+/// are ignored.
+pub fn time_resets(
+    content: &[String],
+    column: usize,
+    rules: &DatetimeRules,
+    threshold_s: f64,
+) -> Vec<TimeReset> {
+    let threshold = TimeDelta::milliseconds((threshold_s * 1000.0) as i64);
+    let mut resets = Vec::new();
+    let mut previous: Option<NaiveDateTime> = None;
+    for (idx, line) in content.iter().enumerate() {
+        let Some(timestamp) = line_timestamp(line, column, rules) else {
+            continue;
+        };
+        if let Some(before) = previous.filter(|&before| before - timestamp > threshold) {
+            resets.push(TimeReset {
+                idx,
+                before,
+                after: timestamp,
+            });
+        }
+        previous = Some(timestamp);
+    }
+    resets
+}
+
+/// duplicate_timestamps returns the runs of consecutive lines of content that have the
+/// same timestamp, each with at least two lines. timestamps are read like in time_span;
+/// a line without one ends a run. only neighbours are compared, so content sorted by
+/// time has all lines with equal timestamps in one run.
+pub fn duplicate_timestamps(
+    content: &[String],
+    column: usize,
+    rules: &DatetimeRules,
+) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut run: Option<(Range<usize>, NaiveDateTime)> = None;
+    for (idx, line) in content.iter().enumerate() {
+        let timestamp = line_timestamp(line, column, rules);
+        match (&mut run, timestamp) {
+            (Some((range, previous)), Some(timestamp)) if *previous == timestamp => {
+                range.end = idx + 1;
+            }
+            _ => {
+                runs.extend(run.take().map(|(range, _)| range).filter(|r| r.len() > 1));
+                run = timestamp.map(|timestamp| (idx..idx + 1, timestamp));
+            }
+        }
+    }
+    runs.extend(run.map(|(range, _)| range).filter(|r| r.len() > 1));
+    runs
+}
+
+/// RemovedLine is a line that was removed from a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemovedLine {
+    /// line number in the original file, starting at 1
+    pub line: usize,
+    /// content of the line, omitted if the report is written without content
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
+/// OscPrefix summarizes what the DateTime prefix did to an OSC file, or why it was
+/// skipped, so that operators can audit the transform.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OscPrefix {
+    /// the datetime the prefixes were derived from, as in the first line of the file;
+    /// of the first acquisition block written if there are several
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_datetime: Option<String>,
+    /// number of data lines that got a prefix, over all blocks written
+    pub lines_prefixed: usize,
+    /// the DateTime column was added to the column header
+    pub header_modified: bool,
+    /// why the prefix was skipped, e.g. an invalid datetime; the file was not prefixed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<String>,
+}
+
+#[cfg(feature = "yaml-config")]
+impl OscPrefix {
+    /// applied returns the summary of a prefix from the datetime in first_line.
+    fn applied(first_line: &str, lines_prefixed: usize) -> Self {
+        OscPrefix {
+            source_datetime: RE_DT.find(first_line).map(|m| m.as_str().to_string()),
+            lines_prefixed,
+            header_modified: true,
+            skipped: None,
+        }
+    }
+}
+
+/// FileOutcome collects everything that happened to a file during cleaning.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileOutcome {
+    #[serde(serialize_with = "report::lossy_path")]
+    pub path: PathBuf,
+    /// the path is not valid UTF-8 and is written with replacement characters, set by
+    /// Report::new
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub path_lossy: bool,
+    /// the station of the file if the cfg file has a station_pattern, see
+    /// station::StationPattern; set by the caller, it never changes how a file is cleaned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub station: Option<String>,
+    /// upper case file extension, if there is one; the rules are looked up with it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extension: Option<String>,
+    /// the extension as it is on disk, e.g. `osc`, if that is not upper case
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk_extension: Option<String>,
+    /// the extension whose rules were applied, if mapped by CleanOptions::ext_map
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effective_extension: Option<String>,
+    /// the extension the content looks like, if it fails the header checks of its own
+    /// extension, see CleanOptions::sniff_type
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detected_type: Option<String>,
+    /// the new path of a misnamed file, see CleanOptions::fix_misnamed
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "report::lossy_opt_path"
+    )]
+    pub renamed_to: Option<PathBuf>,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+    /// checks and transforms that fired, in the order they were applied
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub findings: Vec<Finding>,
+    /// problems that did not lead to an action, e.g. incomplete cfg
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// the file could not be cleaned automatically and should be looked at
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub needs_attention: bool,
+    /// the last line of the file is not terminated by a newline
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub missing_final_newline: bool,
+    /// the file contains both LF and CRLF line terminators
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub mixed_line_endings: bool,
+    /// lines removed from the file, ordered by line number. for deleted files, a
+    /// sample of the first and last lines.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed_lines: Vec<RemovedLine>,
+    /// not all removed lines are recorded in removed_lines, see RemovedCaps
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub removed_lines_truncated: bool,
+    /// time covered by the data, if requested and timestamps are configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_span: Option<TimeSpan>,
+    /// timestamps are configured for the file type, but none could be parsed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_timestamps: bool,
+    /// number of lines dropped from the start of the file, see LeadingGarbage. the line
+    /// numbers of removed_lines count them.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub leading_garbage: usize,
+    /// number of lines removed as copies of the header lines, see PreambleAction. the
+    /// line numbers of removed_lines count them.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub duplicate_preamble: usize,
+    /// number of lines removed because an adjacent line had the same timestamp, see
+    /// DuplicateAction
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub collapsed_lines: usize,
+    /// size of the file after it was rewritten
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub bytes_written: u64,
+    /// size of the file before it was deleted
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub bytes_deleted: u64,
+    /// the I/O error that stopped processing of the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// the outcome the file would have under an alternate cfg, see profile::Profile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alternate: Option<Outcome>,
+    /// the outcome under the alternate cfg differs from the actual one
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub would_differ: bool,
+    /// wall-clock microseconds spent on the file, from reading to rewriting it; deletes
+    /// deferred to the end of the run are not counted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_us: Option<u64>,
+    /// sha256 of the content of an unchanged file as it was checked, if
+    /// CleanOptions::hash_unchanged is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// number of data lines below the header block when the file was read, see
+    /// TypeRules::n_data_lines; None if it was not read that far
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lines_before: Option<usize>,
+    /// number of data lines the file was left with, those of the original file if it
+    /// was split; None if it was deleted or flagged, or not read that far
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lines_after: Option<usize>,
+    /// what the DateTime prefix did to an OSC file, if it was tried
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub osc_prefix: Option<OscPrefix>,
+    /// the ID of the instrument that wrote the file, if its type has an instrument_id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instrument_id: Option<String>,
+    /// the ID the file was compared with, see instrument::InstrumentId::expected_id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_instrument_id: Option<String>,
+    /// where the file was copied to before it was changed, see CleanOptions::backup
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "report::lossy_opt_path"
+    )]
+    pub backup: Option<PathBuf>,
+    /// the size and modification time of the file when it was read; rewrites and
+    /// deletes make sure it still matches, see still_as_read
+    #[serde(skip)]
+    pub stamp: Option<Stamp>,
+    /// how to copy the file before it is first changed, from CleanOptions::backup
+    #[serde(skip)]
+    pub keep_backup: Option<backup::Backup>,
+}
+
+fn is_zero<T: Default + PartialEq>(n: &T) -> bool {
+    *n == T::default()
+}
+
+impl FileOutcome {
+    /// new starts the outcome of the file at path, unchanged so far.
+    pub fn new(path: &Path) -> Self {
+        FileOutcome {
+            path: path.to_path_buf(),
+            path_lossy: false,
+            station: None,
+            extension: None,
+            disk_extension: None,
+            effective_extension: None,
+            detected_type: None,
+            renamed_to: None,
+            outcome: Outcome::Unchanged,
+            findings: Vec::new(),
+            warnings: Vec::new(),
+            needs_attention: false,
+            missing_final_newline: false,
+            mixed_line_endings: false,
+            removed_lines: Vec::new(),
+            removed_lines_truncated: false,
+            time_span: None,
+            no_timestamps: false,
+            leading_garbage: 0,
+            duplicate_preamble: 0,
+            collapsed_lines: 0,
+            bytes_written: 0,
+            bytes_deleted: 0,
+            error: None,
+            alternate: None,
+            would_differ: false,
+            duration_us: None,
+            sha256: None,
+            instrument_id: None,
+            expected_instrument_id: None,
+            lines_before: None,
+            lines_after: None,
+            osc_prefix: None,
+            backup: None,
+            stamp: None,
+            keep_backup: None,
+        }
+    }
+
+    /// race returns the outcome of the file at path if err means it vanished or was
+    /// replaced while it was processed, i.e. something else than the cleaner touched it.
+    pub fn race(path: &Path, err: &io::Error) -> Option<Self> {
+        if let Some(changed) = err
+            .get_ref()
+            .and_then(|err| err.downcast_ref::<ChangedDuringRun>())
+        {
+            return Some(FileOutcome {
+                warnings: vec![changed.to_string()],
+                ..FileOutcome::skipped(path, SkipReason::ChangedDuringRun)
+            });
+        }
+        // a NotFound of something else, e.g. a missing directory for a split, is an error
+        (err.kind() == io::ErrorKind::NotFound
+            && fs::symlink_metadata(path).is_err_and(|err| err.kind() == io::ErrorKind::NotFound))
+        .then(|| FileOutcome::skipped(path, SkipReason::VanishedDuringRun))
+    }
+
+    /// still_as_read tells if the file at path, the renamed one if it was renamed, is
+    /// still the one that was checked, before the caller deletes it. if not, the outcome
+    /// becomes a skip, see race.
+    pub fn still_as_read(&mut self, path: &Path) -> bool {
+        let Some(stamp) = self.stamp else {
+            return true;
+        };
+        let Err(err) = stamp.verify(path) else {
+            return true;
+        };
+        let original = std::mem::take(&mut self.path);
+        *self = FileOutcome::race(path, &err).unwrap_or_else(|| FileOutcome {
+            findings: std::mem::take(&mut self.findings),
+            ..FileOutcome::from_error(path, &err)
+        });
+        self.path = original;
+        false
+    }
+
+    /// verify_stamp makes sure the file at path is still the one that was read, before
+    /// it is rewritten or deleted.
+    #[cfg(feature = "yaml-config")]
+    fn verify_stamp(&self, path: &Path) -> io::Result<()> {
+        self.stamp.map_or(Ok(()), |stamp| stamp.verify(path))
+    }
+
+    /// before_write is verify_stamp for a file that is about to be changed, which is
+    /// also copied with keep_backup the first time.
+    #[cfg(feature = "yaml-config")]
+    fn before_write(&mut self, path: &Path) -> io::Result<()> {
+        self.verify_stamp(path)?;
+        if let (Some(backup), None) = (self.keep_backup, &self.backup) {
+            self.backup = Some(backup.keep(path)?);
+        }
+        Ok(())
+    }
+
+    /// skipped records a file that was not looked at for the given reason.
+    pub fn skipped(path: &Path, reason: SkipReason) -> Self {
+        FileOutcome {
+            outcome: Outcome::Skipped(reason),
+            ..FileOutcome::new(path)
+        }
+    }
+
+    /// from_error records an I/O error that occurred while processing a file.
+    pub fn from_error(path: &Path, err: &io::Error) -> Self {
+        FileOutcome {
+            outcome: Outcome::Error,
+            error: Some(err.to_string()),
+            ..FileOutcome::new(path)
+        }
+    }
+
+    /// group returns what the coverage and the station health group the file by: its
+    /// extension, after its station if it has one, e.g. `A/OSC`. None without extension.
+    pub fn group(&self) -> Option<String> {
+        let ext = self.extension.as_deref()?;
+        Some(match &self.station {
+            Some(station) => format!("{station}/{ext}"),
+            None => ext.to_string(),
+        })
+    }
+
+    /// display_extension returns the extension as it is on disk, to show to users.
+    pub fn display_extension(&self) -> Option<&str> {
+        self.disk_extension.as_deref().or(self.extension.as_deref())
+    }
+
+    /// lines_dropped returns the number of lines removed above the data lines, which
+    /// line numbers in the file count.
+    fn lines_dropped(&self) -> usize {
+        self.leading_garbage + self.duplicate_preamble
+    }
+
+    /// finding adds a finding to the outcome and returns it, to add values to it.
+    fn finding(&mut self, check: CheckId, message: String) -> &mut Finding {
+        self.findings.push(Finding::new(check, message));
+        self.findings.last_mut().unwrap()
+    }
+
+    /// removed records that the line at index idx (starting at 0) was removed.
+    fn removed(&mut self, caps: &RemovedCaps, idx: usize, line: &str) {
+        let bytes: usize = self
+            .removed_lines
+            .iter()
+            .filter_map(|l| l.content.as_ref())
+            .map(String::len)
+            .sum();
+        if self.removed_lines.len() >= caps.max_lines || bytes + line.len() > caps.max_bytes {
+            self.removed_lines_truncated = true;
+            return;
+        }
+        let idx = idx + self.lines_dropped();
+        let pos = self.removed_lines.partition_point(|l| l.line < idx + 1);
+        self.removed_lines.insert(
+            pos,
+            RemovedLine {
+                line: idx + 1,
+                content: Some(line.to_string()),
+            },
+        );
+    }
+
+    /// remove_last removes the last line of content and records it. offset is the index
+    /// of content in the file.
+    fn remove_last(&mut self, caps: &RemovedCaps, content: &mut Vec<String>, offset: usize) {
+        if let Some(line) = content.pop() {
+            self.removed(caps, offset + content.len(), &line);
+        }
+    }
+
+    /// removed_sample records the first and last lines of content, which is removed
+    /// as a whole. offset is the index of content in the file.
+    fn removed_sample(&mut self, caps: &RemovedCaps, content: &[String], offset: usize) {
+        let head = caps.max_lines - caps.max_lines / 2;
+        let tail_start = content.len().saturating_sub(caps.max_lines / 2).max(head);
+        for (i, line) in content.iter().enumerate() {
+            if i < head || i >= tail_start {
+                self.removed(caps, offset + i, line);
+            }
+        }
+        if content.len() > caps.max_lines {
+            self.removed_lines_truncated = true;
+        }
+    }
+
+    /// skip_prefix records why the DateTime prefix of an OSC file was skipped, as a
+    /// warning and in osc_prefix; with attention, the file needs to be looked at.
+    /// returns the summary, to add what is known, e.g. the datetime.
+    #[cfg(feature = "yaml-config")]
+    fn skip_prefix(&mut self, reason: String, attention: bool) -> &mut OscPrefix {
+        self.warnings.push(if attention {
+            format!("{reason} -> skip datetime prefix, needs manual attention")
+        } else {
+            format!("{reason} -> skip datetime prefix")
+        });
+        self.needs_attention |= attention;
+        self.osc_prefix.insert(OscPrefix {
+            skipped: Some(reason),
+            ..Default::default()
+        })
+    }
+}
+
+/// line_counts reads the number of header lines and the minimum number of data lines of
+/// a file type. the older min_n_lines counts both, with the header line at min_n_lines - 2
+/// unless header_line says otherwise, and is translated; see deprecated_settings. freeform
+/// types have no header, their min_n_lines is min_data_lines.
+#[cfg(feature = "yaml-config")]
+fn line_counts(cfg_ext: &Yaml) -> (Option<usize>, Option<usize>) {
+    let min_n_lines = cfg_ext["min_n_lines"].as_i64().map(|n| n.max(0) as usize);
+    if is_freeform(cfg_ext) {
+        let min_data_lines = cfg_ext["min_data_lines"]
+            .as_i64()
+            .map(|n| n.max(0) as usize);
+        return (None, min_data_lines.or(min_n_lines));
+    }
+    let n_header_lines = cfg_header_idx(cfg_ext)
+        .map(|idx| idx + 1)
+        .or(min_n_lines.map(|n| n.saturating_sub(1)));
+    let min_data_lines = match cfg_ext["min_data_lines"].as_i64() {
+        Some(n) => Some(n.max(0) as usize),
+        None => min_n_lines.map(|n| n.saturating_sub(n_header_lines.unwrap_or(1).max(1))),
+    };
+    (n_header_lines, min_data_lines)
+}
+
+/// cfg_header_idx returns the index of the column header line if the cfg of a file
+/// type sets it, by n_header_lines or header_line.
+#[cfg(feature = "yaml-config")]
+pub fn cfg_header_idx(cfg_ext: &Yaml) -> Option<usize> {
+    match cfg_ext["n_header_lines"].as_i64() {
+        Some(n) => Some((n.max(1) - 1) as usize),
+        None => cfg_ext["header_line"]
+            .as_i64()
+            .map(|idx| idx.max(0) as usize),
+    }
+}
+
+/// deprecated_settings lists the settings of the cfg file that are still accepted, but
+/// should be replaced, with their replacement.
+#[cfg(feature = "yaml-config")]
+pub fn deprecated_settings(cfg: &Yaml) -> Vec<String> {
+    let Some(types) = cfg.as_hash() else {
+        return Vec::new();
+    };
+    types
+        .iter()
+        .filter(|(_, cfg_ext)| !cfg_ext["min_n_lines"].is_badvalue())
+        .map(|(ext, cfg_ext)| {
+            let (n_header_lines, min_data_lines) = line_counts(cfg_ext);
+            if is_freeform(cfg_ext) {
+                return format!(
+                    "{}: min_n_lines is deprecated, use min_data_lines: {}",
+                    ext.as_str().unwrap_or("?"),
+                    min_data_lines.unwrap_or(1)
+                );
+            }
+            format!(
+                "{}: min_n_lines is deprecated, use n_header_lines: {} and min_data_lines: {}",
+                ext.as_str().unwrap_or("?"),
+                n_header_lines.unwrap_or(1).max(1),
+                min_data_lines.unwrap_or(1)
+            )
+        })
+        .collect()
+}
+
+/// FileTypeRules are the rules the checks on lines apply to the files of a type. With
+/// the yaml-config feature they are read from the cfg file, see from_cfg; without it
+/// the caller fills them in.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FileTypeRules {
+    /// number of lines up to and including the column header, default 1
+    pub n_header_lines: Option<usize>,
+    /// minimum number of data lines below the column header, default 1; without either
+    /// count a warning is given
+    pub min_data_lines: Option<usize>,
+    pub columns: ColumnFormat,
+    /// normalized reference header, see load_reference_headers
+    pub reference_header: Option<String>,
+    pub on_header_mismatch: HeaderMismatchAction,
+    /// the column header must name each field once, see duplicate_fields
+    pub unique_header_fields: bool,
+    /// field names differing only by case are duplicates, e.g. Temp and TEMP
+    pub header_fields_ignore_case: bool,
+    pub on_duplicate_header_field: HeaderMismatchAction,
+    pub on_missing_final_newline: LastLineAction,
+    pub format: FileFormat,
+}
+
+impl FileTypeRules {
+    /// from_cfg reads the rules from the cfg of a file type. invalid settings are
+    /// replaced by their default, with a warning.
+    #[cfg(feature = "yaml-config")]
+    pub fn from_cfg(
+        cfg_ext: &Yaml,
+        reference_header: Option<&String>,
+        warnings: &mut Vec<String>,
+    ) -> Self {
+        let (n_header_lines, min_data_lines) = line_counts(cfg_ext);
+        FileTypeRules {
+            n_header_lines,
+            min_data_lines,
+            columns: ColumnFormat::from_cfg(cfg_ext).unwrap_or_else(|err| {
+                warnings.push(format!("{err}; defaulting to tab"));
+                ColumnFormat::default()
+            }),
+            reference_header: reference_header.cloned(),
+            on_header_mismatch: match cfg_ext["on_header_mismatch"].as_str() {
+                Some(s) => s.parse().unwrap_or_else(|err| {
+                    warnings.push(format!("{err}; defaulting to warn"));
+                    HeaderMismatchAction::Warn
+                }),
+                None => HeaderMismatchAction::Warn,
+            },
+            unique_header_fields: cfg_ext["unique_header_fields"].as_bool() == Some(true),
+            header_fields_ignore_case: cfg_ext["header_fields_ignore_case"].as_bool() == Some(true),
+            on_duplicate_header_field: match cfg_ext["on_duplicate_header_field"].as_str() {
+                Some(s) => s.parse().unwrap_or_else(|err| {
+                    warnings.push(format!("{err}; defaulting to warn"));
+                    HeaderMismatchAction::Warn
+                }),
+                None => HeaderMismatchAction::Warn,
+            },
+            on_missing_final_newline: match cfg_ext["on_missing_final_newline"].as_str() {
+                Some(s) => s.parse().unwrap_or_else(|err| {
+                    warnings.push(format!("{err}; defaulting to drop"));
+                    LastLineAction::Drop
+                }),
+                None => LastLineAction::Drop,
+            },
+            format: match cfg_ext["format"].as_str() {
+                Some(s) => s.parse().unwrap_or_else(|err| {
+                    warnings.push(format!("{err}; defaulting to columnar"));
+                    FileFormat::Columnar
+                }),
+                None => FileFormat::Columnar,
+            },
+        }
+    }
+
+    /// header_len returns the number of lines up to and including the column header.
+    pub fn header_len(&self) -> usize {
+        self.n_header_lines.unwrap_or(1).max(1)
+    }
+
+    /// header_idx returns the index of the column header line.
+    pub fn header_idx(&self) -> usize {
+        self.header_len() - 1
+    }
+
+    /// n_data_lines returns how many of n_lines lines are data below the header block.
+    /// freeform files have no header, all their lines are data.
+    pub fn n_data_lines(&self, n_lines: usize) -> usize {
+        match self.format {
+            FileFormat::Columnar => n_lines.saturating_sub(self.header_len()),
+            FileFormat::Freeform => n_lines,
+        }
+    }
+
+    /// min_len returns the minimum number of lines, header and data. freeform files
+    /// have no header, all their lines count as data.
+    pub fn min_len(&self) -> usize {
+        match self.format {
+            FileFormat::Columnar => self
+                .header_len()
+                .saturating_add(self.min_data_lines.unwrap_or(1)),
+            FileFormat::Freeform => self.min_data_lines.unwrap_or(1),
+        }
+    }
+
+    /// configured_len returns min_len if the cfg file sets any of the line counts.
+    pub fn configured_len(&self) -> Option<usize> {
+        (self.n_header_lines.is_some() || self.min_data_lines.is_some()).then(|| self.min_len())
+    }
+
+    /// too_few_lines returns the finding of check 2 if content has less data lines below
+    /// the header than min_data_lines.
+    fn too_few_lines(&self, content: &[String]) -> Option<Finding> {
+        if content.len() >= self.min_len() {
+            return None;
+        }
+        let min_data = self.min_data_lines.unwrap_or(1);
+        if self.format == FileFormat::Freeform {
+            let n = content.len();
+            return Some(
+                Finding::new(
+                    CheckId::TooFewLines,
+                    format!("has {n} line(s), want at least {min_data}"),
+                )
+                .with("lines", n)
+                .with("min_data_lines", min_data),
+            );
+        }
+        let n_data = content.len().saturating_sub(self.header_len());
+        Some(
+            Finding::new(
+                CheckId::TooFewLines,
+                format!("has {n_data} data line(s) below the header, want at least {min_data}"),
+            )
+            .with("data_lines", n_data)
+            .with("min_data_lines", min_data),
+        )
+    }
+
+    /// header_problem returns the first header check that content fails, as a finding
+    /// with the reason: the number of fields of the header and the first data line (check 3),
+    /// then the reference header (check 3.1). content shorter than min_len has no
+    /// header problem, it fails check 2; without data lines only the reference header
+    /// is compared. freeform files have no header to fail.
+    pub fn header_problem(
+        &self,
+        content: &[String],
+        enabled: &dyn Fn(CheckId) -> bool,
+    ) -> Option<Finding> {
+        if self.format == FileFormat::Freeform || content.len() < self.min_len() {
+            return None;
+        }
+        let header_idx = self.header_idx();
+        let n_col_header = self.columns.n_header_fields(&content[header_idx]);
+        let n_col_data = content
+            .get(header_idx + 1)
+            .map_or(n_col_header, |line| self.columns.n_fields(line));
+        if enabled(CheckId::FirstDataLineFieldMismatch) && n_col_data != n_col_header {
+            return Some(
+                Finding::new(
+                    CheckId::FirstDataLineFieldMismatch,
+                    "has invalid number of fields in first line of data".to_string(),
+                )
+                .with("fields", n_col_data)
+                .with("header_fields", n_col_header),
+            );
+        }
+        let reference = self
+            .reference_header
+            .as_deref()
+            .filter(|_| enabled(CheckId::HeaderMismatch))?;
+        header_difference(&content[header_idx], reference).map(|difference| {
+            Finding::new(
+                CheckId::HeaderMismatch,
+                format!("column header differs from the reference: {difference}"),
+            )
+        })
+    }
+
+    /// duplicate_fields returns the finding of check 3.2 if header names a field more
+    /// than once, with the positions of each duplicate counted from 1. fields are
+    /// trimmed, empty ones are not names. None if unique_header_fields is not set.
+    pub fn duplicate_fields(&self, header: &str) -> Option<Finding> {
+        if !self.unique_header_fields {
+            return None;
+        }
+        let key = |field: &str| {
+            if self.header_fields_ignore_case {
+                field.to_lowercase()
+            } else {
+                field.to_string()
+            }
+        };
+        // the names of each field, as spelled in the header, with their positions
+        let mut positions: Vec<(Vec<&str>, Vec<usize>)> = Vec::new();
+        let fields = header.split(self.columns.header_delimiter.as_str());
+        for (i, field) in fields.map(str::trim).enumerate() {
+            if field.is_empty() {
+                continue;
+            }
+            match positions
+                .iter_mut()
+                .find(|(names, _)| key(names[0]) == key(field))
+            {
+                Some((names, at)) => {
+                    if !names.contains(&field) {
+                        names.push(field);
+                    }
+                    at.push(i + 1);
+                }
+                None => positions.push((vec![field], vec![i + 1])),
+            }
+        }
+        let duplicates: Vec<String> = positions
+            .iter()
+            .filter(|(_, at)| at.len() > 1)
+            .map(|(names, at)| {
+                let names: Vec<String> = names.iter().map(|name| format!("'{name}'")).collect();
+                let at: Vec<String> = at.iter().map(usize::to_string).collect();
+                format!("{} at fields {}", names.join("/"), at.join(", "))
+            })
+            .collect();
+        if duplicates.is_empty() {
+            return None;
+        }
+        Some(
+            Finding::new(
+                CheckId::DuplicateHeaderField,
+                format!(
+                    "column header names fields more than once: {}",
+                    duplicates.join("; ")
+                ),
+            )
+            .with("duplicates", duplicates.len()),
+        )
+    }
+}
+
+/// Verdict is what clean_lines decided about the content of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// keep the file; modified tells if the content was changed and must be written
+    Keep { modified: bool },
+    /// keep the file, it is too short for the checks after check 2, which were skipped
+    TooShort { modified: bool },
+    /// delete the file because of the check, with the reason
+    Delete(Finding),
+}
+
+/// clean_lines runs checks 2 to 5 on the lines of a file, content is fixed in place.
+/// final_newline tells if the last line was terminated by a newline. findings,
+/// warnings and removed lines are added to outcome. it needs neither the cfg file nor
+/// the file itself, see clean_file for the whole process.
+pub fn clean_lines(
+    content: &mut Vec<String>,
+    final_newline: bool,
+    rules: &FileTypeRules,
+    enabled: &dyn Fn(CheckId) -> bool,
+    caps: &RemovedCaps,
+    outcome: &mut FileOutcome,
+) -> Verdict {
+    let mut modified = false;
+
+    // check #2
+    // remove all empty strings at the end of content (trailing newlines)
+    while enabled(CheckId::TrailingEmptyLine) && content.last().is_some_and(String::is_empty) {
+        outcome.finding(
+            CheckId::TrailingEmptyLine,
+            "last line is empty -> remove line".to_string(),
+        );
+        outcome.remove_last(caps, content, 0);
+        modified = true;
+    }
+
+    // depending on the file extension, determine the number of header lines and the
+    // minimum number of data lines below them. the default is 1 each:
+    if rules.configured_len().is_none() {
+        outcome.warnings.push(format!(
+            "failed to obtain minimum number of lines from cfg file; defaulting to {}",
+            rules.min_len()
+        ));
+    }
+    let header_idx = rules.header_idx();
+
+    if let Some(finding) = rules.too_few_lines(content) {
+        if !enabled(CheckId::TooFewLines) {
+            return Verdict::TooShort { modified };
+        }
+        outcome.removed_sample(caps, content, 0);
+        // these files should be deleted, so we can skip further tests
+        return Verdict::Delete(finding);
+    }
+    // <<< check 2 done.
+
+    // freeform lines have no header or fields for checks 3 and 4
+    if rules.format == FileFormat::Freeform {
+        return Verdict::Keep { modified };
+    }
+
+    // >>> check #3
+    // determine number of columns based on the first line (column header),
+    // and the first line of data. Those must be equal.
+    // >>> check #3.1
+    // compare the column header with the reference header of the file type
+    match rules.header_problem(content, enabled) {
+        Some(finding)
+            if finding.check == CheckId::HeaderMismatch
+                && rules.on_header_mismatch == HeaderMismatchAction::Warn =>
+        {
+            outcome.warnings.push(finding.message)
+        }
+        Some(finding) => {
+            outcome.removed_sample(caps, content, 0);
+            return Verdict::Delete(finding);
+        }
+        None => (),
+    }
+    // >>> check #3.2
+    // the column header must name each field once, a firmware bug once wrote Temp twice
+    match rules
+        .duplicate_fields(&content[header_idx])
+        .filter(|_| enabled(CheckId::DuplicateHeaderField))
+    {
+        Some(finding) if rules.on_duplicate_header_field == HeaderMismatchAction::Warn => {
+            outcome.warnings.push(finding.message)
+        }
+        Some(finding) => {
+            outcome.removed_sample(caps, content, 0);
+            return Verdict::Delete(finding);
+        }
+        None => (),
+    }
+    // a header without data, as min_data_lines: 0 allows, has no last line to check
+    if content.len() == header_idx + 1 {
+        return Verdict::TooShort { modified };
+    }
+    let n_col_header = rules.columns.n_header_fields(&content[header_idx]);
+    // <<< check 3, 3.1 and 3.2 done.
+
+    // >>> check #4.0
+    // a last line without newline was most likely cut off while writing,
+    // even if its number of fields happens to be correct.
+    if enabled(CheckId::MissingFinalNewline) && !final_newline {
+        match rules.on_missing_final_newline {
+            LastLineAction::Drop => {
+                outcome.finding(
+                    CheckId::MissingFinalNewline,
+                    "last line is not terminated by a newline -> remove line".to_string(),
+                );
+                outcome.remove_last(caps, content, 0);
+                modified = true;
+            }
+            LastLineAction::Pad => {
+                // the line is kept, writing the file adds the missing newline
+                outcome.finding(
+                    CheckId::MissingFinalNewline,
+                    "last line is not terminated by a newline -> add newline".to_string(),
+                );
+                modified = true;
+            }
+            LastLineAction::Keep => outcome
+                .warnings
+                .push("last line is not terminated by a newline".to_string()),
+        }
+    }
+    // <<< check 4.0 done.
+
+    // >>> check #4.1
+    // check number of fields in last line, must be the same as column header. if #4.0
+    // removed the only data line, the last line is the header, which is left to #5
+    let n_col_data = rules.columns.n_fields(&content[content.len() - 1]);
+    if enabled(CheckId::LastLineFieldMismatch)
+        && content.len() > header_idx + 1
+        && n_col_data != n_col_header
+    {
+        outcome
+            .finding(
+                CheckId::LastLineFieldMismatch,
+                format!("{n_col_data} field(s) in last line of data but header has {n_col_header} -> remove line"),
+            )
+            .value("fields", n_col_data)
+            .value("header_fields", n_col_header);
+        outcome.remove_last(caps, content, 0); // coming from #3, if we pop one line, we still have at least one line of data
+        modified = true;
+    }
+    // <<< check 4.1 done.
+
+    // >>> check #4.2
+    // check the last field of the last line. assume that the line is
+    // corrupted if that field has less characters than the last field
+    // of the preceeding line.
+    // this can only be done if there are at least two lines of data.
+    let delimiter = rules.columns.delimiter.as_str();
+    if enabled(CheckId::LastFieldTruncated) && content.len() > header_idx + 2 {
+        let have = n_chars_last_field(&content[content.len() - 1], delimiter).unwrap();
+        let want = n_chars_last_field(&content[content.len() - 2], delimiter).unwrap();
+        if have < want {
+            outcome
+                .finding(
+                    CheckId::LastFieldTruncated,
+                    format!("last field of last line has {have} character(s), but want {want} -> remove line"),
+                )
+                .value("chars", have)
+                .value("previous_chars", want);
+            outcome.remove_last(caps, content, 0);
+            modified = true;
+        }
+    }
+    // <<< check 4.2 done.
+
+    // >>> check #5
+    // after removing the last line again in #4.2, content could be too short...
+    if let (true, Some(finding)) = (enabled(CheckId::TooFewLines), rules.too_few_lines(content)) {
+        outcome.removed_sample(caps, content, 0);
+        return Verdict::Delete(finding);
+    }
+    // <<< check 5 done.
+
+    Verdict::Keep { modified }
+}
+
+/// clean_file runs all checks on a file and applies the resulting actions,
+/// i.e. deletes or rewrites the file. files of a readonly type are only checked, see
+/// protected.
+#[cfg(feature = "yaml-config")]
+pub fn clean_file(file_path: &Path, cfg: &Yaml, opts: &CleanOptions) -> io::Result<FileOutcome> {
+    let started = Instant::now();
+    // the acquisition software may rotate files while they are cleaned, that is no error
+    let mut outcome = match clean_file_inner(file_path, cfg, opts) {
+        Ok(outcome) => outcome,
+        Err(err) => FileOutcome::race(file_path, &err).ok_or(err)?,
+    };
+    outcome.duration_us = Some(started.elapsed().as_micros() as u64);
+    Ok(outcome)
+}
+
+/// clean_file_inner is clean_file without the handling of files that vanished or were
+/// replaced meanwhile.
+#[cfg(feature = "yaml-config")]
+fn clean_file_inner(file_path: &Path, cfg: &Yaml, opts: &CleanOptions) -> io::Result<FileOutcome> {
+    let ext = manifest::file_extension(file_path);
+    if cfg[opts.effective_extension(&ext)]["readonly"].as_bool() == Some(true) {
+        return protected(file_path, cfg, opts);
+    }
+    let size = fs::metadata(file_path).map_or(0, |m| m.len());
+    // the hash is taken before the checks, they must not change what they left
+    let checked = opts
+        .hash_unchanged
+        .then(|| cert::sha256_file(file_path))
+        .transpose()?;
+    let mut outcome = check_and_fix(file_path, cfg, opts)?;
+    match outcome.outcome {
+        Outcome::Unchanged => outcome.sha256 = checked,
+        // the whole file is written, whatever changed
+        Outcome::Modified => {
+            let path = outcome.renamed_to.as_deref().unwrap_or(file_path);
+            outcome.bytes_written = fs::metadata(path)?.len()
+        }
+        Outcome::Deleted(_) => outcome.bytes_deleted = size,
+        _ => (),
+    }
+    Ok(outcome)
+}
+
+/// protected checks a file of a readonly type on a copy in a temporary directory, the
+/// file itself is never written. the actions the checks would take become warnings.
+#[cfg(feature = "yaml-config")]
+fn protected(file_path: &Path, cfg: &Yaml, opts: &CleanOptions) -> io::Result<FileOutcome> {
+    let work = tmpfile::temp_path(&std::env::temp_dir().join("v25_readonly"));
+    fs::create_dir(&work)?;
+    let opts = CleanOptions {
+        defer_delete: false,
+        fix_misnamed: false,
+        backup: None,
+        ..opts.clone()
+    };
+    let result = clean_copy(file_path, &work, |copy| check_and_fix(copy, cfg, &opts));
+    let _ = fs::remove_dir_all(&work);
+    let mut outcome = result?;
+    if outcome.outcome == Outcome::Error {
+        return Ok(outcome);
+    }
+    outcome.outcome = Outcome::Protected;
+    for finding in std::mem::take(&mut outcome.findings) {
+        outcome
+            .warnings
+            .push(format!("{} (readonly, not applied)", finding.message));
+    }
+    outcome.removed_lines.clear();
+    outcome.removed_lines_truncated = false;
+    outcome.leading_garbage = 0;
+    outcome.duplicate_preamble = 0;
+    Ok(outcome)
+}
+
+/// clean_copy copies the file at path to a new directory in work, with its modification
+/// time, cleans the copy with clean and returns the outcome with the original path.
+/// that directory is removed afterwards, so threads can share work.
+#[cfg(feature = "yaml-config")]
+pub(crate) fn clean_copy(
+    path: &Path,
+    work: &Path,
+    clean: impl FnOnce(&Path) -> io::Result<FileOutcome>,
+) -> io::Result<FileOutcome> {
+    let work = tmpfile::temp_path(&work.join("copy"));
+    fs::create_dir(&work)?;
+    let result = clean_in_copy(path, &work, clean);
+    let _ = fs::remove_dir_all(&work);
+    result
+}
+
+/// clean_in_copy is clean_copy in the directory work.
+#[cfg(feature = "yaml-config")]
+fn clean_in_copy(
+    path: &Path,
+    work: &Path,
+    clean: impl FnOnce(&Path) -> io::Result<FileOutcome>,
+) -> io::Result<FileOutcome> {
+    let copy = work.join(path.file_name().unwrap_or_default());
+    fs::copy(path, &copy)?;
+    // checks may compare the content with the modification time
+    if let Ok(mtime) = fs::metadata(path).and_then(|m| m.modified()) {
+        fs::File::options()
+            .write(true)
+            .open(&copy)?
+            .set_modified(mtime)?;
+    }
+    let mut outcome = clean(&copy)?;
+    outcome.path = path.to_path_buf();
+    Ok(outcome)
+}
+
+/// check_and_fix is clean_file without the I/O accounting.
+#[cfg(feature = "yaml-config")]
+fn check_and_fix(file_path: &Path, cfg: &Yaml, opts: &CleanOptions) -> io::Result<FileOutcome> {
+    let mut outcome = FileOutcome::new(file_path);
+
+    // sidecar and temporary files are ours, flagged files were checked before
+    if is_tool_artifact(file_path) {
+        outcome.outcome = Outcome::Skipped(SkipReason::ToolArtifact);
+        return Ok(outcome);
+    }
+    if !opts.recheck_flagged && flag_path(file_path).is_file() {
+        outcome.outcome = Outcome::Skipped(SkipReason::Flagged);
+        return Ok(outcome);
+    }
+    // rewrites and deletes make sure the file is still the one read from here on
+    outcome.stamp = Some(Stamp::of(file_path)?);
+    outcome.keep_backup = opts.backup;
+
+    // the extension is matched on the raw name, reports and the manifest use the name
+    // with replacement characters
+    if let Some(name) = file_path.file_name().filter(|name| name.to_str().is_none()) {
+        outcome.warnings.push(format!(
+            "file name is not valid UTF-8, reported as '{}'",
+            name.to_string_lossy()
+        ));
+    }
+
+    // >>> check #1
+    // make sure the file has an extension and it is defined in config file
+    let file_ext = match file_path.extension().map(|ext| ext.to_ascii_uppercase()) {
+        Some(ext) if !ext.is_empty() => match ext.to_str() {
+            Some(ext) => ext.to_owned(),
+            None => {
+                outcome.outcome = Outcome::Skipped(SkipReason::InvalidExtension);
+                return Ok(outcome);
+            }
+        },
+        _ => {
+            if !opts.check_selected(CheckId::NoExtension) {
+                outcome.outcome = Outcome::Skipped(SkipReason::NoExtension);
+                return Ok(outcome);
+            }
+            // without extension there is no cfg, such files are always deleted
+            return delete_file(
+                file_path,
+                Finding::new(CheckId::NoExtension, "has no extension".to_string()),
+                if opts.flag_deletes {
+                    DeleteAction::Flag
+                } else {
+                    DeleteAction::Delete
+                },
+                opts.defer_delete,
+                outcome,
+            );
+        }
+    };
+    outcome.extension = Some(file_ext.clone());
+    outcome.disk_extension = file_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| *ext != file_ext)
+        .map(str::to_owned);
+    let file_ext = match opts.ext_map.get(&file_ext) {
+        Some(mapped) => {
+            outcome.effective_extension = Some(mapped.clone());
+            mapped.clone()
+        }
+        None => file_ext,
+    };
+    if cfg[file_ext.as_str()].is_badvalue() {
+        outcome.outcome = Outcome::Skipped(SkipReason::UnknownExtension);
+        return Ok(outcome);
+    }
+    // <<< check 1 done.
+
+    // a check runs if it was selected and is not disabled in cfg
+    let enabled = |id: CheckId| opts.check_selected(id) && check_enabled(cfg, &file_ext, id);
+
+    let delete_action = match cfg[file_ext.as_str()]["on_delete"].as_str() {
+        Some(s) => s.parse::<DeleteAction>().unwrap_or_else(|err| {
+            outcome
+                .warnings
+                .push(format!("{err}; defaulting to delete"));
+            DeleteAction::Delete
+        }),
+        None => DeleteAction::Delete,
+    };
+    let delete_action = if opts.flag_deletes {
+        DeleteAction::Flag
+    } else {
+        delete_action
+    };
+
+    // fast path for empty files (e.g. after power loss), no need to read them
+    if enabled(CheckId::EmptyFile) && fs::metadata(file_path)?.len() == 0 {
+        return delete_file(
+            file_path,
+            Finding::new(CheckId::EmptyFile, "has a size of zero bytes".to_string())
+                .with("bytes", 0),
+            delete_action,
+            opts.defer_delete,
+            outcome,
+        );
+    }
+
+    // line endings: files written by two writers can mix LF and CRLF
+    let policy = match cfg[file_ext.as_str()]["line_ending"].as_str() {
+        Some(s) => s.parse::<LineEndingPolicy>().unwrap_or_else(|err| {
+            outcome.warnings.push(format!("{err}; defaulting to lf"));
+            LineEndingPolicy::Lf
+        }),
+        None => LineEndingPolicy::Lf,
+    };
+
+    // most files are clean, with the mmap feature they are recognized without reading
+    // them line by line. transforms and the coverage report need the full content, but
+    // large OSC files that are clean otherwise can be prefixed line by line.
+    let cfg_ext = &cfg[file_ext.as_str()];
+    let mut type_rules = FileTypeRules::from_cfg(
+        cfg_ext,
+        opts.reference_headers.get(&file_ext),
+        &mut outcome.warnings,
+    );
+    let columns = &type_rules.columns;
+    let compiled = opts.compiled_rules.get(&file_ext);
+    if compiled.is_none() {
+        for (key, skipped) in [
+            ("skip_leading_garbage", "not skipping leading lines"),
+            ("line_pattern", "not matching lines"),
+            ("header_match", "using n_header_lines"),
+            ("expected_count_from_header", "not checking the count"),
+            ("instrument_id", "not checking the instrument ID"),
+            (
+                "existing_time_pattern",
+                "matching the default datetime format",
+            ),
+        ] {
+            if !cfg_ext[key].is_badvalue() {
+                outcome.warnings.push(format!(
+                    "{key} was not compiled, see compile_rules; {skipped}"
+                ));
+            }
+        }
+    }
+    let garbage = compiled
+        .and_then(|rules| rules.leading_garbage.as_ref())
+        .filter(|_| enabled(CheckId::LeadingGarbage));
+    let line_pattern = compiled
+        .and_then(|rules| rules.line_pattern.as_ref())
+        .filter(|_| {
+            type_rules.format == FileFormat::Freeform && enabled(CheckId::LinePatternMismatch)
+        });
+    let time_pattern = compiled.and_then(|rules| rules.existing_time_pattern.as_ref());
+    let header_match = compiled
+        .and_then(|rules| rules.header_match.as_ref())
+        .filter(|_| type_rules.format == FileFormat::Columnar);
+    let expected_count = compiled
+        .and_then(|rules| rules.expected_count.as_ref())
+        .filter(|_| type_rules.format == FileFormat::Columnar && enabled(CheckId::ExpectedCount));
+    let instrument_id = compiled
+        .and_then(|rules| rules.instrument_id.as_ref())
+        .filter(|_| enabled(CheckId::InstrumentId));
+    let line_pattern_action = match cfg_ext["on_line_pattern_mismatch"].as_str() {
+        Some(s) => s.parse::<LinePatternAction>().unwrap_or_else(|err| {
+            outcome.warnings.push(format!("{err}; defaulting to warn"));
+            LinePatternAction::Warn
+        }),
+        None => LinePatternAction::Warn,
+    };
+    let preamble_action = match cfg_ext["on_duplicate_preamble"].as_str() {
+        Some(s) => s.parse::<PreambleAction>().map(Some).unwrap_or_else(|err| {
+            outcome.warnings.push(format!("{err}; defaulting to warn"));
+            Some(PreambleAction::Warn)
+        }),
+        None => None,
+    }
+    .filter(|_| enabled(CheckId::DuplicatePreamble));
+    // a file losing much of its data is more likely misparsed than broken
+    let max_removed = max_removed_fraction(cfg_ext)
+        .unwrap_or_else(|err| {
+            outcome.warnings.push(format!("{err}; not limiting"));
+            None
+        })
+        .filter(|_| enabled(CheckId::RemovedFraction));
+    let removed_action = match cfg_ext["on_too_many_removed"].as_str() {
+        Some(s) if !opts.flag_deletes => s.parse::<DeleteAction>().unwrap_or_else(|err| {
+            outcome.warnings.push(format!("{err}; defaulting to flag"));
+            DeleteAction::Flag
+        }),
+        _ => DeleteAction::Flag,
+    };
+    let osc_transform = file_ext == "OSC" && enabled(CheckId::OscDatetimePrefix);
+    // the fast paths expect the header at a fixed index, garbage or a repeated preamble
+    // would shift it; the
+    // timestamps are only checked on the full content, and only types with timestamps
+    // have a time span for the coverage report
+    let timestamps = timestamp_rules(cfg_ext).filter(|_| {
+        opts.coverage || enabled(CheckId::DuplicateTimestamp) || enabled(CheckId::TimeReset)
+    });
+    // padded fields are trimmed only if the file is rewritten anyway, unless forced
+    let trim = cfg_ext["trim_fields"].as_bool() == Some(true) && enabled(CheckId::TrimFields);
+    let trim_force = trim && cfg_ext["trim_fields_force_rewrite"].as_bool() == Some(true);
+    // likewise, the fields are joined with the output delimiter
+    let output = OutputDelimiter::from_cfg(cfg_ext)
+        .unwrap_or_else(|err| {
+            outcome
+                .warnings
+                .push(format!("{err}; not converting the delimiter"));
+            None
+        })
+        .filter(|_| enabled(CheckId::OutputDelimiter));
+    let output_force = output.as_ref().is_some_and(|output| output.force);
+    // the fast paths expect a column header, freeform files have none, and do not
+    // compare its fields with each other
+    let full_read = garbage.is_some()
+        || header_match.is_some()
+        || expected_count.is_some()
+        || instrument_id.is_some()
+        || preamble_action.is_some()
+        || type_rules.unique_header_fields
+        || timestamps.is_some()
+        || trim_force
+        || output_force
+        || type_rules.format == FileFormat::Freeform;
+    if let (Some(min_len), false) = (type_rules.configured_len(), full_read) {
+        let expect = inspect::Expect {
+            min_len,
+            header_idx: type_rules.header_idx(),
+            reference: type_rules
+                .reference_header
+                .as_deref()
+                .filter(|_| enabled(CheckId::HeaderMismatch)),
+            columns,
+        };
+        let size = fs::metadata(file_path)?.len();
+        let clean_lines = (!osc_transform && size > 0)
+            .then(|| inspect::mmap_clean_lines(file_path, &expect))
+            .flatten();
+        if let Some(n_lines) = clean_lines {
+            outcome.lines_before = Some(type_rules.n_data_lines(n_lines));
+            outcome.lines_after = outcome.lines_before;
+            return Ok(outcome);
+        }
+        if osc_transform
+            && !trim
+            && output.is_none()
+            && opts.stream_osc_above.is_some_and(|n| size > n)
+        {
+            let header_idx = cfg_header_idx(cfg_ext).unwrap_or(4);
+            let scan = inspect::Scan::read(file_path, (min_len + 1).max(header_idx + 2), &*RE_DT)?;
+            // files with several acquisition blocks or without data right below the
+            // header are left to osc_prefix
+            if scan.is_clean(&expect)
+                && !scan.later_datetime_line
+                && scan
+                    .head
+                    .get(header_idx + 1)
+                    .is_some_and(|line| looks_like_data(line, &columns.delimiter))
+            {
+                // the prefix adds no lines
+                outcome.lines_before = Some(type_rules.n_data_lines(scan.n_lines));
+                outcome.lines_after = outcome.lines_before;
+                if osc_prefix_streamed(
+                    file_path,
+                    cfg_ext,
+                    &scan.head,
+                    scan.n_lines,
+                    scan.line_ending(policy),
+                    time_pattern,
+                    &mut outcome,
+                )? {
+                    outcome.outcome = Outcome::Modified;
+                }
+                return Ok(outcome);
+            }
+        }
+    }
+
+    // load file content to a vector of strings
+    let file_content = content_from_file(file_path)?;
+
+    let mut write: bool = false;
+    let line_ending = file_content.line_ending(policy);
+    if enabled(CheckId::MixedLineEndings) && file_content.mixed_line_endings() {
+        outcome.mixed_line_endings = true;
+        let counts = format!("{} LF and {} CRLF", file_content.n_lf, file_content.n_crlf);
+        if cfg[file_ext.as_str()]["normalize_line_endings"]
+            .as_bool()
+            .unwrap_or(false)
+        {
+            outcome
+                .finding(
+                    CheckId::MixedLineEndings,
+                    format!("has mixed line endings ({counts}) -> rewrite with {line_ending:?}"),
+                )
+                .value("lf", file_content.n_lf)
+                .value("crlf", file_content.n_crlf);
+            write = true;
+        } else {
+            outcome
+                .warnings
+                .push(format!("has mixed line endings ({counts})"));
+        }
+    }
+    let final_newline = file_content.final_newline;
+    outcome.missing_final_newline = !final_newline;
+    let mut content = file_content.lines;
+    let n_read = content.len();
+
+    if let Some(garbage) = &garbage {
+        let n = garbage.count(&content);
+        if n > 0 {
+            for (i, line) in content.drain(..n).enumerate() {
+                outcome.removed(&opts.removed_caps, i, &line);
+            }
+            outcome.leading_garbage = n;
+            outcome
+                .finding(
+                    CheckId::LeadingGarbage,
+                    format!(
+                        "starts with {n} line(s) matching '{}' -> remove lines",
+                        garbage.pattern
+                    ),
+                )
+                .value("lines", n);
+            write = true;
+        }
+    }
+
+    // the column header is found by its content, firmware versions write preambles of
+    // different lengths
+    let mut header_found = false;
+    if let Some(header_match) = header_match {
+        match header_match.find(&content) {
+            Some(idx) => {
+                type_rules.n_header_lines = Some(idx + 1);
+                header_found = true;
+            }
+            None if !enabled(CheckId::MissingHeader) => (),
+            None => {
+                let n = content.len().min(header_match.max_preamble_lines);
+                let found = format!(
+                    "none of the first {n} line(s) matches header_match '{}'",
+                    header_match.pattern
+                );
+                let action = match header_match.on_missing {
+                    MissingHeaderAction::Warn => {
+                        outcome
+                            .warnings
+                            .push(format!("{found}; using n_header_lines"));
+                        None
+                    }
+                    MissingHeaderAction::Delete => Some(delete_action),
+                    MissingHeaderAction::Quarantine => Some(DeleteAction::Flag),
+                };
+                if let Some(action) = action {
+                    outcome.removed_sample(&opts.removed_caps, &content, 0);
+                    let finding = Finding::new(CheckId::MissingHeader, found).with("lines", n);
+                    return delete_file(file_path, finding, action, opts.defer_delete, outcome);
+                }
+            }
+        }
+    }
+    // counted once the header block is known, leading garbage is above it
+    outcome.lines_before = Some(type_rules.n_data_lines(content.len()));
+
+    // an old logger version appended its preamble twice, shifting the data lines
+    if let Some(action) = preamble_action {
+        let header_len = type_rules.header_len();
+        let copies = preamble_copies(&content, header_len);
+        if copies > 0 {
+            let n = copies * header_len;
+            let found =
+                format!("header lines 1-{header_len} are repeated {copies} time(s) below them");
+            match action {
+                PreambleAction::Strip => {
+                    for (i, line) in content.drain(header_len..header_len + n).enumerate() {
+                        outcome.removed(&opts.removed_caps, header_len + i, &line);
+                    }
+                    outcome.duplicate_preamble = n;
+                    outcome
+                        .finding(
+                            CheckId::DuplicatePreamble,
+                            format!("{found} -> remove {n} line(s)"),
+                        )
+                        .value("copies", copies)
+                        .value("lines", n);
+                    write = true;
+                }
+                PreambleAction::Warn => outcome.warnings.push(found),
+            }
+        }
+    }
+
+    // a file failing the header checks of its type may be of another type
+    if opts.sniff_type && type_rules.header_problem(&content, &enabled).is_some() {
+        if let Some(renamed) = misnamed(file_path, &content, &file_ext, cfg, opts, &mut outcome)? {
+            return Ok(renamed);
+        }
+    }
+
+    // checks #2 to #5
+    match clean_lines(
+        &mut content,
+        final_newline,
+        &type_rules,
+        &enabled,
+        &opts.removed_caps,
+        &mut outcome,
+    ) {
+        Verdict::Keep { modified } => write |= modified,
+        Verdict::TooShort { modified } => {
+            // the remaining checks need at least min_n_lines lines, so skip them
+            if let Some(finding) = too_many_removed(max_removed, n_read, &type_rules, &content) {
+                return delete_file(
+                    file_path,
+                    finding,
+                    removed_action,
+                    opts.defer_delete,
+                    outcome,
+                );
+            }
+            if write || modified {
+                outcome.before_write(file_path)?;
+                lines_to_file(file_path, content, line_ending)?;
+                outcome.outcome = Outcome::Modified;
+            }
+            return Ok(outcome);
+        }
+        Verdict::Delete(finding) => {
+            return delete_file(
+                file_path,
+                finding,
+                delete_action,
+                opts.defer_delete,
+                outcome,
+            )
+        }
+    }
+
+    // a file of another instrument in the directory is as good as the others, but its
+    // data is not of this station; a majority is decided by the caller, see instrument
+    if let Some(instrument_id) = instrument_id {
+        match instrument_id.read(&content) {
+            Ok(id) => {
+                outcome.instrument_id = Some(id.clone());
+                let expected = instrument_id.expected_id(opts);
+                outcome.expected_instrument_id = expected.map(str::to_string);
+                if let Some(finding) =
+                    expected.and_then(|expected| instrument::mismatch(&id, expected))
+                {
+                    let action = match instrument_id.on_mismatch {
+                        MissingHeaderAction::Warn => {
+                            outcome.warnings.push(finding.message.clone());
+                            None
+                        }
+                        MissingHeaderAction::Delete => Some(delete_action),
+                        MissingHeaderAction::Quarantine => Some(DeleteAction::Flag),
+                    };
+                    if let Some(action) = action {
+                        outcome.removed_sample(&opts.removed_caps, &content, 0);
+                        return delete_file(file_path, finding, action, opts.defer_delete, outcome);
+                    }
+                }
+            }
+            Err(err) => outcome.warnings.push(format!(
+                "instrument_id: {err}; not checking the instrument ID"
+            )),
+        }
+    }
+
+    // a file with far fewer samples than the logger announced lost data, even if it is
+    // long enough otherwise
+    if let Some(expected_count) = expected_count {
+        match expected_count.count(&content) {
+            Ok(expected) => {
+                let n_data = content.len().saturating_sub(type_rules.header_len());
+                if let Some(finding) = expected_count.short(n_data, expected) {
+                    let action = match expected_count.on_short {
+                        MissingHeaderAction::Warn => {
+                            outcome.warnings.push(finding.message.clone());
+                            None
+                        }
+                        MissingHeaderAction::Delete => Some(delete_action),
+                        MissingHeaderAction::Quarantine => Some(DeleteAction::Flag),
+                    };
+                    if let Some(action) = action {
+                        outcome.removed_sample(&opts.removed_caps, &content, 0);
+                        return delete_file(file_path, finding, action, opts.defer_delete, outcome);
+                    }
+                }
+            }
+            Err(err) => outcome.warnings.push(format!(
+                "expected_count_from_header: {err}; not checking the count"
+            )),
+        }
+    }
+
+    // every line of an event log should look like an event
+    if let Some(pattern) = line_pattern {
+        if line_pattern_mismatch(
+            &mut content,
+            pattern,
+            line_pattern_action,
+            &opts.removed_caps,
+            &mut outcome,
+        ) {
+            write = true;
+            if let (true, Some(finding)) = (
+                enabled(CheckId::TooFewLines),
+                type_rules.too_few_lines(&content),
+            ) {
+                outcome.removed_sample(&opts.removed_caps, &content, 0);
+                return delete_file(
+                    file_path,
+                    finding,
+                    delete_action,
+                    opts.defer_delete,
+                    outcome,
+                );
+            }
+        }
+    }
+
+    // the header lines are exempt from the timestamp checks
+    let header_len = type_rules.header_len();
+    // indices of the lines collapsed, to number the lines after them as in the file
+    let mut collapsed = Vec::new();
+    if let (true, Some((rules, column))) = (enabled(CheckId::DuplicateTimestamp), &timestamps) {
+        collapsed = duplicate_timestamp(
+            cfg_ext,
+            &mut content,
+            header_len,
+            (rules, *column),
+            &opts.removed_caps,
+            &mut outcome,
+        );
+        write |= !collapsed.is_empty();
+    }
+
+    // a split writes the parts right away, so check what was removed so far first
+    if let Some(finding) = too_many_removed(max_removed, n_read, &type_rules, &content) {
+        return delete_file(
+            file_path,
+            finding,
+            removed_action,
+            opts.defer_delete,
+            outcome,
+        );
+    }
+
+    // clocks re-synced while logging, the data before the jump has the wrong time
+    if let (true, Some((rules, column))) = (enabled(CheckId::TimeReset), &timestamps) {
+        match time_reset(
+            file_path,
+            cfg_ext,
+            &mut content,
+            (header_len, &collapsed),
+            (rules, *column),
+            line_ending,
+            &opts.removed_caps,
+            &mut outcome,
+        )? {
+            Some(TimeResetAction::Split) => {
+                outcome.outcome = Outcome::Modified;
+                return Ok(outcome);
+            }
+            Some(_) => write = true,
+            None => (),
+        }
+        if let Some(finding) = too_many_removed(max_removed, n_read, &type_rules, &content) {
+            return delete_file(
+                file_path,
+                finding,
+                removed_action,
+                opts.defer_delete,
+                outcome,
+            );
+        }
+    }
+
+    // time covered by the data, for the coverage report
+    if let (true, Some((rules, column))) = (opts.coverage, timestamp_rules(cfg_ext)) {
+        outcome.time_span = time_span(&content, column, &rules);
+        outcome.no_timestamps = outcome.time_span.is_none();
+    }
+
+    // trim from the column header down, the preamble is left as it is
+    let mut trimmed = None;
+    if trim {
+        let (mut n_lines, mut saved) = (0, 0);
+        for (i, line) in content.iter_mut().enumerate().skip(header_len - 1) {
+            let delimiter = if i == header_len - 1 {
+                &columns.header_delimiter
+            } else {
+                &columns.delimiter
+            };
+            if let Some(new) = trim_fields(line, delimiter) {
+                n_lines += 1;
+                saved += line.len() - new.len();
+                *line = new;
+            }
+        }
+        if saved > 0 {
+            trimmed = Some(
+                Finding::new(
+                    CheckId::TrimFields,
+                    format!("has spaces around fields in {n_lines} line(s) -> trim them, saving {saved} bytes"),
+                )
+                .with("lines", n_lines)
+                .with("bytes_saved", saved),
+            );
+            write |= trim_force;
+        }
+    }
+
+    // convert from the column header down too, the quoted fields are left to the tools
+    let mut converted = None;
+    if let Some(output) = &output {
+        let mut n_lines = 0;
+        for (i, line) in content.iter_mut().enumerate().skip(header_len - 1) {
+            let delimiter = if i == header_len - 1 {
+                &columns.header_delimiter
+            } else {
+                &columns.delimiter
+            };
+            if let Some(new) = output.convert(line, delimiter) {
+                n_lines += 1;
+                *line = new;
+            }
+        }
+        if n_lines > 0 {
+            converted = Some(
+                Finding::new(
+                    CheckId::OutputDelimiter,
+                    format!(
+                        "has fields not delimited by '{}' in {n_lines} line(s) -> convert them",
+                        output.delimiter.escape_default()
+                    ),
+                )
+                .with("lines", n_lines),
+            );
+            write |= output.force;
+        }
+    }
+
+    // all checked, write updated data back to file
+    outcome.lines_after = Some(type_rules.n_data_lines(content.len()));
+    if file_ext == "OSC"
+        && enabled(CheckId::OscDatetimePrefix)
+        && osc_prefix(
+            file_path,
+            &cfg[file_ext.as_str()],
+            &mut content,
+            line_ending,
+            header_found.then(|| type_rules.header_idx()),
+            time_pattern,
+            output.as_ref(),
+            &enabled,
+            &opts.removed_caps,
+            &mut outcome,
+        )?
+    {
+        // special case: oscar / chemiluminescence detector files.
+        outcome.findings.extend(trimmed);
+        outcome.findings.extend(converted);
+        outcome.outcome = Outcome::Modified;
+        return Ok(outcome);
+    }
+    if write {
+        outcome.findings.extend(trimmed);
+        outcome.findings.extend(converted);
+        outcome.before_write(file_path)?;
+        lines_to_file(file_path, content, line_ending)?;
+        outcome.outcome = Outcome::Modified;
+    }
+
+    Ok(outcome)
+}
+
+/// line_pattern_mismatch looks for the lines of a freeform file that do not match
+/// pattern and applies `on_line_pattern_mismatch`. returns true if lines were removed.
+#[cfg(feature = "yaml-config")]
+fn line_pattern_mismatch(
+    content: &mut Vec<String>,
+    pattern: &Regex,
+    action: LinePatternAction,
+    caps: &RemovedCaps,
+    outcome: &mut FileOutcome,
+) -> bool {
+    let mismatched: Vec<usize> = (0..content.len())
+        .filter(|i| !pattern.is_match(&content[*i]))
+        .collect();
+    let Some(first) = mismatched.first() else {
+        return false;
+    };
+    let n = mismatched.len();
+    let found = format!(
+        "{n} line(s) do not match line_pattern '{pattern}', the first is line {}",
+        first + 1 + outcome.lines_dropped()
+    );
+    match action {
+        LinePatternAction::Warn => {
+            outcome.warnings.push(found);
+            false
+        }
+        LinePatternAction::Remove => {
+            for i in mismatched.iter() {
+                outcome.removed(caps, *i, &content[*i]);
+            }
+            let mut i = 0;
+            content.retain(|_| {
+                i += 1;
+                mismatched.binary_search(&(i - 1)).is_err()
+            });
+            outcome
+                .finding(
+                    CheckId::LinePatternMismatch,
+                    format!("{found} -> remove lines"),
+                )
+                .value("lines", n);
+            true
+        }
+    }
+}
+
+/// max_removed_fraction reads `max_removed_fraction` of a file type, the share of its
+/// data lines the checks may remove before the file is escalated. None if not set.
+#[cfg(feature = "yaml-config")]
+fn max_removed_fraction(cfg_ext: &Yaml) -> Result<Option<f64>, String> {
+    let value = &cfg_ext["max_removed_fraction"];
+    if value.is_badvalue() {
+        return Ok(None);
+    }
+    match value.as_f64().or_else(|| value.as_i64().map(|n| n as f64)) {
+        Some(fraction) if (0.0..=1.0).contains(&fraction) => Ok(Some(fraction)),
+        _ => Err(format!(
+            "invalid max_removed_fraction {value:?}, expected a number from 0 to 1"
+        )),
+    }
+}
+
+/// too_many_removed returns the finding to escalate a file with if more than max of
+/// its data lines were removed, by whatever check. n_read lines were read from the
+/// file, content holds those left.
+#[cfg(feature = "yaml-config")]
+fn too_many_removed(
+    max: Option<f64>,
+    n_read: usize,
+    type_rules: &FileTypeRules,
+    content: &[String],
+) -> Option<Finding> {
+    let max = max?;
+    let removed = n_read.saturating_sub(content.len());
+    let data_lines = n_read.saturating_sub(type_rules.header_len()).max(1);
+    let fraction = removed as f64 / data_lines as f64;
+    (fraction > max).then(|| {
+        Finding::new(
+            CheckId::RemovedFraction,
+            format!(
+                "would lose {removed} of {data_lines} data line(s) ({:.0}%), more than max_removed_fraction {max}",
+                fraction * 100.0
+            ),
+        )
+        .with("removed", removed)
+        .with("data_lines", data_lines)
+    })
+}
+
+/// timestamp_rules returns the rules to parse the timestamps of the data lines of a
+/// file type and the tab-separated column they are in, if `timestamp_format` is set.
+#[cfg(feature = "yaml-config")]
+fn timestamp_rules(cfg_ext: &Yaml) -> Option<(DatetimeRules, usize)> {
+    let format = cfg_ext["timestamp_format"].as_str()?;
+    let rules = DatetimeRules {
+        format: format.to_string(),
+        year_pivot: cfg_ext["two_digit_year_pivot"].as_i64().map(|y| y as i32),
+        ..Default::default()
+    };
+    let column = cfg_ext["timestamp_column"].as_i64().unwrap_or(0) as usize;
+    Some((rules, column))
+}
+
+/// duplicate_timestamp looks for runs of data lines with the same timestamp below the
+/// first header_len lines of content and applies `on_duplicate_timestamp`. returns the
+/// indices of the lines removed, in ascending order.
+#[cfg(feature = "yaml-config")]
+fn duplicate_timestamp(
+    cfg_ext: &Yaml,
+    content: &mut Vec<String>,
+    header_len: usize,
+    (rules, column): (&DatetimeRules, usize),
+    caps: &RemovedCaps,
+    outcome: &mut FileOutcome,
+) -> Vec<usize> {
+    if content.len() <= header_len {
+        return Vec::new();
+    }
+    let runs = duplicate_timestamps(&content[header_len..], column, rules);
+    if runs.is_empty() {
+        return Vec::new();
+    }
+    let action = match cfg_ext["on_duplicate_timestamp"].as_str() {
+        Some(s) => s.parse::<DuplicateAction>().unwrap_or_else(|err| {
+            outcome.warnings.push(format!("{err}; defaulting to warn"));
+            DuplicateAction::Warn
+        }),
+        None => DuplicateAction::Warn,
+    };
+    let n_lines: usize = runs.iter().map(|run| run.len()).sum();
+    let first = runs[0].start + header_len + 1 + outcome.lines_dropped();
+    let found = format!(
+        "{n_lines} lines in {} run(s) share a timestamp, first at line {first}",
+        runs.len()
+    );
+    let drop: Vec<usize> = runs
+        .into_iter()
+        .flat_map(|run| match action {
+            DuplicateAction::KeepFirst => run.start + 1..run.end,
+            DuplicateAction::KeepLast => run.start..run.end - 1,
+            DuplicateAction::DropBoth => run,
+            DuplicateAction::Warn => 0..0,
+        })
+        .map(|idx| idx + header_len)
+        .collect();
+    if drop.is_empty() {
+        outcome.warnings.push(found);
+        return drop;
+    }
+    outcome
+        .finding(
+            CheckId::DuplicateTimestamp,
+            format!("{found} -> remove {} line(s)", drop.len()),
+        )
+        .value("duplicates", n_lines)
+        .value("removed", drop.len());
+    outcome.collapsed_lines = drop.len();
+    for &idx in &drop {
+        outcome.removed(caps, idx, &content[idx]);
+    }
+    let mut remove = drop.iter().peekable();
+    let mut idx = 0;
+    content.retain(|_| {
+        let keep = remove.next_if_eq(&&idx).is_none();
+        idx += 1;
+        keep
+    });
+    drop
+}
+
+/// original_idx returns the index a line at idx had before the lines at the ascending
+/// indices removed were taken out.
+#[cfg(feature = "yaml-config")]
+fn original_idx(removed: &[usize], idx: usize) -> usize {
+    removed
+        .iter()
+        .fold(idx, |idx, &r| if r <= idx { idx + 1 } else { idx })
+}
+
+/// time_reset looks for clock resets in the data of a file below its first header_len
+/// lines and applies `on_time_reset`. collapsed are the indices of lines removed from
+/// content before, to report the line numbers of the file. returns the action if the
+/// content was changed (drop_before) or the file was split and written already (split).
+#[cfg(feature = "yaml-config")]
+#[allow(clippy::too_many_arguments)]
+fn time_reset(
+    file_path: &Path,
+    cfg_ext: &Yaml,
+    content: &mut Vec<String>,
+    (header_len, collapsed): (usize, &[usize]),
+    (rules, column): (&DatetimeRules, usize),
+    line_ending: LineEnding,
+    caps: &RemovedCaps,
+    outcome: &mut FileOutcome,
+) -> io::Result<Option<TimeResetAction>> {
+    if content.len() <= header_len {
+        return Ok(None);
+    }
+    let threshold_s =
+        yaml_f64(&cfg_ext["time_reset_threshold_s"]).unwrap_or(DEFAULT_TIME_RESET_THRESHOLD_S);
+    let resets: Vec<TimeReset> = time_resets(&content[header_len..], column, rules, threshold_s)
+        .into_iter()
+        .map(|reset| TimeReset {
+            idx: reset.idx + header_len,
+            ..reset
+        })
+        .collect();
+    if resets.is_empty() {
+        return Ok(None);
+    }
+    let action = match cfg_ext["on_time_reset"].as_str() {
+        Some(s) => s.parse::<TimeResetAction>().unwrap_or_else(|err| {
+            outcome.warnings.push(format!("{err}; defaulting to warn"));
+            TimeResetAction::Warn
+        }),
+        None => TimeResetAction::Warn,
+    };
+    let jumps: Vec<String> = resets
+        .iter()
+        .map(|reset| {
+            format!(
+                "{} -> {} at line {}",
+                reset.before,
+                reset.after,
+                original_idx(collapsed, reset.idx) + 1 + outcome.lines_dropped()
+            )
+        })
+        .collect();
+    let jumps = jumps.join(", ");
+
+    match action {
+        TimeResetAction::Warn => {
+            outcome.warnings.push(format!(
+                "timestamps jump back ({jumps}), needs manual attention"
+            ));
+            outcome.needs_attention = true;
+            Ok(None)
+        }
+        TimeResetAction::Split => {
+            let paths: Vec<PathBuf> = (2..=resets.len() + 1)
+                .map(|n| split_path(file_path, n))
+                .collect();
+            if let Some(path) = paths.iter().find(|path| path.exists()) {
+                outcome.warnings.push(format!(
+                    "timestamps jump back ({jumps}), but {path:?} already exists -> skip splitting"
+                ));
+                outcome.needs_attention = true;
+                return Ok(None);
+            }
+            let names: Vec<String> = paths
+                .iter()
+                .map(|path| {
+                    path.file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect();
+            outcome
+                .finding(
+                    CheckId::TimeReset,
+                    format!(
+                        "timestamps jump back ({jumps}) -> write the data after each jump to {}",
+                        names.join(", ")
+                    ),
+                )
+                .value("resets", resets.len());
+            // each piece gets a copy of the header, the original keeps the first
+            let (header, data) = content.split_at(header_len);
+            let mut starts: Vec<usize> = vec![0];
+            starts.extend(resets.iter().map(|reset| reset.idx - header_len));
+            let mut pieces = starts.iter().enumerate().map(|(k, &start)| {
+                let end = starts.get(k + 1).copied().unwrap_or(data.len());
+                [header, &data[start..end]].concat()
+            });
+            // write the new files first, the original is only truncated if that worked
+            outcome.before_write(file_path)?;
+            let first = pieces.next().unwrap();
+            for (path, piece) in paths.iter().zip(pieces) {
+                lines_to_new_file(path, piece, line_ending)?;
+            }
+            outcome.lines_after = Some(first.len() - header_len);
+            lines_to_file(file_path, first, line_ending)?;
+            Ok(Some(TimeResetAction::Split))
+        }
+        TimeResetAction::DropBefore => {
+            let last = resets.last().unwrap().idx;
+            outcome
+                .finding(
+                    CheckId::TimeReset,
+                    format!(
+                        "timestamps jump back ({jumps}) -> remove {} line(s) before the last jump",
+                        last - header_len
+                    ),
+                )
+                .value("resets", resets.len())
+                .value("removed", last - header_len);
+            for (i, line) in content.drain(header_len..last).enumerate() {
+                outcome.removed(caps, original_idx(collapsed, header_len + i), &line);
+            }
+            Ok(Some(TimeResetAction::DropBefore))
+        }
+    }
+}
+
+/// misnamed looks for another type that the content of a file resembles, once it
+/// failed the header checks of file_ext, see sniff_type. the detected type is reported;
+/// with fix_misnamed the file is renamed to its extension and cleaned with its rules,
+/// and the outcome of that is returned.
+#[cfg(feature = "yaml-config")]
+fn misnamed(
+    file_path: &Path,
+    content: &[String],
+    file_ext: &str,
+    cfg: &Yaml,
+    opts: &CleanOptions,
+    outcome: &mut FileOutcome,
+) -> io::Result<Option<FileOutcome>> {
+    let Some(detected) = sniff_type(content, file_ext, cfg, &opts.reference_headers) else {
+        return Ok(None);
+    };
+    outcome.detected_type = Some(detected.clone());
+    let target = file_path.with_extension(&detected);
+    if !opts.fix_misnamed {
+        outcome
+            .warnings
+            .push(format!("content looks like {detected}"));
+        return Ok(None);
+    }
+    if target.exists() {
+        outcome.warnings.push(format!(
+            "content looks like {detected}, but {target:?} exists -> not renamed"
+        ));
+        return Ok(None);
+    }
+    outcome.before_write(file_path)?;
+    fs::rename(file_path, &target)?;
+    outcome.warnings.push(format!(
+        "content looks like {detected} -> renamed to {target:?}"
+    ));
+    // the renamed file must not be renamed again, nor backed up as the original was
+    let opts = CleanOptions {
+        sniff_type: false,
+        backup: None,
+        ..opts.clone()
+    };
+    let mut renamed = check_and_fix(&target, cfg, &opts)?;
+    outcome.warnings.append(&mut renamed.warnings);
+    renamed.warnings = std::mem::take(&mut outcome.warnings);
+    renamed.path = file_path.to_path_buf();
+    renamed.extension = outcome.extension.take();
+    renamed.disk_extension = outcome.disk_extension.take();
+    renamed.detected_type = Some(detected);
+    renamed.renamed_to = Some(target);
+    renamed.backup = outcome.backup.take();
+    if renamed.outcome == Outcome::Unchanged {
+        renamed.outcome = Outcome::Modified;
+    }
+    Ok(Some(renamed))
+}
+
+/// delete_file deletes a file because of a finding, whose message is the reason, or
+/// flags it if action says so. with defer, the file is only marked as deleted, the
+/// caller removes it.
+#[cfg(feature = "yaml-config")]
+fn delete_file(
+    file_path: &Path,
+    mut finding: Finding,
+    action: DeleteAction,
+    defer: bool,
+    mut outcome: FileOutcome,
+) -> io::Result<FileOutcome> {
+    // deferred deletes are checked again by the caller, see FileOutcome::still_as_read
+    outcome.verify_stamp(file_path)?;
+    let check = finding.check;
+    match action {
+        DeleteAction::Delete => {
+            outcome.before_write(file_path)?;
+            finding.message.push_str(" -> delete file");
+            outcome.findings.push(finding);
+            if !defer {
+                fs::remove_file(file_path)?;
+            }
+            outcome.outcome = Outcome::Deleted(check);
+        }
+        DeleteAction::Flag => {
+            finding.message.push_str(" -> flag file");
+            outcome.findings.push(finding);
+            // the file stays untouched, so nothing was removed
+            outcome.removed_lines.clear();
+            outcome.removed_lines_truncated = false;
+            write_flag(file_path, &outcome.findings)?;
+            outcome.outcome = Outcome::Flagged(check);
+        }
+    }
+    Ok(outcome)
+}
+
+/// DatetimeMatcher finds the datetime that starts an acquisition block of an OSC file,
+/// like `01.06.23 12:00:00.00`. With the regex-checks feature, RE_DT is the matcher of
+/// clean_file; without it, callers provide their own, e.g. a fn.
+pub trait DatetimeMatcher {
+    /// find returns the first datetime in s.
+    fn find<'a>(&self, s: &'a str) -> Option<&'a str>;
+
+    /// is_datetime_line tells if line holds only a datetime.
+    fn is_datetime_line(&self, line: &str) -> bool {
+        self.find(line).is_some_and(|dt| dt == line.trim())
+    }
+}
+
+impl<F> DatetimeMatcher for F
+where
+    F: for<'a> Fn(&'a str) -> Option<&'a str>,
+{
+    fn find<'a>(&self, s: &'a str) -> Option<&'a str> {
+        self(s)
+    }
+}
+
+#[cfg(feature = "regex-checks")]
+impl DatetimeMatcher for Regex {
+    fn find<'a>(&self, s: &'a str) -> Option<&'a str> {
+        Regex::find(self, s).map(|m| m.as_str())
+    }
+}
+
+#[cfg(feature = "regex-checks")]
+lazy_static! { // use lazy_static to avoid regex compilation for each file
+    pub static ref RE_DT: Regex =
+        Regex::new(r"\d{2}\.\d{2}\.\d{2} \d{2}:\d{2}:\d{2}\.\d{2}").unwrap();
+}
+
+/// osc_prefix prefixes the data lines of an OSC file with the datetime from the first
+/// line of its acquisition block and writes the file. header_idx is the index of the
+/// column header if header_match found it, see osc_rules. output is the delimiter the
+/// lines were converted to, if any. returns false if the file was not touched, e.g.
+/// because it was prefixed before or looks broken.
+#[cfg(feature = "yaml-config")]
+#[allow(clippy::too_many_arguments)]
+fn osc_prefix(
+    file_path: &Path,
+    cfg_ext: &Yaml,
+    content: &mut Vec<String>,
+    line_ending: LineEnding,
+    header_idx: Option<usize>,
+    time_pattern: Option<&Regex>,
+    output: Option<&OutputDelimiter>,
+    enabled: &dyn Fn(CheckId) -> bool,
+    caps: &RemovedCaps,
+    outcome: &mut FileOutcome,
+) -> io::Result<bool> {
+    let Some((header_idx, layout, rules)) =
+        osc_rules(cfg_ext, content, header_idx, time_pattern, output, outcome)
+    else {
+        return Ok(false);
+    };
+    let mtime = fs::metadata(file_path)?
+        .modified()
+        .ok()
+        .map(|t| DateTime::<Local>::from(t).naive_local());
+    // an invalid column format was reported by check_and_fix
+    let columns = ColumnFormat::from_cfg(cfg_ext).unwrap_or_default();
+    let columns = match output {
+        Some(output) => output.columns(&columns),
+        None => columns,
+    };
+    let starts = osc_block_starts(content, header_idx, &columns, &*RE_DT);
+
+    if starts.len() == 1 {
+        let Some(prefixes) =
+            osc_block_prefixes(content, 0, header_idx, &rules, mtime, &columns, outcome)
+        else {
+            return Ok(false);
+        };
+        outcome.finding(
+            CheckId::OscDatetimePrefix,
+            prefix_message(&prefixes, rules.sample_interval.is_some()),
+        );
+        // update header line
+        content[header_idx] = layout.header().apply(&content[header_idx], "DateTime");
+        outcome.lines_after = Some(content.len() - header_idx - 1);
+        outcome.osc_prefix = Some(OscPrefix::applied(&content[0], prefixes.len()));
+        outcome.before_write(file_path)?;
+        write_osc(
+            file_path,
+            std::mem::take(content),
+            header_idx + 1,
+            &prefixes,
+            &layout,
+            line_ending,
+        )?;
+        return Ok(true);
+    }
+
+    // the software was restarted and appended new blocks to the file
+    let action = match cfg_ext["on_multiple_blocks"].as_str() {
+        Some(s) => s.parse::<BlocksAction>().unwrap_or_else(|err| {
+            outcome.warnings.push(format!("{err}; defaulting to warn"));
+            BlocksAction::Warn
+        }),
+        None => BlocksAction::Warn,
+    };
+    if action == BlocksAction::Warn {
+        outcome.skip_prefix(format!("has {} acquisition blocks", starts.len()), true);
+        return Ok(false);
+    }
+    let mut blocks: Vec<Vec<String>> = Vec::new();
+    let mut summary: Option<OscPrefix> = None;
+    for (k, &start) in starts.iter().enumerate() {
+        let end = starts.get(k + 1).copied().unwrap_or(content.len());
+        let mut block = content[start..end].to_vec();
+        if !check_block(
+            &mut block, start, header_idx, &columns, enabled, caps, outcome,
+        ) {
+            continue;
+        }
+        let Some(prefixes) =
+            osc_block_prefixes(&block, start, header_idx, &rules, mtime, &columns, outcome)
+        else {
+            return Ok(false);
+        };
+        outcome.finding(
+            CheckId::OscDatetimePrefix,
+            format!(
+                "block at line {start}: {}",
+                prefix_message(&prefixes, rules.sample_interval.is_some())
+            ),
+        );
+        block[header_idx] = layout.header().apply(&block[header_idx], "DateTime");
+        for (line, prefix) in block[header_idx + 1..].iter_mut().zip(prefixes.iter()) {
+            *line = layout.apply(line, prefix);
+        }
+        summary
+            .get_or_insert_with(|| OscPrefix::applied(&block[0], 0))
+            .lines_prefixed += prefixes.len();
+        blocks.push(block);
+    }
+    if blocks.is_empty() {
+        outcome.skip_prefix("no acquisition block with data".to_string(), false);
+        return Ok(false);
+    }
+
+    match action {
+        BlocksAction::Split => {
+            let paths: Vec<PathBuf> = (2..=blocks.len())
+                .map(|n| split_path(file_path, n))
+                .collect();
+            if let Some(path) = paths.iter().find(|path| path.exists()) {
+                outcome.warnings.push(format!(
+                    "{path:?} already exists -> skip splitting acquisition blocks"
+                ));
+                outcome.osc_prefix = Some(OscPrefix {
+                    skipped: Some(format!("{path:?} already exists")),
+                    ..Default::default()
+                });
+                return Ok(false);
+            }
+            outcome.finding(
+                CheckId::OscDatetimePrefix,
+                format!(
+                    "split {} acquisition blocks -> write blocks 2.. to {paths:?}",
+                    blocks.len()
+                ),
+            );
+            // write the new files first, the original is only truncated if that worked
+            outcome.before_write(file_path)?;
+            let mut blocks = blocks.into_iter();
+            let first = blocks.next().unwrap();
+            for (path, block) in paths.iter().zip(blocks) {
+                lines_to_new_file(path, block, line_ending)?;
+            }
+            outcome.lines_after = Some(first.len() - header_idx - 1);
+            lines_to_file(file_path, first, line_ending)?;
+        }
+        _ => {
+            // the column headers of the later blocks stay in the file, they are no data
+            let n_data = blocks
+                .iter()
+                .map(|block| block.len() - header_idx - 1)
+                .sum();
+            outcome.lines_after = Some(n_data);
+            outcome.before_write(file_path)?;
+            lines_to_file(file_path, blocks.concat(), line_ending)?;
+        }
+    }
+    outcome.osc_prefix = summary;
+    Ok(true)
+}
+
+/// osc_rules reads the prefix layout and datetime rules of an OSC file and makes sure the
+/// file was not prefixed before and has no time column of its own. head must hold the
+/// lines up to the first data line. header_idx is the index of the column header if
+/// it is known, else it comes from the cfg. time_pattern matches the first field of a
+/// time column, RE_DT if None. with output, the datetime column is added with the
+/// output delimiter. returns the index of the column header line, the layout
+/// and the rules, or None if the file is not to be prefixed.
+#[cfg(feature = "yaml-config")]
+fn osc_rules(
+    cfg_ext: &Yaml,
+    head: &[String],
+    header_idx: Option<usize>,
+    time_pattern: Option<&Regex>,
+    output: Option<&OutputDelimiter>,
+    outcome: &mut FileOutcome,
+) -> Option<(usize, PrefixLayout, DatetimeRules)> {
+    // index of the column header line, the lines above are preamble
+    let header_idx = header_idx.unwrap_or_else(|| cfg_header_idx(cfg_ext).unwrap_or(4));
+    if head.len() <= header_idx || !RE_DT.is_match(&head[0]) {
+        return None;
+    }
+    let layout = PrefixLayout::from_cfg(cfg_ext).unwrap_or_else(|err| {
+        outcome
+            .warnings
+            .push(format!("{err}; using default layout"));
+        PrefixLayout::default()
+    });
+    let layout = match output {
+        Some(output) => output.layout(layout),
+        None => layout,
+    };
+    let rules = match DatetimeRules::from_cfg(cfg_ext) {
+        Ok(rules) => rules,
+        Err(err) => {
+            outcome.skip_prefix(err, false);
+            return None;
+        }
+    };
+    // make sure the file has not been updated before: the header already has the
+    // DateTime column, with either layout
+    let positions =
+        [PrefixPosition::Prepend, PrefixPosition::Append].map(|position| PrefixLayout {
+            position,
+            ..layout.clone()
+        });
+    if positions
+        .iter()
+        .any(|layout| layout.header().has_column(&head[header_idx], "DateTime"))
+    {
+        return None;
+    }
+    // prefixing a header with a DateTime column elsewhere would name it twice
+    let ignore_case = cfg_ext["header_fields_ignore_case"].as_bool() == Some(true);
+    let datetime_at = head[header_idx]
+        .split(layout.header_separator.as_str())
+        .map(str::trim)
+        .position(|field| {
+            field == "DateTime" || (ignore_case && field.eq_ignore_ascii_case("DateTime"))
+        });
+    if let Some(i) = datetime_at {
+        outcome.skip_prefix(
+            format!("already has a DateTime column at field {}", i + 1),
+            false,
+        );
+        return None;
+    }
+    // files of newer acquisition programs have a time column of their own, under any
+    // name: the first line of data starts or ends with a datetime in the raw or output
+    // format, or starts with one matching the time pattern
+    let data = head
+        .get(header_idx + 1)
+        .map(String::as_str)
+        .unwrap_or_default();
+    let time_column = positions.iter().find(|layout| {
+        layout.column_value(data).is_some_and(|value| {
+            rules.is_prefix(value)
+                || (layout.position == PrefixPosition::Prepend
+                    && time_pattern.map_or_else(
+                        || RE_DT.is_datetime_line(value),
+                        |pattern| pattern.is_match(value),
+                    ))
+        })
+    });
+    if let Some(layout) = time_column {
+        let name = layout
+            .header()
+            .column_value(&head[header_idx])
+            .unwrap_or_default();
+        outcome.skip_prefix(format!("already has time column '{name}'"), false);
+        return None;
+    }
+    Some((header_idx, layout, rules))
+}
+
+/// osc_prefix_streamed prefixes a single block OSC file like osc_prefix, but streams
+/// the file instead of loading it. head holds the first lines of the file, n_lines is
+/// its number of lines; the caller makes sure the file passes the checks unchanged.
+#[cfg(feature = "yaml-config")]
+fn osc_prefix_streamed(
+    file_path: &Path,
+    cfg_ext: &Yaml,
+    head: &[String],
+    n_lines: usize,
+    line_ending: LineEnding,
+    time_pattern: Option<&Regex>,
+    outcome: &mut FileOutcome,
+) -> io::Result<bool> {
+    let Some((header_idx, layout, rules)) =
+        osc_rules(cfg_ext, head, None, time_pattern, None, outcome)
+    else {
+        return Ok(false);
+    };
+    let mtime = fs::metadata(file_path)?
+        .modified()
+        .ok()
+        .map(|t| DateTime::<Local>::from(t).naive_local());
+    // an invalid column format was reported by check_and_fix
+    let columns = ColumnFormat::from_cfg(cfg_ext).unwrap_or_default();
+    let Some(prefixer) = osc_block_prefixer(head, 0, header_idx, &rules, mtime, &columns, outcome)
+    else {
+        return Ok(false);
+    };
+    let n_data = n_lines - header_idx - 1;
+    let bounds: Result<Vec<String>, String> = [0, n_data.saturating_sub(1)]
+        .into_iter()
+        .take(n_data.min(2))
+        .map(|i| prefixer.get(i))
+        .collect();
+    let bounds = match bounds {
+        Ok(bounds) => bounds,
+        Err(err) => {
+            outcome.skip_prefix(err, false);
+            return Ok(false);
+        }
+    };
+    outcome.finding(
+        CheckId::OscDatetimePrefix,
+        prefix_message(&bounds, rules.sample_interval.is_some()),
+    );
+    outcome.osc_prefix = Some(OscPrefix::applied(&head[0], n_data));
+    outcome.before_write(file_path)?;
+    stream_osc(file_path, header_idx, &prefixer, &layout, line_ending)?;
+    Ok(true)
+}
+
+/// osc_block_starts returns the index of the first line of each acquisition block in
+/// content: a line holding only a datetime, followed by the preamble and a column header.
+/// the first block always starts at line 0. datetime lines are recognized by matcher;
+/// one in the preamble or header of the block before does not start a block.
+pub fn osc_block_starts(
+    content: &[String],
+    header_idx: usize,
+    columns: &ColumnFormat,
+    matcher: &dyn DatetimeMatcher,
+) -> Vec<usize> {
+    let mut starts = vec![0];
+    for (i, line) in content.iter().enumerate().skip(1) {
+        if starts.last().is_some_and(|start| i <= start + header_idx) {
+            continue;
+        }
+        if matcher.is_datetime_line(line)
+            && content.get(i + header_idx).is_some_and(|header| {
+                looks_like_header(header, &columns.header_delimiter, columns.min_header_tokens)
+            })
+        {
+            starts.push(i);
+        }
+    }
+    starts
+}
+
+/// check_block runs the length and column checks on one acquisition block of an OSC
+/// file. the last data line of a block is often cut off by the restart, it is removed
+/// if its number of fields does not match the header. returns false if the block has
+/// no usable data and should be dropped. start is the index of the block in the file.
+#[cfg(feature = "yaml-config")]
+fn check_block(
+    block: &mut Vec<String>,
+    start: usize,
+    header_idx: usize,
+    columns: &ColumnFormat,
+    enabled: &dyn Fn(CheckId) -> bool,
+    caps: &RemovedCaps,
+    outcome: &mut FileOutcome,
+) -> bool {
+    // the column header stays, even if it is blank
+    while block.len() > header_idx + 1 && block.last().is_some_and(|line| line.trim().is_empty()) {
+        outcome.remove_last(caps, block, start);
+    }
+    let n_col_header = columns.n_header_fields(&block[header_idx]);
+    if enabled(CheckId::FirstDataLineFieldMismatch)
+        && block
+            .get(header_idx + 1)
+            .is_some_and(|line| columns.n_fields(line) != n_col_header)
+    {
+        outcome.finding(
+            CheckId::FirstDataLineFieldMismatch,
+            format!("block at line {start} has invalid number of fields in first line of data -> remove block"),
+        );
+        outcome.removed_sample(caps, block, start);
+        return false;
+    }
+    if enabled(CheckId::LastLineFieldMismatch) && block.len() > header_idx + 1 {
+        let n_col_data = columns.n_fields(&block[block.len() - 1]);
+        if n_col_data != n_col_header {
+            outcome
+                .finding(
+                    CheckId::LastLineFieldMismatch,
+                    format!("block at line {start}: {n_col_data} field(s) in last line of data but header has {n_col_header} -> remove line"),
+                )
+                .value("fields", n_col_data)
+                .value("header_fields", n_col_header);
+            outcome.remove_last(caps, block, start);
+        }
+    }
+    if enabled(CheckId::TooFewLines) && block.len() <= header_idx + 1 {
+        outcome.finding(
+            CheckId::TooFewLines,
+            format!("block at line {start} has no data -> remove block"),
+        );
+        outcome.removed_sample(caps, block, start);
+        return false;
+    }
+    true
+}
+
+/// osc_block_prefixes validates the datetime and the structure of an acquisition block of
+/// an OSC file and returns the prefix of each of its data lines. problems are added to
+/// the warnings of outcome. start is the index of the block in the file.
+#[cfg(feature = "yaml-config")]
+fn osc_block_prefixes(
+    block: &[String],
+    start: usize,
+    header_idx: usize,
+    rules: &DatetimeRules,
+    mtime: Option<NaiveDateTime>,
+    columns: &ColumnFormat,
+    outcome: &mut FileOutcome,
+) -> Option<Vec<String>> {
+    let prefixer = osc_block_prefixer(block, start, header_idx, rules, mtime, columns, outcome)?;
+    let n_data = block.len() - header_idx - 1;
+    match (0..n_data).map(|i| prefixer.get(i)).collect() {
+        Ok(prefixes) => Some(prefixes),
+        Err(err) => {
+            outcome.skip_prefix(err, false);
+            None
+        }
+    }
+}
+
+/// osc_block_prefixer checks the datetime and column header of an acquisition block and
+/// returns the prefixer for its data lines. block may end after the first data line.
+#[cfg(feature = "yaml-config")]
+fn osc_block_prefixer(
+    block: &[String],
+    start: usize,
+    header_idx: usize,
+    rules: &DatetimeRules,
+    mtime: Option<NaiveDateTime>,
+    columns: &ColumnFormat,
+    outcome: &mut FileOutcome,
+) -> Option<Prefixer> {
+    // check datetime format in first line of the block
+    let datetime = &block[0];
+    let matched = RE_DT.find(datetime)?;
+    // the regex also accepts nonsense like 99.99.99 27:61:61.99
+    let dt = match rules.validate(matched.as_str(), mtime) {
+        Ok(dt) => dt,
+        Err(reason) => {
+            outcome.skip_prefix(reason, true).source_datetime = Some(matched.as_str().to_string());
+            return None;
+        }
+    };
+    // a file that crashed right after writing the preamble has no real
+    // header / data lines, don't stamp a DateTime column onto those
+    if !looks_like_header(
+        &block[header_idx],
+        &columns.header_delimiter,
+        columns.min_header_tokens,
+    ) {
+        outcome.skip_prefix(
+            format!(
+                "line {} does not look like a column header",
+                start + header_idx
+            ),
+            false,
+        );
+        return None;
+    }
+    if !block[header_idx + 1..]
+        .iter()
+        .any(|line| looks_like_data(line, &columns.delimiter))
+    {
+        outcome.skip_prefix("no line of data after the column header".to_string(), false);
+        return None;
+    }
+    match rules.prefixer(datetime, &dt, &block[..header_idx]) {
+        Ok(prefixer) => Some(prefixer),
+        Err(err) => {
+            outcome.skip_prefix(err, false);
+            None
+        }
+    }
+}
+
+#[cfg(feature = "yaml-config")]
+fn prefix_message(prefixes: &[String], interval: bool) -> String {
+    match (prefixes.first(), prefixes.last()) {
+        (Some(first), Some(last)) if interval => {
+            format!("prefix data lines with datetime '{first}' to '{last}'")
+        }
+        (Some(first), _) => format!("prefix data lines with datetime '{first}'"),
+        _ => "no data lines to prefix".to_string(),
+    }
+}
+
+/// split_path returns the path of the n-th acquisition block split off a file,
+/// e.g. `data_2.OSC`.
+#[cfg(feature = "yaml-config")]
+fn split_path(file_path: &Path, n: usize) -> PathBuf {
+    let mut name = file_path.file_stem().unwrap_or_default().to_owned();
+    name.push(format!("_{n}"));
+    if let Some(ext) = file_path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    file_path.with_file_name(name)
+}
@@ -0,0 +1,181 @@
+//! logfile provides a log file with size-based rotation.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// RotatingLog appends lines to a log file. Once the file would grow beyond max_size,
+/// it is renamed to `<name>.1` (older ones shift to `<name>.2` etc., keeping at most
+/// `keep` rotated files) and a fresh file is started. The file is always addressed by
+/// its path, so rotation never loses lines written by the running process.
+#[derive(Debug)]
+pub struct RotatingLog {
+    path: PathBuf,
+    max_size: u64,
+    keep: usize,
+    file: fs::File,
+    size: u64,
+}
+
+impl RotatingLog {
+    /// open opens (or creates) the log file at path for appending.
+    pub fn open(path: impl AsRef<Path>, max_size: u64, keep: usize) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = open_append(&path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingLog {
+            path,
+            max_size,
+            keep,
+            file,
+            size,
+        })
+    }
+
+    /// write_line appends a line to the log, rotating first if the line would not fit.
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let n = line.len() as u64 + 1;
+        if self.size > 0 && self.size + n > self.max_size {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{line}")?;
+        self.size += n;
+        Ok(())
+    }
+
+    /// rotate shifts the rotated files by one, moves the current file to `<name>.1`
+    /// and reopens an empty file at the original path.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            let oldest = rotated_path(&self.path, self.keep);
+            if oldest.exists() {
+                fs::remove_file(&oldest)?;
+            }
+            for n in (1..self.keep).rev() {
+                let from = rotated_path(&self.path, n);
+                if from.exists() {
+                    fs::rename(&from, rotated_path(&self.path, n + 1))?;
+                }
+            }
+            fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<fs::File> {
+    fs::OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// rotated_path returns the path of the n-th rotated log file, e.g. `clean.log.2`.
+pub fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+/// parse_size parses a size like `512`, `64K`, `10M` or `1G` (binary units) to bytes.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (digits, factor) = match s.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => (&s[..s.len() - 1], 1 << 10),
+        Some('M') => (&s[..s.len() - 1], 1 << 20),
+        Some('G') => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1),
+    };
+    digits
+        .trim()
+        .parse::<u64>()
+        .map(|n| n * factor)
+        .map_err(|_| format!("invalid size '{s}', expected e.g. 512, 64K, 10M or 1G"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// log_dir returns an empty directory for the test name.
+    fn log_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("v25_logfile_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn read(path: &Path) -> Option<String> {
+        fs::read_to_string(path).ok()
+    }
+
+    #[test]
+    fn rotates_only_past_the_threshold() {
+        let dir = log_dir("threshold");
+        let path = dir.join("clean.log");
+        // each line takes 4 bytes with its newline
+        let mut log = RotatingLog::open(&path, 8, 3).unwrap();
+        log.write_line("one").unwrap();
+        log.write_line("two").unwrap();
+        assert_eq!(read(&path).as_deref(), Some("one\ntwo\n"), "exactly full");
+        assert!(!rotated_path(&path, 1).exists());
+        log.write_line("six").unwrap();
+        assert_eq!(read(&path).as_deref(), Some("six\n"));
+        assert_eq!(read(&rotated_path(&path, 1)).as_deref(), Some("one\ntwo\n"));
+
+        // a line longer than the limit still goes into an empty file
+        let long = "x".repeat(20);
+        let mut log = RotatingLog::open(dir.join("long.log"), 8, 3).unwrap();
+        log.write_line(&long).unwrap();
+        assert_eq!(read(&dir.join("long.log")), Some(format!("{long}\n")));
+        assert!(!rotated_path(&dir.join("long.log"), 1).exists());
+
+        // the size of an existing file counts
+        let mut log = RotatingLog::open(&path, 8, 3).unwrap();
+        log.write_line("ten").unwrap();
+        log.write_line("end").unwrap();
+        assert_eq!(read(&path).as_deref(), Some("end\n"));
+        assert_eq!(read(&rotated_path(&path, 1)).as_deref(), Some("six\nten\n"));
+        assert_eq!(read(&rotated_path(&path, 2)).as_deref(), Some("one\ntwo\n"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rotated_files_are_numbered_newest_first() {
+        let dir = log_dir("numbering");
+        let path = dir.join("clean.log");
+        let mut log = RotatingLog::open(&path, 4, 2).unwrap();
+        for line in ["aaa", "bbb", "ccc", "ddd"] {
+            log.write_line(line).unwrap();
+        }
+        assert_eq!(rotated_path(&path, 2), dir.join("clean.log.2"));
+        assert_eq!(read(&path).as_deref(), Some("ddd\n"));
+        assert_eq!(read(&dir.join("clean.log.1")).as_deref(), Some("ccc\n"));
+        assert_eq!(read(&dir.join("clean.log.2")).as_deref(), Some("bbb\n"));
+        // only keep files are kept, the oldest is gone
+        assert!(!dir.join("clean.log.3").exists());
+
+        // without rotated files, the log starts over
+        let path = dir.join("none.log");
+        let mut log = RotatingLog::open(&path, 4, 0).unwrap();
+        log.write_line("aaa").unwrap();
+        log.write_line("bbb").unwrap();
+        assert_eq!(read(&path).as_deref(), Some("bbb\n"));
+        assert!(!rotated_path(&path, 1).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn sizes_have_binary_units() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("64k"), Ok(64 << 10));
+        assert_eq!(parse_size(" 10M "), Ok(10 << 20));
+        assert_eq!(parse_size("1G"), Ok(1 << 30));
+        assert!(parse_size("10MB").is_err());
+        assert!(parse_size("").is_err());
+    }
+}
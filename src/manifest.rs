@@ -0,0 +1,254 @@
+//! manifest records the metadata of each file at its last successful clean, so a forced
+//! run can skip files that did not change since and whose cleaning rules are the same.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::Path,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+#[cfg(feature = "yaml-config")]
+use yaml_rust::{Yaml, YamlEmitter};
+
+use crate::{cert::sha256_file, tmpfile, CleanOptions};
+
+/// MANIFEST_FILE is the name of the manifest, next to the done marker.
+pub const MANIFEST_FILE: &str = "V25Logs_cleaned.manifest.json";
+
+/// Manifest maps file names to the state they had after their last successful clean.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub files: BTreeMap<String, Entry>,
+}
+
+/// Entry is the state of a file after it was cleaned.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub size: u64,
+    /// modification time, nanoseconds since the Unix epoch
+    pub mtime_ns: u64,
+    /// sha256 of the content, only recorded if requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// fingerprint of the rules the file was cleaned with, see rules_fingerprint
+    pub rules: String,
+    /// number of data lines before and after the clean, see FileOutcome::lines_before;
+    /// set by the caller from the outcome, missing in manifests of older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lines_before: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lines_after: Option<usize>,
+}
+
+impl Entry {
+    /// of describes the current state of the file at path.
+    pub fn of(path: &Path, rules: &str, with_hash: bool) -> io::Result<Self> {
+        let meta = fs::metadata(path)?;
+        Ok(Entry {
+            size: meta.len(),
+            mtime_ns: mtime_ns(&meta)?,
+            sha256: if with_hash {
+                Some(sha256_file(path)?)
+            } else {
+                None
+            },
+            rules: rules.to_string(),
+            lines_before: None,
+            lines_after: None,
+        })
+    }
+}
+
+impl Manifest {
+    /// read reads the manifest from MANIFEST_FILE in dir; a missing manifest is empty.
+    pub fn read(dir: &Path) -> io::Result<Self> {
+        let file = match fs::File::open(dir.join(MANIFEST_FILE)) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Manifest::default()),
+            Err(err) => return Err(err),
+        };
+        serde_json::from_reader(io::BufReader::new(file)).map_err(io::Error::other)
+    }
+
+    /// write replaces MANIFEST_FILE in dir.
+    pub fn write(&self, dir: &Path) -> io::Result<()> {
+        tmpfile::write_atomic(&dir.join(MANIFEST_FILE), |file| {
+            serde_json::to_writer_pretty(&mut *file, self).map_err(io::Error::other)
+        })
+    }
+
+    /// is_current tells if the file at path is still in the state recorded for it and
+    /// was cleaned with the same rules. size and mtime come from the metadata; the
+    /// content is only read if with_hash is set, and then must match the recorded hash.
+    pub fn is_current(&self, path: &Path, rules: &str, with_hash: bool) -> bool {
+        let Some(entry) = self.files.get(&file_name(path)) else {
+            return false;
+        };
+        if entry.rules != rules || (with_hash && entry.sha256.is_none()) {
+            return false;
+        }
+        Entry::of(path, rules, with_hash).is_ok_and(|now| {
+            now.size == entry.size
+                && now.mtime_ns == entry.mtime_ns
+                && (!with_hash || now.sha256 == entry.sha256)
+        })
+    }
+
+    /// entry returns the entry of the file at path, if it has one.
+    pub fn entry(&self, path: &Path) -> Option<&Entry> {
+        self.files.get(&file_name(path))
+    }
+
+    /// record sets the entry of the file at path.
+    pub fn record(&mut self, path: &Path, entry: Entry) {
+        self.files.insert(file_name(path), entry);
+    }
+
+    /// forget removes the entry of the file at path, it must be checked again.
+    pub fn forget(&mut self, path: &Path) {
+        self.files.remove(&file_name(path));
+    }
+}
+
+/// rules_fingerprint hashes everything that decides how a file of extension ext is
+/// cleaned: the tool version, the cfg section of the extension, its reference header,
+/// the selected checks and --expect-id. a file recorded with another fingerprint is
+/// checked again.
+#[cfg(feature = "yaml-config")]
+pub fn rules_fingerprint(cfg: &Yaml, ext: &str, opts: &CleanOptions) -> String {
+    fingerprint(env!("CARGO_PKG_VERSION"), cfg, ext, opts)
+}
+
+/// fingerprint is rules_fingerprint as a tool of the given version computes it.
+#[cfg(feature = "yaml-config")]
+fn fingerprint(version: &str, cfg: &Yaml, ext: &str, opts: &CleanOptions) -> String {
+    let mut section = String::new();
+    let mut emitter = YamlEmitter::new(&mut section);
+    // a missing section dumps as BadValue, which fails, leaving the fingerprint of ""
+    let _ = emitter.dump(&cfg[ext]);
+    let mut hasher = Sha256::new();
+    hasher.update(version);
+    hasher.update(section);
+    hasher.update(format!(
+        "\n{:?}\n{:?}\n{:?}",
+        opts.reference_headers.get(ext),
+        opts.only_checks,
+        opts.skip_checks
+    ));
+    // fingerprints of runs without it stay as they were
+    if let Some(id) = &opts.expect_id {
+        hasher.update(format!("\n{id:?}"));
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// file_extension returns the upper case extension of path, "" if it has none.
+pub fn file_extension(path: &Path) -> String {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_uppercase())
+        .unwrap_or_default()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+fn mtime_ns(meta: &fs::Metadata) -> io::Result<u64> {
+    let since_epoch = meta
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO);
+    Ok(since_epoch.as_nanos() as u64)
+}
+
+#[cfg(all(test, feature = "yaml-config"))]
+mod tests {
+    use yaml_rust::YamlLoader;
+
+    use super::*;
+
+    fn cfg(text: &str) -> Yaml {
+        YamlLoader::load_from_str(text).unwrap().remove(0)
+    }
+
+    /// cleaned writes a file into a new temp dir and returns it and a manifest that
+    /// records it with rules.
+    fn cleaned(name: &str, rules: &str) -> (std::path::PathBuf, Manifest) {
+        let dir = std::env::temp_dir().join(format!("v25_manifest_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("03120000.DAT");
+        fs::write(&path, "time\tp\n1\t2\n").unwrap();
+        let mut manifest = Manifest::default();
+        manifest.record(&path, Entry::of(&path, rules, true).unwrap());
+        (path, manifest)
+    }
+
+    #[test]
+    fn a_changed_cfg_invalidates() {
+        let opts = CleanOptions::default();
+        let before = rules_fingerprint(&cfg("DAT:\n  min_lines: 2\n"), "DAT", &opts);
+        let after = rules_fingerprint(&cfg("DAT:\n  min_lines: 3\n"), "DAT", &opts);
+        let other_ext = rules_fingerprint(&cfg("DAT:\n  min_lines: 2\nOSC: {}\n"), "DAT", &opts);
+        let (path, manifest) = cleaned("cfg", &before);
+
+        assert!(manifest.is_current(&path, &before, false));
+        assert!(!manifest.is_current(&path, &after, false));
+        // only the section of the extension counts
+        assert!(manifest.is_current(&path, &other_ext, false));
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn another_tool_version_invalidates() {
+        let (cfg, opts) = (cfg("DAT:\n  min_lines: 2\n"), CleanOptions::default());
+        let old = fingerprint("0.0.1", &cfg, "DAT", &opts);
+        let (path, manifest) = cleaned("version", &old);
+
+        assert!(manifest.is_current(&path, &old, false));
+        assert!(!manifest.is_current(&path, &rules_fingerprint(&cfg, "DAT", &opts), false));
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn a_changed_size_or_mtime_invalidates() {
+        let (path, manifest) = cleaned("meta", "rules");
+        assert!(manifest.is_current(&path, "rules", false));
+        assert!(manifest.is_current(&path, "rules", true));
+
+        // same size, content and hash, touched only
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        let recorded = UNIX_EPOCH + Duration::from_nanos(manifest.entry(&path).unwrap().mtime_ns);
+        file.set_modified(recorded + Duration::from_secs(1))
+            .unwrap();
+        assert!(!manifest.is_current(&path, "rules", false));
+        assert!(!manifest.is_current(&path, "rules", true));
+        file.set_modified(recorded).unwrap();
+        assert!(manifest.is_current(&path, "rules", false));
+
+        // a line more, at the same mtime
+        fs::write(&path, "time\tp\n1\t2\n3\t4\n").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(recorded)
+            .unwrap();
+        assert!(!manifest.is_current(&path, "rules", false));
+
+        // neither is a file that is gone
+        fs::remove_file(&path).unwrap();
+        assert!(!manifest.is_current(&path, "rules", false));
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
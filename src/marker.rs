@@ -0,0 +1,159 @@
+//! marker records how far a directory was cleaned: the done marker once every file was
+//! cleaned without error, the partial marker with the files that were not, so that the
+//! next run cleans only those. The done marker names the build that cleaned the
+//! directory and, with --audit, who cleaned it where. With --recursive every cleaned
+//! subdirectory gets its own markers, so a day that was cleaned is skipped next time.
+
+use std::{
+    fs,
+    io::{self, BufRead},
+    path::{Path, PathBuf},
+};
+
+use crate::{audit::Audit, build_info::BuildInfo, tmpfile};
+
+/// DONE_MARKER is the file written once every file of the directory was cleaned. it
+/// holds the build information of the run and its audit, only its existence counts.
+pub const DONE_MARKER: &str = "V25Logs_cleaned.done";
+
+/// PARTIAL_MARKER lists the files of the directory that were not cleaned, one name per
+/// line below a comment, because the run was interrupted or failed on them. On Unix the
+/// names are written as they are on disk, even if they are not valid UTF-8, except for
+/// `\`, a newline and a carriage return, which are escaped as `\\`, `\n` and `\r`.
+pub const PARTIAL_MARKER: &str = "V25Logs_cleaned.partial";
+
+/// State is how far a directory was cleaned according to its markers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum State {
+    /// no marker, never cleaned
+    Fresh,
+    /// all files cleaned
+    Done,
+    /// these files, relative to the directory, are left to clean
+    Partial(Vec<PathBuf>),
+}
+
+/// read reads the markers in dir. the done marker wins if both exist.
+pub fn read(dir: &Path) -> io::Result<State> {
+    if dir.join(DONE_MARKER).is_file() {
+        return Ok(State::Done);
+    }
+    let file = match fs::File::open(dir.join(PARTIAL_MARKER)) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(State::Fresh),
+        Err(err) => return Err(err),
+    };
+    let mut unfinished = Vec::new();
+    for (i, line) in io::BufReader::new(file).split(b'\n').enumerate() {
+        let mut line = line?;
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        // only the first line is the comment, a name may start with # too
+        if !line.is_empty() && (i > 0 || !line.starts_with(b"#")) {
+            unfinished.push(name_from_bytes(unescape(&line)));
+        }
+    }
+    Ok(State::Partial(unfinished))
+}
+
+/// write records the result of a run in dir: the done marker with build and audit if no
+/// file is unfinished, else the partial marker listing the names of the unfinished
+/// files. the other marker is removed.
+pub fn write(
+    dir: &Path,
+    unfinished: &[PathBuf],
+    build: &BuildInfo,
+    audit: Option<&Audit>,
+) -> io::Result<()> {
+    if unfinished.is_empty() {
+        tmpfile::write_atomic(&dir.join(DONE_MARKER), |file| {
+            write!(file, "{build}")?;
+            match audit {
+                Some(audit) => write!(file, "{audit}"),
+                None => Ok(()),
+            }
+        })?;
+        return remove_if_exists(&dir.join(PARTIAL_MARKER));
+    }
+    tmpfile::write_atomic(&dir.join(PARTIAL_MARKER), |file| {
+        writeln!(
+            file,
+            "# files left to clean, the next run cleans only these"
+        )?;
+        for path in unfinished {
+            file.write_all(&escape(&name_bytes(path)))?;
+            writeln!(file)?;
+        }
+        Ok(())
+    })?;
+    remove_if_exists(&dir.join(DONE_MARKER))
+}
+
+/// name_bytes returns the file name of path as a line of the partial marker: the raw
+/// bytes on Unix, else the name with replacement characters where it is not valid
+/// UTF-8.
+#[cfg(unix)]
+fn name_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.file_name().unwrap_or_default().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn name_bytes(path: &Path) -> Vec<u8> {
+    let name = path.file_name().unwrap_or_default();
+    name.to_string_lossy().into_owned().into_bytes()
+}
+
+/// escape escapes the bytes of a name that would end its line or be read as an escape.
+fn escape(name: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(name.len());
+    for &b in name {
+        match b {
+            b'\\' => escaped.extend_from_slice(b"\\\\"),
+            b'\n' => escaped.extend_from_slice(b"\\n"),
+            b'\r' => escaped.extend_from_slice(b"\\r"),
+            _ => escaped.push(b),
+        }
+    }
+    escaped
+}
+
+/// unescape is the reverse of escape. a `\` before any other byte is kept.
+fn unescape(line: &[u8]) -> Vec<u8> {
+    let mut name = Vec::with_capacity(line.len());
+    let mut bytes = line.iter().copied().peekable();
+    while let Some(b) = bytes.next() {
+        let unescaped = match (b, bytes.peek()) {
+            (b'\\', Some(b'\\')) => b'\\',
+            (b'\\', Some(b'n')) => b'\n',
+            (b'\\', Some(b'r')) => b'\r',
+            _ => {
+                name.push(b);
+                continue;
+            }
+        };
+        bytes.next();
+        name.push(unescaped);
+    }
+    name
+}
+
+/// name_from_bytes is the reverse of name_bytes.
+#[cfg(unix)]
+fn name_from_bytes(line: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(line))
+}
+
+#[cfg(not(unix))]
+fn name_from_bytes(line: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&line).into_owned())
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,125 @@
+//! observer lets callers follow a cleaning run as it happens, e.g. to show progress in
+//! a GUI, instead of waiting for the outcomes or parsing the output of the tool.
+
+use std::path::Path;
+
+#[cfg(feature = "yaml-config")]
+use std::path::PathBuf;
+
+#[cfg(feature = "yaml-config")]
+use yaml_rust::Yaml;
+
+#[cfg(feature = "yaml-config")]
+use crate::{clean_file, CleanOptions};
+use crate::{report::Summary, FileOutcome};
+
+/// CleanObserver is notified of the files of a run. All methods do nothing by default,
+/// implement the ones needed. Observers are Sync so that they can be called from
+/// several threads.
+pub trait CleanObserver: Sync {
+    /// on_file_start is called before path is cleaned.
+    fn on_file_start(&self, _path: &Path) {}
+
+    /// on_outcome is called once path was cleaned, or failed with an error outcome.
+    fn on_outcome(&self, _path: &Path, _outcome: &FileOutcome) {}
+
+    /// on_finished is called once all files were cleaned.
+    fn on_finished(&self, _summary: &Summary) {}
+}
+
+/// the unit observer ignores all events.
+impl CleanObserver for () {}
+
+/// clean_files cleans paths one after the other with clean_file and notifies observer.
+/// an I/O error on a file becomes its error outcome and does not stop the run.
+#[cfg(feature = "yaml-config")]
+pub fn clean_files(
+    paths: &[PathBuf],
+    cfg: &Yaml,
+    opts: &CleanOptions,
+    observer: &dyn CleanObserver,
+) -> Vec<FileOutcome> {
+    let mut outcomes = Vec::with_capacity(paths.len());
+    for path in paths {
+        observer.on_file_start(path);
+        let outcome =
+            clean_file(path, cfg, opts).unwrap_or_else(|err| FileOutcome::from_error(path, &err));
+        observer.on_outcome(path, &outcome);
+        outcomes.push(outcome);
+    }
+    observer.on_finished(&Summary::from_outcomes(&outcomes));
+    outcomes
+}
+
+#[cfg(all(test, feature = "yaml-config"))]
+mod tests {
+    use std::{fs, sync::Mutex};
+
+    use yaml_rust::YamlLoader;
+
+    use super::*;
+    use crate::{testutil::FIXTURE_DIR, Outcome};
+
+    /// Recorder keeps the events it was notified of, as lines.
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl Recorder {
+        fn push(&self, event: String) {
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    impl CleanObserver for Recorder {
+        fn on_file_start(&self, path: &Path) {
+            self.push(format!(
+                "start {}",
+                path.file_name().unwrap().to_string_lossy()
+            ));
+        }
+
+        fn on_outcome(&self, path: &Path, outcome: &FileOutcome) {
+            let name = path.file_name().unwrap().to_string_lossy();
+            self.push(format!("outcome {name} {:?}", outcome.outcome));
+        }
+
+        fn on_finished(&self, summary: &Summary) {
+            self.push(format!(
+                "finished {} unchanged, {} modified, {} skipped",
+                summary.unchanged, summary.modified, summary.skipped
+            ));
+        }
+    }
+
+    #[test]
+    fn observer_gets_the_events_in_order() {
+        let dir = std::env::temp_dir().join(format!("v25_observer_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let text = fs::read_to_string(Path::new(FIXTURE_DIR).join("cfg.yml")).unwrap();
+        let cfg = YamlLoader::load_from_str(&text).unwrap().remove(0);
+        fs::write(dir.join("03120000.DAT"), "time\tp\n1\t2\n").unwrap();
+        fs::write(dir.join("03120100.DAT"), "time\tp\n1\t2\n\n").unwrap();
+        let paths = ["03120100.DAT", "03120000.DAT", "03120200.DAT"].map(|name| dir.join(name));
+
+        let recorder = Recorder::default();
+        let outcomes = clean_files(&paths, &cfg, &CleanOptions::default(), &recorder);
+        assert_eq!(
+            recorder.0.into_inner().unwrap(),
+            [
+                "start 03120100.DAT",
+                "outcome 03120100.DAT Modified",
+                "start 03120000.DAT",
+                "outcome 03120000.DAT Unchanged",
+                "start 03120200.DAT",
+                // a file that is gone does not stop the run
+                "outcome 03120200.DAT Skipped(VanishedDuringRun)",
+                "finished 1 unchanged, 1 modified, 1 skipped",
+            ]
+        );
+        // the outcomes are those the observer saw, in the order of paths
+        let seen: Vec<&Outcome> = outcomes.iter().map(|o| &o.outcome).collect();
+        assert_eq!(seen[..2], [&Outcome::Modified, &Outcome::Unchanged]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,142 @@
+//! preflight checks that a run can write where it needs to before any file is touched,
+//! and how much space the file system of the directory has left after it.
+
+use std::{fmt, fs, io, io::Write, path::Path, process, str::FromStr};
+
+use crate::{logfile::parse_size, report::format_size};
+
+/// check_writable creates, writes and removes a probe file in dir, so a read-only or
+/// full file system is detected before the first file is rewritten.
+pub fn check_writable(dir: &Path) -> io::Result<()> {
+    let probe = dir.join(format!(".v25cleaner_probe_{}", process::id()));
+    let result = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+        .and_then(|mut file| {
+            let written = file.write_all(b"probe\n").and_then(|_| file.sync_all());
+            let removed = fs::remove_file(&probe);
+            written.and(removed)
+        });
+    result.map_err(|err| io::Error::new(err.kind(), format!("{dir:?} is not writable: {err}")))
+}
+
+/// DiskSpace is the size of a file system and the part of it the user may still write,
+/// in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskSpace {
+    pub free: u64,
+    pub total: u64,
+}
+
+impl DiskSpace {
+    /// free_percent returns the free space as a percentage of the total, 0 for a file
+    /// system without size.
+    pub fn free_percent(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.free as f64 / self.total as f64 * 100.0
+    }
+}
+
+/// free_space returns the space of the file system that holds dir.
+#[cfg(unix)]
+pub fn free_space(dir: &Path) -> io::Result<DiskSpace> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(dir.as_os_str().as_bytes())?;
+    // SAFETY: statvfs is a plain C struct, all zeroes is a valid value
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: path is NUL terminated and stat is only read if the call succeeded
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let block = stat.f_frsize as u64;
+    Ok(DiskSpace {
+        free: stat.f_bavail as u64 * block,
+        total: stat.f_blocks as u64 * block,
+    })
+}
+
+/// free_space returns the space of the volume that holds dir, as far as quotas let the
+/// user write it.
+#[cfg(windows)]
+pub fn free_space(dir: &Path) -> io::Result<DiskSpace> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let path: Vec<u16> = dir.as_os_str().encode_wide().chain([0]).collect();
+    let (mut free, mut total, mut total_free) = (0u64, 0u64, 0u64);
+    // SAFETY: path is NUL terminated and the three counts outlive the call
+    if unsafe { GetDiskFreeSpaceExW(path.as_ptr(), &mut free, &mut total, &mut total_free) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(DiskSpace { free, total })
+}
+
+/// free_space is not supported on platforms other than Unix and Windows.
+#[cfg(not(any(unix, windows)))]
+pub fn free_space(_dir: &Path) -> io::Result<DiskSpace> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "free space is not known on this platform",
+    ))
+}
+
+/// FreeSpaceThreshold is the free space below which a run warns that the disk is
+/// almost full, e.g. `5G` or `10%` of the file system.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FreeSpaceThreshold {
+    Bytes(u64),
+    Percent(f64),
+}
+
+impl FreeSpaceThreshold {
+    /// is_low tells if space has less free space than the threshold.
+    pub fn is_low(&self, space: &DiskSpace) -> bool {
+        match *self {
+            FreeSpaceThreshold::Bytes(n) => space.free < n,
+            FreeSpaceThreshold::Percent(p) => space.free_percent() < p,
+        }
+    }
+
+    /// warning returns the warning for space if it is low, naming the directory.
+    pub fn warning(&self, space: &DiskSpace, dir: &Path) -> Option<String> {
+        self.is_low(space).then(|| {
+            format!(
+                "!!! the disk is almost full: {} ({:.1}%) free of {} on the file system of \
+                 {dir:?}, below {self}; files written on a full disk end up truncated",
+                format_size(space.free),
+                space.free_percent(),
+                format_size(space.total)
+            )
+        })
+    }
+}
+
+impl FromStr for FreeSpaceThreshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("invalid free space '{s}', expected e.g. 5G or 10%");
+        match s.trim().strip_suffix('%') {
+            Some(percent) => match percent.trim().parse::<f64>() {
+                Ok(p) if (0.0..=100.0).contains(&p) => Ok(FreeSpaceThreshold::Percent(p)),
+                _ => Err(err()),
+            },
+            None => parse_size(s)
+                .map(FreeSpaceThreshold::Bytes)
+                .map_err(|_| err()),
+        }
+    }
+}
+
+impl fmt::Display for FreeSpaceThreshold {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FreeSpaceThreshold::Bytes(n) => f.write_str(&format_size(*n)),
+            FreeSpaceThreshold::Percent(p) => write!(f, "{p}%"),
+        }
+    }
+}
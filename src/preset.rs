@@ -0,0 +1,130 @@
+//! preset bundles command line options for common uses, so new users need not learn
+//! each flag. A preset only sets defaults: options given on the command line win.
+
+use std::{fmt, path::PathBuf, str::FromStr, thread};
+
+use crate::delete::{DeleteStrategy, DEFAULT_WORKERS};
+
+/// Preset is a named set of options, see PresetOptions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// only print what cleaning would do; once run for real, flag the files the checks
+    /// would delete instead of deleting them, back up the others first and quarantine
+    /// what is still removed
+    Paranoid,
+    /// clean and remove deleted files with one thread per core, print only the summary
+    Fast,
+}
+
+impl Preset {
+    /// options returns the options the preset sets.
+    pub fn options(self) -> PresetOptions {
+        match self {
+            Preset::Paranoid => paranoid(),
+            Preset::Fast => fast(),
+        }
+    }
+
+    /// value returns one of the options of the preset, with the preset, for layer.
+    pub fn value<T>(self, field: impl Fn(PresetOptions) -> Option<T>) -> Option<(Preset, T)> {
+        field(self.options()).map(|value| (self, value))
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "paranoid" => Ok(Preset::Paranoid),
+            "fast" => Ok(Preset::Fast),
+            _ => Err(format!(
+                "invalid preset '{s}', expected one of paranoid, fast"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Preset::Paranoid => "paranoid",
+            Preset::Fast => "fast",
+        })
+    }
+}
+
+/// PresetOptions are the options a preset sets; None leaves the option to its default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PresetOptions {
+    /// flag the files the checks would delete, see CleanOptions::flag_deletes
+    pub flag_deletes: Option<bool>,
+    pub delete_strategy: Option<DeleteStrategy>,
+    pub delete_workers: Option<usize>,
+    pub verbose: Option<bool>,
+    pub dry_run: Option<bool>,
+    /// the quarantine, relative to the parent of the directory to clean, see
+    /// QUARANTINE_DIR
+    pub quarantine: Option<PathBuf>,
+    pub backup: Option<bool>,
+    pub jobs: Option<u64>,
+}
+
+/// QUARANTINE_DIR is the quarantine of `--preset paranoid`, next to the directory to
+/// clean, which must not hold it.
+pub const QUARANTINE_DIR: &str = "V25Logs_quarantine";
+
+/// paranoid returns the options of `--preset paranoid`: a dry run, and with
+/// --no-dry-run nothing is deleted, the files the checks would delete are flagged for
+/// review, each file is backed up before it is changed and the files removed anyway,
+/// e.g. with --really-delete, are moved to QUARANTINE_DIR.
+pub fn paranoid() -> PresetOptions {
+    PresetOptions {
+        flag_deletes: Some(true),
+        dry_run: Some(true),
+        quarantine: Some(PathBuf::from(QUARANTINE_DIR)),
+        backup: Some(true),
+        ..Default::default()
+    }
+}
+
+/// fast returns the options of `--preset fast`: files are cleaned and deleted files
+/// removed with one thread per core and only the summary is printed.
+pub fn fast() -> PresetOptions {
+    let cores = thread::available_parallelism().map_or(DEFAULT_WORKERS, |n| n.get());
+    PresetOptions {
+        delete_strategy: Some(DeleteStrategy::Parallel),
+        delete_workers: Some(cores),
+        verbose: Some(false),
+        jobs: Some(cores as u64),
+        ..Default::default()
+    }
+}
+
+/// Source tells where the effective value of an option comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    Preset(Preset),
+    CommandLine,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Default => f.write_str("default"),
+            Source::Preset(preset) => write!(f, "preset {preset}"),
+            Source::CommandLine => f.write_str("command line"),
+        }
+    }
+}
+
+/// layer returns the effective value of an option and its source: the value given on
+/// the command line, else the one of the preset, else the default.
+pub fn layer<T>(given: Option<T>, preset: Option<(Preset, T)>, default: T) -> (T, Source) {
+    match (given, preset) {
+        (Some(value), _) => (value, Source::CommandLine),
+        (None, Some((preset, value))) => (value, Source::Preset(preset)),
+        (None, None) => (default, Source::Default),
+    }
+}
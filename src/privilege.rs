@@ -0,0 +1,58 @@
+//! privilege detects a run as root or as an elevated Windows administrator, where a
+//! wrong directory argument can delete files no ordinary user could.
+
+use std::env;
+
+/// ALLOW_ELEVATED_ENV names the environment variable that lets elevated runs proceed
+/// without --allow-elevated, for containers that always run as root. Any value other
+/// than empty or "0" allows them.
+pub const ALLOW_ELEVATED_ENV: &str = "V25_ALLOW_ELEVATED";
+
+/// is_elevated returns true if the process runs with an effective user id of 0.
+#[cfg(unix)]
+pub fn is_elevated() -> bool {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// is_elevated returns true if the token of the process is elevated, i.e. it was
+/// started with "Run as administrator" or UAC is off for an administrator account.
+#[cfg(windows)]
+pub fn is_elevated() -> bool {
+    use windows_sys::Win32::{
+        Foundation::{CloseHandle, HANDLE},
+        Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY},
+        System::Threading::{GetCurrentProcess, OpenProcessToken},
+    };
+
+    let mut token: HANDLE = std::ptr::null_mut();
+    let mut elevation = TOKEN_ELEVATION { TokenIsElevated: 0 };
+    let mut size = 0u32;
+    // SAFETY: the token handle is only used if OpenProcessToken succeeded and is closed
+    // after use; elevation is a TOKEN_ELEVATION of the size passed along
+    unsafe {
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+            return false;
+        }
+        let ok = GetTokenInformation(
+            token,
+            TokenElevation,
+            &mut elevation as *mut TOKEN_ELEVATION as *mut _,
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut size,
+        );
+        CloseHandle(token);
+        ok != 0 && elevation.TokenIsElevated != 0
+    }
+}
+
+/// is_elevated is false on platforms without a notion of elevation.
+#[cfg(not(any(unix, windows)))]
+pub fn is_elevated() -> bool {
+    false
+}
+
+/// bypassed returns true if ALLOW_ELEVATED_ENV is set to allow elevated runs.
+pub fn bypassed() -> bool {
+    env::var_os(ALLOW_ELEVATED_ENV).is_some_and(|value| !value.is_empty() && value != "0")
+}
@@ -0,0 +1,77 @@
+//! profile evaluates files under an alternate cfg without changing them, e.g. the strict
+//! settings of the archive during a lenient run in the field, to find the files that
+//! only barely pass. Each file is copied to a temporary directory and cleaned there.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use yaml_rust::Yaml;
+
+use crate::{
+    check_formats, check_line_counts, check_osc_prefix, check_readonly, clean_copy, clean_file,
+    compile_rules, load_reference_headers, tmpfile, CleanOptions, FileOutcome, Outcome, SkipReason,
+};
+
+/// Profile is an alternate cfg that files are evaluated under, report only.
+pub struct Profile {
+    cfg: Yaml,
+    opts: CleanOptions,
+    /// the directory files are copied to for evaluation, removed on drop
+    work: PathBuf,
+}
+
+impl Profile {
+    /// new prepares the evaluation under cfg, whose reference header files are relative
+    /// to cfg_dir. opts are the options of the run, the checks selected on the command
+    /// line apply to both.
+    pub fn new(cfg: Yaml, cfg_dir: &Path, opts: &CleanOptions) -> Result<Self, String> {
+        let opts = CleanOptions {
+            reference_headers: check_readonly(&cfg)
+                .and_then(|_| check_line_counts(&cfg))
+                .and_then(|_| check_formats(&cfg))
+                .and_then(|_| check_osc_prefix(&cfg))
+                .and_then(|_| load_reference_headers(&cfg, cfg_dir))?,
+            compiled_rules: compile_rules(&cfg)?,
+            defer_delete: false,
+            hash_unchanged: false,
+            ..opts.clone()
+        };
+        let work = tmpfile::temp_path(&std::env::temp_dir().join("v25_profile"));
+        fs::create_dir(&work).map_err(|err| format!("cannot create {work:?}: {err}"))?;
+        Ok(Profile { cfg, opts, work })
+    }
+
+    /// evaluate returns the outcome the file at path would have under the profile. the
+    /// file itself is not changed.
+    pub fn evaluate(&self, path: &Path) -> io::Result<FileOutcome> {
+        clean_copy(path, &self.work, |copy| {
+            clean_file(copy, &self.cfg, &self.opts)
+        })
+    }
+}
+
+impl Drop for Profile {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.work);
+    }
+}
+
+/// record adds the outcome of a file under a profile to its outcome. files the run
+/// skipped because they were flagged before, are our own artifacts or vanished or
+/// changed meanwhile get none, the copy evaluated under the profile can't know about
+/// that.
+pub fn record(outcome: &mut FileOutcome, alternate: &FileOutcome) {
+    if let Outcome::Skipped(
+        SkipReason::Flagged
+        | SkipReason::ToolArtifact
+        | SkipReason::VanishedDuringRun
+        | SkipReason::ChangedDuringRun,
+    ) = outcome.outcome
+    {
+        return;
+    }
+    outcome.alternate = Some(alternate.outcome);
+    outcome.would_differ = alternate.outcome != outcome.outcome;
+}
@@ -0,0 +1,310 @@
+//! repair finds and fixes OSC files that older versions of the cleaner left in a bad
+//! state: some versions dropped the last data line when prefixing, others only checked
+//! the first data line before prefixing, so files may be prefixed only in part.
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local, NaiveDateTime};
+use serde::Serialize;
+use yaml_rust::Yaml;
+
+use crate::{
+    cfg_header_idx, content_from_file, lines_to_file, osc_block_starts, write_flag, CheckId,
+    ColumnFormat, DatetimeRules, Finding, LineEndingPolicy, PrefixLayout, PrefixPosition, RE_DT,
+};
+
+/// OscState is how far an OSC file was processed by the datetime prefix transform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OscState {
+    /// no DateTime column and no prefixed data line
+    Unprocessed,
+    /// DateTime column and all data lines prefixed
+    Processed,
+    /// some data lines or the header are prefixed, others not
+    HalfPrefixed,
+    /// not a single block OSC file that could be classified
+    Unclassified,
+}
+
+impl fmt::Display for OscState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            OscState::Unprocessed => "unprocessed",
+            OscState::Processed => "processed",
+            OscState::HalfPrefixed => "half prefixed",
+            OscState::Unclassified => "unclassified",
+        })
+    }
+}
+
+/// RepairAction is what repair_osc did to a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepairAction {
+    /// nothing to repair
+    None,
+    /// prefixed this many data lines that were not, and the header if needed
+    Reprefixed(usize),
+    /// the file may have lost its last data line, it was flagged for manual attention
+    Flagged,
+    /// the file could not be classified or repaired, with the reason
+    Skipped(String),
+}
+
+/// Repair is the result of repair_osc for one file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repair {
+    pub path: PathBuf,
+    pub state: OscState,
+    pub action: RepairAction,
+}
+
+/// file_layout returns the layout the data of a file was prefixed with: the configured
+/// one, with the position switched if only that matches the header or a data line.
+pub fn file_layout(
+    header: &str,
+    data: &[String],
+    rules: &DatetimeRules,
+    layout: &PrefixLayout,
+) -> PrefixLayout {
+    let other = match layout.position {
+        PrefixPosition::Prepend => PrefixPosition::Append,
+        PrefixPosition::Append => PrefixPosition::Prepend,
+    };
+    [layout.position, other]
+        .into_iter()
+        .map(|position| PrefixLayout {
+            position,
+            ..layout.clone()
+        })
+        .find(|candidate| {
+            candidate.header().has_column(header, "DateTime")
+                || data.iter().any(|line| is_prefixed(line, rules, candidate))
+        })
+        .unwrap_or_else(|| layout.clone())
+}
+
+/// classify_osc tells the state of an OSC file from its column header and data lines.
+/// unlike the check before prefixing, all data lines are looked at. empty lines are
+/// not data.
+pub fn classify_osc(
+    header: &str,
+    data: &[String],
+    rules: &DatetimeRules,
+    layout: &PrefixLayout,
+) -> OscState {
+    let data: Vec<&String> = data.iter().filter(|line| !line.trim().is_empty()).collect();
+    let n_prefixed = data
+        .iter()
+        .filter(|line| is_prefixed(line, rules, layout))
+        .count();
+    match (layout.header().has_column(header, "DateTime"), n_prefixed) {
+        (false, 0) => OscState::Unprocessed,
+        // every data line starts with a datetime, but no DateTime column was added: the
+        // file has a time column of its own, which the transform leaves alone
+        (false, n) if n == data.len() => OscState::Unprocessed,
+        (true, n) if n == data.len() => OscState::Processed,
+        _ => OscState::HalfPrefixed,
+    }
+}
+
+fn is_prefixed(line: &str, rules: &DatetimeRules, layout: &PrefixLayout) -> bool {
+    layout
+        .column_value(line)
+        .is_some_and(|value| rules.is_prefix(value))
+}
+
+/// repair_osc classifies an OSC file and repairs it where that is safe: the header and
+/// the data lines of a half prefixed file are prefixed where they are not. a processed
+/// file that was last modified before fixed_since, i.e. by a version of the cleaner
+/// that dropped the last data line, is flagged instead, as the line can't be restored.
+pub fn repair_osc(
+    path: &Path,
+    cfg_ext: &Yaml,
+    fixed_since: Option<NaiveDateTime>,
+) -> std::io::Result<Repair> {
+    let skipped = |state, reason: &str| Repair {
+        path: path.to_path_buf(),
+        state,
+        action: RepairAction::Skipped(reason.to_string()),
+    };
+    let content = content_from_file(path)?;
+    let mut lines = content.lines.clone();
+    let header_idx = cfg_header_idx(cfg_ext).unwrap_or(4);
+    if lines.len() <= header_idx || !RE_DT.is_match(&lines[0]) {
+        return Ok(skipped(
+            OscState::Unclassified,
+            "no datetime in the first line",
+        ));
+    }
+    let columns = match ColumnFormat::from_cfg(cfg_ext) {
+        Ok(columns) => columns,
+        Err(err) => return Ok(skipped(OscState::Unclassified, &err)),
+    };
+    if osc_block_starts(&lines, header_idx, &columns, &*RE_DT).len() > 1 {
+        return Ok(skipped(
+            OscState::Unclassified,
+            "several acquisition blocks",
+        ));
+    }
+    let (layout, rules) = match (
+        PrefixLayout::from_cfg(cfg_ext),
+        DatetimeRules::from_cfg(cfg_ext),
+    ) {
+        (Ok(layout), Ok(rules)) => (layout, rules),
+        (Err(err), _) | (_, Err(err)) => return Ok(skipped(OscState::Unclassified, &err)),
+    };
+    let layout = file_layout(
+        &lines[header_idx],
+        &lines[header_idx + 1..],
+        &rules,
+        &layout,
+    );
+    let state = classify_osc(
+        &lines[header_idx],
+        &lines[header_idx + 1..],
+        &rules,
+        &layout,
+    );
+    let mtime = fs::metadata(path)?
+        .modified()
+        .ok()
+        .map(|t| DateTime::<Local>::from(t).naive_local());
+
+    let action = match state {
+        OscState::Processed if fixed_since.is_some_and(|since| mtime < Some(since)) => {
+            let finding = Finding::new(
+                CheckId::OscDatetimePrefix,
+                "prefixed by a cleaner version that dropped the last data line \
+                 -> may have lost its last record, needs manual attention"
+                    .to_string(),
+            );
+            write_flag(path, &[finding])?;
+            RepairAction::Flagged
+        }
+        OscState::HalfPrefixed => {
+            let Some(matched) = RE_DT.find(&lines[0]) else {
+                return Ok(skipped(state, "no datetime in the first line"));
+            };
+            let prefixer = match rules
+                .validate(matched.as_str(), mtime)
+                .and_then(|dt| rules.prefixer(&lines[0], &dt, &lines[..header_idx]))
+            {
+                Ok(prefixer) => prefixer,
+                Err(err) => return Ok(skipped(state, &err)),
+            };
+            let header_layout = layout.header();
+            if !header_layout.has_column(&lines[header_idx], "DateTime") {
+                lines[header_idx] = header_layout.apply(&lines[header_idx], "DateTime");
+            }
+            let mut n = 0;
+            for (i, line) in lines[header_idx + 1..].iter_mut().enumerate() {
+                if line.trim().is_empty() || is_prefixed(line, &rules, &layout) {
+                    continue;
+                }
+                let prefix = match prefixer.get(i) {
+                    Ok(prefix) => prefix,
+                    Err(err) => return Ok(skipped(state, &err)),
+                };
+                *line = layout.apply(line, &prefix);
+                n += 1;
+            }
+            lines_to_file(path, lines, content.line_ending(LineEndingPolicy::Majority))?;
+            RepairAction::Reprefixed(n)
+        }
+        _ => RepairAction::None,
+    };
+    Ok(Repair {
+        path: path.to_path_buf(),
+        state,
+        action,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PREAMBLE: &str = "01.06.23 12:00:00.00\np1\np2\t0.5\np3\n";
+
+    /// repaired writes content with PREAMBLE in front to a file of its own and repairs it
+    /// with the default OSC cfg. returns the repair and the content after it.
+    fn repaired(name: &str, first_line: &str, content: &str) -> (Repair, String) {
+        let dir = std::env::temp_dir().join(format!("v25_repair_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{name}.OSC"));
+        let preamble = PREAMBLE.replacen("01.06.23 12:00:00.00", first_line, 1);
+        fs::write(&path, format!("{preamble}{content}")).unwrap();
+        let cfg = crate::config::parse("OSC: {min_n_lines: 6, header_line: 4}").unwrap();
+        let repair = repair_osc(&path, &cfg["OSC"], None).unwrap();
+        let after = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        (repair, after[preamble.len()..].to_string())
+    }
+
+    #[test]
+    fn unprocessed_file_is_left_alone() {
+        let content = "\th1\th2\n\t1\t2\n\t3\t4\n";
+        let (repair, after) = repaired("unprocessed", "01.06.23 12:00:00.00", content);
+        assert_eq!(repair.state, OscState::Unprocessed);
+        assert_eq!(repair.action, RepairAction::None);
+        assert_eq!(after, content);
+    }
+
+    #[test]
+    fn already_prefixed_file_is_left_alone() {
+        let content =
+            "\tDateTime\th1\th2\n\t01.06.23 12:00:00.00\t1\t2\n\t01.06.23 12:00:00.00\t3\t4\n";
+        let (repair, after) = repaired("processed", "01.06.23 12:00:00.00", content);
+        assert_eq!(repair.state, OscState::Processed);
+        assert_eq!(repair.action, RepairAction::None);
+        assert_eq!(after, content);
+    }
+
+    #[test]
+    fn partially_prefixed_file_is_completed() {
+        let content = "\tDateTime\th1\th2\n\t01.06.23 12:00:00.00\t1\t2\n\t3\t4\n\t5\t6\n";
+        let (repair, after) = repaired("half", "01.06.23 12:00:00.00", content);
+        assert_eq!(repair.state, OscState::HalfPrefixed);
+        assert_eq!(repair.action, RepairAction::Reprefixed(2));
+        let dt = "01.06.23 12:00:00.00";
+        assert_eq!(
+            after,
+            format!("\tDateTime\th1\th2\n\t{dt}\t1\t2\n\t{dt}\t3\t4\n\t{dt}\t5\t6\n")
+        );
+
+        // the header may be the part that was not prefixed
+        let content = "\th1\th2\n\t01.06.23 12:00:00.00\t1\t2\n\t3\t4\n";
+        let (repair, after) = repaired("half_header", "01.06.23 12:00:00.00", content);
+        assert_eq!(repair.action, RepairAction::Reprefixed(1));
+        assert_eq!(
+            after,
+            format!("\tDateTime\th1\th2\n\t{dt}\t1\t2\n\t{dt}\t3\t4\n")
+        );
+    }
+
+    #[test]
+    fn bad_datetime_is_not_used_to_repair() {
+        let content = "\tDateTime\th1\th2\n\t99.99.99 27:61:61.99\t1\t2\n\t3\t4\n";
+        let (repair, after) = repaired("bad_datetime", "99.99.99 27:61:61.99", content);
+        assert_eq!(repair.state, OscState::HalfPrefixed);
+        let RepairAction::Skipped(reason) = &repair.action else {
+            panic!("{repair:?}");
+        };
+        assert!(reason.contains("not a valid datetime"), "{reason}");
+        assert_eq!(after, content);
+    }
+
+    #[test]
+    fn own_time_column_is_not_a_prefix() {
+        let content = "Time\th1\th2\n01.06.23 12:00:00.00\t1\t2\n01.06.23 12:00:01.00\t3\t4\n";
+        let (repair, after) = repaired("time_column", "01.06.23 12:00:00.00", content);
+        assert_eq!(repair.state, OscState::Unprocessed);
+        assert_eq!(repair.action, RepairAction::None);
+        assert_eq!(after, content);
+    }
+}
@@ -0,0 +1,1601 @@
+//! report holds the typed summary of a cleaning run, which can be written to a file
+//! as JSON or YAML. Field order is fixed by the struct definitions and files are sorted
+//! by path, so reports of two runs can be compared with a plain diff. A JSON report can
+//! also be streamed while the files are cleaned, see ReportWriter, which gives up on the
+//! order.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Local, NaiveDateTime};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{
+    audit::Audit, build_info::BuildInfo, spotcheck::SpotCheck, tmpfile::write_atomic, CheckId,
+    FileOutcome, Finding, OscPrefix, Outcome, SkipReason,
+};
+
+/// Report is the summary of a cleaning run, with one entry per file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Report {
+    /// the build of the cleaner that wrote the report
+    #[serde(default)]
+    pub build: BuildInfo,
+    /// who ran the cleaner where, with --audit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit: Option<Audit>,
+    #[serde(serialize_with = "lossy_path")]
+    pub directory: PathBuf,
+    /// the directory is not valid UTF-8 and is written with replacement characters
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub directory_lossy: bool,
+    pub summary: Summary,
+    /// number of skipped files per extension that is not defined in the cfg file
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub unknown_extensions: BTreeMap<String, usize>,
+    /// number of files cleaned with the rules of another extension, e.g. "TXT as DAT"
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mapped_extensions: BTreeMap<String, usize>,
+    /// time covered by the files per extension, see coverage
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub coverage: BTreeMap<String, Coverage>,
+    /// the summary per station, if the cfg file has a station_pattern
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub stations: BTreeMap<String, Summary>,
+    /// number of subdirectories that were not processed
+    #[serde(default)]
+    pub subdirectories: usize,
+    /// the files were a random sample of the directory, see Sample
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample: Option<Sample>,
+    /// the run stopped after a number of files, see Limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<Limit>,
+    /// bytes the user could still write to the file system of the directory after the
+    /// run, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub free_space_bytes: Option<u64>,
+    /// files much smaller than the others of their type, with --size-outliers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub size_outliers: Vec<SizeOutlier>,
+    /// files with the ID of another instrument than expected, see instrument
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub foreign_instruments: Vec<ForeignInstrument>,
+    /// hourly files missing between the first and the last file, with --missing-files
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing_files: Vec<MissingFiles>,
+    /// the run stopped starting new files after --max-runtime; the done marker lists the
+    /// files the next run cleans
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+    /// the outcomes are what the run would have done, with --dry-run; no file was changed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+    /// the unchanged files hashed again after the run, with --spot-check
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spot_check: Option<SpotCheck>,
+    /// where the deleted files were moved to, with --quarantine
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quarantine: Option<Quarantine>,
+    pub files: Vec<FileOutcome>,
+}
+
+/// ReportWriter writes a JSON report one file at a time, while they are cleaned, so that
+/// a directory of a million files takes no more memory than one of ten. The files are
+/// in the order they were pushed, one per line, and the other fields follow them in the
+/// order of their names, once they are known, see finish; Report::read reads it like
+/// any other report.
+pub struct ReportWriter<W: io::Write> {
+    out: W,
+    n_files: usize,
+    include_unchanged: bool,
+    without_content: bool,
+}
+
+impl<W: io::Write> ReportWriter<W> {
+    /// new starts the report in out. include_unchanged and without_content are those of
+    /// Report::new and Report::without_content.
+    pub fn new(mut out: W, include_unchanged: bool, without_content: bool) -> io::Result<Self> {
+        out.write_all(b"{\n  \"files\": [")?;
+        Ok(ReportWriter {
+            out,
+            n_files: 0,
+            include_unchanged,
+            without_content,
+        })
+    }
+
+    /// push writes file to the report.
+    pub fn push(&mut self, file: &FileOutcome) -> io::Result<()> {
+        if !self.include_unchanged && file.outcome == Outcome::Unchanged {
+            return Ok(());
+        }
+        self.out.write_all(if self.n_files == 0 {
+            b"\n    "
+        } else {
+            b",\n    "
+        })?;
+        let lossy = file.path.to_str().is_none();
+        if lossy || self.without_content {
+            let mut file = file.clone();
+            file.path_lossy = lossy;
+            if self.without_content {
+                strip_content(&mut file);
+            }
+            serde_json::to_writer(&mut self.out, &file)
+        } else {
+            serde_json::to_writer(&mut self.out, file)
+        }
+        .map_err(io::Error::other)?;
+        self.n_files += 1;
+        Ok(())
+    }
+
+    /// finish writes the fields of head other than its files, which must have been
+    /// pushed, and returns out. head is usually Report::head.
+    pub fn finish(mut self, head: &Report) -> io::Result<W> {
+        let serde_json::Value::Object(mut fields) =
+            serde_json::to_value(head).map_err(io::Error::other)?
+        else {
+            unreachable!("a report serializes to an object");
+        };
+        fields.remove("files");
+        self.out
+            .write_all(if self.n_files == 0 { b"]" } else { b"\n  ]" })?;
+        for (key, value) in fields.iter() {
+            write!(self.out, ",\n  {}: ", serde_json::Value::from(key.as_str()))?;
+            serde_json::to_writer(&mut self.out, value).map_err(io::Error::other)?;
+        }
+        self.out.write_all(b"\n}\n")?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// strip_content removes the content of the removed lines of file, see
+/// Report::without_content.
+fn strip_content(file: &mut FileOutcome) {
+    for line in file.removed_lines.iter_mut() {
+        line.content = None;
+    }
+}
+
+/// Limit describes a run that stopped after a number of files, e.g. to try a new cfg
+/// file on a huge directory. The other files were not looked at.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Limit {
+    /// number of files to look at; files skipped by the manifest do not count
+    pub limit: u64,
+    /// number of files of the directory, or of the sample
+    pub total: usize,
+    /// number of files that were not looked at
+    pub not_processed: usize,
+}
+
+/// Quarantine describes the directory the files the checks deleted were moved to
+/// instead of being removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quarantine {
+    #[serde(serialize_with = "lossy_path")]
+    pub directory: PathBuf,
+    /// number of files moved there by the run
+    pub files: usize,
+}
+
+/// Sample describes a run on a random sample of the files of a directory, with the
+/// summary extrapolated to all files.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sample {
+    pub seed: u64,
+    /// number of files sampled and number of files in the directory
+    pub sampled: usize,
+    pub total: usize,
+    pub extrapolated: Summary,
+}
+
+/// Coverage is the time covered by the files of one extension.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Coverage {
+    /// time span of each file, sorted by start
+    pub intervals: Vec<Interval>,
+    /// gaps between consecutive files that are longer than the threshold
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gaps: Vec<Gap>,
+    /// files without any parseable timestamp
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "lossy_paths"
+    )]
+    pub no_timestamps: Vec<PathBuf>,
+    /// pairs of files covering the same time, if overlaps were looked for
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overlaps: Vec<Overlap>,
+}
+
+/// Overlap is a time covered by two files, e.g. a file and its copy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Overlap {
+    #[serde(serialize_with = "lossy_path")]
+    pub first: PathBuf,
+    #[serde(serialize_with = "lossy_path")]
+    pub second: PathBuf,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub seconds: i64,
+}
+
+/// Interval is the time span of one file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interval {
+    #[serde(serialize_with = "lossy_path")]
+    pub path: PathBuf,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+/// Gap is a time without data between the end of all files before it and the start of
+/// the next file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Gap {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub seconds: i64,
+    /// the file after the gap
+    #[serde(serialize_with = "lossy_path")]
+    pub before: PathBuf,
+}
+
+/// Summary counts the outcomes of all files of a run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Summary {
+    pub scanned: usize,
+    pub unchanged: usize,
+    pub modified: usize,
+    pub deleted: usize,
+    /// files deleted because they were empty, included in deleted
+    pub empty: usize,
+    /// files that would have been deleted, but were flagged
+    pub flagged: usize,
+    pub skipped: usize,
+    pub errors: usize,
+    /// files of a readonly type, checked but never changed
+    #[serde(default)]
+    pub protected: usize,
+    /// bytes written by rewriting the modified files
+    #[serde(default)]
+    pub bytes_written: u64,
+    /// bytes freed by deleting files
+    #[serde(default)]
+    pub bytes_deleted: u64,
+}
+
+impl Summary {
+    /// from_outcomes counts the outcomes of a run.
+    pub fn from_outcomes<'a>(outcomes: impl IntoIterator<Item = &'a FileOutcome>) -> Self {
+        let mut summary = Summary::default();
+        for file in outcomes {
+            summary.add(file);
+        }
+        summary
+    }
+
+    /// add counts the outcome of one more file.
+    pub fn add(&mut self, file: &FileOutcome) {
+        self.scanned += 1;
+        self.bytes_written += file.bytes_written;
+        self.bytes_deleted += file.bytes_deleted;
+        match file.outcome {
+            Outcome::Unchanged => self.unchanged += 1,
+            Outcome::Modified => self.modified += 1,
+            Outcome::Deleted(CheckId::EmptyFile) => {
+                self.deleted += 1;
+                self.empty += 1;
+            }
+            Outcome::Deleted(_) => self.deleted += 1,
+            Outcome::Flagged(_) => self.flagged += 1,
+            Outcome::Skipped(_) => self.skipped += 1,
+            Outcome::Error => self.errors += 1,
+            Outcome::Protected => self.protected += 1,
+        }
+    }
+
+    /// extrapolated scales the counts and sizes by factor, e.g. from a sample of the
+    /// files to all files, rounded.
+    pub fn extrapolated(&self, factor: f64) -> Self {
+        let n = |n: usize| (n as f64 * factor).round() as usize;
+        let bytes = |b: u64| (b as f64 * factor).round() as u64;
+        Summary {
+            scanned: n(self.scanned),
+            unchanged: n(self.unchanged),
+            modified: n(self.modified),
+            deleted: n(self.deleted),
+            empty: n(self.empty),
+            flagged: n(self.flagged),
+            skipped: n(self.skipped),
+            errors: n(self.errors),
+            protected: n(self.protected),
+            bytes_written: bytes(self.bytes_written),
+            bytes_deleted: bytes(self.bytes_deleted),
+        }
+    }
+
+    /// render_line renders the closing line of a run, e.g. "scanned 12 files: 1 deleted,
+    /// 3 modified, 7 unchanged, 1 skipped, 0 errors in 5.21ms". flagged and protected
+    /// files are only mentioned if there are any.
+    pub fn render_line(&self, elapsed: Duration) -> String {
+        format!("{} in {elapsed:.2?}", self.render_counts())
+    }
+
+    /// render_counts renders the counts of the summary, e.g. for one station.
+    pub fn render_counts(&self) -> String {
+        let mut line = format!(
+            "scanned {} files: {} deleted, {} modified, {} unchanged, ",
+            self.scanned, self.deleted, self.modified, self.unchanged
+        );
+        if self.flagged > 0 {
+            line.push_str(&format!("{} flagged, ", self.flagged));
+        }
+        if self.protected > 0 {
+            line.push_str(&format!("{} protected, ", self.protected));
+        }
+        line.push_str(&format!("{} skipped, {} errors", self.skipped, self.errors));
+        line
+    }
+
+    /// render_io renders how much was written and deleted, e.g. "rewrote 1.4 GiB across
+    /// 212 files, deleted 87.0 MiB across 311 files", or None if nothing was.
+    pub fn render_io(&self) -> Option<String> {
+        if self.modified == 0 && self.deleted == 0 {
+            return None;
+        }
+        Some(format!(
+            "rewrote {} across {} files, deleted {} across {} files",
+            format_size(self.bytes_written),
+            self.modified,
+            format_size(self.bytes_deleted),
+            self.deleted
+        ))
+    }
+}
+
+/// Tally adds up what a report and the log say about all files of a run, one file at a
+/// time, so that a run streaming its report need not keep the files, see ReportWriter.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub summary: Summary,
+    /// the summary per station, of the files that have one
+    pub stations: BTreeMap<String, Summary>,
+    /// number of files skipped because their extension is not defined in the cfg file,
+    /// per extension as it is on disk
+    pub unknown_extensions: BTreeMap<String, usize>,
+    /// number of files per mapped extension, keyed like "txt as DAT" with the extension
+    /// as it is on disk and the one whose rules were applied
+    pub mapped_extensions: BTreeMap<String, usize>,
+    /// the files whose instrument ID is not the expected one, sorted by path
+    pub foreign_instruments: Vec<ForeignInstrument>,
+    /// number of files copied by --backup
+    pub backups: usize,
+}
+
+impl Tally {
+    /// of adds up outcomes.
+    pub fn of<'a>(outcomes: impl IntoIterator<Item = &'a FileOutcome>) -> Self {
+        let mut tally = Tally::default();
+        for file in outcomes {
+            tally.add(file);
+        }
+        tally
+    }
+
+    /// add adds up one more file.
+    pub fn add(&mut self, file: &FileOutcome) {
+        self.summary.add(file);
+        if let Some(station) = &file.station {
+            self.stations.entry(station.clone()).or_default().add(file);
+        }
+        if file.outcome == Outcome::Skipped(SkipReason::UnknownExtension) {
+            let ext = file.display_extension().unwrap_or_default().to_string();
+            *self.unknown_extensions.entry(ext).or_insert(0) += 1;
+        }
+        if let (Some(ext), Some(effective)) = (file.display_extension(), &file.effective_extension)
+        {
+            *self
+                .mapped_extensions
+                .entry(format!("{ext} as {effective}"))
+                .or_insert(0) += 1;
+        }
+        if let (Some(id), Some(expected)) = (&file.instrument_id, &file.expected_instrument_id) {
+            if id != expected {
+                let i = self
+                    .foreign_instruments
+                    .partition_point(|foreign| foreign.path < file.path);
+                self.foreign_instruments.insert(
+                    i,
+                    ForeignInstrument {
+                        path: file.path.clone(),
+                        id: id.clone(),
+                        expected: expected.clone(),
+                        outcome: file.outcome,
+                    },
+                );
+            }
+        }
+        if file.backup.is_some() {
+            self.backups += 1;
+        }
+    }
+}
+
+impl Report {
+    /// new builds the report of a run. The summary always counts all files, unchanged
+    /// files are only listed if include_unchanged is set.
+    pub fn new(directory: &Path, outcomes: &[FileOutcome], include_unchanged: bool) -> Self {
+        let mut files: Vec<FileOutcome> = outcomes
+            .iter()
+            .filter(|f| include_unchanged || f.outcome != Outcome::Unchanged)
+            .cloned()
+            .collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        for file in files.iter_mut() {
+            file.path_lossy = file.path.to_str().is_none();
+        }
+        Report {
+            files,
+            ..Report::head(directory, &Tally::of(outcomes))
+        }
+    }
+
+    /// head builds the report of a run without its list of files, with the totals of
+    /// tally, for ReportWriter.
+    pub fn head(directory: &Path, tally: &Tally) -> Self {
+        Report {
+            build: BuildInfo::current(),
+            audit: None,
+            directory: directory.to_path_buf(),
+            directory_lossy: directory.to_str().is_none(),
+            summary: tally.summary.clone(),
+            unknown_extensions: tally.unknown_extensions.clone(),
+            mapped_extensions: tally.mapped_extensions.clone(),
+            coverage: BTreeMap::new(),
+            stations: tally.stations.clone(),
+            subdirectories: 0,
+            sample: None,
+            limit: None,
+            free_space_bytes: None,
+            size_outliers: Vec::new(),
+            foreign_instruments: tally.foreign_instruments.clone(),
+            missing_files: Vec::new(),
+            timed_out: false,
+            dry_run: false,
+            spot_check: None,
+            quarantine: None,
+            files: Vec::new(),
+        }
+    }
+
+    /// without_content removes the content of removed lines from the report, keeping
+    /// their line numbers.
+    pub fn without_content(mut self) -> Self {
+        for file in self.files.iter_mut() {
+            strip_content(file);
+        }
+        self
+    }
+
+    /// write_json writes the report as pretty-printed JSON.
+    pub fn write_json(&self, filename: impl AsRef<Path>) -> io::Result<()> {
+        let file = fs::File::create(filename)?;
+        serde_json::to_writer_pretty(io::BufWriter::new(file), self).map_err(io::Error::other)
+    }
+
+    /// write_yaml writes the report as YAML.
+    #[cfg(feature = "yaml-config")]
+    pub fn write_yaml(&self, filename: impl AsRef<Path>) -> io::Result<()> {
+        let file = fs::File::create(filename)?;
+        serde_yaml::to_writer(io::BufWriter::new(file), self).map_err(io::Error::other)
+    }
+
+    /// read reads a report written by write_json, or by write_yaml if filename ends in
+    /// `.yaml` or `.yml`.
+    pub fn read(filename: impl AsRef<Path>) -> io::Result<Self> {
+        let filename = filename.as_ref();
+        let reader = io::BufReader::new(fs::File::open(filename)?);
+        let is_yaml = filename
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"));
+        if is_yaml {
+            #[cfg(feature = "yaml-config")]
+            return serde_yaml::from_reader(reader).map_err(io::Error::other);
+            #[cfg(not(feature = "yaml-config"))]
+            return Err(io::Error::other(
+                "YAML reports need the yaml-config feature",
+            ));
+        }
+        serde_json::from_reader(reader).map_err(io::Error::other)
+    }
+
+    /// line_deviations returns the files whose number of data lines after cleaning is
+    /// off expected by more than tolerance, in the order of the report. files without a
+    /// count, e.g. deleted ones, are left out.
+    pub fn line_deviations(&self, expected: usize, tolerance: usize) -> Vec<LineDeviation> {
+        self.files
+            .iter()
+            .filter_map(|file| {
+                let lines = file.lines_after?;
+                (lines.abs_diff(expected) > tolerance).then(|| LineDeviation {
+                    path: file.path.clone(),
+                    lines,
+                    expected,
+                })
+            })
+            .collect()
+    }
+
+    /// n_counted returns the number of files of the report with a line count.
+    pub fn n_counted(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|file| file.lines_after.is_some())
+            .count()
+    }
+}
+
+/// LineDeviation is a file whose number of data lines is not the expected one, e.g. an
+/// hourly file of a 1 s logger that lacks some of its 3600 lines, see
+/// Report::line_deviations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineDeviation {
+    pub path: PathBuf,
+    pub lines: usize,
+    pub expected: usize,
+}
+
+impl LineDeviation {
+    /// render returns the path with its count and the difference, e.g.
+    /// `"a.DAT": 3412 lines (-188)`.
+    pub fn render(&self) -> String {
+        let diff = self.lines as i64 - self.expected as i64;
+        format!("{:?}: {} lines ({diff:+})", self.path, self.lines)
+    }
+}
+
+/// lossy_path writes a path as a string, with replacement characters where it is not
+/// valid UTF-8, e.g. a station directory named in a legacy encoding. serde would fail
+/// the whole report on such a path.
+pub(crate) fn lossy_path<S: Serializer>(path: &Path, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&path.to_string_lossy())
+}
+
+/// lossy_opt_path writes an optional path like lossy_path.
+pub(crate) fn lossy_opt_path<S: Serializer>(
+    path: &Option<PathBuf>,
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    match path {
+        Some(path) => s.serialize_some(&path.to_string_lossy()),
+        None => s.serialize_none(),
+    }
+}
+
+/// lossy_paths writes paths like lossy_path.
+pub(crate) fn lossy_paths<S: Serializer>(paths: &[PathBuf], s: S) -> Result<S::Ok, S::Error> {
+    s.collect_seq(paths.iter().map(|path| path.to_string_lossy()))
+}
+
+/// coverage collects the time spans of the files per extension, or per station and
+/// extension, see FileOutcome::group, and finds the gaps longer than max_gap_s seconds
+/// between them. If overlap_tolerance_s is given, pairs of files overlapping by more
+/// than that are collected, too. Files without time span and extension are not
+/// included.
+pub fn coverage(
+    outcomes: &[FileOutcome],
+    max_gap_s: i64,
+    overlap_tolerance_s: Option<i64>,
+) -> BTreeMap<String, Coverage> {
+    let mut coverage: BTreeMap<String, Coverage> = BTreeMap::new();
+    for file in outcomes {
+        let Some(group) = file.group() else {
+            continue;
+        };
+        if let Some(span) = file.time_span {
+            coverage.entry(group).or_default().intervals.push(Interval {
+                path: file.path.clone(),
+                start: span.start,
+                end: span.end,
+            });
+        } else if file.no_timestamps {
+            let cov = coverage.entry(group).or_default();
+            cov.no_timestamps.push(file.path.clone());
+        }
+    }
+    for cov in coverage.values_mut() {
+        cov.intervals
+            .sort_by(|a, b| (a.start, &a.path).cmp(&(b.start, &b.path)));
+        cov.no_timestamps.sort();
+        let mut covered_until: Option<NaiveDateTime> = None;
+        for interval in cov.intervals.iter() {
+            if let Some(until) = covered_until {
+                let seconds = (interval.start - until).num_seconds();
+                if seconds > max_gap_s {
+                    cov.gaps.push(Gap {
+                        start: until,
+                        end: interval.start,
+                        seconds,
+                        before: interval.path.clone(),
+                    });
+                }
+            }
+            covered_until = covered_until.max(Some(interval.end));
+        }
+        if let Some(tolerance) = overlap_tolerance_s {
+            cov.overlaps = find_overlaps(&cov.intervals, tolerance);
+        }
+    }
+    coverage
+}
+
+/// MissingFiles are the files of an extension that are missing in a directory.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MissingFiles {
+    pub extension: String,
+    /// first and last hour the files of the directory cover, as YYYY-mm-dd HH:MM
+    pub from: String,
+    pub to: String,
+    /// the names of the missing files, in time order
+    pub names: Vec<String>,
+}
+
+/// SizeOutlier is a file much smaller than the other files of its extension, most likely
+/// an aborted acquisition even if it passed the checks, see size_outliers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizeOutlier {
+    #[serde(serialize_with = "lossy_path")]
+    pub path: PathBuf,
+    /// the extension, or station and extension, see FileOutcome::group
+    pub group: String,
+    /// size of the file before cleaning, in bytes
+    pub size: u64,
+    /// median size of the files of the group, in bytes
+    pub median: u64,
+}
+
+/// ForeignInstrument is a file written by another instrument than the files it is
+/// with, see instrument.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForeignInstrument {
+    #[serde(serialize_with = "lossy_path")]
+    pub path: PathBuf,
+    /// the ID in the file
+    pub id: String,
+    pub expected: String,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+/// render_foreign_instruments returns a warning listing the files with the ID of
+/// another instrument, if there are any.
+pub fn render_foreign_instruments(tally: &Tally) -> Option<String> {
+    let foreign = &tally.foreign_instruments;
+    if foreign.is_empty() {
+        return None;
+    }
+    let mut out = format!(
+        "!!! WARNING: {} written by another instrument:",
+        n_files(foreign.len())
+    );
+    for file in foreign {
+        let _ = write!(
+            out,
+            "\n  {:?}: ID {}, expected {} ({})",
+            file.path,
+            file.id,
+            file.expected,
+            outcome_label(file.outcome)
+        );
+    }
+    Some(out)
+}
+
+/// DEFAULT_OUTLIER_FRACTION is the share of the median size below which a file is an
+/// outlier by default.
+pub const DEFAULT_OUTLIER_FRACTION: f64 = 0.1;
+
+/// DEFAULT_OUTLIER_MIN_FILES is the number of files a group needs by default before its
+/// median means anything.
+pub const DEFAULT_OUTLIER_MIN_FILES: usize = 5;
+
+/// size_outliers returns the files smaller than fraction of the median size of their
+/// extension, or station and extension, sorted by path. sizes holds the size of each
+/// file before it was cleaned. only kept files count, deleted and flagged ones are
+/// reported anyway; groups of less than min_files files are left out.
+pub fn size_outliers(
+    outcomes: &[FileOutcome],
+    sizes: &HashMap<PathBuf, u64>,
+    min_files: usize,
+    fraction: f64,
+) -> Vec<SizeOutlier> {
+    let mut groups: BTreeMap<String, Vec<(&Path, u64)>> = BTreeMap::new();
+    for file in outcomes {
+        let kept = matches!(
+            file.outcome,
+            Outcome::Unchanged | Outcome::Modified | Outcome::Protected
+        );
+        if let (true, Some(group), Some(size)) = (kept, file.group(), sizes.get(&file.path)) {
+            groups.entry(group).or_default().push((&file.path, *size));
+        }
+    }
+    let mut outliers = Vec::new();
+    for (group, files) in groups.into_iter() {
+        if files.len() < min_files.max(1) {
+            continue;
+        }
+        let mut sorted: Vec<u64> = files.iter().map(|(_, size)| *size).collect();
+        sorted.sort_unstable();
+        let mid = sorted.len() / 2;
+        let median = if sorted.len().is_multiple_of(2) {
+            (sorted[mid - 1] + sorted[mid]) / 2
+        } else {
+            sorted[mid]
+        };
+        for (path, size) in files {
+            if (size as f64) < median as f64 * fraction {
+                outliers.push(SizeOutlier {
+                    path: path.to_path_buf(),
+                    group: group.clone(),
+                    size,
+                    median,
+                });
+            }
+        }
+    }
+    outliers.sort_by(|a, b| a.path.cmp(&b.path));
+    outliers
+}
+
+/// render_size_outliers returns a warning listing the size outliers, if there are any.
+pub fn render_size_outliers(outliers: &[SizeOutlier]) -> Option<String> {
+    if outliers.is_empty() {
+        return None;
+    }
+    let mut out = format!(
+        "!!! WARNING: {} much smaller than the others of their type, maybe aborted \
+         acquisitions:",
+        n_files(outliers.len())
+    );
+    for outlier in outliers {
+        let _ = write!(
+            out,
+            "\n  {:?}: {}, median of {} {}",
+            outlier.path,
+            format_size(outlier.size),
+            outlier.group,
+            format_size(outlier.median)
+        );
+    }
+    Some(out)
+}
+
+/// render_missing_files returns a warning listing the missing files, if any, at most
+/// cap names per extension.
+pub fn render_missing_files(missing: &[MissingFiles], cap: usize) -> Option<String> {
+    let first = missing.first()?;
+    let n: usize = missing.iter().map(|missing| missing.names.len()).sum();
+    let mut out = format!(
+        "!!! WARNING: {} missing between {} and {}:",
+        n_files(n),
+        first.from,
+        first.to
+    );
+    for missing in missing {
+        let mut names = missing.names[..missing.names.len().min(cap)].join(", ");
+        if missing.names.len() > cap {
+            let _ = write!(names, ", … and {} more", missing.names.len() - cap);
+        }
+        let _ = write!(out, "\n  {}: {names}", missing.extension);
+    }
+    Some(out)
+}
+
+/// find_overlaps returns all pairs of intervals that overlap by more than tolerance_s
+/// seconds. intervals must be sorted by start. Intervals that only touch, i.e. one ends
+/// when the next starts, do not overlap.
+pub fn find_overlaps(intervals: &[Interval], tolerance_s: i64) -> Vec<Overlap> {
+    let mut overlaps = Vec::new();
+    for (i, a) in intervals.iter().enumerate() {
+        for b in intervals[i + 1..].iter().take_while(|b| b.start < a.end) {
+            let end = a.end.min(b.end);
+            let seconds = (end - b.start).num_seconds();
+            if seconds > tolerance_s {
+                overlaps.push(Overlap {
+                    first: a.path.clone(),
+                    second: b.path.clone(),
+                    start: b.start,
+                    end,
+                    seconds,
+                });
+            }
+        }
+    }
+    overlaps
+}
+
+/// render_overlaps returns a warning listing the overlapping files, if there are any.
+pub fn render_overlaps(coverage: &BTreeMap<String, Coverage>) -> Option<String> {
+    let n: usize = coverage.values().map(|c| c.overlaps.len()).sum();
+    if n == 0 {
+        return None;
+    }
+    let mut out = format!("!!! WARNING: {n} pair(s) of files cover the same time:");
+    for overlap in coverage.values().flat_map(|c| c.overlaps.iter()) {
+        let _ = write!(
+            out,
+            "\n  {:?} and {:?} overlap by {} s ({} to {})",
+            overlap.first, overlap.second, overlap.seconds, overlap.start, overlap.end
+        );
+    }
+    Some(out)
+}
+
+/// render_mapped lists the files that were cleaned with the rules of another extension.
+pub fn render_mapped(tally: &Tally) -> Option<String> {
+    let counts = &tally.mapped_extensions;
+    if counts.is_empty() {
+        return None;
+    }
+    let mut msg = "cleaned with the rules of another extension (--ext-map):".to_string();
+    for (mapping, n) in counts.iter() {
+        msg.push_str(&format!(
+            "\n  .{mapping}: {n} file{}",
+            if *n == 1 { "" } else { "s" }
+        ));
+    }
+    Some(msg)
+}
+
+/// render_unknown_warning returns a warning listing the unknown extensions if more
+/// than max_fraction of the scanned files were skipped because of them.
+pub fn render_unknown_warning(tally: &Tally, max_fraction: f64) -> Option<String> {
+    let counts = &tally.unknown_extensions;
+    let n_unknown: usize = counts.values().sum();
+    let n_scanned = tally.summary.scanned;
+    if n_scanned == 0 || n_unknown as f64 / n_scanned as f64 <= max_fraction {
+        return None;
+    }
+    let mut out = format!(
+        "!!! WARNING: {n_unknown} of {n_scanned} files were skipped because their extension \
+         is not defined in the cfg file:"
+    );
+    for (ext, n) in counts.iter() {
+        let _ = write!(out, "\n  .{ext}: {}", n_files(*n));
+    }
+    out.push_str("\n!!! add them to the cfg file if they should be cleaned");
+    Some(out)
+}
+
+/// render_dry_run returns what a run would do to file, e.g. `would modify "a.DAT":
+/// remove line (2 times)`, or None if it would leave it as it is. the actions of a
+/// modified file are its findings, each after its `->`.
+pub fn render_dry_run(file: &FileOutcome) -> Option<String> {
+    match file.outcome {
+        Outcome::Deleted(id) => Some(format!(
+            "would delete {:?} ({})",
+            file.path,
+            check_label(id)
+        )),
+        Outcome::Flagged(id) => Some(format!("would flag {:?} ({})", file.path, check_label(id))),
+        Outcome::Modified => {
+            let mut actions: Vec<(&str, usize)> = Vec::new();
+            for finding in file.findings.iter() {
+                let action = finding
+                    .message
+                    .rsplit_once(" -> ")
+                    .map_or(finding.message.as_str(), |(_, action)| action);
+                match actions.last_mut() {
+                    Some((last, n)) if *last == action => *n += 1,
+                    _ => actions.push((action, 1)),
+                }
+            }
+            let actions: Vec<String> = actions
+                .into_iter()
+                .map(|(action, n)| match n {
+                    1 => action.to_string(),
+                    n => format!("{action} ({n} times)"),
+                })
+                .collect();
+            let mut line = format!("would modify {:?}: {}", file.path, actions.join(", "));
+            if let Some(renamed_to) = &file.renamed_to {
+                let _ = write!(line, ", rename to {renamed_to:?}");
+            }
+            Some(line)
+        }
+        _ => None,
+    }
+}
+
+/// render_dry_run_counts returns the last line of a dry run, with the number of files
+/// that would be deleted, flagged, modified or left untouched.
+pub fn render_dry_run_counts(summary: &Summary) -> String {
+    let untouched = summary.scanned - summary.deleted - summary.flagged - summary.modified;
+    format!(
+        "dry run, nothing was changed: would delete {}, flag {}, modify {} and leave {} \
+         untouched",
+        summary.deleted,
+        summary.flagged,
+        summary.modified,
+        n_files(untouched)
+    )
+}
+
+/// render_would_differ lists the files whose outcome under the alternate cfg at profile
+/// differs from the actual one, at most cap of them, or returns None if there are none.
+pub fn render_would_differ(outcomes: &[FileOutcome], profile: &Path, cap: usize) -> Option<String> {
+    let mut differ: Vec<&FileOutcome> = outcomes.iter().filter(|f| f.would_differ).collect();
+    if differ.is_empty() {
+        return None;
+    }
+    differ.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut out = format!(
+        "{} would end differently under {profile:?}:",
+        n_files(differ.len())
+    );
+    for f in differ.iter().take(cap) {
+        let alternate = f.alternate.map(outcome_label).unwrap_or_default();
+        let _ = write!(
+            out,
+            "\n  {:?}: {} -> {alternate}",
+            f.path,
+            outcome_label(f.outcome)
+        );
+    }
+    if differ.len() > cap {
+        let _ = write!(out, "\n  … and {} more", differ.len() - cap);
+    }
+    Some(out)
+}
+
+/// render_slowest lists the n files the run spent the most time on, slowest first, or
+/// returns None if no file was timed.
+pub fn render_slowest(outcomes: &[FileOutcome], n: usize) -> Option<String> {
+    let mut timed: Vec<(&FileOutcome, u64)> = outcomes
+        .iter()
+        .filter_map(|f| f.duration_us.map(|us| (f, us)))
+        .collect();
+    if timed.is_empty() || n == 0 {
+        return None;
+    }
+    timed.sort_by(|(a, a_us), (b, b_us)| b_us.cmp(a_us).then_with(|| a.path.cmp(&b.path)));
+    let mut out = format!(
+        "slowest {} of {}:",
+        n.min(timed.len()),
+        n_files(timed.len())
+    );
+    for (f, us) in timed.iter().take(n) {
+        let _ = write!(
+            out,
+            "\n  {:>10.2?}  {:?}: {}",
+            Duration::from_micros(*us),
+            f.path,
+            outcome_label(f.outcome)
+        );
+    }
+    Some(out)
+}
+
+/// render_metrics formats the summary of a run in the Prometheus text exposition format.
+/// If directory is given, every sample gets a `directory` label. The free space of the
+/// file system is only written if it is known.
+pub fn render_metrics(
+    summary: &Summary,
+    directory: Option<&Path>,
+    duration: Duration,
+    finished: SystemTime,
+    free_space: Option<u64>,
+) -> String {
+    let labels = match directory {
+        Some(dir) => format!("{{directory=\"{}\"}}", escape_label(&dir.to_string_lossy())),
+        None => String::new(),
+    };
+    let timestamp = finished
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    let mut metrics: Vec<(&str, &str, f64)> = vec![
+        (
+            "v25cleaner_files_scanned",
+            "Number of files scanned in the last run.",
+            summary.scanned as f64,
+        ),
+        (
+            "v25cleaner_files_deleted",
+            "Number of files deleted in the last run.",
+            summary.deleted as f64,
+        ),
+        (
+            "v25cleaner_files_modified",
+            "Number of files modified in the last run.",
+            summary.modified as f64,
+        ),
+        (
+            "v25cleaner_errors",
+            "Number of files that could not be processed in the last run.",
+            summary.errors as f64,
+        ),
+        (
+            "v25cleaner_run_duration_seconds",
+            "Duration of the last run in seconds.",
+            duration.as_secs_f64(),
+        ),
+        (
+            "v25cleaner_last_run_timestamp_seconds",
+            "Unix time at which the last run finished.",
+            timestamp,
+        ),
+    ];
+    if let Some(free) = free_space {
+        metrics.push((
+            "v25cleaner_free_space_bytes",
+            "Bytes free on the file system of the directory after the last run.",
+            free as f64,
+        ));
+    }
+    let mut out = String::new();
+    for (name, help, value) in metrics {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} gauge");
+        let _ = writeln!(out, "{name}{labels} {value}");
+    }
+    out
+}
+
+/// escape_label escapes a Prometheus label value.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// write_metrics writes the metrics atomically, so a collector reading filename never
+/// sees a partially written file.
+pub fn write_metrics(filename: impl AsRef<Path>, metrics: &str) -> io::Result<()> {
+    write_atomic(filename.as_ref(), |file| file.write_all(metrics.as_bytes()))
+}
+
+/// format_size formats a number of bytes with binary units, e.g. 512 B or 1.4 GiB.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+/// render_finding formats a finding on the file at path as one line that starts with
+/// the stable check id and ends with its values, e.g.
+/// `[too_few_lines] nok: "a.DAT": has 0 data line(s) below the header, want at least 1 -> delete file data_lines=0 min_data_lines=1`.
+pub fn render_finding(path: &Path, finding: &Finding) -> String {
+    let mut out = format!("[{}] nok: {path:?}: {}", finding.check, finding.message);
+    for (key, value) in finding.values.iter() {
+        let _ = write!(out, " {key}={value}");
+    }
+    out
+}
+
+/// render_osc_prefix formats what the DateTime prefix did to an OSC file, e.g.
+/// `datetime prefix of "a.OSC": '01.06.23 12:00:00.00' applied to 2 data line(s),
+/// DateTime column added to the header`.
+pub fn render_osc_prefix(path: &Path, prefix: &OscPrefix) -> String {
+    let source = prefix.source_datetime.as_deref().unwrap_or_default();
+    if let Some(reason) = &prefix.skipped {
+        return format!("datetime prefix of {path:?}: skipped, {reason}");
+    }
+    let mut out = format!(
+        "datetime prefix of {path:?}: '{source}' applied to {} data line(s)",
+        prefix.lines_prefixed
+    );
+    if prefix.header_modified {
+        out.push_str(", DateTime column added to the header");
+    }
+    out
+}
+
+/// render_findings formats the findings of all outcomes with render_finding, one per
+/// line, in the order of the outcomes; with max_per_check, see MessageCap.
+pub fn render_findings(outcomes: &[FileOutcome], max_per_check: Option<usize>) -> String {
+    let mut cap = MessageCap::new(max_per_check);
+    let mut out = String::new();
+    for file in outcomes {
+        for finding in file.findings.iter().filter(|f| cap.admit(f.check)) {
+            let _ = writeln!(out, "{}", render_finding(&file.path, finding));
+        }
+    }
+    for line in cap.render_suppressed() {
+        let _ = writeln!(out, "{line}");
+    }
+    out
+}
+
+/// MessageCap limits the lines of findings to the first max of each check, so a badly
+/// corrupted directory does not print thousands of the same message; the others are
+/// only counted. the outcomes keep all findings.
+#[derive(Debug, Clone, Default)]
+pub struct MessageCap {
+    max: Option<usize>,
+    counts: BTreeMap<CheckId, usize>,
+}
+
+impl MessageCap {
+    /// new caps the lines per check at max, None prints all of them.
+    pub fn new(max: Option<usize>) -> Self {
+        MessageCap {
+            max,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    /// admit counts a finding of check and tells if its line is printed.
+    pub fn admit(&mut self, check: CheckId) -> bool {
+        let n = self.counts.entry(check).or_default();
+        *n += 1;
+        self.max.is_none_or(|max| *n <= max)
+    }
+
+    /// render_suppressed returns one line per check whose findings were not all printed,
+    /// e.g. `… suppressed 4,812 further occurrences of [trailing_empty_line]`.
+    pub fn render_suppressed(&self) -> Vec<String> {
+        let Some(max) = self.max else {
+            return Vec::new();
+        };
+        self.counts
+            .iter()
+            .filter(|(_, n)| **n > max)
+            .map(|(check, n)| {
+                let noun = if n - max == 1 {
+                    "occurrence"
+                } else {
+                    "occurrences"
+                };
+                format!(
+                    "… suppressed {} further {noun} of [{check}]",
+                    thousands(n - max)
+                )
+            })
+            .collect()
+    }
+}
+
+/// thousands formats n with a comma between groups of three digits, e.g. "4,812".
+fn thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// HEALTH_FILE is written to the cleaned directory with the station health of the last
+/// run, see render_health, so it can be read over a network share.
+pub const HEALTH_FILE: &str = "STATION_HEALTH.txt";
+
+/// render_health formats one line per extension for field operators, e.g.
+/// `OSC: 24 files, 2 truncated tails, 0 deleted, coverage 00:00–23:59`. Errors,
+/// flagged files and coverage gaps are only mentioned if there are any; the coverage
+/// only if the extension has one, see coverage. Files of unknown extensions and our
+/// own artifacts are not counted.
+pub fn render_health(outcomes: &[FileOutcome], coverage: &BTreeMap<String, Coverage>) -> String {
+    #[derive(Default)]
+    struct Health {
+        files: usize,
+        truncated: usize,
+        deleted: usize,
+        flagged: usize,
+        errors: usize,
+    }
+    let mut health: BTreeMap<String, Health> = BTreeMap::new();
+    for file in outcomes {
+        let Some(group) = file.group() else {
+            continue;
+        };
+        if let Outcome::Skipped(SkipReason::UnknownExtension | SkipReason::ToolArtifact) =
+            file.outcome
+        {
+            continue;
+        }
+        let h = health.entry(group).or_default();
+        h.files += 1;
+        if file.findings.iter().any(|f| {
+            matches!(
+                f.check,
+                CheckId::MissingFinalNewline
+                    | CheckId::LastLineFieldMismatch
+                    | CheckId::LastFieldTruncated
+            )
+        }) {
+            h.truncated += 1;
+        }
+        match file.outcome {
+            Outcome::Deleted(_) => h.deleted += 1,
+            Outcome::Flagged(_) => h.flagged += 1,
+            Outcome::Error => h.errors += 1,
+            _ => (),
+        }
+    }
+
+    let mut out = String::new();
+    for (group, h) in health.iter() {
+        let _ = write!(
+            out,
+            "{group}: {}, {}, {} deleted",
+            n_files(h.files),
+            count(h.truncated, "truncated tail"),
+            h.deleted
+        );
+        if h.flagged > 0 {
+            let _ = write!(out, ", {} flagged", h.flagged);
+        }
+        if h.errors > 0 {
+            let _ = write!(out, ", {}", count(h.errors, "error"));
+        }
+        if let Some(cov) = coverage.get(group) {
+            // intervals are sorted by start, the last one need not end last
+            let start = cov.intervals.first().map(|i| i.start);
+            let end = cov.intervals.iter().map(|i| i.end).max();
+            if let (Some(start), Some(end)) = (start, end) {
+                let format = if start.date() == end.date() {
+                    "%H:%M"
+                } else {
+                    "%Y-%m-%d %H:%M"
+                };
+                let _ = write!(
+                    out,
+                    ", coverage {}–{}",
+                    start.format(format),
+                    end.format(format)
+                );
+            }
+            if !cov.gaps.is_empty() {
+                let _ = write!(out, ", {}", count(cov.gaps.len(), "gap"));
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// write_health writes the station health of a run that finished at finished to
+/// HEALTH_FILE in dir.
+pub fn write_health(dir: &Path, health: &str, finished: SystemTime) -> io::Result<()> {
+    let finished: DateTime<Local> = finished.into();
+    write_atomic(&dir.join(HEALTH_FILE), |file| {
+        writeln!(
+            file,
+            "# station health, run finished {}",
+            finished.format("%Y-%m-%d %H:%M:%S")
+        )?;
+        file.write_all(health.as_bytes())
+    })
+}
+
+/// GROUP_CAP is the number of files listed per group in the default (non-verbose) output.
+pub const GROUP_CAP: usize = 10;
+
+/// check_label turns a check id into words, e.g. "too few lines".
+fn check_label(id: CheckId) -> String {
+    id.as_str().replace('_', " ")
+}
+
+fn skip_label(reason: SkipReason) -> &'static str {
+    match reason {
+        SkipReason::NoExtension => "no extension",
+        SkipReason::UnknownExtension => "unknown extension",
+        SkipReason::InvalidExtension => "invalid extension",
+        SkipReason::Flagged => "flagged before",
+        SkipReason::ToolArtifact => "tool artifact",
+        SkipReason::Manifest => "unchanged since last clean",
+        SkipReason::VanishedDuringRun => "vanished during run",
+        SkipReason::ChangedDuringRun => "changed during run",
+    }
+}
+
+/// outcome_label turns an outcome into words, e.g. "deleted (too few lines)".
+pub fn outcome_label(outcome: Outcome) -> String {
+    match outcome {
+        Outcome::Unchanged => "unchanged".to_string(),
+        Outcome::Modified => "modified".to_string(),
+        Outcome::Deleted(id) => format!("deleted ({})", check_label(id)),
+        Outcome::Flagged(id) => format!("flagged ({})", check_label(id)),
+        Outcome::Skipped(reason) => format!("skipped ({})", skip_label(reason)),
+        Outcome::Error => "error".to_string(),
+        Outcome::Protected => "protected".to_string(),
+    }
+}
+
+fn n_files(n: usize) -> String {
+    count(n, "file")
+}
+
+/// count formats n of a thing with the plural of its noun, e.g. "2 gaps".
+fn count(n: usize, noun: &str) -> String {
+    match n {
+        1 => format!("1 {noun}"),
+        n => format!("{n} {noun}s"),
+    }
+}
+
+/// render_grouped formats the outcomes of a run grouped by action: deleted files per
+/// check, modified files and errors are listed (at most cap files per group if cap is
+/// given), skipped and unchanged files are only counted.
+pub fn render_grouped(outcomes: &[FileOutcome], cap: Option<usize>) -> String {
+    let mut sorted: Vec<&FileOutcome> = outcomes.iter().collect();
+    sorted.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut grouped = Grouped::new(cap);
+    for file in sorted {
+        grouped.add(file);
+    }
+    grouped.render()
+}
+
+/// Grouped collects what render_grouped lists, one file at a time and in any order: of
+/// each group it keeps the first cap entries by path and counts the others.
+#[derive(Debug, Clone)]
+pub struct Grouped {
+    cap: Option<usize>,
+    groups: Vec<Group>,
+    skipped: Vec<(SkipReason, usize)>,
+    unchanged: usize,
+    protected: usize,
+}
+
+/// Group is a group of Grouped.
+#[derive(Debug, Clone)]
+struct Group {
+    label: String,
+    /// number of entries
+    n: usize,
+    /// the entries shown with the paths of their files, sorted by path
+    shown: Vec<(PathBuf, String)>,
+}
+
+impl Group {
+    fn new(label: String) -> Self {
+        Group {
+            label,
+            n: 0,
+            shown: Vec::new(),
+        }
+    }
+}
+
+impl Grouped {
+    pub fn new(cap: Option<usize>) -> Self {
+        let mut groups = Vec::new();
+        for id in CheckId::ALL {
+            groups.push(Group::new(format!("Deleted ({})", check_label(id))));
+        }
+        for id in CheckId::ALL {
+            groups.push(Group::new(format!("Flagged ({})", check_label(id))));
+        }
+        for label in ["Modified", "Errors", "Warnings"] {
+            groups.push(Group::new(label.to_string()));
+        }
+        Grouped {
+            cap,
+            groups,
+            skipped: Vec::new(),
+            unchanged: 0,
+            protected: 0,
+        }
+    }
+
+    /// add adds file to its group, and each of its warnings to the warnings.
+    pub fn add(&mut self, file: &FileOutcome) {
+        let n_checks = CheckId::ALL.len();
+        match file.outcome {
+            Outcome::Deleted(id) => {
+                let i = CheckId::ALL
+                    .iter()
+                    .position(|&x| x == id)
+                    .unwrap_or_default();
+                self.push(i, file, || format!("{:?}", file.path));
+            }
+            Outcome::Flagged(id) => {
+                let i = CheckId::ALL
+                    .iter()
+                    .position(|&x| x == id)
+                    .unwrap_or_default();
+                self.push(n_checks + i, file, || format!("{:?}", file.path));
+            }
+            Outcome::Modified => self.push(2 * n_checks, file, || {
+                let mut checks: Vec<&str> =
+                    file.findings.iter().map(|x| x.check.as_str()).collect();
+                checks.dedup();
+                format!("{:?} ({})", file.path, checks.join(", "))
+            }),
+            Outcome::Error => self.push(2 * n_checks + 1, file, || {
+                format!(
+                    "{:?}: {}",
+                    file.path,
+                    file.error.as_deref().unwrap_or_default()
+                )
+            }),
+            Outcome::Skipped(reason) => match self.skipped.iter_mut().find(|(r, _)| *r == reason) {
+                Some((_, n)) => *n += 1,
+                None => self.skipped.push((reason, 1)),
+            },
+            Outcome::Unchanged => self.unchanged += 1,
+            Outcome::Protected => self.protected += 1,
+        }
+        for w in file.warnings.iter() {
+            self.push(2 * n_checks + 2, file, || format!("{:?}: {w}", file.path));
+        }
+    }
+
+    /// push counts an entry of file in group i and keeps it if it is among the first cap
+    /// by path; entries of the same path stay in the order they were pushed.
+    fn push(&mut self, i: usize, file: &FileOutcome, entry: impl FnOnce() -> String) {
+        let group = &mut self.groups[i];
+        group.n += 1;
+        let at = group.shown.partition_point(|(path, _)| *path <= file.path);
+        if self.cap.is_some_and(|cap| at >= cap) {
+            return;
+        }
+        group.shown.insert(at, (file.path.clone(), entry()));
+        if let Some(cap) = self.cap {
+            group.shown.truncate(cap);
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for group in self.groups.iter().filter(|group| group.n > 0) {
+            let _ = writeln!(out, "{}: {}", group.label, n_files(group.n));
+            for (_, entry) in group.shown.iter() {
+                let _ = writeln!(out, "  {entry}");
+            }
+            if group.shown.len() < group.n {
+                let _ = writeln!(out, "  … and {} more", group.n - group.shown.len());
+            }
+        }
+        if !self.skipped.is_empty() {
+            let n: usize = self.skipped.iter().map(|(_, n)| n).sum();
+            let reasons: Vec<String> = self
+                .skipped
+                .iter()
+                .map(|(r, n)| format!("{}: {n}", skip_label(*r)))
+                .collect();
+            let _ = writeln!(out, "Skipped: {} ({})", n_files(n), reasons.join(", "));
+        }
+        if self.unchanged > 0 {
+            let _ = writeln!(out, "Unchanged: {}", n_files(self.unchanged));
+        }
+        if self.protected > 0 {
+            let _ = writeln!(out, "Protected (readonly): {}", n_files(self.protected));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TimeSpan;
+
+    /// at returns 2026-03-12 at minute m past midnight.
+    fn at(m: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2026, 3, 12)
+            .unwrap()
+            .and_hms_opt(m / 60, m % 60, 0)
+            .unwrap()
+    }
+
+    /// intervals returns an interval per (name, start, end) in minutes, sorted by start.
+    fn intervals(spans: &[(&str, u32, u32)]) -> Vec<Interval> {
+        spans
+            .iter()
+            .map(|&(name, start, end)| Interval {
+                path: PathBuf::from(name),
+                start: at(start),
+                end: at(end),
+            })
+            .collect()
+    }
+
+    /// pairs returns the names of the files of each overlap and its seconds.
+    fn pairs(overlaps: &[Overlap]) -> Vec<(&str, &str, i64)> {
+        fn name(path: &Path) -> &str {
+            path.to_str().unwrap()
+        }
+        overlaps
+            .iter()
+            .map(|o| (name(&o.first), name(&o.second), o.seconds))
+            .collect()
+    }
+
+    /// coverage_of returns the coverage of DAT files with the spans (name, start, end)
+    /// in minutes, with overlaps.
+    fn coverage_of(spans: &[(&str, u32, u32)]) -> BTreeMap<String, Coverage> {
+        let outcomes: Vec<FileOutcome> = spans
+            .iter()
+            .map(|&(name, start, end)| {
+                let mut file = FileOutcome::new(Path::new(name));
+                file.extension = Some("DAT".to_string());
+                file.time_span = Some(TimeSpan {
+                    start: at(start),
+                    end: at(end),
+                });
+                file
+            })
+            .collect();
+        coverage(&outcomes, 0, Some(0))
+    }
+
+    #[test]
+    fn touching_ranges_do_not_overlap() {
+        let adjacent = intervals(&[("a", 0, 60), ("b", 60, 120), ("c", 120, 180)]);
+        assert_eq!(find_overlaps(&adjacent, 0), []);
+    }
+
+    #[test]
+    fn disjoint_ranges_do_not_overlap() {
+        let disjoint = intervals(&[("a", 0, 50), ("b", 60, 110), ("c", 500, 560)]);
+        assert_eq!(find_overlaps(&disjoint, 0), []);
+    }
+
+    #[test]
+    fn overlapping_ranges_are_paired() {
+        let overlapping = intervals(&[("a", 0, 60), ("b", 50, 110)]);
+        let overlaps = find_overlaps(&overlapping, 0);
+        assert_eq!(pairs(&overlaps), [("a", "b", 600)]);
+        assert_eq!((overlaps[0].start, overlaps[0].end), (at(50), at(60)));
+
+        // a copy covers the same time, a long file covers both of the next ones
+        let copies = intervals(&[("long", 0, 180), ("a", 10, 70), ("a_copy", 10, 70)]);
+        assert_eq!(
+            pairs(&find_overlaps(&copies, 0)),
+            [
+                ("long", "a", 3600),
+                ("long", "a_copy", 3600),
+                ("a", "a_copy", 3600)
+            ]
+        );
+    }
+
+    #[test]
+    fn overlaps_up_to_the_tolerance_are_left_out() {
+        let overlapping = intervals(&[("a", 0, 60), ("b", 59, 120)]);
+        assert_eq!(find_overlaps(&overlapping, 60), []);
+        assert_eq!(pairs(&find_overlaps(&overlapping, 59)), [("a", "b", 60)]);
+    }
+
+    #[test]
+    fn gaps_are_measured_from_the_latest_end() {
+        let coverage = coverage_of(&[("a.DAT", 0, 60), ("b.DAT", 30, 40), ("c.DAT", 70, 80)]);
+        let dat = &coverage["DAT"];
+        // b lies within a, the gap before c starts at the end of a
+        assert_eq!(dat.gaps.len(), 1, "{:?}", dat.gaps);
+        assert_eq!((dat.gaps[0].start, dat.gaps[0].end), (at(60), at(70)));
+        assert_eq!(dat.gaps[0].before, Path::new("c.DAT"));
+        assert_eq!(pairs(&dat.overlaps), [("a.DAT", "b.DAT", 600)]);
+
+        // adjacent files leave no gap
+        let coverage = coverage_of(&[("a.DAT", 0, 60), ("b.DAT", 60, 120)]);
+        assert_eq!(coverage["DAT"].gaps, []);
+        assert_eq!(coverage["DAT"].overlaps, []);
+    }
+}
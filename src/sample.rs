@@ -0,0 +1,84 @@
+//! sample selects a random subset of the files of a directory, to estimate from a small
+//! part what cleaning a huge archive would do. The selection only depends on the seed
+//! and the set of files, so a run can be repeated.
+
+/// parse_fraction parses the fraction of files to sample, a number in (0, 1].
+pub fn parse_fraction(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(f) if f > 0.0 && f <= 1.0 => Ok(f),
+        _ => Err(format!(
+            "invalid fraction '{s}', expected a number in (0, 1]"
+        )),
+    }
+}
+
+/// sample_size returns the number of files a fraction of n_total is, at least one.
+pub fn sample_size(n_total: usize, fraction: f64) -> usize {
+    ((n_total as f64 * fraction).ceil() as usize).clamp(1.min(n_total), n_total)
+}
+
+/// select returns n of items, chosen uniformly at random with seed, in sorted order.
+/// all items are returned if there are no more than n.
+pub fn select<T: Ord>(mut items: Vec<T>, n: usize, seed: u64) -> Vec<T> {
+    // the order of the input, e.g. from read_dir, must not change the selection
+    items.sort();
+    let n = n.min(items.len());
+    let mut rng = SplitMix64(seed);
+    // partial Fisher-Yates shuffle, the first n items are the sample
+    for i in 0..n {
+        let j = i + (rng.next() % (items.len() - i) as u64) as usize;
+        items.swap(i, j);
+    }
+    items.truncate(n);
+    items.sort();
+    items
+}
+
+/// SplitMix64 is a small, fast pseudo random generator; good enough to pick files.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(n: usize) -> Vec<String> {
+        (0..n)
+            .map(|i| format!("{:02}{:02}0000.DAT", i / 24, i % 24))
+            .collect()
+    }
+
+    #[test]
+    fn the_same_seed_selects_the_same_files() {
+        let first = select(names(500), 25, 42);
+        assert_eq!(first, select(names(500), 25, 42));
+        // whatever order the files are listed in
+        let mut shuffled = names(500);
+        shuffled.reverse();
+        shuffled.rotate_left(123);
+        assert_eq!(first, select(shuffled, 25, 42));
+
+        assert_eq!(first.len(), 25);
+        assert!(first.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_ne!(first, select(names(500), 25, 43));
+        assert_ne!(first, names(500)[..25]);
+    }
+
+    #[test]
+    fn sample_size_is_at_least_one_file() {
+        assert_eq!(sample_size(1000, 0.01), 10);
+        assert_eq!(sample_size(1000, 0.0001), 1);
+        assert_eq!(sample_size(7, 0.5), 4);
+        assert_eq!(sample_size(0, 0.5), 0);
+        assert_eq!(select(names(3), 10, 1), names(3));
+    }
+}
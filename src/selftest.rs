@@ -0,0 +1,468 @@
+//! selftest checks that a binary and its cfg file behave sanely on a machine without
+//! touching real data: for each file type of the cfg file it generates a valid file and
+//! broken variants of it in a temporary directory, cleans them and compares what
+//! happened with what the cfg file says should happen. Settings the generated content
+//! can't satisfy, e.g. a header_match the generated header does not match, skip the
+//! scenarios they would make fail instead of failing them.
+
+use std::{fmt, fs, io, path::Path};
+
+use chrono::{Local, NaiveDateTime, NaiveTime, TimeDelta};
+use yaml_rust::Yaml;
+
+use crate::{
+    check_enabled, clean_file, format_datetime, max_removed_fraction, report::outcome_label,
+    timestamp_rules, CheckId, CleanOptions, FileFormat, FileOutcome, FileTypeRules,
+    MissingHeaderAction, Outcome, DEFAULT_DATETIME_FORMAT,
+};
+
+/// Scenario is a kind of file generated for a file type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scenario {
+    /// a preamble, the column header and enough data lines
+    Valid,
+    /// the last data line was cut off after its first field
+    Truncated,
+    /// zero bytes
+    Empty,
+    /// empty lines below the last data line
+    TrailingNewline,
+    /// the column header has a field less than the data lines
+    BadHeader,
+}
+
+impl Scenario {
+    /// all scenarios, in the order they are run.
+    pub const ALL: [Scenario; 5] = [
+        Scenario::Valid,
+        Scenario::Truncated,
+        Scenario::Empty,
+        Scenario::TrailingNewline,
+        Scenario::BadHeader,
+    ];
+
+    /// as_str returns the name of the scenario, which is also part of the file name.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scenario::Valid => "valid",
+            Scenario::Truncated => "truncated",
+            Scenario::Empty => "empty",
+            Scenario::TrailingNewline => "trailing_newline",
+            Scenario::BadHeader => "bad_header",
+        }
+    }
+}
+
+impl fmt::Display for Scenario {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// Expected is what cleaning a generated file should do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expected {
+    /// left as it is; OSC files may be prefixed with the datetime
+    Kept,
+    /// rewritten by the check
+    Fixed(CheckId),
+    /// deleted by the check, or flagged if the type sets `on_delete: flag`
+    Removed(CheckId),
+    /// checked but not changed, the type is readonly
+    Protected,
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::Kept => write!(f, "kept"),
+            Expected::Fixed(id) => write!(f, "fixed by {id}"),
+            Expected::Removed(id) => write!(f, "removed by {id}"),
+            Expected::Protected => write!(f, "protected"),
+        }
+    }
+}
+
+/// Generated is a file generated for a scenario, or the reason it was skipped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Generated {
+    pub scenario: Scenario,
+    pub file: Result<(String, Expected), String>,
+}
+
+/// Verdict is the result of a scenario.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Pass(Expected),
+    /// what happened instead of what was expected
+    Fail(String),
+    /// why the scenario was not run
+    Skip(String),
+}
+
+/// ScenarioResult is the verdict of a scenario of a file type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScenarioResult {
+    pub extension: String,
+    pub scenario: Scenario,
+    pub verdict: Verdict,
+}
+
+impl fmt::Display for ScenarioResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (label, detail) = match &self.verdict {
+            Verdict::Pass(expected) => ("PASS", expected.to_string()),
+            Verdict::Fail(got) => ("FAIL", got.clone()),
+            Verdict::Skip(reason) => ("SKIP", reason.clone()),
+        };
+        write!(f, "{label} {} {}: {detail}", self.extension, self.scenario)
+    }
+}
+
+/// run generates the files of all scenarios for each file type of cfg in dir, which is
+/// created if needed, cleans them with opts and returns the verdicts, by type and
+/// scenario. the cleaned files are left in dir.
+pub fn run(cfg: &Yaml, opts: &CleanOptions, dir: &Path) -> io::Result<Vec<ScenarioResult>> {
+    fs::create_dir_all(dir)?;
+    let mut results = Vec::new();
+    let Some(types) = cfg.as_hash() else {
+        return Ok(results);
+    };
+    let mut extensions: Vec<String> = types
+        .iter()
+        .filter(|(_, cfg_ext)| cfg_ext.as_hash().is_some())
+        .filter_map(|(ext, _)| ext.as_str().map(String::from))
+        .collect();
+    extensions.sort();
+    for ext in extensions {
+        for generated in generate(cfg, &ext, opts) {
+            let verdict = match generated.file {
+                Ok((content, expected)) => {
+                    let name = format!("{}_{}.{ext}", ext.to_lowercase(), generated.scenario);
+                    let path = dir.join(name);
+                    fs::write(&path, &content)?;
+                    let outcome = clean_file(&path, cfg, opts)?;
+                    match verify(expected, &outcome, &path, &content) {
+                        Ok(()) => Verdict::Pass(expected),
+                        Err(got) => Verdict::Fail(format!("want {expected}, {got}")),
+                    }
+                }
+                Err(reason) => Verdict::Skip(reason),
+            };
+            results.push(ScenarioResult {
+                extension: ext.clone(),
+                scenario: generated.scenario,
+                verdict,
+            });
+        }
+    }
+    Ok(results)
+}
+
+/// generate returns the content and the expected outcome of each scenario for the file
+/// type ext of cfg. freeform types have no header or fields, so no truncated or
+/// bad_header scenario.
+pub fn generate(cfg: &Yaml, ext: &str, opts: &CleanOptions) -> Vec<Generated> {
+    let cfg_ext = &cfg[ext];
+    let rules = FileTypeRules::from_cfg(cfg_ext, opts.reference_headers.get(ext), &mut Vec::new());
+    let enabled = |id: CheckId| check_enabled(cfg, ext, id);
+    let readonly = cfg_ext["readonly"].as_bool() == Some(true);
+    let expect = |expected: Expected| {
+        if readonly {
+            Expected::Protected
+        } else {
+            expected
+        }
+    };
+    let layout = Layout::new(cfg_ext, ext, &rules);
+    // the broken variants are the valid file with a small change
+    let valid = layout.and_then(|layout| {
+        let lines = layout.lines(&rules);
+        check_generated(ext, opts, &rules, &lines).map(|_| (layout, lines))
+    });
+    let mut generated = Vec::new();
+    for scenario in Scenario::ALL {
+        let freeform = rules.format == FileFormat::Freeform;
+        if freeform && matches!(scenario, Scenario::Truncated | Scenario::BadHeader) {
+            continue;
+        }
+        let needs = match scenario {
+            Scenario::Valid => None,
+            Scenario::Truncated => Some(CheckId::LastLineFieldMismatch),
+            Scenario::Empty => Some(CheckId::EmptyFile),
+            Scenario::TrailingNewline => Some(CheckId::TrailingEmptyLine),
+            Scenario::BadHeader => Some(CheckId::FirstDataLineFieldMismatch),
+        };
+        let file = match needs.filter(|id| !enabled(*id)) {
+            Some(id) => Err(format!("check {id} is disabled")),
+            None if scenario == Scenario::Empty => {
+                Ok((String::new(), expect(Expected::Removed(CheckId::EmptyFile))))
+            }
+            None => valid
+                .as_ref()
+                .map_err(Clone::clone)
+                .and_then(|(layout, lines)| {
+                    let mut lines = lines.clone();
+                    let expected = match scenario {
+                        Scenario::Valid => Expected::Kept,
+                        Scenario::Truncated => {
+                            let last = lines.last_mut().expect("has data lines");
+                            let cut = last.find(layout.delimiter.as_str()).unwrap_or(last.len());
+                            last.truncate(cut);
+                            Expected::Fixed(CheckId::LastLineFieldMismatch)
+                        }
+                        Scenario::TrailingNewline => {
+                            lines.extend([String::new(), String::new()]);
+                            Expected::Fixed(CheckId::TrailingEmptyLine)
+                        }
+                        Scenario::BadHeader => {
+                            let header = &mut lines[layout.header_idx];
+                            let cut = header
+                                .rfind(layout.header_delimiter.as_str())
+                                .unwrap_or_default();
+                            header.truncate(cut);
+                            Expected::Removed(CheckId::FirstDataLineFieldMismatch)
+                        }
+                        Scenario::Empty => unreachable!("generated above"),
+                    };
+                    // the fixes remove a line or two, max_removed_fraction may not
+                    // allow even that
+                    if let (Expected::Fixed(_), Some(reason)) = (expected, &layout.no_fixes) {
+                        return Err(reason.clone());
+                    }
+                    let mut content = lines.join("\n");
+                    content.push('\n');
+                    Ok((content, expect(expected)))
+                }),
+        };
+        generated.push(Generated { scenario, file });
+    }
+    generated
+}
+
+/// Layout is the shape of the generated files of a type.
+struct Layout {
+    /// index of the column header
+    header_idx: usize,
+    header_fields: Vec<String>,
+    delimiter: String,
+    header_delimiter: String,
+    n_data: usize,
+    /// the time of the first line
+    start: NaiveDateTime,
+    /// the first line is a datetime, as in OSC files
+    datetime_line: Option<String>,
+    /// the timestamps of the data lines and their column
+    timestamps: Option<(Vec<String>, usize)>,
+    /// why the fixes can't be tested
+    no_fixes: Option<String>,
+}
+
+impl Layout {
+    /// new derives the layout from the cfg of the type, or returns why files of the type
+    /// can't be generated.
+    fn new(cfg_ext: &Yaml, ext: &str, rules: &FileTypeRules) -> Result<Self, String> {
+        let header_fields: Vec<String> = match &rules.reference_header {
+            Some(reference) => reference.split('\t').map(String::from).collect(),
+            None => (1..=3).map(|i| format!("col{i}")).collect(),
+        };
+        if header_fields.len() < 2 {
+            return Err("the reference header has a single field".to_string());
+        }
+        // a fix removes at most two lines, enough data lines keep that below the limit
+        let (n_data, no_fixes) = match max_removed_fraction(cfg_ext)? {
+            Some(0.0) => (
+                0,
+                Some("max_removed_fraction: 0 escalates every fix".to_string()),
+            ),
+            Some(max) => (((2.0 / max).ceil() as usize).max(3), None),
+            None => (3, None),
+        };
+        let n_data = n_data.max(rules.min_data_lines.unwrap_or(1) + 2);
+        // today at midnight is in any window of plausible years or days, and one second
+        // per line never wraps around midnight
+        let start = Local::now().date_naive().and_time(NaiveTime::MIN);
+        let datetime_line = match ext {
+            "OSC" => {
+                let format = cfg_ext["datetime_format"]
+                    .as_str()
+                    .unwrap_or(DEFAULT_DATETIME_FORMAT);
+                Some(format_datetime(&start, format)?)
+            }
+            _ => None,
+        };
+        let timestamps = match timestamp_rules(cfg_ext) {
+            Some((timestamp_rules, column)) => {
+                let stamps = (0..n_data)
+                    .map(|i| {
+                        let dt = start + TimeDelta::seconds(i as i64 + 1);
+                        format_datetime(&dt, &timestamp_rules.format)
+                    })
+                    .collect::<Result<Vec<String>, String>>()?;
+                if stamps.windows(2).any(|pair| pair[0] == pair[1]) {
+                    return Err(format!(
+                        "timestamp_format '{}' has no seconds, the generated lines would \
+                         share timestamps",
+                        timestamp_rules.format
+                    ));
+                }
+                if stamps
+                    .iter()
+                    .any(|s| s.contains(rules.columns.delimiter.as_str()))
+                {
+                    return Err(format!(
+                        "timestamp_format '{}' contains the delimiter",
+                        timestamp_rules.format
+                    ));
+                }
+                if column >= header_fields.len() {
+                    return Err(format!(
+                        "timestamp_column {column} is beyond the {} fields of the header",
+                        header_fields.len()
+                    ));
+                }
+                Some((stamps, column))
+            }
+            None => None,
+        };
+        Ok(Layout {
+            header_idx: rules.header_idx(),
+            header_fields,
+            delimiter: rules.columns.delimiter.clone(),
+            header_delimiter: rules.columns.header_delimiter.clone(),
+            n_data,
+            start,
+            datetime_line,
+            timestamps,
+            no_fixes,
+        })
+    }
+
+    /// lines returns the lines of a valid file: freeform files are events with a
+    /// timestamp, columnar ones a preamble, the column header and data lines.
+    fn lines(&self, rules: &FileTypeRules) -> Vec<String> {
+        if rules.format == FileFormat::Freeform {
+            return (0..self.n_data)
+                .map(|i| {
+                    let dt = self.start + TimeDelta::seconds(i as i64 + 1);
+                    format!("{} event {}", dt.format("%Y-%m-%d %H:%M:%S"), i + 1)
+                })
+                .collect();
+        }
+        let mut lines: Vec<String> = (0..self.header_idx)
+            .map(|i| format!("preamble {}", i + 1))
+            .collect();
+        if let (Some(datetime), Some(first)) = (&self.datetime_line, lines.first_mut()) {
+            *first = datetime.clone();
+        }
+        lines.push(self.header_fields.join(&self.header_delimiter));
+        for i in 0..self.n_data {
+            let fields: Vec<String> = (0..self.header_fields.len())
+                .map(|j| match &self.timestamps {
+                    Some((stamps, column)) if *column == j => stamps[i].clone(),
+                    _ if j == 0 => (i + 1).to_string(),
+                    _ => format!("{}.{j}", i + 1),
+                })
+                .collect();
+            lines.push(fields.join(&self.delimiter));
+        }
+        lines
+    }
+}
+
+/// check_generated returns why the patterns of the type would treat the generated lines
+/// other than a valid file, if they do.
+fn check_generated(
+    ext: &str,
+    opts: &CleanOptions,
+    rules: &FileTypeRules,
+    lines: &[String],
+) -> Result<(), String> {
+    let Some(compiled) = opts.compiled_rules.get(ext) else {
+        return Ok(());
+    };
+    if let Some(garbage) = &compiled.leading_garbage {
+        if garbage.count(lines) > 0 {
+            return Err(format!(
+                "skip_leading_garbage '{}' matches the generated first line",
+                garbage.pattern
+            ));
+        }
+    }
+    if let Some(header_match) = &compiled.header_match {
+        if header_match.find(lines) != Some(rules.header_idx()) {
+            return Err(format!(
+                "header_match '{}' does not find the generated column header",
+                header_match.pattern
+            ));
+        }
+    }
+    if let Some(pattern) = &compiled.line_pattern {
+        if let Some(line) = lines.iter().find(|line| !pattern.is_match(line)) {
+            return Err(format!(
+                "line_pattern '{pattern}' does not match the generated line '{line}'"
+            ));
+        }
+    }
+    if let Some(expected_count) = &compiled.expected_count {
+        if expected_count.on_short != MissingHeaderAction::Warn {
+            return Err(format!(
+                "expected_count_from_header '{}' can't be announced in a generated preamble",
+                expected_count.pattern
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// verify returns what happened to the file at path, which held content, if it is not
+/// what was expected.
+fn verify(
+    expected: Expected,
+    outcome: &FileOutcome,
+    path: &Path,
+    content: &str,
+) -> Result<(), String> {
+    let got = || {
+        let mut got = format!("got {}", outcome_label(outcome.outcome));
+        let checks: Vec<&str> = outcome
+            .findings
+            .iter()
+            .map(|finding| finding.check.as_str())
+            .collect();
+        if !checks.is_empty() {
+            got.push_str(&format!(" by {}", checks.join(", ")));
+        }
+        if let Some(err) = &outcome.error {
+            got.push_str(&format!(": {err}"));
+        }
+        got
+    };
+    let found = |id: CheckId| outcome.findings.iter().any(|finding| finding.check == id);
+    // the datetime prefix of OSC files and the output delimiter are the only changes of
+    // a valid file
+    let prefixed_only = || {
+        outcome.findings.iter().all(|finding| {
+            matches!(
+                finding.check,
+                CheckId::OscDatetimePrefix | CheckId::OutputDelimiter
+            )
+        })
+    };
+    let ok = match (expected, outcome.outcome) {
+        (Expected::Kept, Outcome::Unchanged) => outcome.findings.is_empty(),
+        (Expected::Kept, Outcome::Modified) => prefixed_only(),
+        (Expected::Fixed(id), Outcome::Modified) => found(id) && path.exists(),
+        (Expected::Removed(id), Outcome::Deleted(by)) => by == id && !path.exists(),
+        (Expected::Removed(id), Outcome::Flagged(by)) => by == id && path.exists(),
+        (Expected::Protected, Outcome::Protected) => {
+            fs::read_to_string(path).is_ok_and(|after| after == content)
+        }
+        _ => false,
+    };
+    if ok {
+        Ok(())
+    } else {
+        Err(got())
+    }
+}
@@ -0,0 +1,89 @@
+//! spotcheck hashes a random sample of the files a run left unchanged once more and
+//! compares them with their hashes from when they were checked, to make sure unchanged
+//! means byte for byte, whatever the fast paths skipped. The files are only hashed
+//! while they are checked if a spot check was asked for, see
+//! CleanOptions::hash_unchanged.
+
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{cert::sha256_file, report::lossy_path, sample::select, FileOutcome, Outcome};
+
+/// SpotCheck is the result of a spot check of the unchanged files of a run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpotCheck {
+    /// number of unchanged files that were hashed again
+    pub checked: usize,
+    /// seed of the random choice, to repeat it
+    pub seed: u64,
+    /// the files whose content is not what was checked
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mismatches: Vec<Mismatch>,
+}
+
+/// Mismatch is an unchanged file that differs from what was checked, or can't be read.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mismatch {
+    #[serde(serialize_with = "lossy_path")]
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// spot_check hashes n of the files that outcomes report as unchanged again, chosen at
+/// random with seed, and compares them with the hashes taken when they were checked.
+/// files without a hash, e.g. members of archives, are not chosen.
+pub fn spot_check(outcomes: &[FileOutcome], n: usize, seed: u64) -> SpotCheck {
+    let unchanged: Vec<(&Path, &str)> = outcomes
+        .iter()
+        .filter(|file| file.outcome == Outcome::Unchanged)
+        .filter_map(|file| Some((file.path.as_path(), file.sha256.as_deref()?)))
+        .collect();
+    let chosen = select(unchanged, n, seed);
+    let mismatches = chosen
+        .iter()
+        .filter_map(|(path, checked)| {
+            let reason = match sha256_file(path) {
+                Ok(now) if now == *checked => return None,
+                Ok(_) => "its content changed after it was checked".to_string(),
+                Err(err) => format!("it can't be read again: {err}"),
+            };
+            Some(Mismatch {
+                path: path.to_path_buf(),
+                reason,
+            })
+        })
+        .collect();
+    SpotCheck {
+        checked: chosen.len(),
+        seed,
+        mismatches,
+    }
+}
+
+impl SpotCheck {
+    /// render returns a line with the result, and a critical error per mismatch.
+    pub fn render(&self) -> String {
+        let mut out = format!(
+            "spot check: hashed {} unchanged file(s) again, {} (seed {})",
+            self.checked,
+            if self.mismatches.is_empty() {
+                "all as checked".to_string()
+            } else {
+                format!("{} differ", self.mismatches.len())
+            },
+            self.seed
+        );
+        for mismatch in &self.mismatches {
+            let _ = write!(
+                out,
+                "\n!!! CRITICAL: {:?} was reported unchanged, but {}",
+                mismatch.path, mismatch.reason
+            );
+        }
+        out
+    }
+}
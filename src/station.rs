@@ -0,0 +1,55 @@
+//! station tells the files of several stations apart that share a directory, e.g. the
+//! files of two V25 units of a mobile lab, `A_03120000.OSC` and `B_03120000.OSC`. The
+//! top-level `station_pattern` of the cfg file captures the station from the file name.
+//! Stations group the report, the coverage and the station health; they never change
+//! how a file is cleaned.
+
+use std::path::Path;
+
+use regex::Regex;
+use yaml_rust::Yaml;
+
+use crate::compile_pattern;
+
+/// STATION_PATTERN_KEY is the top-level key of the cfg file that holds the pattern.
+/// file types are keyed by their upper-case extension, so it can't name one.
+pub const STATION_PATTERN_KEY: &str = "station_pattern";
+
+/// UNASSIGNED is the station of the files whose name does not match the pattern.
+pub const UNASSIGNED: &str = "unassigned";
+
+/// StationPattern captures the station from a file name, with a group named station.
+#[derive(Debug, Clone)]
+pub struct StationPattern(Regex);
+
+impl StationPattern {
+    /// from_cfg compiles the station_pattern of the cfg file, or returns None if it has
+    /// none. an invalid pattern, or one without a group named station, is an error.
+    pub fn from_cfg(cfg: &Yaml) -> Result<Option<Self>, String> {
+        let pattern = match &cfg[STATION_PATTERN_KEY] {
+            Yaml::BadValue => return Ok(None),
+            Yaml::String(pattern) => pattern,
+            _ => return Err(format!("{STATION_PATTERN_KEY} must be a string")),
+        };
+        let regex = compile_pattern(pattern)
+            .map_err(|err| format!("invalid {STATION_PATTERN_KEY} '{pattern}', {err}"))?;
+        if !regex.capture_names().any(|name| name == Some("station")) {
+            return Err(format!(
+                "{STATION_PATTERN_KEY} '{pattern}' has no group named station, e.g. \
+                 '^(?P<station>[A-Z])_'"
+            ));
+        }
+        Ok(Some(StationPattern(regex)))
+    }
+
+    /// station returns the station of the file at path, UNASSIGNED if its name does not
+    /// match. names that are not valid UTF-8 are matched with replacement characters.
+    pub fn station(&self, path: &Path) -> String {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        self.0
+            .captures(&name)
+            .and_then(|captures| captures.name("station"))
+            .filter(|m| !m.is_empty())
+            .map_or_else(|| UNASSIGNED.to_string(), |m| m.as_str().to_string())
+    }
+}
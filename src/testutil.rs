@@ -0,0 +1,311 @@
+//! testutil runs the cleaner over the fixture corpus in tests/fixtures and compares the
+//! outcomes and the cleaned files with the golden files checked in next to it, so a
+//! change of what gets deleted or rewritten can't go unnoticed. The station health and
+//! the verbose lines of the findings are compared, too, in full and capped per check.
+//! With the environment variable V25_UPDATE_GOLDEN set, the golden files are written
+//! instead. Each file is also classified before it is cleaned, and the classification
+//! must list exactly the findings of cleaning it. The corpus is cleaned a second time
+//! with min_n_lines translated to n_header_lines and min_data_lines, which must not
+//! change any outcome.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use yaml_rust::Yaml;
+
+use crate::{
+    check_formats, check_line_counts, check_osc_prefix,
+    classify::{classify_file, Classification},
+    clean_file, compile_rules, load_reference_headers, load_yml,
+    report::{coverage, render_findings, render_health},
+    CleanOptions, FileOutcome, Outcome, FLAG_EXTENSION,
+};
+
+/// FIXTURE_DIR holds the corpus: the cfg file `cfg.yml` with its reference headers, the
+/// files to clean in `input/` and the expected results in `golden/`. Directories for
+/// single options of the binary sit next to them, e.g. `size_outliers/`, and the
+/// inputs the fuzz targets found in `fuzz/<target>/`, see fuzz.
+pub const FIXTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+/// CAPPED is the number of findings per check in `findings_capped.txt`, see
+/// report::MessageCap.
+const CAPPED: usize = 1;
+
+/// UPDATE_GOLDEN is the environment variable that makes run_fixture_dir write the
+/// golden files from the current behaviour.
+pub const UPDATE_GOLDEN: &str = "V25_UPDATE_GOLDEN";
+
+/// INTERRUPT_AFTER is the environment variable that makes the binary act as if Ctrl-C
+/// was pressed once this many files were cleaned, for the tests of an interrupted run.
+/// Only builds with test-util read it.
+pub const INTERRUPT_AFTER: &str = "V25_TEST_INTERRUPT_AFTER";
+
+/// interrupt_after returns the number of files set with INTERRUPT_AFTER, if any.
+pub fn interrupt_after() -> Option<usize> {
+    std::env::var(INTERRUPT_AFTER).ok()?.parse().ok()
+}
+
+/// run_fixture_dir copies the input files of the corpus to tempdir, which must not exist
+/// yet, cleans them one by one in name order with the cfg of the corpus and returns
+/// their outcomes, with paths relative to tempdir. it panics with all differences if the
+/// outcomes or the files left in tempdir differ from the golden files, if a file was
+/// classified other than cleaning it turned out, or if the translated cfg cleans a file
+/// differently.
+pub fn run_fixture_dir(tempdir: &Path) -> Vec<FileOutcome> {
+    let fixtures = Path::new(FIXTURE_DIR);
+    let golden = fixtures.join("golden");
+    let cfg = &load_yml(&fixtures.join("cfg.yml"))[0];
+    let (outcomes, misclassified) = clean_copy(fixtures, tempdir, cfg)
+        .unwrap_or_else(|err| panic!("could not clean the fixtures in {tempdir:?}: {err}"));
+    if !misclassified.is_empty() {
+        panic!(
+            "classify_file differs from clean_file:\n  {}",
+            misclassified.join("\n  ")
+        );
+    }
+    let mut migrated_dir = tempdir.as_os_str().to_owned();
+    migrated_dir.push("_migrated");
+    let migrated_dir = PathBuf::from(migrated_dir);
+    let migrated = clean_copy(fixtures, &migrated_dir, &migrated(cfg))
+        .and_then(|(migrated, _)| outcomes_json(&migrated));
+    let _ = fs::remove_dir_all(&migrated_dir);
+    match migrated {
+        Ok(json) if json == outcomes_json(&outcomes).unwrap_or_default() => (),
+        Ok(_) => panic!("translating min_n_lines in the cfg of the fixtures changes the outcomes"),
+        Err(err) => panic!("could not clean the fixtures in {migrated_dir:?}: {err}"),
+    }
+    if std::env::var_os(UPDATE_GOLDEN).is_some() {
+        write_golden(&golden, tempdir, &outcomes)
+            .unwrap_or_else(|err| panic!("could not write the golden files {golden:?}: {err}"));
+        return outcomes;
+    }
+    let differences = compare(&golden, tempdir, &outcomes)
+        .unwrap_or_else(|err| panic!("could not read the golden files {golden:?}: {err}"));
+    if !differences.is_empty() {
+        panic!(
+            "the fixtures differ from the golden files in {golden:?}, \
+             set {UPDATE_GOLDEN}=1 to accept the changes:\n  {}",
+            differences.join("\n  ")
+        );
+    }
+    outcomes
+}
+
+/// clean_copy copies the input files to tempdir, classifies and cleans them with cfg. it
+/// returns the outcomes and the differences between classification and cleaning.
+fn clean_copy(
+    fixtures: &Path,
+    tempdir: &Path,
+    cfg: &Yaml,
+) -> io::Result<(Vec<FileOutcome>, Vec<String>)> {
+    fs::create_dir_all(tempdir.parent().unwrap_or(Path::new(".")))?;
+    fs::create_dir(tempdir)?;
+    for path in sorted_files(&fixtures.join("input"))? {
+        fs::copy(&path, tempdir.join(path.file_name().unwrap_or_default()))?;
+    }
+    let opts = CleanOptions {
+        reference_headers: check_line_counts(cfg)
+            .and_then(|_| check_formats(cfg))
+            .and_then(|_| check_osc_prefix(cfg))
+            .and_then(|_| load_reference_headers(cfg, fixtures))
+            .map_err(io::Error::other)?,
+        compiled_rules: compile_rules(cfg).map_err(io::Error::other)?,
+        coverage: true,
+        ..Default::default()
+    };
+    let mut outcomes = Vec::new();
+    let mut misclassified = Vec::new();
+    for path in sorted_files(tempdir)? {
+        let class = classify_file(&path, cfg, &opts)?;
+        let mut outcome = clean_file(&path, cfg, &opts)?;
+        assert!(outcome.duration_us.is_some(), "{path:?} was not timed");
+        // the duration differs from run to run
+        outcome.duration_us = None;
+        if let Some(difference) = classification_difference(&class, &outcome) {
+            misclassified.push(format!("{}: {difference}", file_name(&path)));
+        }
+        outcome.path = relative(&outcome.path, tempdir);
+        outcome.renamed_to = outcome.renamed_to.map(|path| relative(&path, tempdir));
+        outcomes.push(outcome);
+    }
+    Ok((outcomes, misclassified))
+}
+
+/// classification_difference tells how the classification of a file differs from the
+/// outcome of cleaning it. readonly types report their findings as warnings only.
+fn classification_difference(class: &Classification, outcome: &FileOutcome) -> Option<String> {
+    if class.outcome != outcome.outcome {
+        return Some(format!(
+            "classified {:?}, cleaned {:?}",
+            class.outcome, outcome.outcome
+        ));
+    }
+    if outcome.outcome != Outcome::Protected && class.findings != outcome.findings {
+        return Some(format!(
+            "classified with findings {:?}, cleaned with {:?}",
+            class.findings, outcome.findings
+        ));
+    }
+    None
+}
+
+/// compare lists the differences between the results in tempdir and the golden files.
+fn compare(golden: &Path, tempdir: &Path, outcomes: &[FileOutcome]) -> io::Result<Vec<String>> {
+    let mut differences = Vec::new();
+    let want = fs::read_to_string(golden.join("outcomes.json"))?;
+    let have = outcomes_json(outcomes)?;
+    for (i, (have, want)) in have.lines().zip(want.lines()).enumerate() {
+        if have != want {
+            differences.push(format!(
+                "outcomes.json line {}: got {:?}, want {:?}",
+                i + 1,
+                have.trim(),
+                want.trim()
+            ));
+            break;
+        }
+    }
+    if differences.is_empty() && have.lines().count() != want.lines().count() {
+        differences.push(format!(
+            "outcomes.json has {} lines, want {}",
+            have.lines().count(),
+            want.lines().count()
+        ));
+    }
+
+    for (name, have) in [
+        ("health.txt", health(outcomes)),
+        ("findings.txt", render_findings(outcomes, None)),
+        (
+            "findings_capped.txt",
+            render_findings(outcomes, Some(CAPPED)),
+        ),
+    ] {
+        let want = fs::read_to_string(golden.join(name))?;
+        if have != want {
+            differences.push(format!("{name}: got\n{have}want\n{want}"));
+        }
+    }
+
+    let names = |dir: &Path| -> io::Result<Vec<String>> {
+        Ok(sorted_files(dir)?
+            .iter()
+            .map(|path| file_name(path))
+            .collect())
+    };
+    let (have_files, want_files) = (names(tempdir)?, names(&golden.join("files"))?);
+    for name in want_files.iter().filter(|name| !have_files.contains(name)) {
+        differences.push(format!("{name}: missing"));
+    }
+    for name in have_files.iter().filter(|name| !want_files.contains(name)) {
+        differences.push(format!("{name}: not expected"));
+    }
+    for name in have_files.iter().filter(|name| want_files.contains(name)) {
+        let have = normalized(name, fs::read(tempdir.join(name))?);
+        let want = normalized(name, fs::read(golden.join("files").join(name))?);
+        if have != want {
+            differences.push(format!("{name}: content differs"));
+        }
+    }
+    Ok(differences)
+}
+
+/// write_golden replaces the golden files with the results in tempdir.
+fn write_golden(golden: &Path, tempdir: &Path, outcomes: &[FileOutcome]) -> io::Result<()> {
+    let files = golden.join("files");
+    if files.is_dir() {
+        fs::remove_dir_all(&files)?;
+    }
+    fs::create_dir_all(&files)?;
+    for path in sorted_files(tempdir)? {
+        let name = file_name(&path);
+        fs::write(files.join(&name), normalized(&name, fs::read(&path)?))?;
+    }
+    fs::write(golden.join("health.txt"), health(outcomes))?;
+    fs::write(golden.join("findings.txt"), render_findings(outcomes, None))?;
+    fs::write(
+        golden.join("findings_capped.txt"),
+        render_findings(outcomes, Some(CAPPED)),
+    )?;
+    fs::write(golden.join("outcomes.json"), outcomes_json(outcomes)?)
+}
+
+/// migrated replaces min_n_lines and header_line in the cfg of each file type by
+/// n_header_lines and min_data_lines, as the cfg file documents the translation.
+fn migrated(cfg: &Yaml) -> Yaml {
+    let Yaml::Hash(types) = cfg else {
+        return cfg.clone();
+    };
+    let key = |name: &str| Yaml::String(name.to_string());
+    let types = types.iter().map(|(ext, cfg_ext)| {
+        let (Yaml::Hash(section), Some(min_n_lines)) = (cfg_ext, cfg_ext["min_n_lines"].as_i64())
+        else {
+            return (ext.clone(), cfg_ext.clone());
+        };
+        let n_header_lines = cfg_ext["header_line"]
+            .as_i64()
+            .map_or(min_n_lines - 1, |idx| idx + 1);
+        let mut section = section.clone();
+        section.remove(&key("min_n_lines"));
+        // freeform types have no header, all lines count as data
+        if cfg_ext["format"].as_str() == Some("freeform") {
+            section.insert(key("min_data_lines"), Yaml::Integer(min_n_lines));
+            return (ext.clone(), Yaml::Hash(section));
+        }
+        section.remove(&key("header_line"));
+        section.insert(key("n_header_lines"), Yaml::Integer(n_header_lines));
+        section.insert(
+            key("min_data_lines"),
+            Yaml::Integer(min_n_lines - n_header_lines),
+        );
+        (ext.clone(), Yaml::Hash(section))
+    });
+    Yaml::Hash(types.collect())
+}
+
+/// health renders the station health of the outcomes, with the coverage report defaults.
+fn health(outcomes: &[FileOutcome]) -> String {
+    render_health(outcomes, &coverage(outcomes, 60, None))
+}
+
+fn outcomes_json(outcomes: &[FileOutcome]) -> io::Result<String> {
+    let mut json = serde_json::to_string_pretty(outcomes).map_err(io::Error::other)?;
+    json.push('\n');
+    Ok(json)
+}
+
+/// normalized drops the time a flag file was written, it changes with every run.
+fn normalized(name: &str, bytes: Vec<u8>) -> Vec<u8> {
+    if !name.ends_with(&format!(".{FLAG_EXTENSION}")) {
+        return bytes;
+    }
+    String::from_utf8_lossy(&bytes)
+        .lines()
+        .filter(|line| !line.starts_with("flagged:"))
+        .map(|line| format!("{line}\n"))
+        .collect::<String>()
+        .into_bytes()
+}
+
+fn sorted_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|r| r.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+fn relative(path: &Path, dir: &Path) -> PathBuf {
+    path.strip_prefix(dir).unwrap_or(path).to_path_buf()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
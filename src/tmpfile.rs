@@ -0,0 +1,232 @@
+//! tmpfile writes files atomically via temporary files next to them. Temporary files
+//! are named `.<name>.<pid>.<n>.v25tmp`, so concurrent writers never collide, and are
+//! removed if writing fails, on panic and on Ctrl-C; sweep_stale removes the ones left
+//! behind by a crash.
+
+use std::{
+    collections::BTreeSet,
+    ffi::OsString,
+    fs,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+/// TEMP_SUFFIX ends the name of every temporary file written by the tool.
+pub const TEMP_SUFFIX: &str = ".v25tmp";
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// temporary files that exist right now, removed by cleanup_registered
+static REGISTRY: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+
+/// temp_path returns a new temporary file name next to target, unique within and across
+/// processes.
+pub fn temp_path(target: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(target.file_name().unwrap_or_default());
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    name.push(format!(".{}.{n}{TEMP_SUFFIX}", process::id()));
+    target.with_file_name(name)
+}
+
+/// is_temp_file tells if path is a temporary file of the tool, i.e. not data.
+pub fn is_temp_file(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.as_encoded_bytes())
+        .is_some_and(|name| name.starts_with(b".") && name.ends_with(TEMP_SUFFIX.as_bytes()))
+}
+
+/// TempFile is a registered temporary file. It is removed when dropped, unless it was
+/// persisted.
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn create(target: &Path) -> io::Result<(Self, fs::File)> {
+        let path = temp_path(target);
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        REGISTRY.lock().unwrap().insert(path.clone());
+        Ok((TempFile { path }, file))
+    }
+
+    /// persist renames the temporary file to target.
+    fn persist(self, target: &Path) -> io::Result<()> {
+        fs::rename(&self.path, target)?;
+        REGISTRY.lock().unwrap().remove(&self.path);
+        std::mem::forget(self);
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        if let Ok(mut registry) = REGISTRY.lock() {
+            registry.remove(&self.path);
+        }
+    }
+}
+
+/// write_atomic writes target via a temporary file that replaces target only once
+/// everything was written and synced, so target is never left half written. The
+/// permissions of an existing target are kept.
+pub fn write_atomic(
+    target: &Path,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    let (temp, file) = TempFile::create(target)?;
+    let mut writer = BufWriter::new(file);
+    write(&mut writer)?;
+    let file = writer.into_inner().map_err(|err| err.into_error())?;
+    file.sync_all()?;
+    if let Ok(meta) = fs::metadata(target) {
+        fs::set_permissions(&temp.path, meta.permissions())?;
+    }
+    temp.persist(target)
+}
+
+/// cleanup_registered removes all temporary files that exist right now, for exits that
+/// skip Drop, e.g. process::exit.
+pub fn cleanup_registered() {
+    if let Ok(registry) = REGISTRY.lock() {
+        for path in registry.iter() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// sweep_stale removes the temporary files in dir that were last modified more than
+/// max_age ago, i.e. left behind by a crashed run. returns the number of removed files.
+pub fn sweep_stale(dir: &Path, max_age: Duration) -> io::Result<usize> {
+    let now = SystemTime::now();
+    let mut n = 0;
+    for entry in fs::read_dir(dir)?.filter_map(|r| r.ok()) {
+        let path = entry.path();
+        if !is_temp_file(&path) || !path.is_file() {
+            continue;
+        }
+        let age = entry
+            .metadata()?
+            .modified()
+            .ok()
+            .and_then(|t| now.duration_since(t).ok())
+            .unwrap_or_default();
+        if age > max_age {
+            fs::remove_file(&path)?;
+            n += 1;
+        }
+    }
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// temp_dir returns an empty directory for the test name.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("v25_tmpfile_{name}_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn temp_names_never_collide() {
+        let target = Path::new("station").join("03120000.OSC");
+        let names: Vec<PathBuf> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| (0..100).map(|_| temp_path(&target)).collect::<Vec<_>>()))
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+        let unique: BTreeSet<&PathBuf> = names.iter().collect();
+        assert_eq!(unique.len(), 800);
+
+        let prefix = format!(".03120000.OSC.{}.", process::id());
+        for name in &names {
+            assert_eq!(name.parent(), Some(Path::new("station")));
+            let file_name = name.file_name().unwrap().to_str().unwrap();
+            let n = file_name
+                .strip_prefix(&prefix)
+                .and_then(|rest| rest.strip_suffix(TEMP_SUFFIX))
+                .unwrap_or_else(|| panic!("{file_name}"));
+            assert!(n.parse::<usize>().is_ok(), "{file_name}");
+            assert!(is_temp_file(name), "{file_name}");
+        }
+        for data in [
+            "03120000.OSC",
+            "03120000.v25tmp",
+            ".hidden.OSC",
+            ".v25tmp.OSC",
+        ] {
+            assert!(!is_temp_file(Path::new(data)), "{data}");
+        }
+    }
+
+    #[test]
+    fn failed_write_leaves_no_temp_file() {
+        let dir = temp_dir("failed");
+        let target = dir.join("03120000.DAT");
+        fs::write(&target, "old\n").unwrap();
+        let err = write_atomic(&target, |out| {
+            out.write_all(b"half")?;
+            Err(io::Error::other("disk full"))
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "disk full");
+        assert_eq!(fs::read_to_string(&target).unwrap(), "old\n");
+        let names: Vec<OsString> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["03120000.DAT"]);
+        assert!(!REGISTRY.lock().unwrap().iter().any(|p| p.starts_with(&dir)));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn sweep_removes_only_stale_temp_files() {
+        let dir = temp_dir("sweep");
+        let hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        let write = |name: &str, modified: SystemTime| {
+            let path = dir.join(name);
+            fs::File::create(&path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+            path
+        };
+        let stale = write(".03120000.OSC.1234.0.v25tmp", hour_ago);
+        let fresh = write(".03120100.OSC.1234.1.v25tmp", SystemTime::now());
+        let data = write("03120000.OSC", hour_ago);
+        let foreign = write("03120000.OSC.tmp", hour_ago);
+        // a directory is never removed, whatever its name
+        let subdir = dir.join(".sub.1234.2.v25tmp");
+        fs::create_dir(&subdir).unwrap();
+
+        assert_eq!(sweep_stale(&dir, Duration::from_secs(600)).unwrap(), 1);
+        assert!(!stale.exists());
+        for kept in [&fresh, &data, &foreign, &subdir] {
+            assert!(kept.exists(), "{kept:?}");
+        }
+        // files younger than max_age are kept, with none all are stale
+        assert_eq!(sweep_stale(&dir, Duration::from_secs(7200)).unwrap(), 0);
+        assert_eq!(sweep_stale(&dir, Duration::ZERO).unwrap(), 1);
+        assert!(!fresh.exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,72 @@
+//! walk finds the directories a run cleans: the given one and, with --recursive, its
+//! subdirectories down to a depth, e.g. the daily directories of a campaign. Each
+//! directory keeps its own markers and manifest, as if it was cleaned on its own.
+//! Symlinked directories are never followed, they could lead out of the tree or around
+//! in a circle; they are listed with the directories that were not processed.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Walk is the result of walk.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Walk {
+    /// the directories to clean, the root first, then in name order
+    pub dirs: Vec<PathBuf>,
+    /// the subdirectories that are not processed: below max_depth, symlinked or not
+    /// readable
+    pub skipped: Vec<PathBuf>,
+}
+
+/// walk returns the directories below root down to max_depth levels, 0 is root alone.
+/// an unreadable root is an error, an unreadable subdirectory is skipped.
+pub fn walk(root: &Path, max_depth: usize) -> io::Result<Walk> {
+    let mut walk = Walk::default();
+    let mut level = vec![root.to_path_buf()];
+    for depth in 0.. {
+        let mut below = Vec::new();
+        for dir in level {
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(err) if depth == 0 => return Err(err),
+                Err(_) => {
+                    walk.skipped.push(dir);
+                    continue;
+                }
+            };
+            for entry in entries.filter_map(|r| r.ok()) {
+                let path = entry.path();
+                // the type of the entry itself, a symlink is not followed
+                let Ok(file_type) = entry.file_type() else {
+                    continue;
+                };
+                if file_type.is_dir() && depth < max_depth {
+                    below.push(path);
+                } else if file_type.is_dir() || (file_type.is_symlink() && path.is_dir()) {
+                    walk.skipped.push(path);
+                }
+            }
+            walk.dirs.push(dir);
+        }
+        if below.is_empty() {
+            break;
+        }
+        below.sort();
+        level = below;
+    }
+    walk.dirs[1..].sort();
+    walk.skipped.sort();
+    Ok(walk)
+}
+
+/// files returns the files in dir, following symlinks to files.
+pub fn files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|r| r.ok())
+        .map(|r| r.path())
+        .filter(|p| p.is_file())
+        .collect();
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,3153 @@
+//! checks the cleaner against the fixture corpus in tests/fixtures, see
+//! cleaner_lib::testutil. Set V25_UPDATE_GOLDEN=1 to accept changed results.
+
+use cleaner_lib::testutil::run_fixture_dir;
+
+#[test]
+fn fixture_corpus_matches_golden() {
+    let tempdir = TempDir::new("fixtures");
+    let outcomes = run_fixture_dir(&tempdir);
+    assert!(!outcomes.is_empty());
+}
+
+#[test]
+fn check_names_never_change() {
+    use cleaner_lib::CheckId;
+
+    // the names are part of the report format, the cfg file and the command line; a
+    // changed name breaks them, a new check only adds one
+    let names: Vec<&str> = CheckId::ALL.iter().map(|id| id.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "no_extension",
+            "empty_file",
+            "mixed_line_endings",
+            "leading_garbage",
+            "missing_header",
+            "duplicate_preamble",
+            "trailing_empty_line",
+            "too_few_lines",
+            "line_pattern_mismatch",
+            "first_data_line_field_mismatch",
+            "header_mismatch",
+            "duplicate_header_field",
+            "missing_final_newline",
+            "last_line_field_mismatch",
+            "last_field_truncated",
+            "instrument_id",
+            "expected_count",
+            "duplicate_timestamp",
+            "time_reset",
+            "removed_fraction",
+            "trim_fields",
+            "output_delimiter",
+            "osc_datetime_prefix",
+        ]
+    );
+    for id in CheckId::ALL {
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(json, format!("\"{}\"", id.as_str()));
+        assert_eq!(serde_json::from_str::<CheckId>(&json).unwrap(), id);
+        assert_eq!(serde_yaml::to_string(&id).unwrap().trim_end(), id.as_str());
+        assert_eq!(id.as_str().parse::<CheckId>(), Ok(id));
+    }
+    let err = serde_json::from_str::<CheckId>("\"no_such_check\"").unwrap_err();
+    assert!(
+        err.to_string().contains("unknown check 'no_such_check'"),
+        "{err}"
+    );
+}
+
+#[test]
+fn capped_findings_sum_up_the_rest() {
+    use cleaner_lib::{report::render_findings, CheckId, FileOutcome, Finding};
+
+    let outcomes: Vec<FileOutcome> = (0..4822)
+        .map(|i| {
+            let mut outcome = FileOutcome::new(std::path::Path::new(&format!("{i}.DAT")));
+            let message = "last line is empty -> remove line".to_string();
+            outcome.findings = vec![Finding::new(CheckId::TrailingEmptyLine, message)];
+            if i % 1000 == 0 {
+                let message = "has no extension -> delete file".to_string();
+                outcome
+                    .findings
+                    .push(Finding::new(CheckId::NoExtension, message));
+            }
+            outcome
+        })
+        .collect();
+    let capped = render_findings(&outcomes, Some(10));
+    let lines: Vec<&str> = capped.lines().collect();
+    assert_eq!(lines.len(), 10 + 5 + 1);
+    assert_eq!(
+        lines.last(),
+        Some(&"… suppressed 4,812 further occurrences of [trailing_empty_line]")
+    );
+    assert_eq!(render_findings(&outcomes, None).lines().count(), 4822 + 5);
+    assert_eq!(
+        outcomes.iter().map(|o| o.findings.len()).sum::<usize>(),
+        4827
+    );
+}
+
+#[test]
+fn line_counts_out_of_range_are_rejected() {
+    use cleaner_lib::{check_line_counts, FileTypeRules};
+    use yaml_rust::YamlLoader;
+
+    let cfg = |yaml: &str| YamlLoader::load_from_str(yaml).unwrap().remove(0);
+    for (yaml, error) in [
+        (
+            "DAT:\n  min_n_lines: 0\n",
+            "DAT: min_n_lines must be an integer from 2 to 10000, not 0",
+        ),
+        (
+            "DAT:\n  min_n_lines: -7\n",
+            "DAT: min_n_lines must be an integer from 2 to 10000, not -7",
+        ),
+        (
+            "DAT:\n  min_n_lines: 1000000000\n",
+            "DAT: min_n_lines must be an integer from 2 to 10000, not 1000000000",
+        ),
+        (
+            "DAT:\n  min_n_lines: 2.5\n",
+            "DAT: min_n_lines must be an integer from 2 to 10000, not '2.5'",
+        ),
+        (
+            "DAT:\n  min_n_lines: 1\n",
+            "DAT: min_n_lines must be an integer from 2 to 10000, not 1",
+        ),
+        (
+            "LOG:\n  format: freeform\n  min_n_lines: 0\n",
+            "LOG: min_n_lines must be an integer from 1 to 10000, not 0",
+        ),
+        (
+            "OSC:\n  n_header_lines: 0\n",
+            "OSC: n_header_lines must be an integer from 1 to 10000, not 0",
+        ),
+        (
+            "OSC:\n  n_header_lines: 5\n  min_data_lines: -1\n",
+            "OSC: min_data_lines must be an integer from 0 to 10000, not -1",
+        ),
+    ] {
+        assert_eq!(
+            check_line_counts(&cfg(yaml)),
+            Err(error.to_string()),
+            "{yaml}"
+        );
+    }
+    let valid = "DAT:\n  min_n_lines: 2\nOSC:\n  n_header_lines: 5\n  min_data_lines: 0\nLIC:\n\
+                 LOG:\n  format: freeform\n  min_n_lines: 1\n";
+    assert_eq!(check_line_counts(&cfg(valid)), Ok(()));
+    let rules = FileTypeRules::from_cfg(&cfg(valid)["LOG"], None, &mut Vec::new());
+    assert_eq!((rules.n_header_lines, rules.min_len()), (None, 1));
+
+    // without the check, out of range values must not make every file too short
+    for yaml in ["DAT:\n  min_n_lines: -7\n", "DAT:\n  min_n_lines: 0\n"] {
+        let rules = FileTypeRules::from_cfg(&cfg(yaml)["DAT"], None, &mut Vec::new());
+        assert!(rules.min_len() <= 2, "{yaml}: {rules:?}");
+    }
+}
+
+#[test]
+fn duplicate_header_fields_by_case() {
+    use cleaner_lib::FileTypeRules;
+
+    let mut rules = FileTypeRules::default();
+    assert_eq!(rules.duplicate_fields("Temp\tTemp"), None, "not enabled");
+    rules.unique_header_fields = true;
+    let message = |header: &str, rules: &FileTypeRules| {
+        rules
+            .duplicate_fields(header)
+            .map(|finding| finding.message)
+    };
+    assert_eq!(message("\tTime\tTemp\tp\t", &rules), None);
+    assert_eq!(
+        message("Time\tTemp\tp\t Temp ", &rules).as_deref(),
+        Some("column header names fields more than once: 'Temp' at fields 2, 4")
+    );
+    assert_eq!(message("Temp\tTEMP\ttemp", &rules), None);
+    rules.header_fields_ignore_case = true;
+    assert_eq!(
+        message("Temp\tTEMP\tp\ttemp\tP", &rules).as_deref(),
+        Some(
+            "column header names fields more than once: 'Temp'/'TEMP'/'temp' at fields 1, 2, 4; \
+             'p'/'P' at fields 3, 5"
+        )
+    );
+}
+
+#[test]
+fn clean_lines_does_not_panic_on_odd_line_counts() {
+    use cleaner_lib::{clean_lines, FileFormat, FileOutcome, FileTypeRules, RemovedCaps};
+
+    let caps = RemovedCaps {
+        max_lines: 10,
+        max_bytes: 1000,
+    };
+    let counts = [(0, 0), (0, 1), (1, 0), (usize::MAX, usize::MAX)];
+    for ((n_header_lines, min_data_lines), format) in counts.into_iter().flat_map(|counts| {
+        [
+            (counts, FileFormat::Columnar),
+            (counts, FileFormat::Freeform),
+        ]
+    }) {
+        let rules = FileTypeRules {
+            n_header_lines: Some(n_header_lines),
+            min_data_lines: Some(min_data_lines),
+            format,
+            ..Default::default()
+        };
+        for lines in [&[][..], &["a\tb"], &["a\tb", "1\t2"], &["a\tb", "1"]] {
+            let mut content: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+            let mut outcome = FileOutcome::new(std::path::Path::new("a.DAT"));
+            clean_lines(&mut content, false, &rules, &|_| true, &caps, &mut outcome);
+        }
+    }
+}
+
+#[test]
+fn bad_patterns_fail_compilation() {
+    use std::path::Path;
+
+    use cleaner_lib::{compile_rules, station::StationPattern};
+    use yaml_rust::YamlLoader;
+
+    let cfg = |yaml: &str| YamlLoader::load_from_str(yaml).unwrap().remove(0);
+    for (yaml, error) in [
+        (
+            "DAT:\n  skip_leading_garbage:\n    pattern: \"^(BOOT\"\n",
+            "DAT: invalid skip_leading_garbage pattern '^(BOOT', unclosed group",
+        ),
+        (
+            "DAT:\n  skip_leading_garbage:\n    max_lines: 5\n",
+            "DAT: skip_leading_garbage has no pattern",
+        ),
+        (
+            "LIC:\nDAT:\n  skip_leading_garbage:\n    pattern: BOOT\n    max_lines: -1\n",
+            "DAT: invalid skip_leading_garbage max_lines",
+        ),
+        (
+            "DAT:\n  header_match: '^(Time'\n",
+            "DAT: invalid header_match '^(Time', unclosed group",
+        ),
+        (
+            "DAT:\n  header_match: '^Time'\n  max_preamble_lines: 0\n",
+            "DAT: invalid max_preamble_lines, want at least 1",
+        ),
+        (
+            "DAT:\n  header_match: '^Time'\n  on_missing_header: flag\n",
+            "DAT: invalid action 'flag', expected one of delete, quarantine, warn",
+        ),
+        (
+            "OSC:\n  existing_time_pattern: '^(\\d{2}:'\n",
+            "OSC: invalid existing_time_pattern '^(\\d{2}:', unclosed group",
+        ),
+        (
+            "DAT:\n  expected_count_from_header: 'Samples: (\\d+)'\n",
+            "DAT: expected_count_from_header must be a map, e.g. {line: 2, pattern: 'Samples: (\\d+)'}",
+        ),
+        (
+            "DAT:\n  expected_count_from_header: {line: 0, pattern: 'Samples: (\\d+)'}\n",
+            "DAT: invalid expected_count_from_header line, want 1 to 10000",
+        ),
+        (
+            "DAT:\n  expected_count_from_header: {line: 2, pattern: 'Samples: \\d+'}\n",
+            "DAT: expected_count_from_header pattern 'Samples: \\d+' has no group to capture the count",
+        ),
+        (
+            "DAT:\n  expected_count_from_header: {line: 2, pattern: 'Samples: (\\d+)', min_fraction: 1.5}\n",
+            "DAT: invalid expected_count_from_header min_fraction Real(\"1.5\"), expected a number in (0, 1]",
+        ),
+    ] {
+        assert_eq!(
+            compile_rules(&cfg(yaml)).map(|_| ()),
+            Err(error.to_string()),
+            "{yaml}"
+        );
+    }
+
+    let valid = "LIC:\nDAT:\n  skip_leading_garbage:\n    pattern: \"^(BOOT|INIT)\"\n";
+    let compiled = compile_rules(&cfg(valid)).unwrap();
+    assert!(compiled["LIC"].leading_garbage.is_none());
+    let garbage = compiled["DAT"].leading_garbage.as_ref().unwrap();
+    assert_eq!(garbage.max_lines, 5);
+    assert!(garbage.pattern.is_match("INIT sensor"));
+
+    for (yaml, error) in [
+        (
+            "station_pattern: '^([A-Z])_'\n",
+            "station_pattern '^([A-Z])_' has no group named station, e.g. '^(?P<station>[A-Z])_'",
+        ),
+        (
+            "station_pattern: '^(?P<station>[A-Z]_'\n",
+            "invalid station_pattern '^(?P<station>[A-Z]_', unclosed group",
+        ),
+        (
+            "station_pattern: [A, B]\n",
+            "station_pattern must be a string",
+        ),
+    ] {
+        assert_eq!(
+            StationPattern::from_cfg(&cfg(yaml)).map(|_| ()),
+            Err(error.to_string()),
+            "{yaml}"
+        );
+    }
+    let stations = StationPattern::from_cfg(&cfg(&format!(
+        "station_pattern: '^(?P<station>[A-Z])_'\n{valid}"
+    )))
+    .unwrap()
+    .unwrap();
+    assert_eq!(stations.station(Path::new("x/A_03120000.OSC")), "A");
+    assert_eq!(stations.station(Path::new("03120000.OSC")), "unassigned");
+}
+
+#[test]
+fn presets_set_defaults_below_given_options() {
+    use cleaner_lib::{
+        delete::DeleteStrategy,
+        preset::{layer, Preset, PresetOptions, Source, QUARANTINE_DIR},
+    };
+
+    assert_eq!(
+        Preset::Paranoid.options(),
+        PresetOptions {
+            flag_deletes: Some(true),
+            dry_run: Some(true),
+            quarantine: Some(QUARANTINE_DIR.into()),
+            backup: Some(true),
+            ..Default::default()
+        }
+    );
+    let fast = Preset::Fast.options();
+    assert_eq!(fast.delete_strategy, Some(DeleteStrategy::Parallel));
+    assert!(fast.delete_workers.is_some_and(|n| n >= 1));
+    assert_eq!(fast.verbose, Some(false));
+    assert_eq!(fast.jobs, fast.delete_workers.map(|n| n as u64));
+    assert_eq!(fast.flag_deletes, None);
+    assert_eq!(fast.dry_run, None);
+    assert_eq!(fast.backup, None);
+    assert_eq!("fast".parse(), Ok(Preset::Fast));
+    assert_eq!(
+        "careful".parse::<Preset>(),
+        Err("invalid preset 'careful', expected one of paranoid, fast".to_string())
+    );
+
+    let preset = |field: fn(PresetOptions) -> Option<bool>| Preset::Paranoid.value(field);
+    assert_eq!(
+        layer(Some(false), preset(|options| options.flag_deletes), false),
+        (false, Source::CommandLine)
+    );
+    assert_eq!(
+        layer(None, preset(|options| options.flag_deletes), false),
+        (true, Source::Preset(Preset::Paranoid))
+    );
+    assert_eq!(
+        layer(None, preset(|options| options.verbose), true),
+        (true, Source::Default)
+    );
+    assert_eq!(
+        Source::Preset(Preset::Paranoid).to_string(),
+        "preset paranoid"
+    );
+
+    // the binary layers them the same way
+    let dir = TempDir::new("presets");
+    let Some((status, paranoid)) = run_cleaner(&dir, &["--preset", "paranoid", "--print-config"])
+    else {
+        return;
+    };
+    let (_, given) = run_again(
+        &dir,
+        &["--preset", "paranoid", "--no-dry-run", "--print-config"],
+    );
+    let (_, fast) = run_again(&dir, &["--preset", "fast", "--jobs", "2", "--print-config"]);
+    let quarantine = dir.join(QUARANTINE_DIR);
+    assert!(status.success(), "{paranoid}");
+    for line in [
+        "flag_deletes: true (preset paranoid)".to_string(),
+        "dry_run: true (preset paranoid)".to_string(),
+        format!("quarantine: {quarantine:?} (preset paranoid)"),
+        "backup: true (preset paranoid)".to_string(),
+    ] {
+        assert!(paranoid.lines().any(|l| l == line), "{line}\n{paranoid}");
+    }
+    assert!(
+        given.contains("\ndry_run: false (command line)\n"),
+        "{given}"
+    );
+    assert!(fast.contains("\njobs: 2 (command line)\n"), "{fast}");
+    assert!(fast.contains("\nbackup: false (default)\n"), "{fast}");
+    assert!(fast.contains("\nquarantine: none (default)\n"), "{fast}");
+}
+
+#[test]
+fn slowest_files_are_listed_first() {
+    use cleaner_lib::{report::render_slowest, FileOutcome};
+
+    let durations = [
+        ("a.DAT", Some(1_200)),
+        ("b.DAT", None),
+        ("c.DAT", Some(90_000_000)),
+        ("d.DAT", Some(35_500)),
+    ];
+    let outcomes: Vec<FileOutcome> = durations
+        .into_iter()
+        .map(|(name, duration_us)| {
+            let mut outcome = FileOutcome::new(std::path::Path::new(name));
+            outcome.duration_us = duration_us;
+            outcome
+        })
+        .collect();
+    assert_eq!(
+        render_slowest(&outcomes, 2).as_deref(),
+        Some(concat!(
+            "slowest 2 of 3 files:\n",
+            "      90.00s  \"c.DAT\": unchanged\n",
+            "     35.50ms  \"d.DAT\": unchanged",
+        ))
+    );
+    assert_eq!(render_slowest(&outcomes[1..2], 10), None);
+}
+
+#[test]
+fn settings_must_fit_the_format() {
+    use cleaner_lib::check_formats;
+    use yaml_rust::YamlLoader;
+
+    let cfg = |yaml: &str| YamlLoader::load_from_str(yaml).unwrap().remove(0);
+    for (yaml, error) in [
+        (
+            "LOG:\n  format: free\n",
+            "LOG: invalid format 'free', expected one of columnar, freeform",
+        ),
+        (
+            "LOG:\n  format: freeform\n  n_header_lines: 1\n",
+            "LOG: n_header_lines only applies to columnar types",
+        ),
+        (
+            "LOG:\n  format: freeform\n  delimiter: \";\"\n",
+            "LOG: delimiter only applies to columnar types",
+        ),
+        (
+            "LOG:\n  format: freeform\n  timestamp_format: \"%Y-%m-%d\"\n",
+            "LOG: timestamp_format only applies to columnar types",
+        ),
+        (
+            "DAT:\n  line_pattern: \"^#\"\n",
+            "DAT: line_pattern only applies to freeform types",
+        ),
+    ] {
+        assert_eq!(check_formats(&cfg(yaml)), Err(error.to_string()), "{yaml}");
+    }
+    let valid = "DAT:\n  n_header_lines: 1\nLOG:\n  format: freeform\n  min_data_lines: 2\n  line_pattern: \"^2\"\n  on_line_pattern_mismatch: remove\n";
+    assert_eq!(check_formats(&cfg(valid)), Ok(()));
+}
+
+#[test]
+fn cfg_must_map_file_types() {
+    use cleaner_lib::config;
+
+    let example = "e.g. `DAT: {min_n_lines: 2}`";
+    for (yaml, error) in [
+        (
+            "- DAT:\n    min_n_lines: 2\n",
+            format!("line 1, column 1: the top level is a sequence, expected a mapping of file types, {example}"),
+        ),
+        (
+            "DAT\n",
+            format!("line 1, column 1: the top level is the scalar 'DAT', expected a mapping of file types, {example}"),
+        ),
+        (
+            "",
+            format!("is empty, expected a mapping of file types, {example}"),
+        ),
+        (
+            "# all commented out\n---\n",
+            format!("is empty, expected a mapping of file types, {example}"),
+        ),
+        (
+            "{}\n",
+            format!("line 1, column 1: the top level is an empty mapping, expected file types, {example}"),
+        ),
+        (
+            "DAT:\n  min_n_lines: 2\nOSC:\n  - min_n_lines: 6\n",
+            "line 3, column 1: OSC is a sequence, expected a mapping of its settings, e.g. `OSC: {min_n_lines: 2}`".to_string(),
+        ),
+        (
+            "station_pattern: '^(?P<station>[A-Z])_'\nDAT: 2\n",
+            "line 2, column 1: DAT is the scalar '2', expected a mapping of its settings, e.g. `DAT: {min_n_lines: 2}`".to_string(),
+        ),
+    ] {
+        assert_eq!(config::parse(yaml).map(|_| ()), Err(error), "{yaml}");
+    }
+    let cfg = config::parse("station_pattern: '^x'\nLIC:\nDAT:\n  min_n_lines: 2\n").unwrap();
+    assert_eq!(cfg["DAT"]["min_n_lines"].as_i64(), Some(2));
+}
+
+#[test]
+fn cfg_includes_override_in_order() {
+    use std::fs;
+
+    use cleaner_lib::config;
+
+    let dir = TempDir::new("include");
+    let groups = dir.join("groups");
+    fs::create_dir_all(&groups).unwrap();
+    let write = |name: &str, yaml: &str| fs::write(dir.join(name), yaml).unwrap();
+    // the paths are relative to the including file, also in a subdirectory
+    write(
+        "main.yml",
+        "include: [groups/a.yml, groups/b.yml]\nTXT:\n  min_n_lines: 9\n",
+    );
+    write(
+        "groups/a.yml",
+        "DAT:\n  min_n_lines: 2\nOSC:\n  min_n_lines: 6\nTXT:\n  min_n_lines: 1\n",
+    );
+    write(
+        "groups/b.yml",
+        "include: [../shared.yml]\nDAT:\n  min_n_lines: 3\n  reference_header_file: ref.txt\n",
+    );
+    write(
+        "shared.yml",
+        "DAT:\n  min_n_lines: 4\nCNT:\n  min_n_lines: 5\n",
+    );
+    let loaded = config::load_with_sources(&dir.join("main.yml"));
+    // the sources are canonical paths
+    let canonical = fs::canonicalize(&dir).unwrap();
+    // a file including itself through another is refused
+    write("cycle_a.yml", "include: [groups/cycle_b.yml]\nDAT:\n");
+    write("groups/cycle_b.yml", "include: [../cycle_a.yml]\nOSC:\n");
+    let cycle = config::load(&dir.join("cycle_a.yml"));
+    write("broken.yml", "include: [groups/missing.yml]\nDAT:\n");
+    let missing = config::load(&dir.join("broken.yml"));
+
+    // a cfg file given relative to the working directory, as `cfg/main.yml`, finds the
+    // reference header of a file it includes; the target directory is below it
+    let target = std::path::Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let relative_dir = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| {
+            target
+                .strip_prefix(cwd)
+                .ok()
+                .map(std::path::Path::to_path_buf)
+        })
+        .map(|target| target.join(format!("v25_include_{}", std::process::id())));
+    let headers = relative_dir.as_ref().map(|relative_dir| {
+        fs::create_dir_all(relative_dir.join("groups")).unwrap();
+        let write =
+            |name: &str, content: &str| fs::write(relative_dir.join(name), content).unwrap();
+        write("main.yml", "include: [groups/b.yml]\n");
+        write("groups/b.yml", "DAT:\n  reference_header_file: ref.txt\n");
+        write("groups/ref.txt", "a\tb\n");
+        let headers = config::load(&relative_dir.join("main.yml"))
+            .and_then(|cfg| cleaner_lib::load_reference_headers(&cfg, relative_dir));
+        let _ = fs::remove_dir_all(relative_dir);
+        headers
+    });
+
+    let (cfg, sources) = loaded.unwrap();
+    let source = |key: &str| {
+        sources[key]
+            .strip_prefix(&canonical)
+            .unwrap()
+            .to_string_lossy()
+            .replace('\\', "/")
+    };
+    // b.yml overrides a.yml and what it includes itself, main.yml overrides all
+    assert_eq!(cfg["DAT"]["min_n_lines"].as_i64(), Some(3));
+    assert_eq!(source("DAT"), "groups/b.yml");
+    assert_eq!(cfg["TXT"]["min_n_lines"].as_i64(), Some(9));
+    assert_eq!(source("TXT"), "main.yml");
+    assert_eq!(cfg["OSC"]["min_n_lines"].as_i64(), Some(6));
+    assert_eq!(source("OSC"), "groups/a.yml");
+    assert_eq!(cfg["CNT"]["min_n_lines"].as_i64(), Some(5));
+    assert_eq!(source("CNT"), "shared.yml");
+    assert!(cfg[config::INCLUDE_KEY].is_badvalue());
+    assert_eq!(sources.len(), 4);
+    // a reference header stays next to the rules that name it
+    assert_eq!(
+        cfg["DAT"]["reference_header_file"]
+            .as_str()
+            .map(std::path::Path::new),
+        Some(groups.join("ref.txt").as_path())
+    );
+    if let Some(headers) = headers {
+        assert_eq!(headers.unwrap()["DAT"], "a\tb");
+    }
+
+    let cycle = cycle.unwrap_err();
+    assert!(cycle.starts_with("include cycle: "), "{cycle}");
+    assert_eq!(cycle.matches("cycle_a.yml").count(), 2, "{cycle}");
+    assert!(cycle.contains("cycle_b.yml"), "{cycle}");
+    let missing = missing.unwrap_err();
+    assert!(
+        missing.starts_with("included ")
+            && missing.contains("missing.yml")
+            && missing.contains("could not read"),
+        "{missing}"
+    );
+}
+
+#[test]
+fn output_delimiter_keeps_the_fields() {
+    use std::{fs, path::Path};
+
+    use cleaner_lib::{
+        clean_file, compile_rules, testutil::FIXTURE_DIR, CheckId, CleanOptions, Outcome,
+        OutputDelimiter,
+    };
+    use yaml_rust::YamlLoader;
+
+    // split_quoted splits a converted line into its fields again, as a CSV reader would
+    let split_quoted = |line: &str, output: &OutputDelimiter| -> Vec<String> {
+        let (mut fields, mut rest, mut quoted) = (vec![String::new()], line, false);
+        while let Some(c) = rest.chars().next() {
+            let n = c.len_utf8();
+            let skip = if quoted && c == output.quote && rest[n..].starts_with(c) {
+                fields.last_mut().unwrap().push(c);
+                2 * n
+            } else if c == output.quote {
+                quoted = !quoted;
+                n
+            } else if !quoted && rest.starts_with(output.delimiter.as_str()) {
+                fields.push(String::new());
+                output.delimiter.len()
+            } else {
+                fields.last_mut().unwrap().push(c);
+                n
+            };
+            rest = &rest[skip..];
+        }
+        fields
+    };
+    let cfg = |yaml: &str| YamlLoader::load_from_str(yaml).unwrap().remove(0);
+    for (yaml, line) in [
+        ("A:\n  output_delimiter: \",\"\n", "\t1\t12.3\t\tok\t"),
+        (
+            "A:\n  output_delimiter: \",\"\n",
+            "1\tpump 1, 2 on\tsaid \"ok\"",
+        ),
+        ("A:\n  output_delimiter: \",\"\n", "\"\t,\t\"\"\t"),
+        (
+            "A:\n  output_delimiter: \"; \"\n  quote_char: \"'\"\n",
+            "it's\ta; b\t;\t ",
+        ),
+    ] {
+        let output = OutputDelimiter::from_cfg(&cfg(yaml)["A"]).unwrap().unwrap();
+        let converted = output.convert(line, "\t").unwrap();
+        let fields: Vec<&str> = line.split('\t').collect();
+        assert_eq!(split_quoted(&converted, &output), fields, "{converted}");
+        assert_eq!(output.convert(&converted, "\t"), None, "{converted}");
+    }
+    for (yaml, error) in [
+        (
+            "A:\n  output_delimiter: \"\"\n",
+            "output_delimiter must be a non-empty string",
+        ),
+        (
+            "A:\n  output_delimiter: \",\"\n  quote_char: \"''\"\n",
+            "invalid quote_char String(\"''\"), expected a single character",
+        ),
+        (
+            "A:\n  output_delimiter: \"'\"\n  quote_char: \"'\"\n",
+            "output_delimiter ''' must not contain quote_char '''",
+        ),
+    ] {
+        assert_eq!(
+            OutputDelimiter::from_cfg(&cfg(yaml)["A"]),
+            Err(error.to_string()),
+            "{yaml}"
+        );
+    }
+
+    // the datetime column of an OSC file is added with the output delimiter, the column
+    // header and the data lines keep the same number of fields
+    let cfg = cfg(
+        "OSC:\n  min_n_lines: 6\n  header_line: 4\n  output_delimiter: \",\"\n  \
+         output_delimiter_force: true\n",
+    );
+    let opts = CleanOptions {
+        compiled_rules: compile_rules(&cfg).unwrap(),
+        ..Default::default()
+    };
+    let dir = TempDir::new("output_delimiter");
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("osc_basic.OSC");
+    fs::copy(Path::new(FIXTURE_DIR).join("input/osc_basic.OSC"), &path).unwrap();
+    let first = clean_file(&path, &cfg, &opts).unwrap();
+    let content = fs::read_to_string(&path).unwrap();
+    let second = clean_file(&path, &cfg, &opts).unwrap();
+    assert_eq!(first.outcome, Outcome::Modified, "{first:?}");
+    let checks: Vec<CheckId> = first.findings.iter().map(|finding| finding.check).collect();
+    assert_eq!(
+        checks,
+        [CheckId::OscDatetimePrefix, CheckId::OutputDelimiter]
+    );
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines[1..4], ["p1", "p2\t0.5", "p3"], "the preamble is kept");
+    assert_eq!(lines[4], ",DateTime,h1,h2");
+    for line in &lines[5..] {
+        assert!(line.starts_with(",01.06.23 12:00:00.00,"), "{line}");
+        assert_eq!(line.split(',').count(), 4, "{line}");
+    }
+    assert_eq!(second.outcome, Outcome::Unchanged, "{second:?}");
+}
+
+#[test]
+fn report_and_done_marker_name_the_build() {
+    use cleaner_lib::{build_info::BuildInfo, marker, report::Report};
+
+    let report = Report::new(std::path::Path::new("station"), &[], true);
+    let json = serde_json::to_value(&report).unwrap();
+    for key in ["version", "git_commit", "git_describe", "target"] {
+        let value = json["build"][key].as_str();
+        assert!(
+            value.is_some_and(|s| !s.is_empty()),
+            "build.{key}: {value:?}"
+        );
+    }
+    assert!(json["build"]["git_dirty"].is_boolean());
+    let features = json["build"]["features"].as_array().unwrap();
+    assert!(features.iter().any(|f| f == "yaml-config"), "{features:?}");
+
+    let dir = TempDir::new("build_info");
+    std::fs::create_dir_all(&dir).unwrap();
+    marker::write(&dir, &[], &BuildInfo::current(), None).unwrap();
+    let done = std::fs::read_to_string(dir.join(marker::DONE_MARKER)).unwrap();
+    assert_eq!(done, BuildInfo::current().to_string());
+    assert!(done.starts_with(&format!("version: {}\n", env!("CARGO_PKG_VERSION"))));
+}
+
+#[test]
+fn certificate_leaves_out_the_health_file() {
+    use cleaner_lib::{cert::CERT_FILE, report::HEALTH_FILE};
+
+    let dir = TempDir::new("certify_health");
+    let Some((status, stdout)) = run_cleaner(&dir, &["--certify", "--health"]) else {
+        return;
+    };
+    assert!(status.success(), "{stdout}");
+    let data = dir.join("data");
+    assert!(data.join(CERT_FILE).is_file(), "{stdout}");
+    assert!(data.join(HEALTH_FILE).is_file(), "{stdout}");
+
+    let output = std::process::Command::new(dir.join("bin").join("v25_datacleaner"))
+        .args(["verify-cert", "-d"])
+        .arg(&data)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(0), "{stdout}");
+    assert!(stdout.contains("unchanged since"), "{stdout}");
+}
+
+#[test]
+fn forced_rerun_skips_the_tool_files() {
+    // clean files but for the one of another instrument, which is flagged; its flag,
+    // the done marker and the certificate are all the first run adds
+    let dir = TempDir::new("forced_rerun");
+    let Some((status, stdout)) = run_cleaner_on(&dir, "instrument_id", &["--certify"]) else {
+        return;
+    };
+    assert!(status.success(), "{stdout}");
+
+    let (status, stdout) = run_again(&dir, &["--force", "--strict-unknown"]);
+    assert_eq!(status.code(), Some(0), "{stdout}");
+    assert!(!stdout.contains("extension is not"), "{stdout}");
+    assert!(stdout.contains("tool artifact: 3"), "{stdout}");
+}
+
+#[cfg(unix)]
+#[test]
+fn names_need_not_be_utf8() {
+    use std::{ffi::OsStr, fs, os::unix::ffi::OsStrExt, path::Path};
+
+    use cleaner_lib::{
+        build_info::BuildInfo, classify::classify_file, clean_file, compile_rules,
+        load_reference_headers, load_yml, marker, report::Report, testutil::FIXTURE_DIR,
+        CleanOptions, Outcome,
+    };
+
+    let fixtures = Path::new(FIXTURE_DIR);
+    let cfg = &load_yml(&fixtures.join("cfg.yml"))[0];
+    let opts = CleanOptions {
+        reference_headers: load_reference_headers(cfg, fixtures).unwrap(),
+        compiled_rules: compile_rules(cfg).unwrap(),
+        ..Default::default()
+    };
+    // a station and its files named in Latin-1, e.g. "Messstation_ä"
+    let mut dir_name = b"v25_station_\xe4_".to_vec();
+    dir_name.extend(std::process::id().to_string().bytes());
+    let dir = TempDir(std::env::temp_dir().join(OsStr::from_bytes(&dir_name)));
+    fs::create_dir_all(&dir).unwrap();
+    let input = fixtures.join("input");
+    let dat = dir.join(OsStr::from_bytes(b"leer_\xe4.DAT"));
+    let trs = dir.join(OsStr::from_bytes(b"uhr_\xfc.TRS"));
+    fs::copy(input.join("dat_trailing_empty_lines.DAT"), &dat).unwrap();
+    fs::copy(input.join("trs_clock_reset.TRS"), &trs).unwrap();
+
+    let mut outcomes = Vec::new();
+    for path in [&dat, &trs] {
+        let class = classify_file(path, cfg, &opts).unwrap();
+        let outcome = clean_file(path, cfg, &opts).unwrap();
+        assert_eq!(class.outcome, Outcome::Modified, "{path:?}");
+        assert_eq!(outcome.outcome, Outcome::Modified, "{path:?}");
+        assert_eq!(class.findings, outcome.findings, "{path:?}");
+        assert!(
+            outcome
+                .warnings
+                .iter()
+                .any(|w| w.contains("not valid UTF-8")),
+            "{:?}",
+            outcome.warnings
+        );
+        outcomes.push(outcome);
+    }
+    // the data after the clock reset keeps the name of its file
+    let split = dir.join(OsStr::from_bytes(b"uhr_\xfc_2.TRS"));
+    assert!(split.is_file(), "{split:?}");
+
+    let json = serde_json::to_value(Report::new(&dir, &outcomes, true)).unwrap();
+    assert_eq!(json["directory_lossy"], true);
+    for file in json["files"].as_array().unwrap() {
+        assert_eq!(file["path_lossy"], true);
+        assert!(
+            file["path"].as_str().unwrap().contains('\u{FFFD}'),
+            "{file}"
+        );
+    }
+
+    // names that look like the comment of the marker or would end its line
+    let odd = [
+        "#1.DAT",
+        "line\nbreak.DAT",
+        "back\\slash\\n.DAT",
+        "cr\r.DAT",
+    ]
+    .map(|name| dir.join(name));
+    let unfinished: Vec<_> = std::iter::once(dat.clone()).chain(odd.clone()).collect();
+    marker::write(&dir, &unfinished, &BuildInfo::current(), None).unwrap();
+    let state = marker::read(&dir).unwrap();
+    assert_eq!(
+        state,
+        marker::State::Partial(
+            unfinished
+                .iter()
+                .map(|path| path.file_name().unwrap().into())
+                .collect()
+        )
+    );
+}
+
+#[test]
+fn files_may_vanish_or_change_during_a_run() {
+    use std::{fs, path::Path};
+
+    use cleaner_lib::{
+        clean_file, compile_rules, load_reference_headers, load_yml, testutil::FIXTURE_DIR,
+        CleanOptions, Outcome, SkipReason,
+    };
+
+    let fixtures = Path::new(FIXTURE_DIR);
+    let cfg = &load_yml(&fixtures.join("cfg.yml"))[0];
+    let opts = CleanOptions {
+        reference_headers: load_reference_headers(cfg, fixtures).unwrap(),
+        compiled_rules: compile_rules(cfg).unwrap(),
+        defer_delete: true,
+        ..Default::default()
+    };
+    let dir = TempDir::new("rotated");
+    fs::create_dir_all(&dir).unwrap();
+    let input = fixtures.join("input");
+    for name in [
+        "dat_clean.DAT",
+        "dat_too_few_lines.DAT",
+        "dat_trailing_empty_lines.DAT",
+    ] {
+        fs::copy(input.join(name), dir.join(name)).unwrap();
+    }
+    let mut listed: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    listed.sort();
+    // the acquisition software rotates a file away after the directory was listed
+    fs::remove_file(&listed[2]).unwrap();
+    let outcomes: Vec<_> = listed
+        .iter()
+        .map(|path| clean_file(path, cfg, &opts).unwrap())
+        .collect();
+
+    // and replaces a file that is to be deleted before the deletes at the end of the run
+    let mut deleted = outcomes[1].clone();
+    let deleted_path = listed[1].clone();
+    let kept_as_read = deleted.clone().still_as_read(&deleted_path);
+    fs::copy(input.join("dat_clean.DAT"), &deleted_path).unwrap();
+    let replaced = !deleted.still_as_read(&deleted_path);
+
+    assert_eq!(outcomes[0].outcome, Outcome::Unchanged);
+    assert_eq!(
+        outcomes[2].outcome,
+        Outcome::Skipped(SkipReason::VanishedDuringRun)
+    );
+    assert!(outcomes[2].error.is_none() && outcomes[2].warnings.is_empty());
+    assert!(matches!(outcomes[1].outcome, Outcome::Deleted(_)));
+    assert!(kept_as_read);
+    assert!(replaced);
+    assert_eq!(
+        deleted.outcome,
+        Outcome::Skipped(SkipReason::ChangedDuringRun)
+    );
+    assert_eq!(deleted.path, deleted_path);
+    assert!(
+        deleted.warnings[0].starts_with("changed on disk after it was checked (size"),
+        "{:?}",
+        deleted.warnings
+    );
+}
+
+#[test]
+fn self_test_passes_on_the_fixture_cfg() {
+    use std::path::Path;
+
+    use cleaner_lib::{
+        compile_rules, load_reference_headers, load_yml,
+        selftest::{self, Expected, Scenario, Verdict},
+        testutil::FIXTURE_DIR,
+        CheckId, CleanOptions,
+    };
+    use yaml_rust::YamlLoader;
+
+    let fixtures = Path::new(FIXTURE_DIR);
+    let cfg = &load_yml(&fixtures.join("cfg.yml"))[0];
+    let opts = CleanOptions {
+        reference_headers: load_reference_headers(cfg, fixtures).unwrap(),
+        compiled_rules: compile_rules(cfg).unwrap(),
+        ..Default::default()
+    };
+    let dir = TempDir::new("selftest");
+    let results = selftest::run(cfg, &opts, &dir);
+    let results = results.unwrap();
+    let failed: Vec<String> = results
+        .iter()
+        .filter(|result| matches!(result.verdict, Verdict::Fail(_)))
+        .map(|result| result.to_string())
+        .collect();
+    assert!(failed.is_empty(), "{}", failed.join("\n"));
+    let verdict = |ext: &str, scenario: Scenario| {
+        results
+            .iter()
+            .find(|result| result.extension == ext && result.scenario == scenario)
+            .map(|result| result.verdict.clone())
+    };
+    assert_eq!(
+        verdict("OMC", Scenario::BadHeader),
+        Some(Verdict::Pass(Expected::Removed(
+            CheckId::FirstDataLineFieldMismatch
+        )))
+    );
+    // readonly types are checked, but never changed
+    assert_eq!(
+        verdict("LIC", Scenario::Truncated),
+        Some(Verdict::Pass(Expected::Protected))
+    );
+    // the generated header does not match header_match, only the empty file is tested
+    assert!(matches!(
+        verdict("HDM", Scenario::Valid),
+        Some(Verdict::Skip(_))
+    ));
+    assert_eq!(
+        verdict("HDM", Scenario::Empty),
+        Some(Verdict::Pass(Expected::Removed(CheckId::EmptyFile)))
+    );
+    // freeform files have no fields to truncate
+    assert_eq!(verdict("LOG", Scenario::Truncated), None);
+
+    let cfg = &YamlLoader::load_from_str("DAT:\n  checks:\n    trailing_empty_line: false\n")
+        .unwrap()
+        .remove(0);
+    let generated = selftest::generate(cfg, "DAT", &CleanOptions::default());
+    let trailing = generated
+        .iter()
+        .find(|generated| generated.scenario == Scenario::TrailingNewline)
+        .unwrap();
+    assert_eq!(
+        trailing.file,
+        Err("check trailing_empty_line is disabled".to_string())
+    );
+}
+
+/// TempDir is the directory of a test in the temporary directory, removed when dropped,
+/// also if an assert failed.
+struct TempDir(std::path::PathBuf);
+
+impl TempDir {
+    /// new returns the directory v25_<name>_<pid>, which is not created.
+    fn new(name: &str) -> Self {
+        TempDir(std::env::temp_dir().join(format!("v25_{name}_{}", std::process::id())))
+    }
+}
+
+impl std::ops::Deref for TempDir {
+    type Target = std::path::Path;
+
+    fn deref(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+impl AsRef<std::path::Path> for TempDir {
+    fn as_ref(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// run_cleaner runs the binary with the cfg of the corpus on a copy of its input files
+/// in dir, which must not exist yet, and returns its exit status and output. it returns
+/// None if the binary was not built, i.e. without the cli feature.
+fn run_cleaner(dir: &std::path::Path, args: &[&str]) -> Option<(std::process::ExitStatus, String)> {
+    run_cleaner_on(dir, "input", args)
+}
+
+/// run_cleaner_on is run_cleaner on a copy of the files in the directory input of the
+/// corpus, with its subdirectories.
+fn run_cleaner_on(
+    dir: &std::path::Path,
+    input: &str,
+    args: &[&str],
+) -> Option<(std::process::ExitStatus, String)> {
+    run_cleaner_env(dir, input, args, &[])
+}
+
+/// run_cleaner_env is run_cleaner_on with the environment variables env set.
+fn run_cleaner_env(
+    dir: &std::path::Path,
+    input: &str,
+    args: &[&str],
+    env: &[(&str, &str)],
+) -> Option<(std::process::ExitStatus, String)> {
+    use std::{fs, path::Path, process::Command};
+
+    use cleaner_lib::testutil::FIXTURE_DIR;
+
+    let exe = Path::new(option_env!("CARGO_BIN_EXE_v25_datacleaner")?);
+    // the binary reads its cfg file from cfg/ next to it
+    let fixtures = Path::new(FIXTURE_DIR);
+    let bin_dir = dir.join("bin");
+    fs::create_dir_all(bin_dir.join("cfg")).unwrap();
+    fs::copy(exe, bin_dir.join("v25_datacleaner")).unwrap();
+    fs::copy(
+        fixtures.join("cfg.yml"),
+        bin_dir.join("cfg").join("v25_data_cfg.yml"),
+    )
+    .unwrap();
+    fs::copy(
+        fixtures.join("ref_OMC.txt"),
+        bin_dir.join("cfg").join("ref_OMC.txt"),
+    )
+    .unwrap();
+    let data = dir.join("data");
+    copy_tree(&fixtures.join(input), &data);
+    let output = Command::new(bin_dir.join("v25_datacleaner"))
+        .arg("-d")
+        .arg(&data)
+        .arg("--allow-elevated")
+        .args(args)
+        // only the arguments turn on the options under test
+        .env_remove(cleaner_lib::audit::AUDIT_ENV)
+        .envs(env.iter().copied())
+        .output()
+        .unwrap();
+    Some((
+        output.status,
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+    ))
+}
+
+/// run_again runs the binary of a run_cleaner in dir once more on its data.
+fn run_again(dir: &std::path::Path, args: &[&str]) -> (std::process::ExitStatus, String) {
+    let output = std::process::Command::new(dir.join("bin").join("v25_datacleaner"))
+        .arg("-d")
+        .arg(dir.join("data"))
+        .arg("--allow-elevated")
+        .args(args)
+        .env_remove(cleaner_lib::audit::AUDIT_ENV)
+        .output()
+        .unwrap();
+    (
+        output.status,
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+    )
+}
+
+/// copy_tree copies the directory from with its subdirectories to to, which must not
+/// exist yet.
+fn copy_tree(from: &std::path::Path, to: &std::path::Path) {
+    std::fs::create_dir(to).unwrap();
+    for entry in std::fs::read_dir(from).unwrap() {
+        let path = entry.unwrap().path();
+        let target = to.join(path.file_name().unwrap());
+        if path.is_dir() {
+            copy_tree(&path, &target);
+        } else {
+            std::fs::copy(&path, target).unwrap();
+        }
+    }
+}
+
+#[test]
+fn size_outliers_are_reported() {
+    let dir = TempDir::new("size_outliers");
+    let report = dir.join("report.json");
+    let Some((status, stdout)) = run_cleaner_on(
+        &dir,
+        "size_outliers",
+        &["--size-outliers", "--report-json", report.to_str().unwrap()],
+    ) else {
+        return;
+    };
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+    let runt = dir.join("data").join("03120300.DAT");
+    let kept = runt.exists();
+    assert!(status.success(), "{stdout}");
+    assert!(
+        stdout.contains("!!! WARNING: 1 file much smaller than the others of their type"),
+        "{stdout}"
+    );
+    let outliers = json["size_outliers"].as_array().unwrap();
+    assert_eq!(outliers.len(), 1, "{outliers:?}");
+    assert_eq!(outliers[0]["path"], runt.to_str().unwrap());
+    // the cfg of the corpus has a station_pattern these names don't match
+    assert_eq!(outliers[0]["group"], "unassigned/DAT");
+    assert_eq!(outliers[0]["size"], 31);
+    assert_eq!(outliers[0]["median"], 1293);
+    // the check only reports
+    assert!(kept);
+    assert_eq!(json["summary"]["unchanged"], 6);
+
+    // five files of a type are needed before the median counts
+    let dir = TempDir::new("size_outliers_min");
+    let (status, stdout) = run_cleaner_on(
+        &dir,
+        "size_outliers",
+        &["--size-outliers", "--size-outlier-min-files", "7"],
+    )
+    .unwrap();
+    assert!(status.success(), "{stdout}");
+    assert!(!stdout.contains("much smaller"), "{stdout}");
+}
+
+#[test]
+fn foreign_instrument_ids_are_flagged() {
+    use std::fs;
+
+    use cleaner_lib::flag_path;
+
+    // four files of NOX-01 and one of NOX-07, the cfg expects the majority
+    let dir = TempDir::new("instrument_id");
+    let report = dir.join("report.json");
+    let Some((status, stdout)) = run_cleaner_on(
+        &dir,
+        "instrument_id",
+        &["--report-json", report.to_str().unwrap()],
+    ) else {
+        return;
+    };
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+    let foreign = dir.join("data").join("03120300.NOX");
+    let flagged = flag_path(&foreign).exists();
+    let kept = foreign.exists();
+    assert!(status.success(), "{stdout}");
+    assert!(
+        stdout.contains("!!! WARNING: 1 file written by another instrument:"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("03120300.NOX\": ID NOX-07, expected NOX-01"),
+        "{stdout}"
+    );
+    let listed = json["foreign_instruments"].as_array().unwrap();
+    assert_eq!(listed.len(), 1, "{listed:?}");
+    assert_eq!(listed[0]["path"], foreign.to_str().unwrap());
+    assert_eq!(listed[0]["id"], "NOX-07");
+    assert_eq!(listed[0]["expected"], "NOX-01");
+    assert_eq!(listed[0]["status"], "flagged");
+    assert_eq!(json["summary"]["flagged"], 1);
+    assert_eq!(json["summary"]["unchanged"], 4);
+    // quarantine flags the file for review, without --quarantine it stays where it is
+    assert!(flagged && kept);
+
+    // --expect-id makes the majority the foreign files
+    let dir = TempDir::new("instrument_id_expect");
+    let report = dir.join("report.json");
+    let (status, stdout) = run_cleaner_on(
+        &dir,
+        "instrument_id",
+        &[
+            "--expect-id",
+            "NOX-07",
+            "--report-json",
+            report.to_str().unwrap(),
+        ],
+    )
+    .unwrap();
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+    assert!(status.success(), "{stdout}");
+    assert!(
+        stdout.contains("!!! WARNING: 4 files written by another instrument:"),
+        "{stdout}"
+    );
+    assert_eq!(json["foreign_instruments"].as_array().unwrap().len(), 4);
+    assert_eq!(json["summary"]["flagged"], 4);
+    assert_eq!(json["summary"]["unchanged"], 1);
+
+    // with --quarantine the foreign file is moved there, with its flag
+    let dir = TempDir::new("instrument_id_moved");
+    let quarantine = dir.join("quarantine");
+    let (status, stdout) = run_cleaner_on(
+        &dir,
+        "instrument_id",
+        &["--quarantine", quarantine.to_str().unwrap()],
+    )
+    .unwrap();
+    let foreign = dir.join("data").join("03120300.NOX");
+    let moved = quarantine.join("03120300.NOX");
+    let left = [foreign.exists(), flag_path(&foreign).exists()];
+    let arrived = [moved.exists(), flag_path(&moved).exists()];
+    assert!(status.success(), "{stdout}");
+    assert!(
+        stdout.contains("moved 1 of 1 files of another instrument to the quarantine"),
+        "{stdout}"
+    );
+    assert_eq!(left, [false, false]);
+    assert_eq!(arrived, [true, true]);
+}
+
+#[test]
+fn line_counts_are_recorded_and_checked() {
+    use std::{fs, process::Command};
+
+    use cleaner_lib::manifest::Manifest;
+
+    let dir = TempDir::new("line_counts");
+    let report = dir.join("report.json");
+    let Some((status, stdout)) = run_cleaner_on(
+        &dir,
+        "line_counts",
+        &[
+            "--force",
+            "--trust-manifest",
+            "--report-json",
+            report.to_str().unwrap(),
+        ],
+    ) else {
+        return;
+    };
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+    let manifest = Manifest::read(&dir.join("data")).unwrap();
+    let expect = |lines: &[&str]| {
+        Command::new(dir.join("bin").join("v25_datacleaner"))
+            .arg("report")
+            .arg(&report)
+            .args(lines)
+            .output()
+            .unwrap()
+    };
+    let deviating = expect(&["--expect-lines", "60", "--tolerance", "1"]);
+    let exact = expect(&["--expect-lines", "60"]);
+    let lenient = expect(&["--expect-lines", "60", "--tolerance", "20"]);
+    assert!(status.success(), "{stdout}");
+
+    // the garbage line above the header and the trailing empty lines are not data, the
+    // empty lines are removed
+    let counts: Vec<(&str, u64, u64)> = json["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|file| {
+            let name = file["path"].as_str().unwrap().rsplit(['/', '\\']).next();
+            (
+                name.unwrap(),
+                file["lines_before"].as_u64().unwrap(),
+                file["lines_after"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        counts,
+        [
+            ("03120000.DAT", 60, 60),
+            ("03120100.DAT", 59, 59),
+            ("03120200.DAT", 42, 42),
+            ("03120300.DAT", 62, 60),
+        ]
+    );
+    let entry = &manifest.files["03120300.DAT"];
+    assert_eq!(
+        (entry.lines_before, entry.lines_after),
+        (Some(62), Some(60))
+    );
+
+    let stdout = String::from_utf8_lossy(&deviating.stdout);
+    assert_eq!(deviating.status.code(), Some(1), "{stdout}");
+    assert!(
+        stdout.starts_with("1 of 4 counted file(s) deviate from 60 ± 1 data lines\n"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("03120200.DAT\": 42 lines (-18)"),
+        "{stdout}"
+    );
+    let stdout = String::from_utf8_lossy(&exact.stdout);
+    assert!(stdout.contains("03120100.DAT\": 59 lines (-1)"), "{stdout}");
+    assert!(stdout.starts_with("2 of 4"), "{stdout}");
+    assert!(lenient.status.success());
+}
+
+#[test]
+fn osc_prefix_is_summed_up() {
+    let dir = TempDir::new("osc_prefix");
+    let report = dir.join("report.json");
+    let Some((status, stdout)) = run_cleaner(
+        &dir,
+        &["--verbose", "--report-json", report.to_str().unwrap()],
+    ) else {
+        return;
+    };
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+    let data = dir.join("data");
+    assert!(status.success(), "{stdout}");
+    let summary = |name: &str| {
+        json["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|file| file["path"].as_str().unwrap().ends_with(name))
+            .map(|file| file["osc_prefix"].clone())
+            .unwrap()
+    };
+
+    let basic = summary("osc_basic.OSC");
+    assert_eq!(basic["source_datetime"], "01.06.23 12:00:00.00");
+    assert_eq!(basic["lines_prefixed"], 2);
+    assert_eq!(basic["header_modified"], true);
+    assert!(basic.get("skipped").is_none(), "{basic}");
+    // the truncated last line is removed before the prefix
+    assert_eq!(summary("osc_last_line_fields.OSC")["lines_prefixed"], 2);
+
+    let bad = summary("osc_bad_datetime.OSC");
+    assert_eq!(bad["source_datetime"], "99.99.99 27:61:61.99");
+    assert_eq!(bad["lines_prefixed"], 0);
+    assert_eq!(bad["header_modified"], false);
+    assert!(
+        bad["skipped"]
+            .as_str()
+            .unwrap()
+            .ends_with("is not a valid datetime (input is out of range)"),
+        "{bad}"
+    );
+    // files that are not OSC never get a summary
+    assert!(summary("dat_clean.DAT").is_null());
+
+    let line = format!(
+        "datetime prefix of {:?}: '01.06.23 12:00:00.00' applied to 2 data line(s), DateTime column added to the header",
+        data.join("osc_basic.OSC")
+    );
+    assert!(stdout.contains(&line), "{stdout}");
+    let line = format!(
+        "datetime prefix of {:?}: skipped, '99.99.99 27:61:61.99' is not a valid datetime",
+        data.join("osc_bad_datetime.OSC")
+    );
+    assert!(stdout.contains(&line), "{stdout}");
+}
+
+#[test]
+fn limit_stops_after_n_files() {
+    use cleaner_lib::marker;
+
+    let dir = TempDir::new("limit");
+    let report = dir.join("report.json");
+    let run = run_cleaner(
+        &dir,
+        &["--limit", "7", "--report-json", report.to_str().unwrap()],
+    );
+    let Some((status, stdout)) = run else {
+        return;
+    };
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+    let data = dir.join("data");
+    let markers = [marker::DONE_MARKER, marker::PARTIAL_MARKER].map(|m| data.join(m).exists());
+    assert!(status.success(), "{stdout}");
+    assert!(stdout.contains("limited by --limit to 7 files"), "{stdout}");
+    assert_eq!(json["summary"]["scanned"], 7);
+    assert_eq!(json["files"].as_array().unwrap().len(), 7);
+    assert_eq!(json["limit"]["limit"], 7);
+    assert_eq!(markers, [false, false]);
+}
+
+#[test]
+fn max_runtime_stops_and_leaves_the_rest() {
+    use std::time::Duration;
+
+    use cleaner_lib::{marker, parse_duration};
+
+    for (s, duration) in [
+        ("9m", Ok(Duration::from_secs(540))),
+        ("1h30m", Ok(Duration::from_secs(5400))),
+        ("1h 30m 5s", Ok(Duration::from_secs(5405))),
+        ("500ms", Ok(Duration::from_millis(500))),
+        (
+            "90",
+            Err("invalid duration '90', expected e.g. 90s, 9m or 1h30m".to_string()),
+        ),
+        (
+            "m",
+            Err("invalid duration 'm', expected e.g. 90s, 9m or 1h30m".to_string()),
+        ),
+    ] {
+        assert_eq!(parse_duration(s), duration, "{s}");
+    }
+
+    // the budget is used up before the first file, which is still cleaned
+    let dir = TempDir::new("max_runtime");
+    let report = dir.join("report.json");
+    let Some((status, stdout)) = run_cleaner(
+        &dir,
+        &[
+            "--max-runtime",
+            "1ms",
+            "--report-json",
+            report.to_str().unwrap(),
+        ],
+    ) else {
+        return;
+    };
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+    let data = dir.join("data");
+    let done = data.join(marker::DONE_MARKER).exists();
+    let partial = std::fs::read_to_string(data.join(marker::PARTIAL_MARKER)).unwrap_or_default();
+    assert_eq!(status.code(), Some(11), "{stdout}");
+    assert!(
+        stdout.contains("! stopped by --max-runtime after "),
+        "{stdout}"
+    );
+    assert_eq!(json["summary"]["scanned"], 1);
+    assert_eq!(json["timed_out"], true);
+    assert!(!done);
+    assert!(partial.lines().count() > 1, "{partial}");
+}
+
+#[test]
+fn interrupted_run_lists_the_files_left() {
+    use cleaner_lib::marker;
+
+    let dir = TempDir::new("interrupted");
+    let env = [(cleaner_lib::testutil::INTERRUPT_AFTER, "5")];
+    let Some((status, stdout)) = run_cleaner_env(&dir, "input", &[], &env) else {
+        return;
+    };
+    let data = dir.join("data");
+    let done = data.join(marker::DONE_MARKER).exists();
+    let state = marker::read(&data);
+    assert_eq!(status.code(), Some(130), "{stdout}");
+    let line = stdout
+        .lines()
+        .find(|line| line.starts_with("! interrupted, cleaned 5 of "))
+        .unwrap_or_else(|| panic!("{stdout}"));
+    let total: usize = line["! interrupted, cleaned 5 of ".len()..]
+        .split(' ')
+        .next()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(!done);
+    let Ok(marker::State::Partial(left)) = state else {
+        panic!("{state:?}");
+    };
+    assert_eq!(left.len(), total - 5, "{left:?}");
+}
+
+#[test]
+fn partial_marker_resumes_the_files_left() {
+    use std::fs;
+
+    use cleaner_lib::{backup::BACKUP_DIR, marker};
+
+    // the backup of an earlier run makes --backup fail on this file, and only on it
+    let dir = TempDir::new("partial");
+    let Some((status, stdout)) = run_cleaner(&dir, &["--dry-run"]) else {
+        return;
+    };
+    assert!(status.success(), "{stdout}");
+    let data = dir.join("data");
+    fs::create_dir(data.join(BACKUP_DIR)).unwrap();
+    let failing = "dat_trailing_empty_lines.DAT";
+    fs::write(data.join(BACKUP_DIR).join(failing), "earlier run\n").unwrap();
+    let (_, failed_stdout) = run_again(&dir, &["--backup"]);
+    let failed = marker::read(&data);
+
+    // the next run cleans only that file, and the directory is done
+    fs::remove_file(data.join(BACKUP_DIR).join(failing)).unwrap();
+    let (resumed_status, resumed_stdout) = run_again(&dir, &["--backup"]);
+    let resumed = marker::read(&data);
+
+    // --force cleans every file, whatever the partial marker lists
+    marker::write(
+        &data,
+        &[data.join(failing)],
+        &cleaner_lib::build_info::BuildInfo::current(),
+        None,
+    )
+    .unwrap();
+    let (forced_status, forced_stdout) = run_again(&dir, &["--force"]);
+    let forced = marker::read(&data);
+
+    assert!(failed_stdout.contains("1 errors"), "{failed_stdout}");
+    assert_eq!(
+        failed.unwrap(),
+        marker::State::Partial(vec![failing.into()]),
+        "{failed_stdout}"
+    );
+    assert!(resumed_status.success(), "{resumed_stdout}");
+    assert!(
+        resumed_stdout.contains("resuming, cleaning the 1 file(s) left in"),
+        "{resumed_stdout}"
+    );
+    assert!(
+        resumed_stdout.contains("\nscanned 1 files: "),
+        "{resumed_stdout}"
+    );
+    assert_eq!(resumed.unwrap(), marker::State::Done);
+    assert!(forced_status.success(), "{forced_stdout}");
+    assert!(!forced_stdout.contains("resuming"), "{forced_stdout}");
+    assert!(
+        !forced_stdout.contains("\nscanned 1 files: "),
+        "{forced_stdout}"
+    );
+    assert_eq!(forced.unwrap(), marker::State::Done);
+}
+
+#[test]
+fn audit_names_user_host_and_directory() {
+    use cleaner_lib::marker::DONE_MARKER;
+
+    let dir = TempDir::new("audit");
+    let report = dir.join("report.json");
+    let Some((status, stdout)) = run_cleaner(
+        &dir,
+        &["--audit", "--report-json", report.to_str().unwrap()],
+    ) else {
+        return;
+    };
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+    let done = std::fs::read_to_string(dir.join("data").join(DONE_MARKER)).unwrap_or_default();
+    assert!(status.success(), "{stdout}");
+    assert!(stdout.contains("audit: "), "{stdout}");
+    let audit = json["audit"].as_object().unwrap();
+    let cwd = std::env::current_dir().unwrap();
+    assert_eq!(audit["working_directory"], cwd.to_str().unwrap());
+    // unknown values are left out, never empty
+    assert!(
+        audit
+            .values()
+            .all(|v| v.as_str().is_some_and(|s| !s.is_empty())),
+        "{audit:?}"
+    );
+    assert!(
+        done.contains(&format!("working directory: {}", cwd.display())),
+        "{done}"
+    );
+
+    let dir = TempDir::new("no_audit");
+    let report = dir.join("report.json");
+    run_cleaner(&dir, &["--report-json", report.to_str().unwrap()]);
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+    assert!(json.get("audit").is_none(), "{}", json["audit"]);
+}
+
+#[test]
+fn low_free_space_warns() {
+    use std::path::Path;
+
+    use cleaner_lib::preflight::{DiskSpace, FreeSpaceThreshold};
+
+    for (s, threshold) in [
+        ("5G", Ok(FreeSpaceThreshold::Bytes(5 << 30))),
+        ("512", Ok(FreeSpaceThreshold::Bytes(512))),
+        ("2.5%", Ok(FreeSpaceThreshold::Percent(2.5))),
+        (
+            "150%",
+            Err("invalid free space '150%', expected e.g. 5G or 10%".to_string()),
+        ),
+        (
+            "5X",
+            Err("invalid free space '5X', expected e.g. 5G or 10%".to_string()),
+        ),
+    ] {
+        assert_eq!(s.parse::<FreeSpaceThreshold>(), threshold, "{s}");
+    }
+
+    // a disk of 100 GiB with 1 GiB free, as the helper would report it
+    let space = DiskSpace {
+        free: 1 << 30,
+        total: 100 << 30,
+    };
+    let low = |s: &str| s.parse::<FreeSpaceThreshold>().unwrap().is_low(&space);
+    assert!(low("5G"));
+    assert!(!low("1G"), "exactly at the threshold is not low");
+    assert!(low("2%"));
+    assert!(!low("1%"));
+    let threshold: FreeSpaceThreshold = "5G".parse().unwrap();
+    assert_eq!(
+        threshold.warning(&space, Path::new("/data")).as_deref(),
+        Some(
+            "!!! the disk is almost full: 1.0 GiB (1.0%) free of 100.0 GiB on the file system \
+             of \"/data\", below 5.0 GiB; files written on a full disk end up truncated"
+        )
+    );
+
+    // every disk that is not empty has less than 100% free
+    let dir = TempDir::new("free_space");
+    let report = dir.join("report.json");
+    let metrics = dir.join("metrics.prom");
+    let Some((status, stdout)) = run_cleaner(
+        &dir,
+        &[
+            "--warn-free-space",
+            "100%",
+            "--report-json",
+            report.to_str().unwrap(),
+            "--metrics",
+            metrics.to_str().unwrap(),
+        ],
+    ) else {
+        return;
+    };
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+    let metrics = std::fs::read_to_string(&metrics).unwrap();
+    assert!(status.success(), "{stdout}");
+    assert!(
+        stdout
+            .lines()
+            .any(|line| line.starts_with("!!! the disk is almost full: ")),
+        "{stdout}"
+    );
+    assert!(json["free_space_bytes"].as_u64().is_some(), "{json}");
+    assert!(
+        metrics.contains("\nv25cleaner_free_space_bytes "),
+        "{metrics}"
+    );
+
+    let dir = TempDir::new("free_space_off");
+    let (status, stdout) = run_cleaner(&dir, &["--warn-free-space", "0"]).unwrap();
+    assert!(status.success(), "{stdout}");
+    assert!(!stdout.contains("almost full"), "{stdout}");
+}
+
+#[test]
+fn hook_shell_is_unix_only() {
+    use cleaner_lib::hook::{Hook, DELETE_PLACEHOLDERS};
+
+    let hook = Hook::new(
+        "echo {path}",
+        DELETE_PLACEHOLDERS,
+        true,
+        std::time::Duration::from_secs(1),
+    );
+    assert_eq!(hook.is_err(), cfg!(windows), "{hook:?}");
+}
+
+#[cfg(unix)]
+#[test]
+fn hooks_run_for_deleted_files_and_at_the_end() {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    let dir = TempDir::new("hooks");
+    fs::create_dir_all(&dir).unwrap();
+    // records the arguments of each call as one line, separated by |
+    let script = dir.join("record.sh");
+    fs::write(
+        &script,
+        "#!/bin/sh\nfor arg in \"$@\"; do printf '%s|' \"$arg\"; done >> \"$(dirname \"$0\")/calls.txt\"\n\
+         echo >> \"$(dirname \"$0\")/calls.txt\"\n",
+    )
+    .unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    let report = dir.join("report.json");
+    let on_delete = format!("{} {{name}} {{check}} {{reason}}", script.display());
+    let on_finish = format!(
+        "echo {{count_deleted}} {{directory}} > {}",
+        dir.join("finish.txt").display()
+    );
+    let Some((status, stdout)) = run_cleaner(
+        &dir,
+        &[
+            "--really-delete",
+            "--report-json",
+            report.to_str().unwrap(),
+            "--on-delete-cmd",
+            &on_delete,
+            "--hook-shell",
+            "--on-finish-cmd",
+            &on_finish,
+        ],
+    ) else {
+        return;
+    };
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+    let calls = fs::read_to_string(dir.join("calls.txt")).unwrap_or_default();
+    let finish = fs::read_to_string(dir.join("finish.txt")).unwrap_or_default();
+    let data = dir.join("data");
+    assert!(status.success(), "{stdout}");
+    let deleted = json["summary"]["deleted"].as_u64().unwrap();
+    assert!(deleted > 0);
+    assert_eq!(calls.lines().count() as u64, deleted, "{calls}");
+    // a reason with spaces is one argument, no shell split it
+    assert!(
+        calls
+            .lines()
+            .any(|line| line == "no_extension|no_extension|has no extension -> delete file|"),
+        "{calls}"
+    );
+    assert_eq!(finish.trim(), format!("{deleted} {}", data.display()));
+
+    // a failing hook is logged, the run goes on; a bad one stops it before cleaning
+    let dir = TempDir::new("bad_hooks");
+    let missing = dir.join("missing.sh");
+    let (status, stdout) =
+        run_cleaner(&dir, &["--on-finish-cmd", missing.to_str().unwrap()]).unwrap();
+    assert!(status.success(), "{stdout}");
+    assert!(
+        stdout.contains(&format!(
+            "! --on-finish-cmd: could not start '{}'",
+            missing.display()
+        )),
+        "{stdout}"
+    );
+    let dir = TempDir::new("bad_hooks_refused");
+    let (status, stdout) = run_cleaner(&dir, &["--on-delete-cmd", "notify {file}"]).unwrap();
+    let untouched = dir.join("data").join("no_extension").exists();
+    assert!(!status.success(), "{stdout}");
+    assert!(untouched);
+    assert!(
+        stdout.contains(
+            "! invalid --on-delete-cmd 'notify {file}': unknown placeholder '{file}', expected \
+             one of {path}, {name}, {check}, {reason}"
+        ),
+        "{stdout}"
+    );
+}
+
+/// the delete hook runs after all files were checked, so it can change a file that was
+/// reported unchanged, as a buggy fast path or another process would
+#[cfg(unix)]
+#[test]
+fn spot_check_catches_changed_files() {
+    use std::fs;
+
+    let dir = TempDir::new("spot_check");
+    let report = dir.join("report.json");
+    let Some((status, stdout)) = run_cleaner(
+        &dir,
+        &[
+            "--spot-check",
+            "1000",
+            "--seed",
+            "7",
+            "--report-json",
+            report.to_str().unwrap(),
+        ],
+    ) else {
+        return;
+    };
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+    assert!(status.success(), "{stdout}");
+    let unchanged = json["summary"]["unchanged"].as_u64().unwrap();
+    assert!(unchanged > 0);
+    assert_eq!(json["spot_check"]["checked"].as_u64(), Some(unchanged));
+    assert!(
+        stdout.contains(&format!(
+            "spot check: hashed {unchanged} unchanged file(s) again, all as checked (seed 7)"
+        )),
+        "{stdout}"
+    );
+
+    let dir = TempDir::new("spot_check_changed");
+    let report = dir.join("report.json");
+    let changed = dir.join("data").join("dat_clean.DAT");
+    let on_delete = format!("printf x >> {}", changed.display());
+    let (status, stdout) = run_cleaner(
+        &dir,
+        &[
+            "--spot-check",
+            "1000",
+            "--really-delete",
+            "--hook-shell",
+            "--on-delete-cmd",
+            &on_delete,
+            "--report-json",
+            report.to_str().unwrap(),
+        ],
+    )
+    .unwrap();
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+    assert_eq!(status.code(), Some(12), "{stdout}");
+    assert!(
+        stdout.contains(&format!(
+            "!!! CRITICAL: {changed:?} was reported unchanged, but its content changed after \
+             it was checked"
+        )),
+        "{stdout}"
+    );
+    let mismatches = json["spot_check"]["mismatches"].as_array().unwrap();
+    assert_eq!(mismatches.len(), 1, "{mismatches:?}");
+    assert_eq!(mismatches[0]["path"].as_str(), changed.to_str());
+}
+
+#[test]
+fn stations_group_the_report() {
+    use cleaner_lib::marker::DONE_MARKER;
+
+    let dir = TempDir::new("stations");
+    let report = dir.join("report.json");
+    let Some((status, stdout)) = run_cleaner(
+        &dir,
+        &[
+            "--detect-overlaps",
+            "--report-json",
+            report.to_str().unwrap(),
+        ],
+    ) else {
+        return;
+    };
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+    assert!(status.success(), "{stdout}");
+    let stations = json["stations"].as_object().unwrap();
+    assert_eq!(
+        stations.keys().collect::<Vec<_>>(),
+        ["A", "B", "unassigned"]
+    );
+    assert_eq!(stations["A"]["scanned"], 2);
+    assert_eq!(stations["B"]["modified"], 1);
+    assert!(
+        stdout.contains("  station B: scanned 1 files: "),
+        "{stdout}"
+    );
+    // A and B cover the same time, only the two files of A overlap
+    let overlaps = json["coverage"]["A/TRW"]["overlaps"].as_array().unwrap();
+    assert_eq!(overlaps.len(), 1, "{overlaps:?}");
+    assert!(json["coverage"]["B/TRW"]["overlaps"].is_null());
+    assert!(
+        stdout.contains("1 pair(s) of files cover the same time"),
+        "{stdout}"
+    );
+
+    let dir = TempDir::new("station_a");
+    let report = dir.join("report.json");
+    run_cleaner(
+        &dir,
+        &["--station", "A", "--report-json", report.to_str().unwrap()],
+    );
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+    let done = dir.join("data").join(DONE_MARKER).exists();
+    let files = json["files"].as_array().unwrap();
+    assert_eq!(files.len(), 2, "{files:?}");
+    assert!(files.iter().all(|f| f["station"] == "A"), "{files:?}");
+    assert!(!done, "one station is not the whole directory");
+}
+
+#[test]
+fn missing_hourly_files_are_reported() {
+    use cleaner_lib::{completeness::name_formats, config};
+
+    let dir = TempDir::new("missing_files");
+    let report = dir.join("report.json");
+    let Some((status, stdout)) = run_cleaner_on(
+        &dir,
+        "missing_files",
+        &["--missing-files", "--report-json", report.to_str().unwrap()],
+    ) else {
+        return;
+    };
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+    // report only, unless asked for
+    assert!(status.success(), "{stdout}");
+    let missing = json["missing_files"].as_array().unwrap();
+    assert_eq!(missing.len(), 1, "{missing:?}");
+    assert_eq!(missing[0]["extension"], "DAT");
+    assert_eq!(missing[0]["names"], serde_json::json!(["03121400.DAT"]));
+    assert!(missing[0]["from"]
+        .as_str()
+        .unwrap()
+        .ends_with("-03-12 00:00"));
+    assert!(missing[0]["to"].as_str().unwrap().ends_with("-03-12 23:00"));
+    assert!(
+        stdout.contains("!!! WARNING: 1 file missing between "),
+        "{stdout}"
+    );
+
+    let dir = TempDir::new("strict_missing");
+    let status = run_cleaner_on(
+        &dir,
+        "missing_files",
+        &["--missing-files", "--strict-missing"],
+    )
+    .map(|(status, _)| status);
+    assert_eq!(status.and_then(|status| status.code()), Some(13));
+
+    let cfg = config::parse("DAT: {hourly_file_name: '%m%d%M'}").unwrap();
+    let err = name_formats(&cfg).unwrap_err();
+    assert!(
+        err.starts_with("DAT: hourly_file_name unsupported '%M'"),
+        "{err}"
+    );
+}
+
+#[test]
+fn dry_run_changes_nothing() {
+    use std::{collections::BTreeMap, fs, path::Path};
+
+    use cleaner_lib::{
+        manifest::MANIFEST_FILE,
+        marker::{DONE_MARKER, PARTIAL_MARKER},
+        testutil::FIXTURE_DIR,
+    };
+
+    let statuses = |report: &Path| -> BTreeMap<String, String> {
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(report).unwrap()).unwrap();
+        json["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| {
+                let name = file["path"].as_str().unwrap().rsplit(['/', '\\']).next();
+                (name.unwrap().to_string(), file["status"].to_string())
+            })
+            .collect()
+    };
+    let dir = TempDir::new("dry_run");
+    let report = dir.join("report.json");
+    let Some((status, stdout)) = run_cleaner(
+        &dir,
+        &[
+            "--dry-run",
+            "--really-delete",
+            "--force",
+            "--trust-manifest",
+            "--report-json",
+            report.to_str().unwrap(),
+        ],
+    ) else {
+        return;
+    };
+    let data = dir.join("data");
+    let unchanged = fs::read_dir(Path::new(FIXTURE_DIR).join("input"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .all(|path| fs::read(&path).ok() == fs::read(data.join(path.file_name().unwrap())).ok());
+    let n_files = fs::read_dir(&data).unwrap().count();
+    let written = [DONE_MARKER, PARTIAL_MARKER, MANIFEST_FILE].map(|name| data.join(name).exists());
+    let dry = statuses(&report);
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+
+    // the same decisions as a real run
+    let dir_real = TempDir::new("dry_run_real");
+    let report_real = dir_real.join("report.json");
+    run_cleaner(
+        &dir_real,
+        &[
+            "--really-delete",
+            "--report-json",
+            report_real.to_str().unwrap(),
+        ],
+    );
+    let real = statuses(&report_real);
+
+    assert!(status.success(), "{stdout}");
+    assert!(unchanged, "{stdout}");
+    assert_eq!(
+        n_files,
+        fs::read_dir(Path::new(FIXTURE_DIR).join("input"))
+            .unwrap()
+            .count()
+    );
+    assert_eq!(written, [false; 3]);
+    assert_eq!(json["dry_run"], true);
+    assert_eq!(dry, real);
+    assert!(
+        stdout.contains("would delete \"") && stdout.contains("would modify \""),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("dry run, nothing was changed: would delete 19, flag 2, modify 33"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn osc_prefix_with_the_data_delimiter_is_quoted_or_refused() {
+    use std::fs;
+
+    use cleaner_lib::{check_osc_prefix, clean_file, compile_rules, config, CleanOptions, Outcome};
+
+    let tab = "OSC: {min_n_lines: 6, header_line: 4, prefix_output_format: \"%Y-%m-%d\\t%H:%M:%S\"";
+    let space = "OSC: {min_n_lines: 6, header_line: 4, delimiter: ' ', prefix_separator: ' '";
+    let err = check_osc_prefix(&config::parse(&format!("{tab}}}")).unwrap()).unwrap_err();
+    assert!(
+        err.starts_with("OSC: the datetime prefix '2000-01-01\t00:00:00' of format"),
+        "{err}"
+    );
+    assert!(err.contains("delimiter '\\t'"), "{err}");
+    // the raw datetime has a space, like the default datetime format
+    let err = check_osc_prefix(&config::parse(&format!("{space}}}")).unwrap()).unwrap_err();
+    assert!(err.contains("delimiter ' '"), "{err}");
+    // quoting the prefix can't help if the data is delimited by something else
+    let cfg = "OSC: {min_n_lines: 6, header_line: 4, delimiter: ' ', quote_char: '\"'}";
+    assert!(check_osc_prefix(&config::parse(cfg).unwrap()).is_err());
+
+    let dir = TempDir::new("quoted_prefix");
+    fs::create_dir_all(&dir).unwrap();
+    let cases = [
+        (
+            tab,
+            "01.06.23 12:00:00.00\np1\np2\t0.5\np3\nh1\th2\n1\t2\n3\t4\n",
+            "\t\"2023-06-01\t12:00:00\"\t1\t2",
+        ),
+        (
+            space,
+            "01.06.23 12:00:00.00\np1\np2 0.5\np3\nh1 h2\n1 2\n3 4\n",
+            " \"01.06.23 12:00:00.00\" 1 2",
+        ),
+    ];
+    let mut results = Vec::new();
+    for (i, (cfg, content, first_data_line)) in cases.into_iter().enumerate() {
+        let cfg = format!("{cfg}, quote_char: '\"', join_delimiter: true}}");
+        let cfg = &config::parse(&cfg).unwrap();
+        check_osc_prefix(cfg).unwrap();
+        let opts = CleanOptions {
+            compiled_rules: compile_rules(cfg).unwrap(),
+            ..Default::default()
+        };
+        let path = dir.join(format!("{i}.OSC"));
+        fs::write(&path, content).unwrap();
+        let first = clean_file(&path, cfg, &opts).unwrap();
+        let prefixed = fs::read_to_string(&path).unwrap();
+        // the next run finds the file as the first left it
+        let second = clean_file(&path, cfg, &opts).unwrap();
+        results.push((first, prefixed, second, first_data_line));
+    }
+    for (first, prefixed, second, first_data_line) in results {
+        assert_eq!(first.outcome, Outcome::Modified, "{first:?}");
+        assert_eq!(prefixed.lines().nth(5), Some(first_data_line), "{prefixed}");
+        assert_eq!(second.outcome, Outcome::Unchanged, "{second:?}");
+        assert!(second.findings.is_empty(), "{second:?}");
+    }
+}
+
+#[test]
+fn recursive_cleans_subdirectories_with_their_own_markers() {
+    use std::{fs, path::Path};
+
+    use cleaner_lib::{marker::DONE_MARKER, testutil::FIXTURE_DIR};
+
+    let run = |name: &str, args: &[&str]| {
+        let dir = TempDir::new(name);
+        let result = run_cleaner_on(&dir, "nested", args);
+        (dir, result)
+    };
+    let done = |data: &Path| -> Vec<String> {
+        ["", "20260312", "20260313", "campaign", "campaign/20260314"]
+            .into_iter()
+            .filter(|sub| data.join(sub).join(DONE_MARKER).exists())
+            .map(String::from)
+            .collect()
+    };
+
+    // without --recursive only the files directly in the directory are cleaned
+    let (flat_dir, flat) = run("flat", &[]);
+    let Some((flat_status, flat_stdout)) = flat else {
+        return;
+    };
+    let flat_done = done(&flat_dir.join("data"));
+    let name = Path::new("20260312").join("dat_trailing_empty_lines.DAT");
+    let flat_untouched = fs::read(flat_dir.join("data").join(&name)).ok()
+        == fs::read(Path::new(FIXTURE_DIR).join("nested").join(&name)).ok();
+
+    // a directory with only directories in it is fine, deeper ones are listed
+    let (depth_dir, depth) = run("max_depth", &["--recursive", "--max-depth", "1"]);
+    let (depth_status, depth_stdout) = depth.unwrap();
+    let depth_done = done(&depth_dir.join("data"));
+
+    let (all_dir, all) = run("recursive", &["--recursive"]);
+    let (all_status, all_stdout) = all.unwrap();
+    let all_data = all_dir.join("data");
+    let all_done = done(&all_data);
+    // a second run finds every directory done
+    let again = run_again(&all_dir, &["--recursive"]).1;
+
+    // a symlinked directory is not followed
+    #[cfg(unix)]
+    let linked = {
+        std::os::unix::fs::symlink(all_data.join("20260312"), all_data.join("link")).unwrap();
+        run_again(&all_dir, &["--recursive", "--force"]).1
+    };
+
+    assert!(flat_status.success(), "{flat_stdout}");
+    assert_eq!(flat_done, [""]);
+    assert!(flat_untouched);
+    assert!(
+        flat_stdout.contains(
+            "3 subdirectories were not processed, only files directly in the directory are \
+             cleaned:\n  20260312\n  20260313\n  campaign"
+        ),
+        "{flat_stdout}"
+    );
+
+    assert!(depth_status.success(), "{depth_stdout}");
+    assert_eq!(depth_done, ["", "20260312", "20260313", "campaign"]);
+    assert!(
+        depth_stdout.contains(
+            "1 subdirectory was not processed, symlinked, unreadable or deeper than \
+             --max-depth:\n  campaign/20260314"
+        ),
+        "{depth_stdout}"
+    );
+
+    assert!(all_status.success(), "{all_stdout}");
+    assert_eq!(
+        all_done,
+        ["", "20260312", "20260313", "campaign", "campaign/20260314"]
+    );
+    assert!(
+        all_stdout.contains("cleaning 5 director(ies), 0 skipped as already done"),
+        "{all_stdout}"
+    );
+    assert!(!all_stdout.contains("not processed"), "{all_stdout}");
+    assert!(
+        all_stdout.contains("modified") && all_stdout.contains("dat_no_final_newline.DAT"),
+        "{all_stdout}"
+    );
+    assert!(again.contains("cleanup was already done"), "{again}");
+
+    #[cfg(unix)]
+    assert!(
+        linked.contains("1 subdirectory was not processed, symlinked, unreadable or deeper than --max-depth:\n  link")
+            && linked.contains("cleaning 5 director(ies)"),
+        "{linked}"
+    );
+}
+
+#[test]
+fn fuzz_findings_stay_fixed() {
+    use std::{fs, path::Path, sync::mpsc, thread, time::Duration};
+
+    use cleaner_lib::{fuzz, testutil::FIXTURE_DIR};
+
+    let targets = [
+        ("clean_lines", fuzz::fuzz_clean_lines as fn(&[u8])),
+        ("fields", fuzz::fuzz_fields),
+        ("osc", fuzz::fuzz_osc),
+    ];
+    for (target, run) in targets {
+        let Ok(entries) = fs::read_dir(Path::new(FIXTURE_DIR).join("fuzz").join(target)) else {
+            continue;
+        };
+        for entry in entries {
+            let path = entry.unwrap().path();
+            let data = fs::read(&path).unwrap();
+            let (done, finished) = mpsc::channel();
+            thread::spawn(move || {
+                run(&data);
+                let _ = done.send(());
+            });
+            // an input that hangs fails the test instead of stalling it
+            match finished.recv_timeout(Duration::from_secs(10)) {
+                Ok(()) => (),
+                Err(mpsc::RecvTimeoutError::Timeout) => panic!("fuzz_{target} hangs on {path:?}"),
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    panic!("fuzz_{target} panics on {path:?}")
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn quarantine_keeps_the_deleted_files() {
+    use std::fs;
+
+    use cleaner_lib::testutil::FIXTURE_DIR;
+
+    let dir = TempDir::new("quarantine");
+    let quarantine = dir.join("quarantine");
+    let report = dir.join("report.json");
+    // a file of an earlier run has the name of one deleted now
+    fs::create_dir_all(&quarantine).unwrap();
+    fs::write(quarantine.join("dat_empty.DAT"), "earlier run\n").unwrap();
+    let Some((status, stdout)) = run_cleaner(
+        &dir,
+        &[
+            "--really-delete",
+            "--quarantine",
+            quarantine.to_str().unwrap(),
+            "--report-json",
+            report.to_str().unwrap(),
+        ],
+    ) else {
+        return;
+    };
+    let data = dir.join("data");
+    let removed = !data.join("dat_empty.DAT").exists();
+    let earlier = fs::read_to_string(quarantine.join("dat_empty.DAT")).unwrap();
+    let moved = fs::read(quarantine.join("dat_empty_1.DAT")).ok();
+    let n_quarantined = fs::read_dir(&quarantine).unwrap().count();
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+
+    // the quarantine must not be the directory that is cleaned
+    let dir_same = TempDir::new("quarantine_same");
+    let (status_same, stdout_same) = run_cleaner(
+        &dir_same,
+        &["--quarantine", dir_same.join("data").to_str().unwrap()],
+    )
+    .unwrap();
+
+    assert!(status.success(), "{stdout}");
+    assert!(removed, "{stdout}");
+    assert_eq!(earlier, "earlier run\n");
+    assert_eq!(
+        moved,
+        fs::read(std::path::Path::new(FIXTURE_DIR).join("input/dat_empty.DAT")).ok()
+    );
+    assert_eq!(n_quarantined, 20);
+    assert!(stdout.contains("quarantined 19 file(s) in \""), "{stdout}");
+    assert_eq!(json["quarantine"]["files"], 19);
+    assert!(!status_same.success(), "{stdout_same}");
+    assert!(stdout_same.contains("! --quarantine:"), "{stdout_same}");
+}
+
+#[test]
+fn backup_keeps_the_originals_of_changed_files() {
+    use std::{fs, path::Path};
+
+    use cleaner_lib::{backup::BACKUP_DIR, marker::DONE_MARKER, testutil::FIXTURE_DIR};
+
+    let dir = TempDir::new("backup");
+    let Some((status, stdout)) = run_cleaner(&dir, &["--really-delete", "--backup"]) else {
+        return;
+    };
+    let data = dir.join("data");
+    let backups = data.join(BACKUP_DIR);
+    let input = |name: &str| fs::read(Path::new(FIXTURE_DIR).join("input").join(name)).unwrap();
+    let deleted =
+        (!data.join("dat_empty.DAT").exists()).then(|| fs::read(backups.join("dat_empty.DAT")));
+    let modified = fs::read(backups.join("dat_trailing_empty_lines.DAT")).ok();
+    let unchanged_kept = backups.join("dat_clean.DAT").exists();
+
+    // the backup of an earlier run is kept, and the file left as it is, unless --force
+    let run_again = |args: &[&str]| {
+        let _ = fs::remove_file(data.join(DONE_MARKER));
+        fs::write(
+            data.join("dat_trailing_empty_lines.DAT"),
+            input("dat_trailing_empty_lines.DAT"),
+        )
+        .unwrap();
+        fs::write(
+            backups.join("dat_trailing_empty_lines.DAT"),
+            "earlier run\n",
+        )
+        .unwrap();
+        let (_, stdout) = run_again(&dir, args);
+        (
+            stdout,
+            fs::read(data.join("dat_trailing_empty_lines.DAT")).unwrap(),
+            fs::read(backups.join("dat_trailing_empty_lines.DAT")).unwrap(),
+        )
+    };
+    let (kept_stdout, kept_file, kept_backup) = run_again(&["--backup"]);
+    let (forced_stdout, forced_file, forced_backup) = run_again(&["--backup", "--force"]);
+
+    assert!(status.success(), "{stdout}");
+    assert_eq!(deleted.map(Result::ok), Some(Some(input("dat_empty.DAT"))));
+    assert_eq!(modified, Some(input("dat_trailing_empty_lines.DAT")));
+    assert!(!unchanged_kept);
+    assert!(
+        stdout.contains("backed up 52 file(s) before changing them"),
+        "{stdout}"
+    );
+
+    assert!(
+        kept_stdout.contains("pass --force to replace it"),
+        "{kept_stdout}"
+    );
+    assert_eq!(kept_file, input("dat_trailing_empty_lines.DAT"));
+    assert_eq!(kept_backup, b"earlier run\n");
+    // the backups are not a subdirectory to warn about
+    assert!(!kept_stdout.contains("not processed"), "{kept_stdout}");
+
+    assert_ne!(
+        forced_file,
+        input("dat_trailing_empty_lines.DAT"),
+        "{forced_stdout}"
+    );
+    assert_eq!(forced_backup, input("dat_trailing_empty_lines.DAT"));
+}
+
+#[test]
+fn jobs_clean_in_parallel_with_the_same_output() {
+    use std::fs;
+
+    // the lines of a run without the times it took
+    let run = |name: &str, input: &str, args: &[&str]| {
+        let dir = TempDir::new(&format!("jobs_{name}"));
+        let report = dir.join("report.json");
+        let args = [args, &["--report-json", report.to_str().unwrap()]].concat();
+        let result = run_cleaner_on(&dir, input, &args).map(|(status, stdout)| {
+            let lines: Vec<String> = stdout
+                .lines()
+                .filter(|line| !line.starts_with("removed ") && !line.starts_with("scanned "))
+                .map(|line| line.replace(dir.to_str().unwrap(), "DIR"))
+                .collect();
+            let json: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+            (status.success(), lines, json["summary"].clone())
+        });
+        result
+    };
+    let args = ["--really-delete", "--no-group", "--verbose", "--diff"];
+    let Some(one) = run("one", "input", &[&args[..], &["--jobs", "1"]].concat()) else {
+        return;
+    };
+    let four = run("four", "input", &[&args[..], &["--jobs", "4"]].concat()).unwrap();
+    // files of the same name in several directories are evaluated side by side
+    let dry = ["--dry-run", "--recursive", "--no-group", "--verbose"];
+    let dry_one = run("dry_one", "nested", &[&dry[..], &["--jobs", "1"]].concat()).unwrap();
+    let dry_four = run("dry_four", "nested", &[&dry[..], &["--jobs", "4"]].concat()).unwrap();
+
+    assert!(one.0, "{:?}", one.1);
+    assert_eq!(one, four);
+    assert!(dry_one.0, "{:?}", dry_one.1);
+    assert_eq!(dry_one, dry_four);
+}
+
+#[test]
+fn unordered_report_matches_the_sorted_one() {
+    use cleaner_lib::report::Report;
+    use regex::Regex;
+
+    // the durations differ from run to run
+    let duration = Regex::new(r"[0-9.]+(ns|µs|ms|s)\b").unwrap();
+    let run = |name: &str, input: &str, args: &[&str]| {
+        let dir = TempDir::new(&format!("unordered_{name}"));
+        let path = dir.join("report.json");
+        let quarantine = dir.join("quarantine");
+        let args = [
+            args,
+            &["--report-json", path.to_str().unwrap()],
+            &["--quarantine", quarantine.to_str().unwrap()],
+        ]
+        .concat();
+        let result = run_cleaner_on(&dir, input, &args).map(|(status, stdout)| {
+            let prefix = dir.to_str().unwrap();
+            let stdout = duration
+                .replace_all(&stdout.replace(prefix, ""), "")
+                .into_owned();
+            let mut report = Report::read(&path).unwrap();
+            report.files.sort_by(|a, b| a.path.cmp(&b.path));
+            report.free_space_bytes = None;
+            for file in report.files.iter_mut() {
+                file.duration_us = None;
+            }
+            let report = serde_json::to_string(&report).unwrap().replace(prefix, "");
+            (status.success(), stdout, report)
+        });
+        result
+    };
+    let Some(sorted) = run("sorted", "input", &["--really-delete"]) else {
+        return;
+    };
+    let unordered = run(
+        "unordered",
+        "input",
+        &["--really-delete", "--report-unordered"],
+    )
+    .unwrap();
+    assert!(unordered.0, "{}", unordered.1);
+    assert_eq!(unordered, sorted);
+
+    // the foreign files are only known, and moved, after the loop
+    let sorted = run("sorted_majority", "instrument_id", &["--verbose"]).unwrap();
+    let args = ["--verbose", "--report-unordered"];
+    let unordered = run("unordered_majority", "instrument_id", &args).unwrap();
+    assert!(sorted.1.contains("moved 1 of 1 files"), "{}", sorted.1);
+    assert_eq!(unordered, sorted);
+}
+
+#[test]
+fn yaml_report_reads_back_as_the_json_one() {
+    use cleaner_lib::report::Report;
+
+    let dir = TempDir::new("report_yaml");
+    let json = dir.join("report.json");
+    let yaml = dir.join("report.yaml");
+    let args = ["--report-json", json.to_str().unwrap()];
+    let args = [&args[..], &["--report-yaml", yaml.to_str().unwrap()]].concat();
+    let Some((status, stdout)) = run_cleaner(&dir, &args) else {
+        return;
+    };
+    assert!(status.success(), "{stdout}");
+
+    let from_json = Report::read(&json).unwrap();
+    let from_yaml = Report::read(&yaml).unwrap();
+    assert!(!from_json.files.is_empty());
+    assert_eq!(from_yaml, from_json);
+
+    let text = serde_yaml::to_string(&from_json).unwrap();
+    let again: Report = serde_yaml::from_str(&text).unwrap();
+    assert_eq!(again, from_json);
+}
+
+#[test]
+fn unknown_extensions_are_skipped_without_verbose() {
+    use cleaner_lib::testutil::FIXTURE_DIR;
+
+    let dir = TempDir::new("unknown_quiet");
+    let Some((status, stdout)) = run_cleaner(&dir, &[]) else {
+        return;
+    };
+    assert!(status.success(), "{stdout}");
+    for name in ["unknown_extension.XYZ", "unknown_lowercase.xyz"] {
+        let input = std::path::Path::new(FIXTURE_DIR).join("input").join(name);
+        let cleaned = std::fs::read(dir.join("data").join(name));
+        assert_eq!(cleaned.ok(), std::fs::read(input).ok(), "{name}");
+    }
+}
+
+#[test]
+fn prefixed_osc_files_get_their_other_fixes() {
+    let dir = TempDir::new("osc_prefixed_fixes");
+    let Some((status, stdout)) = run_cleaner(&dir, &[]) else {
+        return;
+    };
+    assert!(status.success(), "{stdout}");
+    let path = dir.join("data").join("osc_processed_trailing_empty.OSC");
+    let cleaned = std::fs::read_to_string(path).unwrap();
+    assert!(cleaned.starts_with("01.06.23 12:00:00.00\n"), "{cleaned}");
+    assert!(cleaned.ends_with("\t3\t4\n"), "{cleaned}");
+    assert_eq!(cleaned.matches("DateTime").count(), 1, "{cleaned}");
+}
+
+#[test]
+fn osc_prefix_layouts() {
+    use std::fs;
+
+    use cleaner_lib::{clean_file, compile_rules, config, CleanOptions, Outcome};
+
+    let dir = TempDir::new("osc_layouts");
+    fs::create_dir_all(&dir).unwrap();
+    let v25 = "01.06.23 12:00:00.00\np1\np2\t0.5\np3\n\th1\th2\n\t1\t2\n";
+    let bare = "01.06.23 12:00:00.00\np1\np2\t0.5\np3\nh1\th2\n1\t2\n";
+    let dt = "01.06.23 12:00:00.00";
+    // the default is the layout of the first versions: a tab, the datetime, the line
+    let cases = [
+        ("", v25, format!("\tDateTime\th1\th2\n\t{dt}\t1\t2\n")),
+        ("", bare, format!("\tDateTimeh1\th2\n\t{dt}1\t2\n")),
+        (
+            "join_delimiter: true",
+            v25,
+            format!("\tDateTime\th1\th2\n\t{dt}\t1\t2\n"),
+        ),
+        (
+            "join_delimiter: true",
+            bare,
+            format!("\tDateTime\th1\th2\n\t{dt}\t1\t2\n"),
+        ),
+        (
+            "leading_delimiter: false",
+            v25,
+            format!("DateTime\th1\th2\n{dt}\t1\t2\n"),
+        ),
+        (
+            "leading_delimiter: false, join_delimiter: true",
+            bare,
+            format!("DateTime\th1\th2\n{dt}\t1\t2\n"),
+        ),
+        (
+            "prefix_position: append",
+            v25,
+            format!("\th1\th2\tDateTime\n\t1\t2\t{dt}\n"),
+        ),
+        (
+            "prefix_position: append",
+            bare,
+            format!("h1\th2\tDateTime\n1\t2\t{dt}\n"),
+        ),
+    ];
+    for (i, (layout, content, want)) in cases.into_iter().enumerate() {
+        let cfg = format!("OSC: {{min_n_lines: 6, header_line: 4, {layout}}}");
+        let cfg = &config::parse(&cfg).unwrap();
+        let opts = CleanOptions {
+            compiled_rules: compile_rules(cfg).unwrap(),
+            ..Default::default()
+        };
+        let path = dir.join(format!("{i}.OSC"));
+        fs::write(&path, content).unwrap();
+        let first = clean_file(&path, cfg, &opts).unwrap();
+        let prefixed = fs::read_to_string(&path).unwrap();
+        assert_eq!(first.outcome, Outcome::Modified, "{layout}: {first:?}");
+        assert_eq!(
+            prefixed,
+            format!("{dt}\np1\np2\t0.5\np3\n{want}"),
+            "{layout}"
+        );
+        // the next run finds the file as the first left it
+        let second = clean_file(&path, cfg, &opts).unwrap();
+        assert_eq!(second.outcome, Outcome::Unchanged, "{layout}: {second:?}");
+        assert_eq!(fs::read_to_string(&path).unwrap(), prefixed, "{layout}");
+    }
+}
+
+#[test]
+fn every_command_validates_the_cfg() {
+    use std::{fs, process::Command};
+
+    let dir = TempDir::new("validate_cfg");
+    let Some((status, stdout)) = run_cleaner(&dir, &[]) else {
+        return;
+    };
+    assert!(status.success(), "{stdout}");
+    // the setting deletes the files of a type that must never be changed
+    let cfg = "OSC:\n  min_n_lines: 6\nDAT:\n  min_n_lines: 2\n  readonly: true\n  \
+               on_header_mismatch: delete\n";
+    fs::write(dir.join("bin").join("cfg").join("v25_data_cfg.yml"), cfg).unwrap();
+    let data = dir.join("data");
+    let file = data.join("dat_clean.DAT");
+    let before = fs::read(&file).unwrap();
+    for args in [
+        vec!["checks".as_ref(), "file".as_ref(), file.as_os_str()],
+        vec!["repair-osc".as_ref(), "-d".as_ref(), data.as_os_str()],
+        vec!["self-test".as_ref()],
+        vec![
+            "-d".as_ref(),
+            data.as_os_str(),
+            "--force".as_ref(),
+            "--allow-elevated".as_ref(),
+        ],
+    ] {
+        let output = Command::new(dir.join("bin").join("v25_datacleaner"))
+            .args(&args)
+            .env_remove(cleaner_lib::audit::AUDIT_ENV)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(output.status.code(), Some(1), "{args:?}: {stdout}");
+        assert!(
+            stdout.contains("! invalid cfg file") && stdout.contains("readonly"),
+            "{args:?}: {stdout}"
+        );
+    }
+    assert_eq!(fs::read(&file).unwrap(), before);
+}
+
+#[test]
+fn repair_osc_follows_the_ext_map() {
+    use std::{fs, process::Command};
+
+    let dir = TempDir::new("repair_ext_map");
+    let Some((status, stdout)) = run_cleaner_on(&dir, "nested", &["--dry-run"]) else {
+        return;
+    };
+    assert!(status.success(), "{stdout}");
+    let data = dir.join("data");
+    let dt = "01.06.23 12:00:00.00";
+    let half = format!("{dt}\np1\np2\t0.5\np3\n\tDateTime\th1\th2\n\t{dt}\t1\t2\n\t3\t4\n");
+    fs::write(data.join("half.OSX"), &half).unwrap();
+    let repair = |args: &[&str]| {
+        let output = Command::new(dir.join("bin").join("v25_datacleaner"))
+            .args(["repair-osc", "-d"])
+            .arg(&data)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    let stdout = repair(&[]);
+    assert!(!stdout.contains("half.OSX"), "{stdout}");
+    assert_eq!(fs::read_to_string(data.join("half.OSX")).unwrap(), half);
+
+    let stdout = repair(&["--ext-map", "OSX=OSC"]);
+    assert!(
+        stdout.contains("half.OSX\": half prefixed -> prefixed 1 data lines"),
+        "{stdout}"
+    );
+    let repaired = fs::read_to_string(data.join("half.OSX")).unwrap();
+    assert!(
+        repaired.ends_with(&format!("\n\t{dt}\t3\t4\n")),
+        "{repaired}"
+    );
+}
+
+#[test]
+fn metrics_parse_as_prometheus_text() {
+    use std::{
+        collections::BTreeMap,
+        path::Path,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use cleaner_lib::report::{render_metrics, Summary};
+
+    /// label_value reads a quoted label value from the start of s, undoing the escapes
+    /// of the exposition format, and returns it with the rest of s after the quote.
+    fn label_value(s: &str) -> (String, &str) {
+        let mut value = String::new();
+        let mut chars = s.strip_prefix('"').unwrap().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                // i counts from after the opening quote
+                '"' => return (value, &s[i + 2..]),
+                '\\' => match chars.next().unwrap().1 {
+                    'n' => value.push('\n'),
+                    c @ ('\\' | '"') => value.push(c),
+                    c => panic!("invalid escape \\{c} in {s}"),
+                },
+                c => value.push(c),
+            }
+        }
+        panic!("unterminated label value in {s}")
+    }
+
+    let summary = Summary {
+        scanned: 7,
+        modified: 2,
+        deleted: 1,
+        errors: 3,
+        ..Default::default()
+    };
+    let dir = Path::new("C:\\data\\\"station\"\nB");
+    let finished = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let text = render_metrics(
+        &summary,
+        Some(dir),
+        Duration::from_millis(1500),
+        finished,
+        Some(4096),
+    );
+
+    /// Metric is what the text has on one metric: the HELP and TYPE text and the
+    /// samples, as directory label and value.
+    #[derive(Default)]
+    struct Metric<'a> {
+        help: Option<&'a str>,
+        kind: Option<&'a str>,
+        samples: Vec<(String, f64)>,
+    }
+    let mut metrics: BTreeMap<&str, Metric> = BTreeMap::new();
+    for line in text.lines() {
+        if let Some(rest) = line.strip_prefix("# HELP ") {
+            let (name, help) = rest.split_once(' ').unwrap();
+            metrics.entry(name).or_default().help = Some(help);
+        } else if let Some(rest) = line.strip_prefix("# TYPE ") {
+            let (name, kind) = rest.split_once(' ').unwrap();
+            metrics.entry(name).or_default().kind = Some(kind);
+        } else {
+            let (name, rest) = line
+                .split_once("{directory=")
+                .unwrap_or_else(|| panic!("{line}"));
+            let (directory, rest) = label_value(rest);
+            let value = rest.strip_prefix("} ").unwrap().parse().unwrap();
+            metrics
+                .entry(name)
+                .or_default()
+                .samples
+                .push((directory, value));
+        }
+    }
+    let names: Vec<&str> = metrics.keys().copied().collect();
+    assert_eq!(
+        names,
+        [
+            "v25cleaner_errors",
+            "v25cleaner_files_deleted",
+            "v25cleaner_files_modified",
+            "v25cleaner_files_scanned",
+            "v25cleaner_free_space_bytes",
+            "v25cleaner_last_run_timestamp_seconds",
+            "v25cleaner_run_duration_seconds",
+        ]
+    );
+    let directory = dir.to_string_lossy().into_owned();
+    for (name, metric) in &metrics {
+        // [a-zA-Z_:][a-zA-Z0-9_:]* in the exposition format
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == ':';
+        assert!(!name.starts_with(|c: char| c.is_ascii_digit()), "{name}");
+        assert!(name.chars().all(valid), "{name}");
+        assert!(metric.help.is_some_and(|help| !help.is_empty()), "{name}");
+        assert_eq!(metric.kind, Some("gauge"), "{name}");
+        assert_eq!(metric.samples.len(), 1, "{name}: {:?}", metric.samples);
+        assert_eq!(metric.samples[0].0, directory, "{name}");
+    }
+    let value = |name: &str| metrics[name].samples[0].1;
+    assert_eq!(value("v25cleaner_files_scanned"), 7.0);
+    assert_eq!(value("v25cleaner_files_modified"), 2.0);
+    assert_eq!(value("v25cleaner_files_deleted"), 1.0);
+    assert_eq!(value("v25cleaner_errors"), 3.0);
+    assert_eq!(value("v25cleaner_run_duration_seconds"), 1.5);
+    assert_eq!(
+        value("v25cleaner_last_run_timestamp_seconds"),
+        1_700_000_000.0
+    );
+    assert_eq!(value("v25cleaner_free_space_bytes"), 4096.0);
+
+    // without a directory, the samples have no labels
+    let text = render_metrics(&summary, None, Duration::ZERO, finished, None);
+    assert!(text.contains("\nv25cleaner_files_scanned 7\n"), "{text}");
+    assert!(!text.contains("free_space"), "{text}");
+}
+
+#[test]
+fn files_over_the_memory_budget_are_cleaned_one_at_a_time() {
+    use std::fs;
+
+    use cleaner_lib::report::Report;
+
+    let dir = TempDir::new("memory_budget");
+    let Some((status, stdout)) = run_cleaner_on(&dir, "size_outliers", &["--dry-run"]) else {
+        return;
+    };
+    assert!(status.success(), "{stdout}");
+    // 4 files of 1.5 MiB each, over the budget of 1 MB alone; the last line is cut off
+    let data = dir.join("data");
+    let line = "1\t2\t3\n";
+    let body = line.repeat(1536 * 1024 / line.len());
+    for hour in 4..8 {
+        let content = format!("time\tp\tT\n{body}4\t5\n\n\n");
+        fs::write(data.join(format!("031{hour:02}00.DAT")), content).unwrap();
+    }
+    let report = dir.join("report.json");
+    let args = ["--force", "--memory-budget", "1", "--jobs", "4"];
+    let args = [&args[..], &["--report-json", report.to_str().unwrap()]].concat();
+    let (status, stdout) = run_again(&dir, &args);
+    assert!(status.success(), "{stdout}");
+
+    let report = Report::read(&report).unwrap();
+    for hour in 4..8 {
+        let name = format!("031{hour:02}00.DAT");
+        let file = report
+            .files
+            .iter()
+            .find(|file| file.path.ends_with(&name))
+            .unwrap_or_else(|| panic!("{name}: {stdout}"));
+        assert_eq!(file.outcome, cleaner_lib::Outcome::Modified, "{name}");
+        let cleaned = fs::read_to_string(data.join(&name)).unwrap();
+        assert_eq!(cleaned, format!("time\tp\tT\n{body}"), "{name}");
+    }
+}
+
+#[test]
+fn streamed_osc_prefix_matches_the_in_memory_one() {
+    use std::{collections::BTreeMap, fs, path::Path};
+
+    use cleaner_lib::{report::Report, FileOutcome};
+
+    /// cleaned returns the content of the files in data and their outcomes in the report,
+    /// by file name, without what differs between the runs anyway.
+    fn cleaned(dir: &Path) -> BTreeMap<String, (Vec<u8>, Option<FileOutcome>)> {
+        let report = Report::read(dir.join("report.json")).unwrap();
+        let mut outcomes: BTreeMap<String, FileOutcome> = report
+            .files
+            .into_iter()
+            .map(|mut file| {
+                let name = file.path.file_name().unwrap().to_string_lossy().to_string();
+                file.path = Path::new(&name).to_path_buf();
+                file.duration_us = None;
+                (name, file)
+            })
+            .collect();
+        fs::read_dir(dir.join("data"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.is_file())
+            .map(|path| {
+                let name = path.file_name().unwrap().to_string_lossy().to_string();
+                let outcome = outcomes.remove(&name);
+                (name, (fs::read(&path).unwrap(), outcome))
+            })
+            .collect()
+    }
+
+    let in_memory = TempDir::new("osc_in_memory");
+    let streamed = TempDir::new("osc_streamed");
+    let report = |dir: &TempDir| dir.join("report.json").to_str().unwrap().to_string();
+    let Some((status, stdout)) = run_cleaner(&in_memory, &["--report-json", &report(&in_memory)])
+    else {
+        return;
+    };
+    assert!(status.success(), "{stdout}");
+    let args = [
+        "--stream-osc-above",
+        "0",
+        "--report-json",
+        &report(&streamed),
+    ];
+    let (status, stdout) = run_cleaner(&streamed, &args).unwrap();
+    assert!(status.success(), "{stdout}");
+
+    let (in_memory, streamed) = (cleaned(&in_memory), cleaned(&streamed));
+    assert_eq!(
+        in_memory.keys().collect::<Vec<_>>(),
+        streamed.keys().collect::<Vec<_>>()
+    );
+    for (name, (content, outcome)) in &in_memory {
+        let (streamed_content, streamed_outcome) = &streamed[name];
+        assert!(
+            content == streamed_content,
+            "{name}:\n{}\nstreamed:\n{}",
+            String::from_utf8_lossy(content),
+            String::from_utf8_lossy(streamed_content)
+        );
+        assert_eq!(outcome, streamed_outcome, "{name}");
+    }
+    // the corpus has OSC files that are prefixed and nothing else, those are streamed
+    let prefixed = in_memory
+        .iter()
+        .filter(|(_, (_, outcome))| outcome.as_ref().is_some_and(|o| o.osc_prefix.is_some()))
+        .count();
+    assert!(prefixed >= 3, "{prefixed}");
+}
+
+#[test]
+fn ext_map_applies_the_rules_of_the_mapped_type() {
+    use std::fs;
+
+    use cleaner_lib::report::Report;
+
+    let dir = TempDir::new("ext_map");
+    let Some((status, stdout)) = run_cleaner_on(&dir, "size_outliers", &["--dry-run"]) else {
+        return;
+    };
+    assert!(status.success(), "{stdout}");
+    let data = dir.join("data");
+    let misnamed = data.join("03120400.TXT");
+    fs::write(&misnamed, "time\tp\tT\n1\t2\t3\n4\t5\t6\n\n\n").unwrap();
+    let report = dir.join("report.json");
+
+    // the mapping must be unambiguous, else nothing is touched
+    for mappings in [
+        &["TXT=DAT", "TXT=OSC"][..],
+        &["TXT=DAT", "txt=.dat"],
+        &["TXT=DAT", "DAT=OSC"],
+    ] {
+        let args: Vec<&str> = mappings.iter().flat_map(|m| ["--ext-map", m]).collect();
+        let (status, stdout) = run_again(&dir, &[&["--force"], &args[..]].concat());
+        assert!(!status.success(), "{mappings:?}: {stdout}");
+        assert!(
+            stdout.contains("! invalid --ext-map: TXT is mapped"),
+            "{stdout}"
+        );
+    }
+    assert!(fs::read_to_string(&misnamed).unwrap().ends_with("\n\n"));
+
+    let args = ["--force", "--ext-map", "TXT=DAT", "--report-json"];
+    let (status, stdout) = run_again(&dir, &[&args[..], &[report.to_str().unwrap()]].concat());
+    assert!(status.success(), "{stdout}");
+    let report = Report::read(&report).unwrap();
+    let file = report
+        .files
+        .iter()
+        .find(|file| file.path.ends_with("03120400.TXT"))
+        .unwrap_or_else(|| panic!("{stdout}"));
+    assert_eq!(file.outcome, cleaner_lib::Outcome::Modified);
+    assert_eq!(file.extension.as_deref(), Some("TXT"));
+    assert_eq!(file.effective_extension.as_deref(), Some("DAT"));
+    assert_eq!(report.mapped_extensions["TXT as DAT"], 1);
+    assert_eq!(
+        fs::read_to_string(&misnamed).unwrap(),
+        "time\tp\tT\n1\t2\t3\n4\t5\t6\n"
+    );
+}
+
+#[test]
+fn sniff_type_detects_misnamed_but_not_ambiguous_files() {
+    use std::fs;
+
+    use cleaner_lib::report::Report;
+
+    let dir = TempDir::new("sniff_type");
+    let Some((status, stdout)) = run_cleaner_on(&dir, "size_outliers", &["--dry-run"]) else {
+        return;
+    };
+    assert!(status.success(), "{stdout}");
+    // OMX and OMY differ in one column, a header of one resembles both
+    let cfg_dir = dir.join("bin").join("cfg");
+    let mut cfg = "DAT:\n  min_n_lines: 2\n".to_string();
+    for (ext, header) in [
+        ("OMC", "time\tO3\tp"),
+        ("OMX", "time\tNO\tNO2\tp"),
+        ("OMY", "time\tNO\tNO2\tT"),
+    ] {
+        fs::write(cfg_dir.join(format!("ref_{ext}.txt")), header).unwrap();
+        cfg.push_str(&format!(
+            "{ext}:\n  min_n_lines: 2\n  reference_header_file: ref_{ext}.txt\n  on_header_mismatch: warn\n"
+        ));
+    }
+    fs::write(cfg_dir.join("v25_data_cfg.yml"), cfg).unwrap();
+    let data = dir.join("data");
+    fs::write(data.join("misnamed.OMX"), "time\tO3\tp\n1\t2\t3\n").unwrap();
+    fs::write(data.join("ambiguous.OMC"), "time\tNO\tNO2\tp\n1\t2\t3\t4\n").unwrap();
+    let report = dir.join("report.json");
+    let run = |args: &[&str]| {
+        let args = [
+            args,
+            &["--force", "--report-json", report.to_str().unwrap()],
+        ]
+        .concat();
+        let (status, stdout) = run_again(&dir, &args);
+        assert!(status.success(), "{stdout}");
+        let report = Report::read(&report).unwrap();
+        let file = |name: &str| {
+            let file = report.files.iter().find(|file| file.path.ends_with(name));
+            file.cloned().unwrap_or_else(|| panic!("{name}: {stdout}"))
+        };
+        (file("misnamed.OMX"), file("ambiguous.OMC"))
+    };
+
+    let (misnamed, ambiguous) = run(&["--sniff-type"]);
+    assert_eq!(misnamed.detected_type.as_deref(), Some("OMC"));
+    assert!(
+        misnamed
+            .warnings
+            .contains(&"content looks like OMC".to_string()),
+        "{:?}",
+        misnamed.warnings
+    );
+    assert_eq!(ambiguous.detected_type, None);
+    assert!(
+        !ambiguous.warnings.iter().any(|w| w.contains("looks like")),
+        "{:?}",
+        ambiguous.warnings
+    );
+    assert!(data.join("misnamed.OMX").exists());
+
+    let (misnamed, ambiguous) = run(&["--sniff-type", "--fix-misnamed"]);
+    assert_eq!(misnamed.renamed_to, Some(data.join("misnamed.OMC")));
+    assert_eq!(
+        fs::read_to_string(data.join("misnamed.OMC")).unwrap(),
+        "time\tO3\tp\n1\t2\t3\n"
+    );
+    assert!(!data.join("misnamed.OMX").exists());
+    assert_eq!(ambiguous.renamed_to, None);
+    assert!(data.join("ambiguous.OMC").exists());
+}
+
+#[test]
+fn also_evaluate_reports_without_changing_files() {
+    use std::{collections::BTreeMap, fs};
+
+    use cleaner_lib::{report::Report, Outcome, FLAG_EXTENSION};
+
+    let dir = TempDir::new("also_evaluate");
+    let Some((status, stdout)) = run_cleaner_on(&dir, "size_outliers", &["--dry-run"]) else {
+        return;
+    };
+    assert!(status.success(), "{stdout}");
+    let data = dir.join("data");
+    fs::write(data.join("03120600.DAT"), "time\tp\tT\n1\t2\t3\n\n\n").unwrap();
+    // under the stricter cfg, the short files are deleted, i.e. flagged as root
+    let strict = dir.join("strict.yml");
+    fs::write(&strict, "DAT:\n  min_n_lines: 50\n").unwrap();
+    let files = || -> BTreeMap<String, String> {
+        fs::read_dir(&data)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "DAT"))
+            .map(|path| {
+                let name = path.file_name().unwrap().to_string_lossy().to_string();
+                (name, fs::read_to_string(&path).unwrap())
+            })
+            .collect()
+    };
+    let mut expected = files();
+    expected.insert(
+        "03120600.DAT".to_string(),
+        "time\tp\tT\n1\t2\t3\n".to_string(),
+    );
+    let report = dir.join("report.json");
+
+    let args = ["--force", "--also-evaluate", strict.to_str().unwrap()];
+    let args = [&args[..], &["--report-json", report.to_str().unwrap()]].concat();
+    let (status, stdout) = run_again(&dir, &args);
+    assert!(status.success(), "{stdout}");
+
+    // the files are cleaned as the main cfg says, nothing more
+    assert_eq!(files(), expected);
+    let flagged = fs::read_dir(&data).unwrap().any(|entry| {
+        let path = entry.unwrap().path();
+        path.extension().is_some_and(|ext| ext == FLAG_EXTENSION)
+    });
+    assert!(!flagged);
+    let report = Report::read(&report).unwrap();
+    let differ: Vec<(&str, Option<Outcome>)> = report
+        .files
+        .iter()
+        .filter(|file| file.would_differ)
+        .map(|file| {
+            (
+                file.path.file_name().unwrap().to_str().unwrap(),
+                file.alternate,
+            )
+        })
+        .collect();
+    let names: Vec<&str> = differ.iter().map(|(name, _)| *name).collect();
+    assert_eq!(names, ["03120300.DAT", "03120600.DAT"]);
+    assert!(
+        differ.iter().all(|(_, alternate)| matches!(
+            alternate,
+            Some(Outcome::Deleted(_) | Outcome::Flagged(_))
+        )),
+        "{differ:?}"
+    );
+    assert!(report.files.iter().all(|file| file.alternate.is_some()));
+    assert!(
+        stdout.contains("2 files would end differently under"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("03120300.DAT\": unchanged -> "), "{stdout}");
+    assert!(stdout.contains("03120600.DAT\": modified -> "), "{stdout}");
+}
+
+#[cfg(unix)]
+#[test]
+fn elevated_runs_are_refused_unless_allowed() {
+    use std::{fs, process::Command};
+
+    use cleaner_lib::privilege::{is_elevated, ALLOW_ELEVATED_ENV};
+
+    let dir = TempDir::new("elevated");
+    let Some((status, stdout)) = run_cleaner_on(&dir, "size_outliers", &["--dry-run"]) else {
+        return;
+    };
+    assert!(status.success(), "{stdout}");
+    let dirty = dir.join("data").join("03120600.DAT");
+    fs::write(&dirty, "time\tp\tT\n1\t2\t3\n\n").unwrap();
+    let run = |allow: Option<&str>| {
+        let mut command = Command::new(dir.join("bin").join("v25_datacleaner"));
+        command
+            .arg("-d")
+            .arg(dir.join("data"))
+            .arg("--force")
+            .env_remove(ALLOW_ELEVATED_ENV)
+            .env_remove(cleaner_lib::audit::AUDIT_ENV);
+        if let Some(value) = allow {
+            command.env(ALLOW_ELEVATED_ENV, value);
+        }
+        let output = command.output().unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        (output.status.code(), stdout)
+    };
+
+    if !is_elevated() {
+        // nothing to refuse as an ordinary user
+        let (code, stdout) = run(None);
+        assert_eq!(code, Some(0), "{stdout}");
+        return;
+    }
+    // empty and 0 do not allow it
+    for allow in [None, Some(""), Some("0")] {
+        let (code, stdout) = run(allow);
+        assert_eq!(code, Some(10), "{allow:?}: {stdout}");
+        assert!(
+            stdout.starts_with("! running as root or elevated administrator, nothing was cleaned"),
+            "{stdout}"
+        );
+        assert_eq!(
+            fs::read_to_string(&dirty).unwrap(),
+            "time\tp\tT\n1\t2\t3\n\n"
+        );
+    }
+    let (code, stdout) = run(Some("1"));
+    assert_eq!(code, Some(0), "{stdout}");
+    assert_eq!(fs::read_to_string(&dirty).unwrap(), "time\tp\tT\n1\t2\t3\n");
+}
+
+#[test]
+fn timings_have_a_duration_for_every_checked_file() {
+    use std::fs;
+
+    let dir = TempDir::new("timings");
+    let (json, yaml) = (dir.join("report.json"), dir.join("report.yml"));
+    let args = [
+        "--timings",
+        "3",
+        "--report-json",
+        json.to_str().unwrap(),
+        "--report-yaml",
+        yaml.to_str().unwrap(),
+    ];
+    let Some((status, stdout)) = run_cleaner_on(&dir, "size_outliers", &args) else {
+        return;
+    };
+    assert!(status.success(), "{stdout}");
+
+    // the durations differ from run to run, only their place is checked
+    let json: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&json).unwrap()).unwrap();
+    let yaml: serde_json::Value =
+        serde_yaml::from_str(&fs::read_to_string(&yaml).unwrap()).unwrap();
+    for report in [&json, &yaml] {
+        let files = report["files"].as_array().unwrap();
+        assert_eq!(files.len(), 6);
+        for file in files {
+            assert!(file["duration_us"].is_u64(), "{file}");
+        }
+    }
+
+    let lines: Vec<&str> = stdout
+        .lines()
+        .skip_while(|line| !line.starts_with("slowest "))
+        .collect();
+    assert_eq!(lines.first(), Some(&"slowest 3 of 6 files:"), "{stdout}");
+    for line in &lines[1..4] {
+        // e.g. `     1.52ms  "…/data/03120000.DAT": unchanged`
+        let (duration, file) = line.trim_start().split_once("  ").unwrap();
+        assert!(
+            duration.ends_with('s') && duration.starts_with(|c: char| c.is_ascii_digit()),
+            "{line}"
+        );
+        assert!(file.starts_with('"') && file.contains(".DAT\": "), "{line}");
+    }
+    assert!(
+        lines.get(4).is_none_or(|line| !line.starts_with("  ")),
+        "{stdout}"
+    );
+}
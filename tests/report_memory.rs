@@ -0,0 +1,209 @@
+//! checks that --report-unordered writes the report without holding the files of the
+//! run. These tests have a file of their own: the allocator counts for the whole test
+//! binary, and the peak memory of the cleaner is that of all children of the process.
+
+use std::{fs, path::Path};
+
+use cleaner_lib::{
+    report::{Report, ReportWriter, Tally},
+    CheckId, FileOutcome, Finding, Outcome, RemovedLine,
+};
+
+/// Counting is the system allocator, counting the bytes each thread holds, for
+/// report_writer_holds_one_file_at_a_time.
+struct Counting;
+
+thread_local! {
+    static HELD: std::cell::Cell<isize> = const { std::cell::Cell::new(0) };
+    static PEAK: std::cell::Cell<isize> = const { std::cell::Cell::new(0) };
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// count adds delta to the bytes the thread holds. a thread that is torn down has no
+/// counters left, the memory it frees then is not counted.
+fn count(delta: isize) {
+    let _ = HELD.try_with(|held| {
+        held.set(held.get() + delta);
+        let _ = PEAK.try_with(|peak| peak.set(peak.get().max(held.get())));
+    });
+}
+
+unsafe impl std::alloc::GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+        let ptr = unsafe { std::alloc::System.alloc(layout) };
+        if !ptr.is_null() {
+            count(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+        unsafe { std::alloc::System.dealloc(ptr, layout) };
+        count(-(layout.size() as isize));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: std::alloc::Layout, size: usize) -> *mut u8 {
+        let new = unsafe { std::alloc::System.realloc(ptr, layout, size) };
+        if !new.is_null() {
+            count(size as isize - layout.size() as isize);
+        }
+        new
+    }
+}
+
+/// peak_held returns the most bytes the thread held at once while f ran, on top of
+/// those it held before.
+fn peak_held(f: impl FnOnce()) -> isize {
+    let before = HELD.with(|held| held.get());
+    PEAK.with(|peak| peak.set(before));
+    f();
+    PEAK.with(|peak| peak.get()) - before
+}
+
+#[test]
+fn report_writer_holds_one_file_at_a_time() {
+    use std::io;
+
+    /// write writes a report of n files to out, made one at a time, as the writer takes
+    /// them.
+    fn write<W: io::Write>(out: W, n: usize) -> W {
+        let outcomes = (0..n).map(move |i| {
+            let mut outcome = FileOutcome::new(Path::new(&format!("{:07}.DAT", n - i)));
+            outcome.outcome = Outcome::Modified;
+            let message = "last line is empty -> remove line".to_string();
+            outcome.findings = vec![Finding::new(CheckId::TrailingEmptyLine, message)];
+            outcome.removed_lines = vec![RemovedLine {
+                line: 3,
+                content: Some("x".repeat(200)),
+            }];
+            outcome
+        });
+        let mut writer = ReportWriter::new(out, true, true).unwrap();
+        let mut tally = Tally::default();
+        for outcome in outcomes {
+            writer.push(&outcome).unwrap();
+            tally.add(&outcome);
+        }
+        writer
+            .finish(&Report::head(Path::new("station"), &tally))
+            .unwrap()
+    }
+
+    let small = peak_held(|| {
+        write(io::sink(), 100);
+    });
+    let large = peak_held(|| {
+        write(io::sink(), 100_000);
+    });
+    // a copy of the 100 000 outcomes alone would take tens of megabytes
+    assert!(
+        large <= small + 1024,
+        "{small} bytes for 100, {large} for 100 000"
+    );
+
+    let json = write(Vec::new(), 3);
+    let report: Report = serde_json::from_slice(&json).unwrap();
+    let paths: Vec<&Path> = report
+        .files
+        .iter()
+        .map(|file| file.path.as_path())
+        .collect();
+    assert_eq!(
+        paths,
+        ["0000003.DAT", "0000002.DAT", "0000001.DAT"].map(Path::new),
+        "in the order they came"
+    );
+    assert_eq!(report.files[0].removed_lines[0].content, None);
+    assert_eq!(report.summary.modified, 3);
+    assert_eq!(report.directory, Path::new("station"));
+}
+
+/// peak_rss returns the most memory in KiB a child of the process that was waited for
+/// held at once, None where it is not known.
+#[cfg(target_os = "linux")]
+fn peak_rss() -> Option<u64> {
+    // SAFETY: rusage is a plain C struct, all zeroes is a valid value
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    // SAFETY: usage is only read if the call succeeded
+    let ok = unsafe { libc::getrusage(libc::RUSAGE_CHILDREN, &mut usage) } == 0;
+    ok.then_some(usage.ru_maxrss as u64)
+}
+
+/// peak_rss is only known on Linux, elsewhere ru_maxrss is in other units or missing.
+#[cfg(not(target_os = "linux"))]
+fn peak_rss() -> Option<u64> {
+    None
+}
+
+/// TempDir is a directory of a test, removed when the test is done, passed or not.
+struct TempDir(std::path::PathBuf);
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn report_streams_in_constant_memory() {
+    use std::process::Command;
+
+    use cleaner_lib::testutil::FIXTURE_DIR;
+
+    let Some(exe) = option_env!("CARGO_BIN_EXE_v25_datacleaner") else {
+        return;
+    };
+    let dir =
+        TempDir(std::env::temp_dir().join(format!("v25_report_memory_{}", std::process::id())));
+    let dir = &dir.0;
+    // the binary reads its cfg file from cfg/ next to it
+    let bin_dir = dir.join("bin");
+    fs::create_dir_all(bin_dir.join("cfg")).unwrap();
+    fs::copy(exe, bin_dir.join("v25_datacleaner")).unwrap();
+    for (name, target) in [
+        ("cfg.yml", "v25_data_cfg.yml"),
+        ("ref_OMC.txt", "ref_OMC.txt"),
+    ] {
+        fs::copy(
+            Path::new(FIXTURE_DIR).join(name),
+            bin_dir.join("cfg").join(target),
+        )
+        .unwrap();
+    }
+    // each file loses 200 empty lines, with a finding for each
+    let content = format!("time\tp\tT\n{}{}", "1\t2\t3\n".repeat(3), "\n".repeat(200));
+    let run = |name: &str, n: usize| {
+        let data = dir.join(name);
+        fs::create_dir(&data).unwrap();
+        for i in 0..n {
+            fs::write(data.join(format!("{i:05}.DAT")), &content).unwrap();
+        }
+        let report = dir.join(format!("{name}.json"));
+        let output = Command::new(bin_dir.join("v25_datacleaner"))
+            .arg("-d")
+            .arg(&data)
+            .args(["--allow-elevated", "--report-unordered", "--report-json"])
+            .arg(&report)
+            .env_remove(cleaner_lib::audit::AUDIT_ENV)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        assert!(output.status.success(), "{stdout}");
+        (Report::read(&report).unwrap(), peak_rss())
+    };
+    let (_, small) = run("small", 100);
+    let (report, large) = run("large", 2000);
+
+    assert_eq!(report.files.len(), 2000);
+    assert_eq!(report.summary.modified, 2000);
+    // the peak of the children only grows, it is that of the large run now; the outcomes
+    // of its files take some 90 MiB
+    if let (Some(small), Some(large)) = (small, large) {
+        assert!(
+            large <= small + 16 * 1024,
+            "{small} KiB for 100 files, {large} KiB for 2000"
+        );
+    }
+}